@@ -0,0 +1,99 @@
+//! Benchmarks for rendering templates, run with `cargo bench`.
+//!
+//! To check a change for regressions, run `make bench-baseline` before it and `make bench-check`
+//! after it, which fails if a benchmark got more than `BENCH_THRESHOLD` percent slower; see
+//! `examples/bench_gate.rs`.
+
+extern crate criterion;
+extern crate ktmpl;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use ktmpl::{ParameterValue, ParameterValues, Template};
+
+const SMALL_TEMPLATE: &str = include_str!("../example.yml");
+
+fn small_parameter_values() -> ParameterValues {
+    let mut parameter_values = ParameterValues::new();
+
+    for &(name, value) in &[("MONGODB_PASSWORD", "secret"), ("MONGODB_USER", "carl"), ("REPLICA_COUNT", "2")] {
+        parameter_values.insert(name.to_string(), ParameterValue::Plain(value.to_string()));
+    }
+
+    parameter_values
+}
+
+/// A template of 50 ConfigMaps, each with 100 data keys of about 1KB, about 5MB in total.
+fn config_map_template() -> String {
+    let line = format!("{}$(SETTING){}", "a".repeat(500), "b".repeat(500));
+    let mut template = String::from("---\nkind: Template\napiVersion: v1\nmetadata:\n  name: config\nobjects:\n");
+
+    for config_map in 0..50 {
+        template.push_str(&format!(
+            "  - kind: ConfigMap\n    apiVersion: v1\n    metadata:\n      name: config-{}\n    data:\n",
+            config_map,
+        ));
+
+        for key in 0..100 {
+            template.push_str(&format!("      key-{}: \"{}\"\n", key, line));
+        }
+    }
+
+    template.push_str("parameters:\n  - name: SETTING\n    value: enabled\n");
+
+    template
+}
+
+/// A template of 10,000 small Services.
+fn many_objects_template() -> String {
+    let mut template = String::from("---\nkind: Template\napiVersion: v1\nmetadata:\n  name: services\nobjects:\n");
+
+    for service in 0..10000 {
+        template.push_str(&format!(
+            "  - kind: Service\n    apiVersion: v1\n    metadata:\n      name: \"$(PREFIX)-{}\"\n      \
+             namespace: \"$(NAMESPACE)\"\n    spec:\n      ports:\n        - port: \"$((PORT))\"\n      \
+             selector:\n        app: \"$(PREFIX)-{}\"\n",
+            service, service,
+        ));
+    }
+
+    template.push_str(
+        "parameters:\n  - name: PREFIX\n    value: web\n  - name: NAMESPACE\n    value: default\n  \
+         - name: PORT\n    parameterType: int\n    value: \"80\"\n",
+    );
+
+    template
+}
+
+fn render(template_contents: &str, parameter_values: ParameterValues) -> String {
+    Template::new(template_contents.to_string(), parameter_values, None)
+        .and_then(Template::process)
+        .expect("Failed to render template.")
+}
+
+fn small(criterion: &mut Criterion) {
+    criterion.bench_function("small", |bencher| bencher.iter(|| render(SMALL_TEMPLATE, small_parameter_values())));
+}
+
+fn config_maps(criterion: &mut Criterion) {
+    let template = config_map_template();
+    let mut group = criterion.benchmark_group("config_maps");
+
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(template.len() as u64));
+    group.bench_function("5MB", |bencher| bencher.iter(|| render(&template, ParameterValues::new())));
+    group.finish();
+}
+
+fn many_objects(criterion: &mut Criterion) {
+    let template = many_objects_template();
+    let mut group = criterion.benchmark_group("many_objects");
+
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(10000));
+    group.bench_function("10k", |bencher| bencher.iter(|| render(&template, ParameterValues::new())));
+    group.finish();
+}
+
+criterion_group!(benches, small, config_maps, many_objects);
+criterion_main!(benches);
@@ -0,0 +1,102 @@
+//! A performance regression gate for the rendering benchmarks.
+//!
+//! Save a baseline on the base revision, then compare the change against it and check the result:
+//!
+//! ```text
+//! cargo bench --bench render -- --save-baseline base
+//! cargo bench --bench render -- --baseline base
+//! cargo run --example bench_gate -- 10
+//! ```
+//!
+//! The gate fails if any benchmark got slower than the baseline by more than the given percentage
+//! (10 by default), judged by the lower bound of Criterion's confidence interval for the change in
+//! mean time, so noise alone doesn't fail it. `make bench-check` runs the last two steps.
+//! Criterion's results are read from `target/criterion`, or `$CARGO_TARGET_DIR/criterion`.
+
+extern crate serde_json;
+
+use std::env::{args, var};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use serde_json::Value;
+
+fn main() {
+    let threshold = match args().nth(1).map(|threshold| threshold.parse::<f64>()) {
+        None => 10.0,
+        Some(Ok(threshold)) if threshold >= 0.0 => threshold,
+        Some(_) => {
+            eprintln!("Error: The threshold must be a non-negative percentage, e.g. 10.");
+            exit(2);
+        }
+    };
+    let results = PathBuf::from(var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string())).join("criterion");
+    let mut changes = vec![];
+
+    if let Err(err) = collect_changes(&results, &results, &mut changes) {
+        eprintln!("Error: {}", err);
+        exit(2);
+    }
+
+    if changes.is_empty() {
+        eprintln!(
+            "Error: No comparisons found in {}; run `cargo bench -- --baseline NAME` first.",
+            results.display(),
+        );
+        exit(2);
+    }
+
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut regressed = false;
+
+    for (benchmark, change) in changes {
+        let status = if change > threshold {
+            regressed = true;
+
+            "regressed"
+        } else {
+            "ok"
+        };
+
+        println!("{}: {:+.1}% {}", benchmark, change, status);
+    }
+
+    if regressed {
+        eprintln!("Error: Some benchmarks are more than {}% slower than the baseline.", threshold);
+        exit(1);
+    }
+}
+
+/// Collects each benchmark's change in mean time from the baseline, as the lower bound of its
+/// confidence interval in percent, from the `change/estimates.json` files under `dir`.
+fn collect_changes(results: &Path, dir: &Path, changes: &mut Vec<(String, f64)>) -> Result<(), String> {
+    let entries = read_dir(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+
+    for entry in entries {
+        let path = entry.map_err(|err| format!("{}: {}", dir.display(), err))?.path();
+
+        if !path.is_dir() || path.file_name() == Some("report".as_ref()) {
+            continue;
+        }
+
+        let estimates = path.join("change").join("estimates.json");
+
+        if estimates.is_file() {
+            let contents = read_to_string(&estimates).map_err(|err| format!("{}: {}", estimates.display(), err))?;
+            let json: Value = serde_json::from_str(&contents)
+                .map_err(|err| format!("{}: {}", estimates.display(), err))?;
+            let lower_bound = json["mean"]["confidence_interval"]["lower_bound"]
+                .as_f64()
+                .ok_or_else(|| format!("{}: Missing the mean's confidence interval.", estimates.display()))?;
+            let benchmark = path.strip_prefix(results).unwrap_or(&path).display().to_string();
+
+            changes.push((benchmark, lower_bound * 100.0));
+        } else {
+            collect_changes(results, &path, changes)?;
+        }
+    }
+
+    Ok(())
+}
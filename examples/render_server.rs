@@ -0,0 +1,33 @@
+//! A minimal render service built on ktmpl's HTTP handler.
+//!
+//! Run it with `cargo run --example render_server --features ktmpl-server`, then render a
+//! template with:
+//!
+//! ```text
+//! curl --data '{"template": "...", "parameters": {"NAME": "value"}}' http://127.0.0.1:8080/render
+//! ```
+//!
+//! Set `KTMPL_SERVER_ADDRESS` to listen on a different address.
+
+extern crate axum;
+extern crate ktmpl;
+extern crate tokio;
+
+use std::env::var;
+use std::future::IntoFuture;
+
+use ktmpl::{ServerOptions, router};
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+fn main() {
+    let address = var("KTMPL_SERVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let runtime = Runtime::new().expect("Failed to start the Tokio runtime.");
+    let listener = runtime.block_on(TcpListener::bind(&address)).expect("Failed to bind the address.");
+
+    println!("Rendering templates on http://{}/render", address);
+
+    runtime
+        .block_on(axum::serve(listener, router(ServerOptions::default())).into_future())
+        .expect("Server failed.");
+}
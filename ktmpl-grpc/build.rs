@@ -0,0 +1,7 @@
+fn main() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc for this platform.");
+
+    std::env::set_var("PROTOC", protoc);
+
+    tonic_prost_build::compile_protos("proto/ktmpl.proto").expect("Failed to compile proto/ktmpl.proto.");
+}
@@ -0,0 +1,319 @@
+//! A gRPC service for rendering ktmpl templates, defined in `proto/ktmpl.proto`.
+//!
+//! `RenderService` implements the `ktmpl.v1.Renderer` service. Serve it with tonic:
+//!
+//! ```no_run
+//! use ktmpl_grpc::{RenderService, RendererServer};
+//!
+//! # async fn serve() -> Result<(), tonic::transport::Error> {
+//! tonic::transport::Server::builder()
+//!     .add_service(RendererServer::new(RenderService))
+//!     .serve("127.0.0.1:50051".parse().unwrap())
+//!     .await
+//! # }
+//! ```
+
+#![deny(missing_debug_implementations)]
+#![deny(missing_docs)]
+
+use ktmpl::{
+    check_control_characters,
+    load_yaml,
+    validate_limits,
+    ControlCharacters,
+    ParameterValue,
+    ParameterValues,
+    Secret,
+    Secrets,
+    Template,
+};
+use tokio::sync::mpsc::{channel, Sender};
+use tokio::task::spawn_blocking;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::adapters::Chain;
+use tokio_stream::{Iter, StreamExt};
+use tonic::{Request, Response, Status};
+
+pub use proto::renderer_server::{Renderer, RendererServer};
+
+/// The messages and service definitions generated from `proto/ktmpl.proto`.
+#[allow(missing_docs)]
+pub mod proto {
+    tonic::include_proto!("ktmpl.v1");
+}
+
+use proto::{RenderRequest, RenderedObject, ValidateResponse};
+
+/// An implementation of the `Renderer` service.
+///
+/// Templates are rendered on Tokio's blocking thread pool, so large templates don't stall other
+/// requests, and each object is streamed to the client as soon as it's processed. As with the
+/// command line utility, parameter values containing control characters are rejected.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderService;
+
+#[tonic::async_trait]
+impl Renderer for RenderService {
+    type RenderTemplateStream = Chain<Iter<std::option::IntoIter<ObjectResult>>, ReceiverStream<ObjectResult>>;
+
+    async fn render_template(
+        &self,
+        request: Request<RenderRequest>,
+    ) -> Result<Response<Self::RenderTemplateStream>, Status> {
+        let request = request.into_inner();
+        let (sender, mut receiver) = channel(OBJECT_BUFFER);
+        let errors = sender.clone();
+        let rendering = spawn_blocking(move || render(request, &sender));
+
+        tokio::spawn(async move {
+            if let Err(err) = rendering.await {
+                let _ = errors.send(Err(Status::internal(err.to_string()))).await;
+            }
+        });
+
+        // A template that fails before any object is processed fails the call rather than the stream.
+        match receiver.recv().await {
+            Some(Err(status)) => Err(status),
+            first => Ok(Response::new(tokio_stream::iter(first).chain(ReceiverStream::new(receiver)))),
+        }
+    }
+
+    async fn validate_template(
+        &self,
+        request: Request<RenderRequest>,
+    ) -> Result<Response<ValidateResponse>, Status> {
+        let request = request.into_inner();
+        let response = spawn_blocking(move || validate(request))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+
+        Ok(Response::new(response))
+    }
+}
+
+/// A processed object, or the error that stopped rendering, as sent to the client.
+type ObjectResult = Result<RenderedObject, Status>;
+
+/// How many processed objects are buffered for a client that's slow to receive them.
+const OBJECT_BUFFER: usize = 16;
+
+/// Renders a request's template, sending each processed object, and then the error that stopped
+/// rendering, if any, to `sender`. Rendering stops if the client goes away.
+fn render(request: RenderRequest, sender: &Sender<ObjectResult>) {
+    let mut index = 0;
+    let result = template(request).and_then(|template| {
+        template
+            .process_each_document(|manifest| {
+                let object = rendered_object(index, manifest);
+
+                index += 1;
+                sender.blocking_send(object).map_err(|_| "The client went away.".to_string())
+            })
+            .map_err(String::from)
+    });
+
+    if let Err(err) = result {
+        let _ = sender.blocking_send(Err(Status::invalid_argument(err)));
+    }
+}
+
+/// Creates a template from a request.
+fn template(request: RenderRequest) -> Result<Template, String> {
+    let mut parameter_values = ParameterValues::new();
+
+    for (name, value) in request.parameters {
+        parameter_values.insert(name, ParameterValue::Plain(value));
+    }
+
+    for (name, value) in request.base64_parameters {
+        if parameter_values.contains_key(&name) {
+            return Err(format!("Parameter {} was given both a plain text and a Base64 value.", name));
+        }
+
+        parameter_values.insert(name, ParameterValue::Encoded(value));
+    }
+
+    check_control_characters(&mut parameter_values, ControlCharacters::Reject, &[])?;
+
+    let secrets = if request.secrets.is_empty() {
+        None
+    } else {
+        Some(
+            request
+                .secrets
+                .into_iter()
+                .map(|secret| Secret { name: secret.name, namespace: secret.namespace })
+                .collect::<Secrets>(),
+        )
+    };
+
+    let mut template = Template::new(request.template, parameter_values, secrets)?;
+
+    template.set_redact_secrets(request.redact_secrets);
+
+    Ok(template)
+}
+
+fn rendered_object(index: usize, manifest: String) -> Result<RenderedObject, Status> {
+    let object = load_yaml(&manifest)
+        .map_err(|err| Status::internal(err.to_string()))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Status::internal("An object was emitted as an empty document."))?;
+    let field = |value: Option<&str>| value.unwrap_or_default().to_string();
+
+    Ok(RenderedObject {
+        index: index as u32,
+        kind: field(object["kind"].as_str()),
+        name: field(object["metadata"]["name"].as_str()),
+        namespace: field(object["metadata"]["namespace"].as_str()),
+        manifest,
+    })
+}
+
+fn validate(request: RenderRequest) -> ValidateResponse {
+    let result = template(request)
+        .and_then(|template| template.process().map_err(String::from))
+        .and_then(|manifests| load_yaml(&manifests).map_err(|err| err.to_string()))
+        .and_then(|objects| validate_limits(&objects).map(|_| objects.len()));
+
+    match result {
+        Ok(object_count) => ValidateResponse { valid: true, errors: vec![], object_count: object_count as u32 },
+        Err(err) => ValidateResponse { valid: false, errors: vec![err], object_count: 0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_stream::StreamExt;
+    use tonic::Request;
+
+    use super::proto::{RenderRequest, Secret};
+    use super::{RenderService, Renderer};
+
+    const TEMPLATE: &str = r#"
+---
+kind: "Template"
+apiVersion: "v1"
+metadata:
+  name: "example"
+objects:
+  - kind: "ConfigMap"
+    apiVersion: "v1"
+    metadata:
+      name: "$(NAME)"
+      namespace: "apps"
+    data:
+      greeting: "hello"
+  - kind: "Secret"
+    apiVersion: "v1"
+    metadata:
+      name: "credentials"
+    data:
+      password: "$(PASSWORD)"
+parameters:
+  - name: "NAME"
+    required: true
+  - name: "PASSWORD"
+    required: true
+"#;
+
+    fn request() -> RenderRequest {
+        RenderRequest {
+            template: TEMPLATE.to_string(),
+            parameters: [("NAME", "settings"), ("PASSWORD", "hunter2")]
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            secrets: vec![Secret { name: "credentials".to_string(), namespace: None }],
+            ..RenderRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn render_template_streams_objects() {
+        let response = RenderService.render_template(Request::new(request())).await.unwrap();
+        let objects: Vec<_> = response.into_inner().collect::<Result<_, _>>().await.unwrap();
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!((objects[0].index, objects[0].kind.as_str()), (0, "ConfigMap"));
+        assert_eq!((objects[0].name.as_str(), objects[0].namespace.as_str()), ("settings", "apps"));
+        assert!(objects[0].manifest.starts_with("---\n"));
+        assert_eq!((objects[1].index, objects[1].name.as_str()), (1, "credentials"));
+        assert!(objects[1].manifest.contains("aHVudGVyMg=="));
+    }
+
+    #[tokio::test]
+    async fn render_template_rejects_missing_parameters() {
+        let mut request = request();
+
+        request.parameters.remove("NAME");
+
+        let status = RenderService.render_template(Request::new(request)).await.err().unwrap();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn validate_template_reports_errors() {
+        let response = RenderService.validate_template(Request::new(request())).await.unwrap().into_inner();
+
+        assert!(response.valid);
+        assert_eq!(response.object_count, 2);
+
+        let mut request = request();
+
+        request.parameters.insert("NAME".to_string(), "x".repeat(300));
+
+        let response = RenderService.validate_template(Request::new(request)).await.unwrap().into_inner();
+
+        assert!(!response.valid);
+        assert_eq!(response.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn render_template_rejects_conflicting_values_and_control_characters() {
+        let mut request = request();
+
+        // Base64 values are interpolated as given, so the secret must not be encoded again.
+        request.secrets.clear();
+        request.parameters.remove("PASSWORD");
+        request.base64_parameters.insert("PASSWORD".to_string(), "aHVudGVyMg==".to_string());
+
+        let response = RenderService.render_template(Request::new(request.clone())).await.unwrap();
+        let objects: Vec<_> = response.into_inner().collect::<Result<_, _>>().await.unwrap();
+
+        assert!(objects[1].manifest.contains("aHVudGVyMg=="));
+
+        request.parameters.insert("PASSWORD".to_string(), "hunter2".to_string());
+
+        let status = RenderService.render_template(Request::new(request)).await.err().unwrap();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("both a plain text and a Base64 value"));
+
+        let mut request = self::request();
+
+        request.parameters.insert("NAME".to_string(), "settings\u{7}".to_string());
+
+        let status = RenderService.render_template(Request::new(request)).await.err().unwrap();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn render_template_streams_objects_before_an_error() {
+        let mut request = request();
+
+        // PASSWORD is only found to have no value once the second object is interpolated.
+        request.template = TEMPLATE.replace("    required: true\n", "");
+        request.secrets.clear();
+        request.parameters.remove("PASSWORD");
+
+        let mut objects = RenderService.render_template(Request::new(request)).await.unwrap().into_inner();
+
+        assert_eq!(objects.next().await.unwrap().unwrap().kind, "ConfigMap");
+        assert_eq!(objects.next().await.unwrap().unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert!(objects.next().await.is_none());
+    }
+}
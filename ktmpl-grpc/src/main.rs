@@ -0,0 +1,48 @@
+use std::env::var;
+use std::net::SocketAddr;
+use std::process::exit;
+
+use ktmpl_grpc::{RenderService, RendererServer};
+use log::{LevelFilter, Log, Metadata, Record, error, info};
+use tonic::transport::Server;
+
+/// Writes log records to stderr.
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{} [{}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[tokio::main]
+async fn main() {
+    log::set_logger(&LOGGER).expect("Failed to set the logger.");
+    log::set_max_level(LevelFilter::Info);
+
+    let address = var("KTMPL_GRPC_ADDRESS").unwrap_or_else(|_| "127.0.0.1:50051".to_string());
+    let address: SocketAddr = match address.parse() {
+        Ok(address) => address,
+        Err(err) => {
+            error!("Invalid KTMPL_GRPC_ADDRESS {}: {}", address, err);
+            exit(1);
+        }
+    };
+
+    info!("Serving ktmpl.v1.Renderer on {}", address);
+
+    if let Err(err) = Server::builder().add_service(RendererServer::new(RenderService)).serve(address).await {
+        error!("Server failed: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+use checksum::sha256_hex;
+use source::object_line_ranges;
+use terraform::json_escape;
+
+/// A CI system whose annotation format problems can be reported in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CiAnnotations {
+    /// GitHub Actions workflow commands, e.g. `::error file=web.yml,line=12::...`, which are shown
+    /// inline on pull requests.
+    GitHub,
+    /// A GitLab Code Quality report, which is shown on merge requests when saved as a
+    /// `codequality` report artifact.
+    GitLab,
+}
+
+/// A problem found in a template, located as precisely as its error message allows.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Annotation {
+    /// A summary of the kind of problem, e.g. `Objects exceed Kubernetes limits`.
+    pub title: Option<String>,
+    /// The problem itself.
+    pub message: String,
+    /// The one-based line of the template the problem is on, if known.
+    pub line: Option<usize>,
+}
+
+/// Splits an error message into annotations, locating each in the template's source if given.
+///
+/// Messages listing several violations, such as those of `validate_limits` and `validate_crds`,
+/// give an annotation per violation, titled with the message's first line. Violations of an
+/// object, e.g. `objects[2].metadata.name: ...`, are placed on the line starting that object in
+/// a block-style template, and YAML syntax errors on the line they report.
+pub fn annotations(message: &str, template: Option<&str>) -> Vec<Annotation> {
+    let mut lines = message.lines();
+    let first = lines.next().unwrap_or_default();
+    let violations: Vec<&str> = lines.filter(|line| !line.trim().is_empty()).collect();
+
+    if violations.is_empty() || !first.ends_with(':') {
+        return vec![annotation(None, message, template)];
+    }
+
+    let title = first.trim_end_matches(':');
+
+    violations.into_iter().map(|violation| annotation(Some(title), violation.trim(), template)).collect()
+}
+
+/// Formats annotations of a template file for a CI system.
+///
+/// GitHub annotations are workflow commands, one per line, to be written to the job's output.
+/// GitLab annotations are a Code Quality report, a JSON array to be saved as an artifact.
+pub fn format_annotations(format: CiAnnotations, file: &str, annotations: &[Annotation]) -> String {
+    match format {
+        CiAnnotations::GitHub => annotations
+            .iter()
+            .map(|annotation| {
+                let mut properties = format!("file={}", escape_property(file));
+
+                if let Some(line) = annotation.line {
+                    properties.push_str(&format!(",line={}", line));
+                }
+
+                if let Some(ref title) = annotation.title {
+                    properties.push_str(&format!(",title={}", escape_property(title)));
+                }
+
+                format!("::error {}::{}", properties, escape_data(&annotation.message))
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        CiAnnotations::GitLab => {
+            let issues: Vec<String> = annotations
+                .iter()
+                .map(|annotation| {
+                    let line = annotation.line.unwrap_or(1);
+                    let description = match annotation.title {
+                        Some(ref title) => format!("{}: {}", title, annotation.message),
+                        None => annotation.message.clone(),
+                    };
+                    let fingerprint = sha256_hex(format!("{}\n{}\n{}", file, line, description).as_bytes());
+
+                    format!(
+                        "{{\"check_name\":\"ktmpl\",\"description\":\"{}\",\"fingerprint\":\"{}\",\
+                         \"location\":{{\"lines\":{{\"begin\":{}}},\"path\":\"{}\"}},\"severity\":\"major\"}}",
+                        json_escape(&description),
+                        fingerprint,
+                        line,
+                        json_escape(file),
+                    )
+                })
+                .collect();
+
+            format!("[{}]", issues.join(","))
+        }
+    }
+}
+
+impl FromStr for CiAnnotations {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(CiAnnotations::GitHub),
+            "gitlab" => Ok(CiAnnotations::GitLab),
+            _ => Err("CI annotations must be github or gitlab.".to_owned()),
+        }
+    }
+}
+
+fn annotation(title: Option<&str>, message: &str, template: Option<&str>) -> Annotation {
+    lazy_static! {
+        static ref OBJECT: Regex = Regex::new(r"^objects\[(\d+)\]").expect("Failed to compile regex.");
+        static ref SYNTAX_ERROR: Regex = Regex::new(r" at line (\d+) column \d+$").expect("Failed to compile regex.");
+    }
+
+    let line = if let Some(captures) = SYNTAX_ERROR.captures(message) {
+        captures[1].parse().ok()
+    } else {
+        OBJECT
+            .captures(message)
+            .and_then(|captures| captures[1].parse().ok())
+            .and_then(|index| template.and_then(|template| object_line(template, index)))
+    };
+
+    Annotation { title: title.map(|title| title.to_string()), message: message.to_string(), line }
+}
+
+/// Returns the one-based line on which the object at an index of a block-style template's
+/// `objects` sequence starts.
+fn object_line(template: &str, index: usize) -> Option<usize> {
+    object_line_ranges(template).get(index).map(|&(first, _)| first)
+}
+
+/// Escapes the message of a GitHub workflow command.
+fn escape_data(data: &str) -> String {
+    data.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a property of a GitHub workflow command.
+fn escape_property(property: &str) -> String {
+    escape_data(property).replace(':', "%3A").replace(',', "%2C")
+}
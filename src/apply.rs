@@ -0,0 +1,428 @@
+use std::time::{Duration, Instant};
+
+use yaml::yaml::Hash;
+use yaml::{Yaml, YamlLoader};
+
+use kubectl;
+use loader::load_yaml;
+use progress::Outcome;
+use template::dump;
+
+const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+const MANAGED_BY: &str = "ktmpl";
+
+/// Options for applying processed objects to a cluster.
+#[derive(Debug, Default)]
+pub struct ApplyOptions {
+    /// The kubeconfig context to apply to, or `None` for the current context.
+    pub context: Option<String>,
+    /// The field manager recorded as the owner of applied fields.
+    pub field_manager: Option<String>,
+    /// Whether to take ownership of fields managed by others when using server-side apply.
+    pub force_conflicts: bool,
+    /// A label selector identifying the objects previously applied from the same template.
+    /// When set, applied objects are labeled as managed by ktmpl, and objects matching the
+    /// selector with that label which are no longer part of the template are deleted.
+    pub prune_selector: Option<String>,
+    /// Whether to use server-side apply rather than client-side apply.
+    pub server_side: bool,
+}
+
+/// Applies objects to the cluster using `kubectl apply`, returning `kubectl`'s output.
+///
+/// # Errors
+///
+/// Returns an error if the options are inconsistent, or `kubectl` could not be run or failed to
+/// apply the objects or prune old ones.
+pub fn apply(objects: &[Yaml], options: &ApplyOptions) -> Result<String, String> {
+    if options.force_conflicts && !options.server_side {
+        return Err("Forcing conflicts requires server-side apply.".to_string());
+    }
+
+    let objects = labeled(objects, options)?;
+    let mut output = kubectl::run_with_input(&apply_args(options), Some(&dump(objects.clone())?))?;
+
+    if let Some(ref selector) = options.prune_selector {
+        output.push_str(&prune(&objects, selector, options)?);
+    }
+
+    Ok(output)
+}
+
+/// Applies objects to the cluster like `apply`, but one at a time, calling `report` with each
+/// object and the outcome of applying it, so progress can be shown. An object `kubectl` reports
+/// as unchanged is skipped. Returns `kubectl`'s output.
+///
+/// Every object is tried even if applying one fails, but nothing is pruned if any failed.
+///
+/// # Errors
+///
+/// Returns the first error applying an object, or an error if the options are inconsistent or
+/// pruning failed.
+pub fn apply_each<F>(objects: &[Yaml], options: &ApplyOptions, mut report: F) -> Result<String, String>
+where
+    F: FnMut(&Yaml, &Outcome),
+{
+    if options.force_conflicts && !options.server_side {
+        return Err("Forcing conflicts requires server-side apply.".to_string());
+    }
+
+    let objects = labeled(objects, options)?;
+    let args = apply_args(options);
+    let mut output = String::new();
+    let mut first_error = None;
+
+    for object in &objects {
+        let outcome = match kubectl::run_with_input(&args, Some(&dump(vec![object.clone()])?)) {
+            Ok(applied) => {
+                let outcome = if applied.trim_end().ends_with(" unchanged") {
+                    Outcome::Skipped("unchanged".to_string())
+                } else {
+                    Outcome::Done
+                };
+
+                output.push_str(&applied);
+
+                outcome
+            }
+            Err(err) => {
+                first_error.get_or_insert_with(|| err.clone());
+
+                Outcome::Failed(err)
+            }
+        };
+
+        report(object, &outcome);
+    }
+
+    if let Some(err) = first_error {
+        return Err(err);
+    }
+
+    if let Some(ref selector) = options.prune_selector {
+        output.push_str(&prune(&objects, selector, options)?);
+    }
+
+    Ok(output)
+}
+
+/// Returns the objects to apply, labeled as managed by ktmpl when pruning.
+fn labeled(objects: &[Yaml], options: &ApplyOptions) -> Result<Vec<Yaml>, String> {
+    let mut objects = objects.to_vec();
+
+    if options.prune_selector.is_some() {
+        for object in objects.iter_mut() {
+            set_managed_by_label(object)?;
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Returns the arguments for `kubectl` to apply objects read from stdin.
+fn apply_args(options: &ApplyOptions) -> Vec<&str> {
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["apply", "--filename", "-"]);
+
+    if options.server_side {
+        args.push("--server-side");
+    }
+
+    if let Some(ref field_manager) = options.field_manager {
+        args.extend(["--field-manager", field_manager]);
+    }
+
+    if options.force_conflicts {
+        args.push("--force-conflicts");
+    }
+
+    args
+}
+
+/// Labels an object as managed by ktmpl, so pruning can find it once it's no longer rendered.
+pub fn set_managed_by_label(object: &mut Yaml) -> Result<(), String> {
+    let metadata = match *object {
+        Yaml::Hash(ref mut hash) => hash
+            .entry(Yaml::String("metadata".to_string()))
+            .or_insert_with(|| Yaml::Hash(Default::default())),
+        _ => return Err("Encountered a resource that is not a hash.".to_string()),
+    };
+
+    let labels = match *metadata {
+        Yaml::Hash(ref mut metadata) => metadata
+            .entry(Yaml::String("labels".to_string()))
+            .or_insert_with(|| Yaml::Hash(Default::default())),
+        _ => return Err("Encountered a resource with a non-hash \"metadata\" field.".to_string()),
+    };
+
+    match *labels {
+        Yaml::Hash(ref mut labels) => {
+            labels.insert(Yaml::String(MANAGED_BY_LABEL.to_string()), Yaml::String(MANAGED_BY.to_string()));
+
+            Ok(())
+        }
+        _ => Err("Encountered a resource with a non-hash \"metadata.labels\" field.".to_string()),
+    }
+}
+
+/// Deletes objects matching the selector and managed by ktmpl that aren't among `objects`.
+fn prune(objects: &[Yaml], selector: &str, options: &ApplyOptions) -> Result<String, String> {
+    let mut output = String::new();
+
+    for object in prunable(objects, selector, options)? {
+        output.push_str(&delete(&object, options)?);
+    }
+
+    Ok(output)
+}
+
+/// Returns the objects in the cluster matching the selector and managed by ktmpl that aren't
+/// among `objects`, which pruning deletes.
+fn prunable(objects: &[Yaml], selector: &str, options: &ApplyOptions) -> Result<Vec<Yaml>, String> {
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["api-resources", "--verbs", "list,delete", "--output", "name"]);
+
+    let resources = kubectl::run(&args)?;
+    let resources: Vec<&str> = resources.lines().filter(|line| !line.is_empty()).collect();
+    let selector = format!("{},{}={}", selector, MANAGED_BY_LABEL, MANAGED_BY);
+    let resource_list = resources.join(",");
+
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend([
+        "get",
+        &resource_list,
+        "--all-namespaces",
+        "--selector",
+        &selector,
+        "--output",
+        "yaml",
+    ]);
+
+    let listed = YamlLoader::load_from_str(&kubectl::run(&args)?).map_err(|err| err.to_string())?;
+    let items = listed.first().and_then(|list| list["items"].as_vec()).cloned().unwrap_or_default();
+
+    Ok(items.into_iter().filter(|item| !is_rendered(item, objects)).collect())
+}
+
+/// Returns whether an object in the cluster is among the rendered `objects`, matching an object
+/// without a namespace in any namespace.
+pub fn is_rendered(item: &Yaml, objects: &[Yaml]) -> bool {
+    let kind = item["kind"].as_str().unwrap_or_default();
+    let name = item["metadata"]["name"].as_str().unwrap_or_default();
+    let namespace = item["metadata"]["namespace"].as_str();
+
+    objects.iter().any(|object| {
+        object["kind"].as_str() == Some(kind)
+            && object["metadata"]["name"].as_str() == Some(name)
+            && (object["metadata"]["namespace"].as_str().is_none()
+                || object["metadata"]["namespace"].as_str() == namespace)
+    })
+}
+
+/// Deletes an object from the cluster, if it exists.
+fn delete(object: &Yaml, options: &ApplyOptions) -> Result<String, String> {
+    let resource = qualified_kind(object);
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["delete", &resource, object["metadata"]["name"].as_str().unwrap_or_default(), "--ignore-not-found"]);
+
+    if let Some(namespace) = object["metadata"]["namespace"].as_str() {
+        args.extend(["--namespace", namespace]);
+    }
+
+    kubectl::run(&args)
+}
+
+/// Returns an object's kind qualified with its API version and group, e.g. `Deployment.v1.apps`,
+/// so `kubectl` acts on the right resource.
+pub fn qualified_kind(object: &Yaml) -> String {
+    let kind = object["kind"].as_str().unwrap_or_default();
+
+    match object["apiVersion"].as_str().and_then(|api_version| api_version.find('/').map(|index| {
+        (&api_version[..index], &api_version[index + 1..])
+    })) {
+        Some((group, version)) => format!("{}.{}.{}", kind, version, group),
+        None => kind.to_string(),
+    }
+}
+
+/// Captures the current state of the objects about to be applied, returning a rollback bundle
+/// that `rollback` can use to restore it.
+///
+/// The bundle is a YAML document listing the objects to restore, with server-populated fields
+/// removed, and the objects that don't exist yet, which rolling back deletes. When pruning, the
+/// objects the apply will delete are captured to be restored too.
+///
+/// # Errors
+///
+/// Returns an error if `kubectl` could not fetch an object.
+pub fn capture_rollback(objects: &[Yaml], options: &ApplyOptions) -> Result<String, String> {
+    let mut captured = vec![];
+
+    for object in objects {
+        let resource = qualified_kind(object);
+        let name = object["metadata"]["name"].as_str().unwrap_or_default();
+        let mut args = kubectl::context_args(options.context.as_deref());
+
+        args.extend(["get", &resource, name, "--ignore-not-found", "--output", "yaml"]);
+
+        if let Some(namespace) = object["metadata"]["namespace"].as_str() {
+            args.extend(["--namespace", namespace]);
+        }
+
+        let current = load_yaml(&kubectl::run(&args)?).map_err(|err| err.to_string())?;
+
+        captured.push((object, current.into_iter().next()));
+    }
+
+    let pruned = match options.prune_selector {
+        Some(ref selector) => prunable(objects, selector, options)?,
+        None => vec![],
+    };
+
+    rollback_bundle(captured, pruned)
+}
+
+/// Returns a rollback bundle for objects about to be applied, given each object's current state
+/// in the cluster, if it exists, and the objects pruning will delete.
+pub fn rollback_bundle(captured: Vec<(&Yaml, Option<Yaml>)>, pruned: Vec<Yaml>) -> Result<String, String> {
+    let mut restore = vec![];
+    let mut delete = vec![];
+
+    for (object, current) in captured {
+        match current {
+            Some(mut current) => {
+                kubectl::strip_server_fields(&mut current);
+                restore.push(current);
+            }
+            None => {
+                let name = object["metadata"]["name"].as_str().unwrap_or_default();
+                let mut metadata = Hash::new();
+
+                metadata.insert(ystring("name"), ystring(name));
+
+                if let Some(namespace) = object["metadata"]["namespace"].as_str() {
+                    metadata.insert(ystring("namespace"), ystring(namespace));
+                }
+
+                let mut reference = Hash::new();
+
+                reference.insert(ystring("apiVersion"), object["apiVersion"].clone());
+                reference.insert(ystring("kind"), object["kind"].clone());
+                reference.insert(ystring("metadata"), Yaml::Hash(metadata));
+                delete.push(Yaml::Hash(reference));
+            }
+        }
+    }
+
+    for mut object in pruned {
+        kubectl::strip_server_fields(&mut object);
+        restore.push(object);
+    }
+
+    let mut bundle = Hash::new();
+
+    bundle.insert(ystring("delete"), Yaml::Array(delete));
+    bundle.insert(ystring("kind"), ystring("RollbackBundle"));
+    bundle.insert(ystring("restore"), Yaml::Array(restore));
+
+    dump(vec![Yaml::Hash(bundle)])
+}
+
+/// Rolls back an apply using a bundle from `capture_rollback`, re-applying the captured objects
+/// and deleting the objects the apply created. Returns `kubectl`'s output.
+///
+/// # Errors
+///
+/// Returns an error if the bundle is invalid or `kubectl` fails.
+pub fn rollback(bundle: &str, options: &ApplyOptions) -> Result<String, String> {
+    let docs = load_yaml(bundle).map_err(|err| err.to_string())?;
+
+    let bundle = match docs.first() {
+        Some(bundle) if docs.len() == 1 && bundle["kind"].as_str() == Some("RollbackBundle") => bundle,
+        _ => return Err("A rollback bundle must be a single RollbackBundle document.".to_string()),
+    };
+
+    let mut output = String::new();
+
+    if let Some(restore) = bundle["restore"].as_vec().filter(|restore| !restore.is_empty()) {
+        output.push_str(&apply(restore, options)?);
+    }
+
+    for object in bundle["delete"].as_vec().unwrap_or(&vec![]) {
+        output.push_str(&delete(object, options)?);
+    }
+
+    Ok(output)
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
+
+/// Waits for applied Deployments and StatefulSets to finish rolling out and Jobs to complete,
+/// returning `kubectl`'s progress output.
+///
+/// # Errors
+///
+/// Returns an error if an object failed or did not become ready within the timeout, including
+/// the object's recent events.
+pub fn wait_for_ready(objects: &[Yaml], timeout: Duration, options: &ApplyOptions) -> Result<String, String> {
+    let deadline = Instant::now() + timeout;
+    let mut output = String::new();
+
+    for object in objects {
+        let kind = object["kind"].as_str().unwrap_or_default();
+        let name = object["metadata"]["name"].as_str().unwrap_or_default();
+        let namespace = object["metadata"]["namespace"].as_str();
+        let resource = format!("{}/{}", kind.to_lowercase(), name);
+        let remaining = format!(
+            "{}s",
+            deadline.saturating_duration_since(Instant::now()).as_secs().max(1)
+        );
+
+        let mut args = kubectl::context_args(options.context.as_deref());
+
+        match kind {
+            "Deployment" | "StatefulSet" | "DaemonSet" => {
+                args.extend(["rollout", "status", &resource, "--timeout", &remaining]);
+            }
+            "Job" => {
+                args.extend(["wait", &resource, "--for", "condition=complete", "--timeout", &remaining]);
+            }
+            _ => continue,
+        }
+
+        if let Some(namespace) = namespace {
+            args.extend(["--namespace", namespace]);
+        }
+
+        info!("Waiting for {} to become ready", resource);
+
+        match kubectl::run(&args) {
+            Ok(progress) => output.push_str(&progress),
+            Err(error) => return Err(format!("{}\n{}", error, events(name, namespace, options))),
+        }
+    }
+
+    Ok(output)
+}
+
+fn events(name: &str, namespace: Option<&str>, options: &ApplyOptions) -> String {
+    let field_selector = format!("involvedObject.name={}", name);
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["get", "events", "--field-selector", &field_selector, "--sort-by", ".lastTimestamp"]);
+
+    if let Some(namespace) = namespace {
+        args.extend(["--namespace", namespace]);
+    }
+
+    match kubectl::run(&args) {
+        Ok(events) => format!("Events for {}:\n{}", name, events.trim_end()),
+        Err(error) => format!("Events for {} could not be fetched: {}", name, error),
+    }
+}
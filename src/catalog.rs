@@ -0,0 +1,93 @@
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+use yaml::YamlLoader;
+
+use parameter::Parameter;
+use template::template_parameters;
+
+/// A template found while scanning a directory tree.
+#[derive(Debug)]
+pub struct CatalogEntry {
+    /// The template's `metadata.name`.
+    pub name: String,
+    /// The template's `description` annotation, if any.
+    pub description: Option<String>,
+    /// The parameters declared by the template, in declaration order.
+    pub parameters: Vec<Parameter>,
+    /// The path of the template file.
+    pub path: PathBuf,
+}
+
+/// Scans a directory tree for templates, returning an entry for each, sorted by path.
+///
+/// Files with a `.yml` or `.yaml` extension are considered. Files that are not a single YAML
+/// document of kind `Template` are skipped, as are templates that can't be parsed, with a
+/// warning.
+///
+/// # Errors
+///
+/// Returns an error if a directory can't be read.
+pub fn catalog(directory: &Path) -> Result<Vec<CatalogEntry>, String> {
+    Ok(template_paths(directory)?.iter().filter_map(|path| load_entry(path)).collect())
+}
+
+/// Scans a directory tree for files with a `.yml` or `.yaml` extension, returning their paths,
+/// sorted.
+///
+/// # Errors
+///
+/// Returns an error if a directory can't be read.
+pub fn template_paths(directory: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut paths = vec![];
+
+    scan(directory, &mut paths)?;
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn scan(directory: &Path, paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    let dir_entries = read_dir(directory)
+        .map_err(|err| format!("Failed to read {}: {}", directory.display(), err))?;
+
+    for dir_entry in dir_entries {
+        let path = dir_entry.map_err(|err| err.to_string())?.path();
+
+        if path.is_dir() {
+            scan(&path, paths)?;
+        } else if path.extension().is_some_and(|extension| extension == "yml" || extension == "yaml") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn load_entry(path: &Path) -> Option<CatalogEntry> {
+    let contents = read_to_string(path).ok()?;
+    let docs = YamlLoader::load_from_str(&contents).ok()?;
+
+    if docs.len() != 1 || docs[0]["kind"].as_str() != Some("Template") {
+        return None;
+    }
+
+    let parameters = match template_parameters(&contents) {
+        Ok(parameters) => parameters,
+        Err(error) => {
+            warn!("Skipping {}: {}", path.display(), error);
+
+            return None;
+        }
+    };
+
+    Some(CatalogEntry {
+        name: docs[0]["metadata"]["name"].as_str().unwrap_or_default().to_string(),
+        description: docs[0]["metadata"]["annotations"]["description"].as_str().map(|description| {
+            description.to_string()
+        }),
+        parameters,
+        path: path.to_path_buf(),
+    })
+}
@@ -0,0 +1,24 @@
+use sha2::{Digest, Sha256};
+
+/// Verifies that the SHA-256 digest of some content matches a pinned, hex-encoded digest.
+///
+/// # Errors
+///
+/// Returns an error naming the actual digest if it does not match the expected one.
+pub fn verify_sha256(contents: &[u8], expected: &str) -> Result<(), String> {
+    let actual = sha256_hex(contents);
+
+    if actual == expected.trim().to_lowercase() {
+        Ok(())
+    } else {
+        Err(format!("SHA-256 digest {} does not match the pinned digest {}.", actual, expected))
+    }
+}
+
+/// Returns the hex-encoded SHA-256 digest of some content.
+pub fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,306 @@
+use std::str::from_utf8;
+
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use checksum::sha256_hex;
+use loader::load_yaml_or_json;
+use processor::unresolved_placeholders;
+
+/// The bytes every compiled template starts with.
+const MAGIC: &[u8] = b"KTMPLC";
+
+/// The version of the binary format, increased whenever it changes.
+const FORMAT_VERSION: u8 = 1;
+
+const NULL: u8 = 0;
+const BAD_VALUE: u8 = 1;
+const FALSE: u8 = 2;
+const TRUE: u8 = 3;
+const INTEGER: u8 = 4;
+const REAL: u8 = 5;
+const STRING: u8 = 6;
+const ARRAY: u8 = 7;
+const HASH: u8 = 8;
+const ALIAS: u8 = 9;
+
+/// A template that has already been parsed, which can be saved to and loaded from a compact binary
+/// cache so unchanged templates don't need to be parsed again.
+///
+/// A compiled template holds the template's document, the names of the placeholders its objects
+/// refer to, and the SHA-256 digest of the source it was compiled from. It doesn't hold any
+/// parameter values; see `Template::from_compiled`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledTemplate {
+    document: Yaml,
+    placeholders: Vec<String>,
+    source_sha256: String,
+}
+
+impl CompiledTemplate {
+    /// Parses a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents are not valid YAML or JSON, or contain more than one document.
+    pub fn compile(template_contents: &str) -> Result<Self, String> {
+        let mut docs = load_yaml_or_json(template_contents)?;
+
+        if docs.len() != 1 {
+            return Err("Only one YAML document can be present in the template.".to_owned());
+        }
+
+        let document = docs.remove(0);
+        let mut placeholders = vec![];
+
+        if let Some(objects) = document["objects"].as_vec() {
+            for object in objects {
+                unresolved_placeholders(object, &mut placeholders);
+            }
+        }
+
+        placeholders.sort();
+        placeholders.dedup();
+
+        Ok(CompiledTemplate {
+            document,
+            placeholders,
+            source_sha256: sha256_hex(template_contents.as_bytes()),
+        })
+    }
+
+    /// Loads a compiled template from the bytes returned by `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a compiled template, or were written by a version of
+    /// ktmpl using a different format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not a compiled template.".to_string());
+        }
+
+        let version = reader.byte()?;
+
+        if version != FORMAT_VERSION {
+            return Err(format!(
+                "Compiled template has format version {}, but this version of ktmpl reads version {}.",
+                version,
+                FORMAT_VERSION,
+            ));
+        }
+
+        let source_sha256 = reader.string()?;
+        let document = reader.node()?;
+        let placeholder_count = reader.length()?;
+        let mut placeholders = Vec::with_capacity(placeholder_count.min(bytes.len()));
+
+        for _ in 0..placeholder_count {
+            placeholders.push(reader.string()?);
+        }
+
+        if reader.position != bytes.len() {
+            return Err("Compiled template has trailing data.".to_string());
+        }
+
+        Ok(CompiledTemplate { document, placeholders, source_sha256 })
+    }
+
+    /// Serializes the compiled template to bytes that `from_bytes` can load.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+
+        bytes.push(FORMAT_VERSION);
+        write_string(&self.source_sha256, &mut bytes);
+        write_node(&self.document, &mut bytes);
+        write_length(self.placeholders.len(), &mut bytes);
+
+        for placeholder in &self.placeholders {
+            write_string(placeholder, &mut bytes);
+        }
+
+        bytes
+    }
+
+    /// Returns whether the template was compiled from the given contents, i.e. whether it's still
+    /// up to date. Hashing the contents is much faster than parsing them.
+    pub fn is_compiled_from(&self, template_contents: &str) -> bool {
+        self.source_sha256 == sha256_hex(template_contents.as_bytes())
+    }
+
+    /// The names of the placeholders in the template's objects, sorted and without duplicates.
+    pub fn placeholders(&self) -> &[String] {
+        &self.placeholders
+    }
+
+    /// Returns the template's document.
+    pub fn document(&self) -> &Yaml {
+        &self.document
+    }
+
+    /// Returns the template's document.
+    pub fn into_document(self) -> Yaml {
+        self.document
+    }
+}
+
+fn write_node(node: &Yaml, bytes: &mut Vec<u8>) {
+    match *node {
+        Yaml::Null => bytes.push(NULL),
+        Yaml::BadValue => bytes.push(BAD_VALUE),
+        Yaml::Boolean(false) => bytes.push(FALSE),
+        Yaml::Boolean(true) => bytes.push(TRUE),
+        Yaml::Integer(integer) => {
+            bytes.push(INTEGER);
+            bytes.extend_from_slice(&integer.to_le_bytes());
+        }
+        Yaml::Real(ref real) => {
+            bytes.push(REAL);
+            write_string(real, bytes);
+        }
+        Yaml::String(ref string) => {
+            bytes.push(STRING);
+            write_string(string, bytes);
+        }
+        Yaml::Array(ref array) => {
+            bytes.push(ARRAY);
+            write_length(array.len(), bytes);
+
+            for item in array {
+                write_node(item, bytes);
+            }
+        }
+        Yaml::Hash(ref hash) => {
+            bytes.push(HASH);
+            write_length(hash.len(), bytes);
+
+            for (key, value) in hash {
+                write_node(key, bytes);
+                write_node(value, bytes);
+            }
+        }
+        Yaml::Alias(anchor_id) => {
+            bytes.push(ALIAS);
+            bytes.extend_from_slice(&(anchor_id as u64).to_le_bytes());
+        }
+    }
+}
+
+fn write_string(string: &str, bytes: &mut Vec<u8>) {
+    write_length(string.len(), bytes);
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// Writes a length as a variable-length integer, seven bits per byte with the high bit set on all
+/// but the last byte.
+fn write_length(mut length: usize, bytes: &mut Vec<u8>) {
+    while length >= 0x80 {
+        bytes.push((length & 0x7f) as u8 | 0x80);
+        length >>= 7;
+    }
+
+    bytes.push(length as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len())
+            .ok_or("Compiled template is truncated.")?;
+        let taken = &self.bytes[self.position..end];
+
+        self.position = end;
+
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let mut buffer = [0; 8];
+
+        buffer.copy_from_slice(self.take(8)?);
+
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let mut length: u64 = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.byte()?;
+
+            if shift > 63 {
+                return Err("Compiled template contains an invalid length.".to_string());
+            }
+
+            length |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        if length > (self.bytes.len() - self.position) as u64 {
+            return Err("Compiled template is truncated.".to_string());
+        }
+
+        Ok(length as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.length()?;
+
+        from_utf8(self.take(length)?)
+            .map(|string| string.to_string())
+            .map_err(|_| "Compiled template contains a string that is not valid UTF-8.".to_string())
+    }
+
+    fn node(&mut self) -> Result<Yaml, String> {
+        let node = match self.byte()? {
+            NULL => Yaml::Null,
+            BAD_VALUE => Yaml::BadValue,
+            FALSE => Yaml::Boolean(false),
+            TRUE => Yaml::Boolean(true),
+            INTEGER => Yaml::Integer(self.u64()? as i64),
+            REAL => Yaml::Real(self.string()?),
+            STRING => Yaml::String(self.string()?),
+            ARRAY => {
+                let length = self.length()?;
+                let mut array = Vec::with_capacity(length);
+
+                for _ in 0..length {
+                    array.push(self.node()?);
+                }
+
+                Yaml::Array(array)
+            }
+            HASH => {
+                let length = self.length()?;
+                let mut hash = Hash::new();
+
+                for _ in 0..length {
+                    let key = self.node()?;
+
+                    hash.insert(key, self.node()?);
+                }
+
+                Yaml::Hash(hash)
+            }
+            ALIAS => Yaml::Alias(self.u64()? as usize),
+            tag => return Err(format!("Compiled template contains an unknown node type {}.", tag)),
+        };
+
+        Ok(node)
+    }
+}
@@ -0,0 +1,215 @@
+use yaml::yaml::Hash;
+use yaml::{Yaml, YamlLoader};
+
+use template::dump;
+
+/// Converts a Docker Compose file into a template, returning the template's YAML.
+///
+/// Each service with an `image` becomes a Deployment, and a Service if it publishes ports. The
+/// service's image, replica count (`deploy.replicas`, or 1), and published ports become
+/// parameters named after the service, e.g. `WEB_IMAGE`, `WEB_REPLICAS`, and `WEB_PORT`, with
+/// the Compose file's values as defaults. `environment` entries are copied into the container.
+/// Other Compose features, such as volumes and networks, are not converted, so the result is a
+/// starting point to edit rather than a finished template.
+///
+/// # Errors
+///
+/// Returns an error if the contents are not a Compose file with a `services` hash, or a service
+/// has no image or has ports that can't be parsed.
+pub fn compose_to_template(compose_contents: &str) -> Result<String, String> {
+    let docs = YamlLoader::load_from_str(compose_contents).map_err(|err| err.to_string())?;
+    let compose = docs.first().ok_or("The Compose file is empty.")?;
+    let services = compose["services"].as_hash().ok_or("The Compose file must have a \"services\" hash.")?;
+
+    let mut objects = vec![];
+    let mut parameters = vec![];
+
+    for (service_name, service) in services {
+        let service_name = service_name.as_str().ok_or("Service names must be strings.")?;
+        let prefix = parameter_prefix(service_name);
+        let image = match service["image"] {
+            Yaml::String(ref image) => image.clone(),
+            _ => return Err(format!("Service {} must have an image; builds can't be converted.", service_name)),
+        };
+        let replicas = service["deploy"]["replicas"].as_i64().unwrap_or(1);
+
+        parameters.push(parameter(&format!("{}_IMAGE", prefix), &format!("Image for {}", service_name), "string", &image));
+        parameters.push(parameter(
+            &format!("{}_REPLICAS", prefix),
+            &format!("Number of {} replicas", service_name),
+            "int",
+            &replicas.to_string(),
+        ));
+
+        let ports = match service["ports"].as_vec() {
+            Some(ports) => ports.iter().map(parse_port).collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Service {}: {}", service_name, err))?,
+            None => vec![],
+        };
+
+        let mut container_ports = vec![];
+        let mut service_ports = vec![];
+
+        for (index, &(published, target)) in ports.iter().enumerate() {
+            let port_parameter = if ports.len() == 1 {
+                format!("{}_PORT", prefix)
+            } else {
+                format!("{}_PORT_{}", prefix, index + 1)
+            };
+
+            parameters.push(parameter(
+                &port_parameter,
+                &format!("Port {} exposes on {}'s Service", target, service_name),
+                "int",
+                &published.to_string(),
+            ));
+
+            container_ports.push(hash(vec![("containerPort", Yaml::Integer(target))]));
+            service_ports.push(hash(vec![
+                ("name", ystring(&format!("port-{}", target))),
+                ("port", ystring(&format!("$(({}))", port_parameter))),
+                ("targetPort", Yaml::Integer(target)),
+            ]));
+        }
+
+        let mut container = vec![
+            ("image", ystring(&format!("$({}_IMAGE)", prefix))),
+            ("name", ystring(service_name)),
+        ];
+
+        if !container_ports.is_empty() {
+            container.push(("ports", Yaml::Array(container_ports)));
+        }
+
+        let env = environment(&service["environment"]);
+
+        if !env.is_empty() {
+            container.push(("env", Yaml::Array(env)));
+        }
+
+        let labels = hash(vec![("app", ystring(service_name))]);
+
+        objects.push(hash(vec![
+            ("apiVersion", ystring("apps/v1")),
+            ("kind", ystring("Deployment")),
+            ("metadata", hash(vec![("labels", labels.clone()), ("name", ystring(service_name))])),
+            ("spec", hash(vec![
+                ("replicas", ystring(&format!("$(({}_REPLICAS))", prefix))),
+                ("selector", hash(vec![("matchLabels", labels.clone())])),
+                ("template", hash(vec![
+                    ("metadata", hash(vec![("labels", labels.clone())])),
+                    ("spec", hash(vec![("containers", Yaml::Array(vec![hash(container)]))])),
+                ])),
+            ])),
+        ]));
+
+        if !service_ports.is_empty() {
+            objects.push(hash(vec![
+                ("apiVersion", ystring("v1")),
+                ("kind", ystring("Service")),
+                ("metadata", hash(vec![("labels", labels.clone()), ("name", ystring(service_name))])),
+                ("spec", hash(vec![("ports", Yaml::Array(service_ports)), ("selector", labels)])),
+            ]));
+        }
+    }
+
+    let name = compose["name"].as_str().unwrap_or("compose");
+
+    dump(vec![hash(vec![
+        ("apiVersion", ystring("v1")),
+        ("kind", ystring("Template")),
+        ("metadata", hash(vec![("name", ystring(name))])),
+        ("objects", Yaml::Array(objects)),
+        ("parameters", Yaml::Array(parameters)),
+    ])])
+}
+
+/// Parses a Compose port, e.g. `80`, `"8080:80"`, or `"127.0.0.1:8080:80/tcp"`, into its
+/// published and target ports.
+fn parse_port(port: &Yaml) -> Result<(i64, i64), String> {
+    let port = match *port {
+        Yaml::Integer(port) => return Ok((port, port)),
+        Yaml::String(ref port) => port.split('/').next().unwrap_or_default().to_string(),
+        Yaml::Hash(_) => match (port["published"].as_i64(), port["target"].as_i64()) {
+            (Some(published), Some(target)) => return Ok((published, target)),
+            (None, Some(target)) => return Ok((target, target)),
+            _ => return Err("Long-form ports must have a numeric \"target\".".to_string()),
+        },
+        _ => return Err("Ports must be numbers, strings, or hashes.".to_string()),
+    };
+
+    let parts: Vec<&str> = port.split(':').collect();
+    let invalid = || format!("Invalid port \"{}\".", port);
+    let target = parts[parts.len() - 1].parse().map_err(|_| invalid())?;
+    let published = if parts.len() > 1 {
+        parts[parts.len() - 2].parse().map_err(|_| invalid())?
+    } else {
+        target
+    };
+
+    Ok((published, target))
+}
+
+fn environment(environment: &Yaml) -> Vec<Yaml> {
+    let variables: Vec<(String, String)> = match *environment {
+        Yaml::Hash(ref variables) => variables
+            .iter()
+            .filter_map(|(name, value)| {
+                let value = match *value {
+                    Yaml::String(ref value) | Yaml::Real(ref value) => value.clone(),
+                    Yaml::Integer(value) => value.to_string(),
+                    Yaml::Boolean(value) => value.to_string(),
+                    _ => String::new(),
+                };
+
+                name.as_str().map(|name| (name.to_string(), value))
+            })
+            .collect(),
+        Yaml::Array(ref variables) => variables
+            .iter()
+            .filter_map(|variable| variable.as_str())
+            .map(|variable| match variable.find('=') {
+                Some(index) => (variable[..index].to_string(), variable[index + 1..].to_string()),
+                None => (variable.to_string(), String::new()),
+            })
+            .collect(),
+        _ => vec![],
+    };
+
+    variables
+        .into_iter()
+        .map(|(name, value)| hash(vec![("name", ystring(&name)), ("value", ystring(&value))]))
+        .collect()
+}
+
+fn parameter(name: &str, description: &str, parameter_type: &str, value: &str) -> Yaml {
+    hash(vec![
+        ("description", ystring(description)),
+        ("name", ystring(name)),
+        ("parameterType", ystring(parameter_type)),
+        ("required", Yaml::Boolean(false)),
+        ("value", ystring(value)),
+    ])
+}
+
+/// Converts a service name like `web-api` to a parameter prefix like `WEB_API`.
+fn parameter_prefix(service_name: &str) -> String {
+    service_name
+        .chars()
+        .map(|character| if character.is_ascii_alphanumeric() { character.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+fn hash(entries: Vec<(&str, Yaml)>) -> Yaml {
+    let mut hash = Hash::new();
+
+    for (key, value) in entries {
+        hash.insert(ystring(key), value);
+    }
+
+    Yaml::Hash(hash)
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
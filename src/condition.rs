@@ -0,0 +1,145 @@
+use regex::Regex;
+use yaml::Yaml;
+use yaml::yaml::Hash;
+
+use parameter::ParamMap;
+use processor::process_yaml;
+
+lazy_static! {
+    static ref CONDITION: Regex =
+        Regex::new(r"^\$\((if|unless)\s+([^\s()]+)\s*\)$").expect("Failed to compile regex.");
+}
+
+/// Returns the parameter named by a conditional key, e.g. `$(if ENABLE_TLS)` or
+/// `$(unless ENABLE_TLS)`, and whether the section is included when the parameter is true.
+pub fn condition(key: &str) -> Option<(&str, bool)> {
+    CONDITION.captures(key).map(|captures| {
+        let name = captures.get(2).expect("Failed to extract regex capture group.").as_str();
+
+        (name, &captures[1] == "if")
+    })
+}
+
+/// Includes or drops the conditional sections of an object, according to the values of the bool
+/// parameters they name.
+///
+/// A hash entry whose key is `$(if NAME)` must have a hash as its value, whose entries are merged
+/// into the enclosing hash if the parameter is true, e.g. to add an Ingress's `tls` block. An array
+/// item that's a hash with only a `$(if NAME)` key is replaced by the key's value, or by each of
+/// its items if it's an array, e.g. to add a sidecar container. `$(unless NAME)` includes the
+/// section if the parameter is false. Conditions naming parameters that aren't declared are left
+/// in place, so conditions on deferred parameters are evaluated by a later render.
+///
+/// # Errors
+///
+/// Returns an error if a parameter's value isn't `true` or `false`, if a conditional hash entry's
+/// value isn't a hash, or if it sets a key the enclosing hash already has.
+pub fn apply_conditions(yaml: &mut Yaml, parameters: &ParamMap) -> Result<(), String> {
+    match *yaml {
+        Yaml::Array(ref mut array) => {
+            let mut items = vec![];
+
+            for mut item in array.drain(..) {
+                let section = match item {
+                    Yaml::Hash(ref hash) if hash.len() == 1 => {
+                        hash.keys().next().and_then(|key| conditional_key(key, parameters))
+                    }
+                    _ => None,
+                };
+
+                match section {
+                    Some((_, name, when)) => {
+                        if is_true(&name, parameters)? != when {
+                            continue;
+                        }
+
+                        let section = item.into_hash().and_then(|hash| hash.into_iter().next());
+
+                        if let Some((_, mut section)) = section {
+                            apply_conditions(&mut section, parameters)?;
+
+                            match section {
+                                Yaml::Array(section) => items.extend(section),
+                                section => items.push(section),
+                            }
+                        }
+                    }
+                    None => {
+                        apply_conditions(&mut item, parameters)?;
+                        items.push(item);
+                    }
+                }
+            }
+
+            *array = items;
+        }
+        Yaml::Hash(ref mut hash) => {
+            // Sections may contain further conditional keys, which are merged in turn.
+            loop {
+                let sections: Vec<(Yaml, String, bool)> =
+                    hash.keys().filter_map(|key| conditional_key(key, parameters)).collect();
+
+                if sections.is_empty() {
+                    break;
+                }
+
+                for (key, name, when) in sections {
+                    let key_string = key.as_str().unwrap_or_default().to_string();
+                    let section = match hash.remove(&key) {
+                        Some(Yaml::Hash(section)) => section,
+                        _ => return Err(format!("The value of {} must be a hash.", key_string)),
+                    };
+
+                    if is_true(&name, parameters)? == when {
+                        merge(hash, section, &key_string)?;
+                    }
+                }
+            }
+
+            for value in hash.values_mut() {
+                apply_conditions(value, parameters)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Returns a conditional key, the declared parameter it names, and whether the section is
+/// included when the parameter is true, if the key is one.
+fn conditional_key(key: &Yaml, parameters: &ParamMap) -> Option<(Yaml, String, bool)> {
+    key.as_str()
+        .and_then(condition)
+        .filter(|&(name, _)| parameters.contains_key(name))
+        .map(|(name, when)| (key.clone(), name.to_string(), when))
+}
+
+fn is_true(name: &str, parameters: &ParamMap) -> Result<bool, String> {
+    let mut value = Yaml::String(format!("$(({}))", name));
+
+    match process_yaml(&mut value, parameters)? {
+        Some(Yaml::Boolean(value)) => Ok(value),
+        _ => {
+            let value = parameters.get(name).and_then(|parameter| parameter.value.clone()).unwrap_or_default();
+
+            Err(format!("Parameter {} must be true or false for a conditional section, not \"{}\".", name, value))
+        }
+    }
+}
+
+fn merge(hash: &mut Hash, section: Hash, condition: &str) -> Result<(), String> {
+    for (key, value) in section {
+        if hash.contains_key(&key) {
+            return Err(format!(
+                "Key {} is set both inside and outside {}.",
+                key.as_str().unwrap_or_default(),
+                condition,
+            ));
+        }
+
+        hash.insert(key, value);
+    }
+
+    Ok(())
+}
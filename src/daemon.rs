@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fs::read;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+
+use serde_json::{Map, Value};
+
+use compiled::CompiledTemplate;
+use loader::decode_text;
+use parameter::{ControlCharacters, check_control_characters};
+use registry::TemplateRegistry;
+use request::{request_parameter_values, request_secrets};
+use template::Template;
+
+/// Renders templates for a long-running process, such as `ktmpl daemon`, keeping each template
+/// file it has parsed in memory so build systems that render thousands of times an hour don't pay
+/// to start ktmpl and parse the template each time.
+///
+/// A template file is read for every request, and only parsed again if its contents changed.
+/// Templates can also be requested by name from a `TemplateRegistry`; see `set_registry`.
+#[derive(Debug, Default)]
+pub struct Daemon {
+    redact_secrets: bool,
+    registry: Option<Arc<TemplateRegistry>>,
+    templates: Mutex<HashMap<String, CompiledTemplate>>,
+}
+
+impl Daemon {
+    /// Creates a daemon with no templates cached.
+    pub fn new() -> Self {
+        Daemon::default()
+    }
+
+    /// Sets whether to replace the values of sensitive parameters in the output; see
+    /// `Template::set_redact_secrets`.
+    pub fn set_redact_secrets(&mut self, redact_secrets: bool) {
+        self.redact_secrets = redact_secrets;
+    }
+
+    /// Sets the registry of templates that requests can name instead of giving a template file.
+    pub fn set_registry(&mut self, registry: Arc<TemplateRegistry>) {
+        self.registry = Some(registry);
+    }
+
+    /// Renders the template file in a JSON request, returning the manifests.
+    ///
+    /// The request is an object with the template's path as `template`, relative to the daemon's
+    /// working directory, or the name of a template in the registry as `name`, and optional
+    /// `parameters` and `secrets` like `render_request`, e.g.:
+    ///
+    /// ```json
+    /// {"template": "deploy/web.yml", "parameters": {"REPLICA_COUNT": 2}}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request is malformed, the template file can't be read, the named
+    /// template isn't in the registry, or the template can't be processed.
+    pub fn render_request(&self, request: &Value) -> Result<String, String> {
+        let mut parameter_values = request_parameter_values(&request["parameters"])?;
+        let secrets = request_secrets(&request["secrets"])?;
+
+        check_control_characters(&mut parameter_values, ControlCharacters::Reject, &[])?;
+
+        let (compiled, file_name) = match (request["template"].as_str(), request["name"].as_str()) {
+            (Some(path), None) => (self.compiled(path)?, path),
+            (None, Some(name)) => (self.registered(name)?, name),
+            _ => return Err("Request must have either a \"template\" or a \"name\" string.".to_string()),
+        };
+        let mut template = Template::from_compiled(compiled, parameter_values, secrets)?;
+
+        template.set_file_name(file_name);
+        template.set_redact_secrets(self.redact_secrets);
+
+        Ok(template.process()?)
+    }
+
+    /// Answers requests on a Unix socket until the process is stopped, handling each connection on
+    /// its own thread.
+    ///
+    /// Each request is a JSON object on a line of its own, as taken by `render_request`, and each
+    /// is answered in order with a line containing either `{"manifests": "..."}` or
+    /// `{"error": "..."}`. A connection can send any number of requests.
+    pub fn serve(self, listener: UnixListener) {
+        let daemon = Arc::new(self);
+
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let daemon = daemon.clone();
+
+                    spawn(move || daemon.answer(stream));
+                }
+                Err(err) => error!("Failed to accept a connection: {}", err),
+            }
+        }
+    }
+
+    /// Answers the requests on a connection until it's closed.
+    fn answer(&self, stream: UnixStream) {
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(err) => {
+                error!("Failed to read from a connection: {}", err);
+
+                return;
+            }
+        };
+        let mut writer = stream;
+
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    debug!("Closing a connection that couldn't be read: {}", err);
+
+                    return;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str(&line) {
+                Ok(request) => match self.render_request(&request) {
+                    Ok(manifests) => response("manifests", manifests),
+                    Err(err) => response("error", err),
+                },
+                Err(err) => response("error", format!("Request is not valid JSON: {}", err)),
+            };
+
+            if let Err(err) = writeln!(writer, "{}", response) {
+                debug!("Closing a connection that couldn't be written to: {}", err);
+
+                return;
+            }
+        }
+    }
+
+    /// Returns the compiled template for a file, parsing it only if it isn't cached or its
+    /// contents changed since it was.
+    fn compiled(&self, path: &str) -> Result<CompiledTemplate, String> {
+        let bytes = read(path).map_err(|err| format!("{}: {}", path, err))?;
+        let contents = decode_text(bytes).map_err(|err| format!("{}: {}", path, err))?;
+
+        if let Some(compiled) = self.templates.lock().expect("template cache lock was poisoned").get(path) {
+            if compiled.is_compiled_from(&contents) {
+                return Ok(compiled.clone());
+            }
+        }
+
+        let compiled = CompiledTemplate::compile(&contents).map_err(|err| format!("{}: {}", path, err))?;
+
+        self.templates
+            .lock()
+            .expect("template cache lock was poisoned")
+            .insert(path.to_string(), compiled.clone());
+
+        Ok(compiled)
+    }
+
+    /// Returns a copy of the template with the given name in the registry.
+    fn registered(&self, name: &str) -> Result<CompiledTemplate, String> {
+        let registry = self.registry.as_ref().ok_or("Templates can't be requested by name without a registry.")?;
+
+        match registry.get(name) {
+            Some(compiled) => Ok((*compiled).clone()),
+            None => Err(format!("No template is named {}.", name)),
+        }
+    }
+}
+
+fn response(key: &str, value: String) -> Value {
+    let mut response = Map::new();
+
+    response.insert(key.to_string(), Value::String(value));
+
+    Value::Object(response)
+}
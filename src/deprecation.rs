@@ -0,0 +1,412 @@
+use std::collections::btree_map::Entry;
+
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+/// A deprecated API: its API version, the kind it applies to (or `*` for every kind), the
+/// Kubernetes minor versions it was deprecated and removed in, and the API version replacing it.
+type DeprecatedApi = (&'static str, &'static str, u32, u32, Option<&'static str>);
+
+/// Deprecated APIs of Kubernetes 1.x, from the Kubernetes deprecated API migration guide.
+const DEPRECATED_APIS: [DeprecatedApi; 32] = [
+    ("admissionregistration.k8s.io/v1beta1", "*", 16, 22, Some("admissionregistration.k8s.io/v1")),
+    ("apiextensions.k8s.io/v1beta1", "CustomResourceDefinition", 16, 22, Some("apiextensions.k8s.io/v1")),
+    ("apiregistration.k8s.io/v1beta1", "APIService", 19, 22, Some("apiregistration.k8s.io/v1")),
+    ("apps/v1beta1", "*", 9, 16, Some("apps/v1")),
+    ("apps/v1beta2", "*", 9, 16, Some("apps/v1")),
+    ("autoscaling/v2beta1", "HorizontalPodAutoscaler", 22, 25, Some("autoscaling/v2")),
+    ("autoscaling/v2beta2", "HorizontalPodAutoscaler", 23, 26, Some("autoscaling/v2")),
+    ("batch/v1beta1", "CronJob", 21, 25, Some("batch/v1")),
+    ("certificates.k8s.io/v1beta1", "CertificateSigningRequest", 19, 22, Some("certificates.k8s.io/v1")),
+    ("coordination.k8s.io/v1beta1", "Lease", 19, 22, Some("coordination.k8s.io/v1")),
+    ("discovery.k8s.io/v1beta1", "EndpointSlice", 21, 25, Some("discovery.k8s.io/v1")),
+    ("events.k8s.io/v1beta1", "Event", 19, 25, Some("events.k8s.io/v1")),
+    ("extensions/v1beta1", "DaemonSet", 9, 16, Some("apps/v1")),
+    ("extensions/v1beta1", "Deployment", 9, 16, Some("apps/v1")),
+    ("extensions/v1beta1", "Ingress", 14, 22, Some("networking.k8s.io/v1")),
+    ("extensions/v1beta1", "NetworkPolicy", 9, 16, Some("networking.k8s.io/v1")),
+    ("extensions/v1beta1", "PodSecurityPolicy", 11, 16, Some("policy/v1beta1")),
+    ("extensions/v1beta1", "ReplicaSet", 9, 16, Some("apps/v1")),
+    ("flowcontrol.apiserver.k8s.io/v1beta1", "*", 23, 26, Some("flowcontrol.apiserver.k8s.io/v1")),
+    ("flowcontrol.apiserver.k8s.io/v1beta2", "*", 26, 29, Some("flowcontrol.apiserver.k8s.io/v1")),
+    ("flowcontrol.apiserver.k8s.io/v1beta3", "*", 29, 32, Some("flowcontrol.apiserver.k8s.io/v1")),
+    ("networking.k8s.io/v1beta1", "Ingress", 19, 22, Some("networking.k8s.io/v1")),
+    ("networking.k8s.io/v1beta1", "IngressClass", 19, 22, Some("networking.k8s.io/v1")),
+    ("node.k8s.io/v1beta1", "RuntimeClass", 20, 25, Some("node.k8s.io/v1")),
+    ("policy/v1beta1", "PodDisruptionBudget", 21, 25, Some("policy/v1")),
+    ("policy/v1beta1", "PodSecurityPolicy", 21, 25, None),
+    ("rbac.authorization.k8s.io/v1beta1", "*", 17, 22, Some("rbac.authorization.k8s.io/v1")),
+    ("scheduling.k8s.io/v1beta1", "PriorityClass", 14, 22, Some("scheduling.k8s.io/v1")),
+    ("storage.k8s.io/v1beta1", "CSIDriver", 19, 22, Some("storage.k8s.io/v1")),
+    ("storage.k8s.io/v1beta1", "CSINode", 17, 22, Some("storage.k8s.io/v1")),
+    ("storage.k8s.io/v1beta1", "CSIStorageCapacity", 24, 27, Some("storage.k8s.io/v1")),
+    ("storage.k8s.io/v1beta1", "StorageClass", 19, 22, Some("storage.k8s.io/v1")),
+];
+
+/// Checks objects' API versions against the Kubernetes version they'll be applied to, e.g.
+/// `1.29`, returning a warning for each object using an API deprecated in that version.
+///
+/// The deprecated APIs are those in the Kubernetes deprecated API migration guide, such as
+/// `extensions/v1beta1` Ingresses and `batch/v1beta1` CronJobs.
+///
+/// # Errors
+///
+/// Returns an error if the version isn't a Kubernetes 1.x version, or listing each object using an
+/// API that version no longer serves.
+pub fn check_api_versions(objects: &[Yaml], target_version: &str) -> Result<Vec<String>, String> {
+    let minor = parse_minor_version(target_version)?;
+    let mut warnings = vec![];
+    let mut removals = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let api_version = object["apiVersion"].as_str().unwrap_or_default();
+        let kind = object["kind"].as_str().unwrap_or_default();
+        let (_, _, deprecated, removed, replacement) = match deprecated_api(object) {
+            Some(deprecation) if deprecation.2 <= minor => *deprecation,
+            _ => continue,
+        };
+        let replacement = replacement_advice(replacement);
+
+        if removed <= minor {
+            removals.push(format!(
+                "objects[{}]: {} {} was removed in Kubernetes 1.{}; {}.",
+                index,
+                api_version,
+                kind,
+                removed,
+                replacement,
+            ));
+        } else {
+            warnings.push(format!(
+                "objects[{}]: {} {} is deprecated since Kubernetes 1.{} and removed in 1.{}; {}.",
+                index,
+                api_version,
+                kind,
+                deprecated,
+                removed,
+                replacement,
+            ));
+        }
+    }
+
+    if removals.is_empty() {
+        Ok(warnings)
+    } else {
+        Err(format!("Objects use APIs Kubernetes 1.{} doesn't serve:\n{}", minor, removals.join("\n")))
+    }
+}
+
+/// Returns a warning for each object using an API deprecated in any Kubernetes version, e.g.
+/// `objects[0]: apps/v1beta1 Deployment is deprecated since Kubernetes 1.9; use apps/v1 instead.`
+pub fn deprecated_apis(objects: &[Yaml]) -> Vec<String> {
+    objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| {
+            deprecated_api(object).map(|&(_, _, deprecated, _, replacement)| {
+                format!(
+                    "objects[{}]: {} {} is deprecated since Kubernetes 1.{}; {}.",
+                    index,
+                    object["apiVersion"].as_str().unwrap_or_default(),
+                    object["kind"].as_str().unwrap_or_default(),
+                    deprecated,
+                    replacement_advice(replacement),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the deprecated API an object uses, if any.
+fn deprecated_api(object: &Yaml) -> Option<&'static DeprecatedApi> {
+    let api_version = object["apiVersion"].as_str().unwrap_or_default();
+    let kind = object["kind"].as_str().unwrap_or_default();
+
+    DEPRECATED_APIS.iter().find(|api| api.0 == api_version && (api.1 == "*" || api.1 == kind))
+}
+
+fn replacement_advice(replacement: Option<&str>) -> String {
+    match replacement {
+        Some(replacement) => format!("use {} instead", replacement),
+        None => "it has no replacement".to_string(),
+    }
+}
+
+/// Parses the minor version of a Kubernetes version such as `1.29`, `v1.29`, or `1.29.3`.
+fn parse_minor_version(version: &str) -> Result<u32, String> {
+    let mut parts = version.trim_start_matches('v').split('.');
+
+    match (parts.next(), parts.next().map(str::parse)) {
+        (Some("1"), Some(Ok(minor))) => Ok(minor),
+        _ => Err(format!("Target version {} must be a Kubernetes version like 1.29.", version)),
+    }
+}
+
+/// Rewrites an object from a deprecated API version to its current equivalent, where the
+/// conversion is mechanical, returning the API version it was migrated to, if any.
+///
+/// Ingresses move to `networking.k8s.io/v1`, with backends restructured and a `pathType` of
+/// `ImplementationSpecific` given to paths without one. Workloads move to `apps/v1`, with a
+/// selector matching their pod template's labels if they had none, and the `OnDelete` update
+/// strategy DaemonSets and StatefulSets previously defaulted to. HorizontalPodAutoscalers move to
+/// `autoscaling/v2`, with `autoscaling/v2beta1` metric targets restructured. CronJobs,
+/// NetworkPolicies, PodDisruptionBudgets, IngressClasses, PriorityClasses, Leases, RuntimeClasses,
+/// RBAC objects, and storage objects keep their fields. Other deprecated APIs, such as
+/// `apiextensions.k8s.io/v1beta1` CustomResourceDefinitions, are left as they are.
+///
+/// # Errors
+///
+/// Returns an error if the object can't be migrated without changing its meaning, e.g. a
+/// workload without a selector or pod template labels.
+pub fn migrate_api_version(object: &mut Yaml) -> Result<Option<&'static str>, String> {
+    let api_version = object["apiVersion"].as_str().unwrap_or_default().to_string();
+    let kind = object["kind"].as_str().unwrap_or_default().to_string();
+    let migrated = match (api_version.as_str(), kind.as_str()) {
+        ("extensions/v1beta1", "Ingress") | ("networking.k8s.io/v1beta1", "Ingress") => {
+            migrate_ingress(object);
+
+            "networking.k8s.io/v1"
+        }
+        ("extensions/v1beta1", "DaemonSet")
+        | ("extensions/v1beta1", "Deployment")
+        | ("extensions/v1beta1", "ReplicaSet")
+        | ("apps/v1beta1", _)
+        | ("apps/v1beta2", _) => {
+            migrate_workload(object, &api_version, &kind)?;
+
+            "apps/v1"
+        }
+        ("autoscaling/v2beta1", "HorizontalPodAutoscaler") => {
+            if let Yaml::Hash(ref mut hash) = *object {
+                if let Some(&mut Yaml::Hash(ref mut spec)) = hash.get_mut(&ystring("spec")) {
+                    if let Some(&mut Yaml::Array(ref mut metrics)) = spec.get_mut(&ystring("metrics")) {
+                        for metric in metrics.iter_mut() {
+                            *metric = migrate_metric(metric);
+                        }
+                    }
+                }
+            }
+
+            "autoscaling/v2"
+        }
+        ("policy/v1beta1", "PodDisruptionBudget") => {
+            if object["spec"]["selector"].as_hash().is_some_and(|selector| selector.is_empty()) {
+                return Err(
+                    "A PodDisruptionBudget with an empty selector selects no pods in policy/v1beta1, but every pod \
+                     in policy/v1."
+                        .to_string(),
+                );
+            }
+
+            "policy/v1"
+        }
+        ("autoscaling/v2beta2", "HorizontalPodAutoscaler") => "autoscaling/v2",
+        ("batch/v1beta1", "CronJob") => "batch/v1",
+        ("coordination.k8s.io/v1beta1", "Lease") => "coordination.k8s.io/v1",
+        ("extensions/v1beta1", "NetworkPolicy") => "networking.k8s.io/v1",
+        ("networking.k8s.io/v1beta1", "IngressClass") => "networking.k8s.io/v1",
+        ("node.k8s.io/v1beta1", "RuntimeClass") => "node.k8s.io/v1",
+        ("rbac.authorization.k8s.io/v1beta1", _) => "rbac.authorization.k8s.io/v1",
+        ("scheduling.k8s.io/v1beta1", "PriorityClass") => "scheduling.k8s.io/v1",
+        ("storage.k8s.io/v1beta1", "CSIDriver")
+        | ("storage.k8s.io/v1beta1", "CSINode")
+        | ("storage.k8s.io/v1beta1", "CSIStorageCapacity")
+        | ("storage.k8s.io/v1beta1", "StorageClass") => "storage.k8s.io/v1",
+        _ => return Ok(None),
+    };
+
+    if let Yaml::Hash(ref mut hash) = *object {
+        hash.insert(ystring("apiVersion"), ystring(migrated));
+    }
+
+    Ok(Some(migrated))
+}
+
+fn migrate_ingress(object: &mut Yaml) {
+    let spec = match *object {
+        Yaml::Hash(ref mut hash) => match hash.get_mut(&ystring("spec")) {
+            Some(&mut Yaml::Hash(ref mut spec)) => spec,
+            _ => return,
+        },
+        _ => return,
+    };
+
+    if let Some(backend) = spec.remove(&ystring("backend")) {
+        spec.insert(ystring("defaultBackend"), migrate_backend(backend));
+    }
+
+    if let Some(&mut Yaml::Array(ref mut rules)) = spec.get_mut(&ystring("rules")) {
+        for rule in rules.iter_mut() {
+            let paths = match *rule {
+                Yaml::Hash(ref mut rule) => match rule.get_mut(&ystring("http")) {
+                    Some(&mut Yaml::Hash(ref mut http)) => match http.get_mut(&ystring("paths")) {
+                        Some(&mut Yaml::Array(ref mut paths)) => paths,
+                        _ => continue,
+                    },
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            for path in paths.iter_mut() {
+                if let Yaml::Hash(ref mut path) = *path {
+                    path.entry(ystring("pathType")).or_insert_with(|| ystring("ImplementationSpecific"));
+
+                    if let Some(backend) = path.remove(&ystring("backend")) {
+                        path.insert(ystring("backend"), migrate_backend(backend));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Restructures an Ingress backend's `serviceName` and `servicePort` as a `service`.
+fn migrate_backend(backend: Yaml) -> Yaml {
+    let mut backend = match backend {
+        Yaml::Hash(backend) => backend,
+        backend => return backend,
+    };
+    let name = backend.remove(&ystring("serviceName"));
+    let port = backend.remove(&ystring("servicePort"));
+
+    if name.is_none() && port.is_none() {
+        return Yaml::Hash(backend);
+    }
+
+    let mut service = Hash::new();
+    let mut service_port = Hash::new();
+
+    match port {
+        Some(Yaml::Integer(number)) => {
+            service_port.insert(ystring("number"), Yaml::Integer(number));
+        }
+        Some(Yaml::String(ref port)) => {
+            match port.parse() {
+                Ok(number) => service_port.insert(ystring("number"), Yaml::Integer(number)),
+                Err(_) => service_port.insert(ystring("name"), ystring(port)),
+            };
+        }
+        _ => {}
+    }
+
+    if let Some(name) = name {
+        service.insert(ystring("name"), name);
+    }
+
+    if !service_port.is_empty() {
+        service.insert(ystring("port"), Yaml::Hash(service_port));
+    }
+
+    backend.insert(ystring("service"), Yaml::Hash(service));
+
+    Yaml::Hash(backend)
+}
+
+fn migrate_workload(object: &mut Yaml, api_version: &str, kind: &str) -> Result<(), String> {
+    let labels = object["spec"]["template"]["metadata"]["labels"].clone();
+    let defaults_to_on_delete = match kind {
+        "DaemonSet" => api_version != "apps/v1beta2",
+        "StatefulSet" => api_version == "apps/v1beta1",
+        _ => false,
+    };
+    let spec = match *object {
+        Yaml::Hash(ref mut hash) => match hash.get_mut(&ystring("spec")) {
+            Some(&mut Yaml::Hash(ref mut spec)) => spec,
+            _ => return Err(format!("{} has no spec.", kind)),
+        },
+        _ => return Err("Encountered a resource that is not a hash.".to_string()),
+    };
+
+    if let Entry::Vacant(selector) = spec.entry(ystring("selector")) {
+        match labels {
+            Yaml::Hash(ref labels) if !labels.is_empty() => {
+                let mut match_labels = Hash::new();
+
+                match_labels.insert(ystring("matchLabels"), Yaml::Hash(labels.clone()));
+                selector.insert(Yaml::Hash(match_labels));
+            }
+            _ => return Err(format!("{} must have a selector or pod template labels to move to apps/v1.", kind)),
+        }
+    }
+
+    if defaults_to_on_delete {
+        let strategy = spec.entry(ystring("updateStrategy")).or_insert_with(|| Yaml::Hash(Hash::new()));
+
+        if let Yaml::Hash(ref mut strategy) = *strategy {
+            strategy.entry(ystring("type")).or_insert_with(|| ystring("OnDelete"));
+        }
+    }
+
+    spec.remove(&ystring("rollbackTo"));
+    spec.remove(&ystring("templateGeneration"));
+
+    Ok(())
+}
+
+/// Restructures an `autoscaling/v2beta1` metric's target as an `autoscaling/v2` one.
+fn migrate_metric(metric: &Yaml) -> Yaml {
+    let mut metric = match *metric {
+        Yaml::Hash(ref metric) => metric.clone(),
+        ref metric => return metric.clone(),
+    };
+    let source_key = match metric.get(&ystring("type")).and_then(Yaml::as_str) {
+        Some("Resource") => "resource",
+        Some("Pods") => "pods",
+        Some("Object") => "object",
+        Some("External") => "external",
+        _ => return Yaml::Hash(metric),
+    };
+    let mut source = match metric.remove(&ystring(source_key)) {
+        Some(Yaml::Hash(source)) => source,
+        Some(source) => {
+            metric.insert(ystring(source_key), source);
+
+            return Yaml::Hash(metric);
+        }
+        None => return Yaml::Hash(metric),
+    };
+    let mut target = Hash::new();
+    let described_object = if source_key == "object" { source.remove(&ystring("target")) } else { None };
+
+    for (old, kind, new) in &[
+        ("targetAverageUtilization", "Utilization", "averageUtilization"),
+        ("targetAverageValue", "AverageValue", "averageValue"),
+        ("averageValue", "AverageValue", "averageValue"),
+        ("targetValue", "Value", "value"),
+    ] {
+        if let Some(value) = source.remove(&ystring(old)) {
+            target.insert(ystring("type"), ystring(kind));
+            target.insert(ystring(new), value);
+        }
+    }
+
+    if source_key != "resource" {
+        let mut identifier = Hash::new();
+
+        if let Some(name) = source.remove(&ystring("metricName")) {
+            identifier.insert(ystring("name"), name);
+        }
+
+        let selector = source.remove(&ystring("metricSelector")).or_else(|| source.remove(&ystring("selector")));
+
+        if let Some(selector) = selector {
+            identifier.insert(ystring("selector"), selector);
+        }
+
+        source.insert(ystring("metric"), Yaml::Hash(identifier));
+    }
+
+    if let Some(described_object) = described_object {
+        source.insert(ystring("describedObject"), described_object);
+    }
+
+    source.insert(ystring("target"), Yaml::Hash(target));
+    metric.insert(ystring(source_key), Yaml::Hash(source));
+
+    Yaml::Hash(metric)
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
@@ -0,0 +1,86 @@
+use yaml::Yaml;
+
+use scan::SCAN_ANNOTATION;
+use source::SOURCE_ANNOTATION;
+
+const DIRECTIVE_PREFIX: &str = "ktmpl.io/";
+
+/// Processing directives read from an object's `ktmpl.io/` annotations.
+#[derive(Debug, Default)]
+pub struct Directives {
+    /// Values used for parameters that otherwise have no value, while processing the object.
+    pub defaults: Vec<(String, String)>,
+    /// Parameters that must have a value for the object to be processed.
+    pub required: Vec<String>,
+}
+
+/// Removes the `ktmpl.io/` annotations from an object, returning the directives they express.
+///
+/// `ktmpl.io/param-default` holds `NAME=VALUE` entries, one per line, and `ktmpl.io/required`
+/// holds parameter names separated by commas or whitespace. The object's `metadata.annotations`
+/// is removed entirely if no other annotations remain. `ktmpl.io/scan-secrets` and
+/// `ktmpl.io/source` aren't directives, and are left in place.
+///
+/// # Errors
+///
+/// Returns an error if a directive is unknown, is not a string, or is malformed.
+pub fn take_directives(object: &mut Yaml) -> Result<Directives, String> {
+    let mut directives = Directives::default();
+
+    let metadata = match *object {
+        Yaml::Hash(ref mut hash) => match hash.get_mut(&Yaml::String("metadata".to_string())) {
+            Some(&mut Yaml::Hash(ref mut metadata)) => metadata,
+            _ => return Ok(directives),
+        },
+        _ => return Ok(directives),
+    };
+
+    let annotations_key = Yaml::String("annotations".to_string());
+
+    let annotations = match metadata.get_mut(&annotations_key) {
+        Some(&mut Yaml::Hash(ref mut annotations)) => annotations,
+        _ => return Ok(directives),
+    };
+
+    let directive_keys: Vec<Yaml> = annotations
+        .keys()
+        .filter(|key| {
+            key.as_str().is_some_and(|key| {
+                key.starts_with(DIRECTIVE_PREFIX) && key != SCAN_ANNOTATION && key != SOURCE_ANNOTATION
+            })
+        })
+        .cloned()
+        .collect();
+
+    for key in directive_keys {
+        let name = key.as_str().expect("Directive key was not a string.").to_string();
+        let value = match annotations.remove(&key) {
+            Some(Yaml::String(value)) => value,
+            _ => return Err(format!("Annotation {} must be a string.", name)),
+        };
+
+        match &name[DIRECTIVE_PREFIX.len()..] {
+            "param-default" => for line in value.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                match line.find('=') {
+                    Some(index) if index > 0 => directives.defaults.push(
+                        (line[..index].trim().to_string(), line[index + 1..].trim().to_string())
+                    ),
+                    _ => return Err(format!("Annotation {} entries must be of the form NAME=VALUE.", name)),
+                }
+            },
+            "required" => directives.required.extend(
+                value
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|parameter| !parameter.is_empty())
+                    .map(|parameter| parameter.to_string())
+            ),
+            _ => return Err(format!("Unknown directive annotation {}.", name)),
+        }
+    }
+
+    if annotations.is_empty() {
+        metadata.remove(&annotations_key);
+    }
+
+    Ok(directives)
+}
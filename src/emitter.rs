@@ -0,0 +1,142 @@
+use regex::Regex;
+use yaml::Yaml;
+
+/// Values that a YAML 1.1 parser, such as the one Kubernetes uses, reads as booleans or null.
+const YAML_1_1_KEYWORDS: [&str; 35] = [
+    "y", "Y", "yes", "Yes", "YES", "n", "N", "no", "No", "NO",
+    "true", "True", "TRUE", "false", "False", "FALSE",
+    "on", "On", "ON", "off", "Off", "OFF",
+    "null", "Null", "NULL", "~",
+    ".inf", ".Inf", ".INF", "+.inf", "+.Inf", "+.INF", ".nan", ".NaN", ".NAN",
+];
+
+/// Appends a YAML document to a string, in the same layout as yaml-rust's `YamlEmitter`.
+///
+/// Unlike `YamlEmitter`, every string that a YAML parser could read as another type is quoted,
+/// e.g. `0x1F`, `1_000`, `yes`, and `'quoted'`, so strings stay strings. Integers and floats are
+/// emitted as they were loaded; see `load_yaml`.
+///
+/// # Errors
+///
+/// Returns an error if a hash has an array or hash as a key.
+pub fn emit(doc: &Yaml, output: &mut String) -> Result<(), String> {
+    output.push_str("---\n");
+
+    Emitter { output, level: -1 }.emit_node(doc)
+}
+
+struct Emitter<'a> {
+    output: &'a mut String,
+    level: isize,
+}
+
+impl<'a> Emitter<'a> {
+    fn write_indent(&mut self) {
+        for _ in 0..self.level.max(0) {
+            self.output.push_str("  ");
+        }
+    }
+
+    fn emit_node(&mut self, node: &Yaml) -> Result<(), String> {
+        match *node {
+            Yaml::Array(ref array) if array.is_empty() => self.output.push_str("[]"),
+            Yaml::Array(ref array) => {
+                if self.level >= 0 {
+                    self.output.push('\n');
+                }
+
+                self.level += 1;
+
+                for (index, item) in array.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push('\n');
+                    }
+
+                    self.write_indent();
+                    self.output.push_str("- ");
+                    self.emit_node(item)?;
+                }
+
+                self.level -= 1;
+            }
+            Yaml::Hash(ref hash) if hash.is_empty() => self.output.push_str("{}"),
+            Yaml::Hash(ref hash) => {
+                if self.level >= 0 {
+                    self.output.push('\n');
+                }
+
+                self.level += 1;
+
+                for (index, (key, value)) in hash.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push('\n');
+                    }
+
+                    self.write_indent();
+
+                    match *key {
+                        Yaml::Array(_) | Yaml::Hash(_) => return Err("Bad hashmap key in YAML structure.".to_owned()),
+                        _ => self.emit_node(key)?,
+                    }
+
+                    self.output.push_str(": ");
+                    self.emit_node(value)?;
+                }
+
+                self.level -= 1;
+            }
+            Yaml::String(ref string) if needs_quotes(string) => escape(string, self.output),
+            Yaml::String(ref string) | Yaml::Real(ref string) => self.output.push_str(string),
+            Yaml::Integer(integer) => self.output.push_str(&integer.to_string()),
+            Yaml::Boolean(boolean) => self.output.push_str(if boolean { "true" } else { "false" }),
+            Yaml::Null | Yaml::BadValue => self.output.push('~'),
+            Yaml::Alias(_) => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether a string must be quoted to be read back as the same string.
+pub fn needs_quotes(string: &str) -> bool {
+    lazy_static! {
+        static ref NUMBER: Regex = Regex::new(
+            r"^[-+]?(0[xX][0-9a-fA-F_]+|0[bB][01_]+|0[oO][0-7_]+|[0-9][0-9_]*(\.[0-9_]*)?([eE][-+]?[0-9]+)?|\.[0-9_]+([eE][-+]?[0-9]+)?)$"
+        ).expect("Failed to compile regex.");
+    }
+
+    string.is_empty()
+        || string.starts_with(' ')
+        || string.ends_with(' ')
+        || string.starts_with(['\'', '"'])
+        || string.contains(|character: char| match character {
+            ':' | '{' | '}' | '[' | ']' | ',' | '&' | '*' | '#' | '?' | '|' | '-' | '<' | '>' | '=' | '!' | '%'
+            | '@' | '`' | '\\' | '\u{2028}' | '\u{2029}' | '\u{feff}' => true,
+            _ => character.is_control(),
+        })
+        || YAML_1_1_KEYWORDS.contains(&string)
+        || NUMBER.is_match(string)
+        || !matches!(Yaml::from_str(string), Yaml::String(_))
+}
+
+/// Appends a string in double quotes, escaping quotes, backslashes, and control characters.
+pub fn escape(string: &str, output: &mut String) {
+    output.push('"');
+
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{8}' => output.push_str("\\b"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\u{c}' => output.push_str("\\f"),
+            '\r' => output.push_str("\\r"),
+            '\u{2028}' | '\u{2029}' | '\u{feff}' => output.push_str(&format!("\\u{:04x}", character as u32)),
+            character if character.is_control() => output.push_str(&format!("\\u{:04x}", character as u32)),
+            character => output.push(character),
+        }
+    }
+
+    output.push('"');
+}
@@ -0,0 +1,123 @@
+use std::io::{Read, Write};
+
+use age::{Decryptor, Encryptor, Identity, IdentityFile, Recipient};
+use age::x25519;
+use base64::{decode, encode};
+use yaml::{Yaml, YamlLoader};
+
+use emitter::emit;
+use parameter::{ParameterValue, ParameterValues};
+
+/// What an encrypted parameter value starts with, followed by its Base64-encoded age ciphertext
+/// and `]`.
+const ENCRYPTED_PREFIX: &str = "ENC[age:";
+
+/// Encrypts the values of a parameter file to age recipients, e.g. `age1ql3z7hjy54pw3...`,
+/// returning the encrypted parameter file.
+///
+/// Each value is replaced by `ENC[age:...]`, so the parameter names stay readable and a change to
+/// one value changes one line. Values that are already encrypted are left as they are, so a
+/// parameter file can be re-encrypted after plain values are added to it. Comments aren't kept,
+/// and parameters are sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if no recipients are given, if a recipient isn't an age X25519 public key, or
+/// if the contents aren't a valid parameter file.
+pub fn encrypt_parameter_file(contents: &str, recipients: &[&str]) -> Result<String, String> {
+    if recipients.is_empty() {
+        return Err("Parameter values must be encrypted to at least one recipient.".to_string());
+    }
+
+    let recipients = recipients
+        .iter()
+        .map(|recipient| {
+            recipient
+                .parse::<x25519::Recipient>()
+                .map_err(|err| format!("Recipient {} is not an age public key: {}.", recipient, err))
+        })
+        .collect::<Result<Vec<x25519::Recipient>, String>>()?;
+    let mut output = String::new();
+
+    for doc in YamlLoader::load_from_str(contents).map_err(|err| err.to_string())? {
+        let mut hash = match doc {
+            Yaml::Hash(hash) => hash,
+            _ => return Err("YAML documents in parameter files must be hashes.".to_string()),
+        };
+
+        for (name, value) in hash.iter_mut() {
+            match *value {
+                Yaml::String(ref mut value) => {
+                    if !is_encrypted(value) {
+                        *value = encrypt(value, &recipients)?;
+                    }
+                }
+                _ => return Err(format!(
+                    "Parameter {} must be a string to be encrypted.",
+                    name.as_str().unwrap_or_default(),
+                )),
+            }
+        }
+
+        emit(&Yaml::Hash(hash), &mut output)?;
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Decrypts parameter values encrypted by `encrypt_parameter_file` in place, with the identities
+/// in the contents of an age identity file, i.e. lines of `AGE-SECRET-KEY-1...`.
+///
+/// # Errors
+///
+/// Returns an error naming the parameter if a value can't be decrypted with any of the
+/// identities, or if the identities can't be parsed.
+pub fn decrypt_parameter_values(parameter_values: &mut ParameterValues, identities: &str) -> Result<(), String> {
+    let identities = IdentityFile::from_buffer(identities.as_bytes())
+        .map_err(|err| err.to_string())
+        .and_then(|file| file.into_identities().map_err(|err| err.to_string()))
+        .map_err(|err| format!("Failed to parse the age identities: {}", err))?;
+
+    for (name, value) in parameter_values.iter_mut() {
+        let value = match *value {
+            ParameterValue::Plain(ref mut value) | ParameterValue::Encoded(ref mut value) => value,
+        };
+
+        if is_encrypted(value) {
+            *value = decrypt(value, &identities).map_err(|err| format!("Failed to decrypt parameter {}: {}", name, err))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether a parameter value was encrypted by `encrypt_parameter_file`.
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX) && value.ends_with(']')
+}
+
+fn encrypt(plaintext: &str, recipients: &[x25519::Recipient]) -> Result<String, String> {
+    let encryptor = Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn Recipient))
+        .map_err(|err| err.to_string())?;
+    let mut ciphertext = vec![];
+    let mut writer = encryptor.wrap_output(&mut ciphertext).map_err(|err| err.to_string())?;
+
+    writer.write_all(plaintext.as_bytes()).map_err(|err| err.to_string())?;
+    writer.finish().map_err(|err| err.to_string())?;
+
+    Ok(format!("{}{}]", ENCRYPTED_PREFIX, encode(&ciphertext)))
+}
+
+fn decrypt(value: &str, identities: &[Box<dyn Identity>]) -> Result<String, String> {
+    let ciphertext = decode(&value[ENCRYPTED_PREFIX.len()..value.len() - 1]).map_err(|err| err.to_string())?;
+    let decryptor = Decryptor::new(&ciphertext[..]).map_err(|err| err.to_string())?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref() as &dyn Identity))
+        .map_err(|err| err.to_string())?;
+    let mut plaintext = String::new();
+
+    reader.read_to_string(&mut plaintext).map_err(|err| err.to_string())?;
+
+    Ok(plaintext)
+}
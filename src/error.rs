@@ -0,0 +1,51 @@
+use std::error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Why a template or parameter file couldn't be loaded or processed.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A file couldn't be read, e.g. a parameter file that doesn't exist.
+    Io(String),
+    /// The contents aren't valid YAML, or don't have the structure expected of them, e.g. a
+    /// template without `objects` or a parameter file that isn't a hash.
+    Parse(String),
+    /// A parameter is declared or given a value incorrectly, or has no value where one is needed,
+    /// e.g. a required parameter without a value.
+    Parameter(String),
+    /// A specified secret wasn't found among the objects, or couldn't be Base64 encoded.
+    Secret(String),
+    /// The objects couldn't be changed as asked after interpolation, e.g. a patch matched no
+    /// object.
+    Process(String),
+    /// The processed objects couldn't be emitted as YAML, or written.
+    Emit(String),
+}
+
+impl Error {
+    /// Returns the error's message.
+    pub fn message(&self) -> &str {
+        match *self {
+            Error::Io(ref message)
+            | Error::Parse(ref message)
+            | Error::Parameter(ref message)
+            | Error::Secret(ref message)
+            | Error::Process(ref message)
+            | Error::Emit(ref message) => message,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str(self.message())
+    }
+}
+
+impl error::Error for Error {}
+
+/// Lets functions that return `String` errors propagate an `Error` with `?`.
+impl From<Error> for String {
+    fn from(err: Error) -> String {
+        err.to_string()
+    }
+}
@@ -0,0 +1,188 @@
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use kubectl;
+use loader::load_yaml;
+use template::dump;
+
+/// Fetches objects from the cluster and converts them into a template, returning its YAML.
+///
+/// References take the form `KIND/NAME`, as accepted by `kubectl get`. Server-populated fields
+/// are removed, and parameters are suggested for each object's name (e.g. `WEB_NAME`), replica
+/// count (`WEB_REPLICAS`), and container images (`WEB_NGINX_IMAGE`), plus `NAMESPACE` for the
+/// objects' namespace, with the current values as defaults. Objects sharing a name share its
+/// parameters.
+///
+/// # Errors
+///
+/// Returns an error if a reference is malformed or `kubectl` fails to fetch an object.
+pub fn extract(references: &[&str], namespace: Option<&str>, context: Option<&str>) -> Result<String, String> {
+    let mut objects = vec![];
+    let mut parameters: Vec<(String, String, &str)> = vec![];
+
+    for reference in references {
+        if reference.split('/').count() != 2 {
+            return Err(format!("Object reference \"{}\" must be of the form KIND/NAME.", reference));
+        }
+
+        let mut args = kubectl::context_args(context);
+
+        args.extend(["get", reference, "--output", "yaml"]);
+
+        if let Some(namespace) = namespace {
+            args.extend(["--namespace", namespace]);
+        }
+
+        let mut object = load_yaml(&kubectl::run(&args)?)
+            .map_err(|err| err.to_string())?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("kubectl returned nothing for {}.", reference))?;
+
+        kubectl::strip_server_fields(&mut object);
+
+        if let Some(spec) = get_mut(&mut object, &["spec"]) {
+            remove(spec, "clusterIP");
+            remove(spec, "clusterIPs");
+        }
+
+        let name = object["metadata"]["name"].as_str().unwrap_or_default().to_string();
+        let prefix = parameter_prefix(&name);
+
+        if let Some(value) = get_mut(&mut object, &["metadata", "namespace"]) {
+            let namespace = value.as_str().unwrap_or_default().to_string();
+            let parameter = add_parameter(&mut parameters, "NAMESPACE", namespace, "string");
+
+            *value = ystring(&format!("$({})", parameter));
+        }
+
+        if let Some(value) = get_mut(&mut object, &["metadata", "name"]) {
+            let parameter = add_parameter(&mut parameters, &format!("{}_NAME", prefix), name.clone(), "string");
+
+            *value = ystring(&format!("$({})", parameter));
+        }
+
+        if let Some(value) = get_mut(&mut object, &["spec", "replicas"]) {
+            if let Some(replicas) = value.as_i64() {
+                let parameter = add_parameter(
+                    &mut parameters,
+                    &format!("{}_REPLICAS", prefix),
+                    replicas.to_string(),
+                    "int",
+                );
+
+                *value = ystring(&format!("$(({}))", parameter));
+            }
+        }
+
+        let pod_spec_paths: [&[&str]; 3] = [
+            &["spec"],
+            &["spec", "template", "spec"],
+            &["spec", "jobTemplate", "spec", "template", "spec"],
+        ];
+
+        for pod_spec_path in pod_spec_paths.iter() {
+            for containers_key in &["initContainers", "containers"] {
+                let mut path = pod_spec_path.to_vec();
+
+                path.push(containers_key);
+
+                if let Some(&mut Yaml::Array(ref mut containers)) = get_mut(&mut object, &path) {
+                    for container in containers.iter_mut() {
+                        let container_name = container["name"].as_str().unwrap_or_default().to_string();
+
+                        if let Some(image) = get_mut(container, &["image"]) {
+                            let parameter = add_parameter(
+                                &mut parameters,
+                                &format!("{}_{}_IMAGE", prefix, parameter_prefix(&container_name)),
+                                image.as_str().unwrap_or_default().to_string(),
+                                "string",
+                            );
+
+                            *image = ystring(&format!("$({})", parameter));
+                        }
+                    }
+                }
+            }
+        }
+
+        objects.push(object);
+    }
+
+    let parameters = parameters.into_iter().map(|(name, value, parameter_type)| {
+        let mut parameter = Hash::new();
+
+        parameter.insert(ystring("name"), ystring(&name));
+        parameter.insert(ystring("parameterType"), ystring(parameter_type));
+        parameter.insert(ystring("required"), Yaml::Boolean(false));
+        parameter.insert(ystring("value"), ystring(&value));
+
+        Yaml::Hash(parameter)
+    }).collect();
+
+    let mut metadata = Hash::new();
+
+    metadata.insert(ystring("name"), ystring("extracted"));
+
+    let mut template = Hash::new();
+
+    template.insert(ystring("apiVersion"), ystring("v1"));
+    template.insert(ystring("kind"), ystring("Template"));
+    template.insert(ystring("metadata"), Yaml::Hash(metadata));
+    template.insert(ystring("objects"), Yaml::Array(objects));
+    template.insert(ystring("parameters"), Yaml::Array(parameters));
+
+    dump(vec![Yaml::Hash(template)])
+}
+
+/// Adds a suggested parameter, returning its name. A parameter with the same name and default is
+/// shared; one with a different default gets a numbered name.
+fn add_parameter(
+    parameters: &mut Vec<(String, String, &'static str)>,
+    name: &str,
+    value: String,
+    parameter_type: &'static str,
+) -> String {
+    let mut candidate = name.to_string();
+    let mut number = 1;
+
+    loop {
+        match parameters.iter().find(|parameter| parameter.0 == candidate) {
+            Some(parameter) if parameter.1 == value => return candidate,
+            Some(_) => {
+                number += 1;
+                candidate = format!("{}_{}", name, number);
+            }
+            None => break,
+        }
+    }
+
+    parameters.push((candidate.clone(), value, parameter_type));
+
+    candidate
+}
+
+fn get_mut<'a>(yaml: &'a mut Yaml, path: &[&str]) -> Option<&'a mut Yaml> {
+    path.iter().try_fold(yaml, |yaml, key| match *yaml {
+        Yaml::Hash(ref mut hash) => hash.get_mut(&ystring(key)),
+        _ => None,
+    })
+}
+
+fn remove(yaml: &mut Yaml, key: &str) {
+    if let Yaml::Hash(ref mut hash) = *yaml {
+        hash.remove(&ystring(key));
+    }
+}
+
+/// Converts an object name like `web-api` to a parameter prefix like `WEB_API`.
+fn parameter_prefix(name: &str) -> String {
+    name
+        .chars()
+        .map(|character| if character.is_ascii_alphanumeric() { character.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use emitter::{escape, needs_quotes};
+use loader::load_yaml;
+
+/// Keys written before the others in a hash, in this order. The rest are sorted.
+const LEADING_KEYS: [&str; 4] = ["apiVersion", "kind", "name", "metadata"];
+
+/// Formats a template's source canonically, like `gofmt` does for Go.
+///
+/// The template is written in block style with two-space indentation. String values are double
+/// quoted, or written as literal block scalars if they span lines, and keys are quoted only when
+/// they must be. Each hash starts with whichever of `apiVersion`, `kind`, `name`, and `metadata` it
+/// has, with its other keys sorted, and the template's parameters are sorted by name. Comments are
+/// kept with the lines they annotate. Formatting never changes what the template loads as, and
+/// formatting a formatted template returns it unchanged.
+///
+/// # Errors
+///
+/// Returns an error if the template is not a single YAML document, or a comment can't be placed,
+/// e.g. because it's within a flow collection.
+pub fn format_template(template_contents: &str) -> Result<String, String> {
+    let mut docs = load_yaml(template_contents).map_err(|err| err.to_string())?;
+
+    if docs.len() != 1 {
+        return Err("Only one YAML document can be present in the template.".to_owned());
+    }
+
+    let doc = docs.remove(0);
+    let mut formatter = Formatter { comments: Comments::collect(template_contents), output: String::new() };
+
+    formatter.output.push_str("---\n");
+    formatter.write_comments(&formatter.comments.header.clone(), 0);
+
+    match doc {
+        Yaml::Hash(ref hash) => formatter.write_hash(hash, &mut vec![], 0, false),
+        _ => return Err("The template must be a hash.".to_string()),
+    }
+
+    formatter.write_comments(&formatter.comments.footer.clone(), 0);
+
+    if !formatter.comments.leading.is_empty() || !formatter.comments.trailing.is_empty() {
+        return Err("The template has comments that can't be placed; it must be formatted by hand.".to_string());
+    }
+
+    let formatted = formatter.output;
+    let mut expected = doc;
+
+    if let Yaml::Hash(ref mut hash) = expected {
+        if let Some(&mut Yaml::Array(ref mut parameters)) = hash.get_mut(&Yaml::String("parameters".to_string())) {
+            parameters.sort_by_key(|parameter| parameter["name"].as_str().unwrap_or_default().to_string());
+        }
+    }
+
+    if load_yaml(&formatted).map_err(|err| err.to_string())? != vec![expected] {
+        return Err("Formatting would change the template's content; it must be formatted by hand.".to_string());
+    }
+
+    Ok(formatted)
+}
+
+/// A step in the path to a node: a hash key, as written by `key_text`, or an array index.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The comments of a template's source, keyed by the path of the node they annotate.
+#[derive(Default)]
+struct Comments {
+    /// Comments before the template's first node.
+    header: Vec<String>,
+    /// Comments on their own lines before a node.
+    leading: HashMap<Vec<Segment>, Vec<String>>,
+    /// Comments at the end of the line a node starts on.
+    trailing: HashMap<Vec<Segment>, String>,
+    /// Comments after the template's last node.
+    footer: Vec<String>,
+}
+
+impl Comments {
+    /// Finds the comments in a template's source, working out the path of the node each annotates
+    /// from the indentation of the lines around it. Only comments in block collections are found.
+    fn collect(source: &str) -> Comments {
+        let mut comments = Comments::default();
+        // The indentation and path segment of each collection entry enclosing the current line.
+        let mut stack: Vec<(usize, Segment)> = vec![];
+        let mut pending = vec![];
+        // The indentation of the entry whose block scalar the following lines belong to.
+        let mut block_scalar: Option<usize> = None;
+        let mut seen_node = false;
+
+        for line in source.lines() {
+            let content = line.trim_start();
+            let mut indent = line.len() - content.len();
+
+            if let Some(scalar_indent) = block_scalar {
+                if content.is_empty() || indent > scalar_indent {
+                    continue;
+                }
+
+                block_scalar = None;
+            }
+
+            if content.is_empty() || content == "---" || content == "..." {
+                continue;
+            }
+
+            if content.starts_with('#') {
+                pending.push(content.trim_end().to_string());
+                continue;
+            }
+
+            let mut content = content;
+            let mut first_segment = None;
+
+            while content == "-" || content.starts_with("- ") {
+                stack.retain(|entry| entry.0 <= indent);
+
+                let index = match stack.last() {
+                    Some(&(entry_indent, Segment::Index(index))) if entry_indent == indent => {
+                        stack.pop();
+
+                        index + 1
+                    }
+                    _ => 0,
+                };
+
+                stack.push((indent, Segment::Index(index)));
+                first_segment.get_or_insert(stack.len());
+
+                let rest = content[1..].trim_start();
+
+                indent += content.len() - rest.len();
+                content = rest;
+            }
+
+            let value = match split_key(content) {
+                Some((key, value)) => {
+                    stack.retain(|entry| entry.0 < indent);
+                    stack.push((indent, Segment::Key(key)));
+                    first_segment.get_or_insert(stack.len());
+
+                    value
+                }
+                None => content,
+            };
+            let path: Vec<Segment> = stack.iter().map(|entry| entry.1.clone()).collect();
+            let (value, comment) = split_comment(value);
+
+            if !pending.is_empty() {
+                if seen_node {
+                    let first = first_segment.unwrap_or(path.len());
+
+                    comments.leading.entry(path[..first].to_vec()).or_default().append(&mut pending);
+                } else {
+                    comments.header.append(&mut pending);
+                }
+            }
+
+            if let Some(comment) = comment {
+                comments.trailing.insert(path.clone(), comment.to_string());
+            }
+
+            if value.starts_with(['|', '>']) {
+                block_scalar = stack.last().map(|entry| entry.0);
+            }
+
+            seen_node = true;
+        }
+
+        comments.footer = pending;
+
+        comments
+    }
+}
+
+struct Formatter {
+    comments: Comments,
+    output: String,
+}
+
+impl Formatter {
+    fn write_indent(&mut self, indent: usize) {
+        for _ in 0..indent {
+            self.output.push(' ');
+        }
+    }
+
+    fn write_comments(&mut self, comments: &[String], indent: usize) {
+        for comment in comments {
+            self.write_indent(indent);
+            self.output.push_str(comment);
+            self.output.push('\n');
+        }
+    }
+
+    fn write_leading_comments(&mut self, path: &[Segment], indent: usize) {
+        if let Some(comments) = self.comments.leading.remove(path) {
+            self.write_comments(&comments, indent);
+        }
+    }
+
+    fn write_trailing_comment(&mut self, path: &[Segment]) {
+        if let Some(comment) = self.comments.trailing.remove(path) {
+            self.output.push(' ');
+            self.output.push_str(&comment);
+        }
+    }
+
+    /// Writes the entries of a hash, each on its own line. If `inline` is true, the first entry
+    /// continues the current line, after an array item's `- `.
+    fn write_hash(&mut self, hash: &Hash, path: &mut Vec<Segment>, indent: usize, inline: bool) {
+        let mut entries: Vec<(String, &Yaml, &Yaml)> = hash
+            .iter()
+            .map(|(key, value)| (key_text(key), key, value))
+            .collect();
+
+        entries.sort_by_key(|entry| key_order(&entry.0));
+
+        for (position, (text, key, value)) in entries.into_iter().enumerate() {
+            path.push(Segment::Key(text));
+
+            if position > 0 || !inline {
+                self.write_leading_comments(path, indent);
+                self.write_indent(indent);
+            }
+
+            match *key {
+                Yaml::String(ref key) if needs_quotes(key) => escape(key, &mut self.output),
+                _ => self.output.push_str(&key_text(key)),
+            }
+
+            self.output.push(':');
+            self.write_value(value, path, indent);
+            path.pop();
+        }
+    }
+
+    /// Writes the items of an array, each starting its own line. The template's parameters are
+    /// written sorted by name.
+    fn write_array(&mut self, array: &[Yaml], path: &mut Vec<Segment>, indent: usize) {
+        let mut order: Vec<usize> = (0..array.len()).collect();
+
+        if *path == [Segment::Key("parameters".to_string())] {
+            order.sort_by_key(|&index| array[index]["name"].as_str().unwrap_or_default().to_string());
+        }
+
+        for index in order {
+            let item = &array[index];
+
+            path.push(Segment::Index(index));
+            self.write_leading_comments(path, indent);
+
+            match *item {
+                Yaml::Hash(ref hash) if !hash.is_empty() => {
+                    if let Some(comment) = self.comments.trailing.remove(&path[..]) {
+                        self.write_comments(&[comment], indent);
+                    }
+
+                    let mut first = hash.keys().map(key_text).collect::<Vec<String>>();
+
+                    first.sort_by_key(|key| key_order(key));
+                    path.push(Segment::Key(first.remove(0)));
+                    self.write_leading_comments(path, indent);
+                    path.pop();
+
+                    self.write_indent(indent);
+                    self.output.push_str("- ");
+                    self.write_hash(hash, path, indent + 2, true);
+                }
+                _ => {
+                    self.write_indent(indent);
+                    self.output.push('-');
+                    self.write_value(item, path, indent);
+                }
+            }
+
+            path.pop();
+        }
+    }
+
+    /// Writes a value after a key's `:` or an array item's `-`, ending the line it's on.
+    fn write_value(&mut self, value: &Yaml, path: &mut Vec<Segment>, indent: usize) {
+        match *value {
+            Yaml::Hash(ref hash) if !hash.is_empty() => {
+                self.write_trailing_comment(path);
+                self.output.push('\n');
+                self.write_hash(hash, path, indent + 2, false);
+            }
+            Yaml::Array(ref array) if !array.is_empty() => {
+                self.write_trailing_comment(path);
+                self.output.push('\n');
+                self.write_array(array, path, indent + 2);
+            }
+            Yaml::String(ref string) if is_literal_block(string) => {
+                self.output.push_str(if string.ends_with('\n') { " |" } else { " |-" });
+                self.write_trailing_comment(path);
+                self.output.push('\n');
+
+                for line in string.lines() {
+                    if !line.is_empty() {
+                        self.write_indent(indent + 2);
+                        self.output.push_str(line);
+                    }
+
+                    self.output.push('\n');
+                }
+            }
+            _ => {
+                self.output.push(' ');
+
+                match *value {
+                    Yaml::Array(_) => self.output.push_str("[]"),
+                    Yaml::Hash(_) => self.output.push_str("{}"),
+                    Yaml::String(ref string) => escape(string, &mut self.output),
+                    Yaml::Real(ref real) => self.output.push_str(real),
+                    Yaml::Integer(integer) => self.output.push_str(&integer.to_string()),
+                    Yaml::Boolean(boolean) => self.output.push_str(if boolean { "true" } else { "false" }),
+                    Yaml::Null | Yaml::BadValue | Yaml::Alias(_) => self.output.push_str("null"),
+                }
+
+                self.write_trailing_comment(path);
+                self.output.push('\n');
+            }
+        }
+    }
+}
+
+/// Returns how a hash key is identified in a path: a string key is its value, and other keys
+/// are written as they'd appear in YAML.
+fn key_text(key: &Yaml) -> String {
+    match *key {
+        Yaml::String(ref string) | Yaml::Real(ref string) => string.clone(),
+        Yaml::Integer(integer) => integer.to_string(),
+        Yaml::Boolean(boolean) => boolean.to_string(),
+        _ => "null".to_string(),
+    }
+}
+
+/// Returns the position of a key among its hash's keys, to sort them by.
+fn key_order(key: &str) -> (usize, String) {
+    let leading = LEADING_KEYS.iter().position(|leading| *leading == key).unwrap_or(LEADING_KEYS.len());
+
+    (leading, key.to_string())
+}
+
+/// Splits a line of a block hash into its key, as written by `key_text`, and the rest of the
+/// line, or returns `None` if the line doesn't start with a key.
+fn split_key(content: &str) -> Option<(String, &str)> {
+    let end = if content.starts_with(['"', '\'']) {
+        let quote = content.chars().next().expect("Content was empty.");
+        let mut escaped = false;
+
+        1 + content[1..].find(|character: char| {
+            let closes = character == quote && !escaped;
+
+            escaped = quote == '"' && character == '\\' && !escaped;
+
+            closes
+        })? + 1
+    } else if content.starts_with(['{', '[', '&', '*', '!', '|', '>', '#']) {
+        return None;
+    } else {
+        content
+            .char_indices()
+            .find(|&(index, character)| {
+                character == ':' && content[index + 1..].chars().next().is_none_or(char::is_whitespace)
+            })?
+            .0
+    };
+
+    if !content[end..].starts_with(':') || !content[end + 1..].chars().next().is_none_or(char::is_whitespace) {
+        return None;
+    }
+
+    let key = load_yaml(&content[..end]).ok()?.into_iter().next()?;
+
+    Some((key_text(&key), content[end + 1..].trim_start()))
+}
+
+/// Splits a comment from the end of a value, outside of any quotes.
+fn split_comment(value: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+
+    for (index, character) in value.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if character == '\\' => escaped = true,
+            Some(open) if character == open => quote = None,
+            Some(_) => {}
+            None if character == '#' && previous.is_whitespace() => {
+                return (value[..index].trim_end(), Some(value[index..].trim_end()));
+            }
+            None if matches!(character, '"' | '\'') && (previous.is_whitespace() || "[{,".contains(previous)) => {
+                quote = Some(character);
+            }
+            None => {}
+        }
+
+        previous = character;
+    }
+
+    (value, None)
+}
+
+/// Returns whether a string is written as a literal block scalar, which it can be if it spans
+/// lines and keeps its exact content when written as one.
+fn is_literal_block(string: &str) -> bool {
+    string.contains('\n')
+        && !string.ends_with("\n\n")
+        && !string.starts_with([' ', '\n'])
+        && string.lines().all(|line| line == line.trim_end())
+        && !string.contains(|character: char| character.is_control() && character != '\n' && character != '\t')
+}
@@ -0,0 +1,144 @@
+use std::fs::read_dir;
+use std::path::{Component, Path, PathBuf};
+
+/// Returns whether a path contains glob metacharacters: `*`, `?`, or `[`.
+pub fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Expands a glob pattern into the paths of the files it matches, sorted, so patterns work where
+/// the shell doesn't expand them, such as PowerShell and cmd.
+///
+/// In each component of the pattern, `*` matches any run of characters, `?` any one character,
+/// and `[abc]`, `[a-z]`, or `[!abc]` one character from (or not from) a set. A component of just
+/// `**` matches any number of directories, including none. Like a shell, wildcards don't match a
+/// leading `.`, so hidden files and directories are only matched by patterns naming them.
+///
+/// Drive letters, UNC shares such as `\\server\share`, and root directories are taken literally,
+/// as are components without metacharacters. Directories that can't be read are skipped.
+pub fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+
+    for component in Path::new(pattern).components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            _ => {
+                for path in &mut paths {
+                    path.push(component.as_os_str());
+                }
+
+                continue;
+            }
+        };
+
+        if !is_glob(&name) {
+            for path in &mut paths {
+                path.push(&*name);
+            }
+
+            continue;
+        }
+
+        let component: Vec<char> = name.chars().collect();
+        let mut expanded = vec![];
+
+        for path in &paths {
+            if name == "**" {
+                directories(path, &mut expanded);
+
+                continue;
+            }
+
+            for entry in entries(path) {
+                let entry_name: Vec<char> = entry.chars().collect();
+
+                if (entry_name.first() != Some(&'.') || component.first() == Some(&'.'))
+                    && matches(&component, &entry_name)
+                {
+                    expanded.push(path.join(entry));
+                }
+            }
+        }
+
+        paths = expanded;
+    }
+
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    paths.dedup();
+
+    paths
+}
+
+/// Adds a directory and the directories beneath it, other than hidden ones.
+fn directories(path: &Path, found: &mut Vec<PathBuf>) {
+    found.push(path.to_path_buf());
+
+    for entry in entries(path) {
+        let entry_path = path.join(&entry);
+
+        if !entry.starts_with('.') && entry_path.is_dir() {
+            directories(&entry_path, found);
+        }
+    }
+}
+
+/// Returns the names of a directory's entries, or none if it can't be read.
+fn entries(path: &Path) -> Vec<String> {
+    let directory = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+
+    match read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Returns whether a file name matches a pattern component.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skipped| matches(&pattern[1..], &name[skipped..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => match (class_end(pattern), name.first()) {
+            (Some(end), Some(&character)) => {
+                in_class(&pattern[1..end], character) && matches(&pattern[end + 1..], &name[1..])
+            }
+            (Some(_), None) => false,
+            (None, _) => name.first() == Some(&'[') && matches(&pattern[1..], &name[1..]),
+        },
+        Some(&literal) => name.first() == Some(&literal) && matches(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Returns the index of the `]` closing a character class at the start of a pattern, if any. A
+/// `]` first in the class, after any `!`, is part of it.
+fn class_end(pattern: &[char]) -> Option<usize> {
+    let first = if pattern.get(1) == Some(&'!') { 2 } else { 1 };
+
+    pattern.iter().skip(first + 1).position(|&character| character == ']').map(|index| index + first + 1)
+}
+
+/// Returns whether a character is in a character class, given without its brackets.
+fn in_class(class: &[char], character: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut index = 0;
+    let mut found = false;
+
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            found |= class[index] <= character && character <= class[index + 2];
+            index += 3;
+        } else {
+            found |= class[index] == character;
+            index += 1;
+        }
+    }
+
+    found != negated
+}
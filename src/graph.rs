@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use yaml::Yaml;
+
+use loader::load_yaml_or_json;
+use reference::{references, selects};
+use template::{parameter_usages, template_parameters};
+
+/// A graph description language.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz's DOT language, rendered with e.g. `dot -Tsvg`.
+    Dot,
+    /// A Mermaid flowchart, which GitHub and GitLab render in Markdown.
+    Mermaid,
+}
+
+/// An edge between two objects, by index, and what the reference is.
+type Edge = (usize, usize, &'static str);
+
+/// Returns a graph of a template's parameters and objects, for reviewing how they relate.
+///
+/// Each parameter has an edge to the objects that refer to it. Objects have edges to the objects
+/// they refer to: a `Service` to the workloads its selector matches, a workload to the
+/// `ConfigMap`s, `Secret`s, `PersistentVolumeClaim`s, and `ServiceAccount` its pods use, an
+/// `Ingress` to its backend `Service`s, and a `HorizontalPodAutoscaler` to its target. Names and
+/// labels are compared as written, so a reference through a parameter matches an object named
+/// with the same parameter.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as `template_parameters`.
+pub fn template_graph(template_contents: &str, format: GraphFormat) -> Result<String, String> {
+    let doc = load_yaml_or_json(template_contents)?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::BadValue);
+    let objects = doc["objects"].as_vec().cloned().unwrap_or_default();
+    let parameters: Vec<String> = template_parameters(template_contents)?
+        .into_iter()
+        .map(|parameter| parameter.name)
+        .collect();
+    let mut parameter_edges = vec![];
+
+    for usage in parameter_usages(template_contents)? {
+        let parameter = parameters.iter().position(|name| *name == usage.name).expect("Usage was undeclared.");
+
+        for path in usage.paths {
+            let index = path["objects[".len()..].split(']').next().and_then(|index| index.parse().ok());
+
+            if let Some(index) = index {
+                if !parameter_edges.contains(&(parameter, index)) {
+                    parameter_edges.push((parameter, index));
+                }
+            }
+        }
+    }
+
+    let mut object_edges = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        for reference in references(object) {
+            for (target, candidate) in objects.iter().enumerate() {
+                let edge = (index, target, reference.via);
+
+                if target != index && matches(candidate, reference.kind, &reference.name) && !object_edges.contains(&edge) {
+                    object_edges.push(edge);
+                }
+            }
+        }
+
+        if object["kind"].as_str() == Some("Service") {
+            for (target, candidate) in objects.iter().enumerate() {
+                if selects(object, candidate) {
+                    object_edges.push((index, target, "selector"));
+                }
+            }
+        }
+    }
+
+    let object_labels: Vec<String> = objects
+        .iter()
+        .map(|object| format!(
+            "{}\n{}",
+            object["kind"].as_str().unwrap_or("object"),
+            object["metadata"]["name"].as_str().unwrap_or_default(),
+        ))
+        .collect();
+
+    Ok(match format {
+        GraphFormat::Dot => {
+            dot(doc["metadata"]["name"].as_str(), &parameters, &object_labels, &parameter_edges, &object_edges)
+        }
+        GraphFormat::Mermaid => mermaid(&parameters, &object_labels, &parameter_edges, &object_edges),
+    })
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err("Graph format must be dot or mermaid.".to_owned()),
+        }
+    }
+}
+
+fn dot(
+    name: Option<&str>,
+    parameters: &[String],
+    objects: &[String],
+    parameter_edges: &[(usize, usize)],
+    object_edges: &[Edge],
+) -> String {
+    let quote = |label: &str| format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"));
+    let mut lines = vec![
+        format!("digraph {} {{", quote(name.unwrap_or("template"))),
+        "  rankdir=LR;".to_string(),
+        "  node [shape=box];".to_string(),
+    ];
+
+    for (index, parameter) in parameters.iter().enumerate() {
+        lines.push(format!("  p{} [label={}, shape=ellipse];", index, quote(parameter)));
+    }
+
+    for (index, object) in objects.iter().enumerate() {
+        lines.push(format!("  o{} [label={}];", index, quote(object)));
+    }
+
+    for &(parameter, object) in parameter_edges {
+        lines.push(format!("  p{} -> o{} [style=dashed];", parameter, object));
+    }
+
+    for &(from, to, reference) in object_edges {
+        lines.push(format!("  o{} -> o{} [label={}];", from, to, quote(reference)));
+    }
+
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn mermaid(
+    parameters: &[String],
+    objects: &[String],
+    parameter_edges: &[(usize, usize)],
+    object_edges: &[Edge],
+) -> String {
+    let quote = |label: &str| format!("\"{}\"", label.replace('"', "#quot;").replace('\n', "<br>"));
+    let mut lines = vec!["flowchart LR".to_string()];
+
+    for (index, parameter) in parameters.iter().enumerate() {
+        lines.push(format!("  p{}([{}])", index, quote(parameter)));
+    }
+
+    for (index, object) in objects.iter().enumerate() {
+        lines.push(format!("  o{}[{}]", index, quote(object)));
+    }
+
+    for &(parameter, object) in parameter_edges {
+        lines.push(format!("  p{} -.-> o{}", parameter, object));
+    }
+
+    for &(from, to, reference) in object_edges {
+        lines.push(format!("  o{} -- {} --> o{}", from, reference, to));
+    }
+
+    lines.join("\n")
+}
+
+fn matches(object: &Yaml, kind: &str, name: &str) -> bool {
+    object["kind"].as_str() == Some(kind) && object["metadata"]["name"].as_str() == Some(name)
+}
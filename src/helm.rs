@@ -0,0 +1,181 @@
+use std::fs::{read_dir, read_to_string};
+use std::path::Path;
+
+use regex::{Captures, Regex};
+use yaml::yaml::Hash;
+use yaml::{Yaml, YamlLoader};
+
+use loader::load_yaml;
+use template::dump;
+
+/// Converts a Helm chart into a template, returning the template's YAML.
+///
+/// This is experimental, and only supports charts whose templates use nothing but `.Values`
+/// interpolation, e.g. `{{ .Values.image.tag }}`. Each distinct value becomes a parameter named
+/// after its path (`IMAGE_TAG`), with its default, and type if it's a bool or an int, taken from
+/// the chart's `values.yaml`. A value standing alone as a YAML scalar is interpolated as a
+/// literal, so it keeps its type. Files in `templates` other than YAML files, such as
+/// `_helpers.tpl` and `NOTES.txt`, are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the chart can't be read, or a template uses any other template action.
+pub fn helm_to_template(chart_directory: &Path) -> Result<String, String> {
+    lazy_static! {
+        static ref ACTION: Regex = Regex::new(r"\{\{-?\s*(.*?)\s*-?\}\}").expect("Failed to compile regex.");
+        static ref VALUES_REFERENCE: Regex = Regex::new(
+            r"^\.Values((?:\.[A-Za-z0-9_]+)+)$"
+        ).expect("Failed to compile regex.");
+        static ref STANDALONE_ACTION: Regex = Regex::new(
+            r"(?m)^([ \t]*(?:- |[^:#\n]+: ))(\{\{-?\s*[^}]*?\s*-?\}\})[ \t]*$"
+        ).expect("Failed to compile regex.");
+    }
+
+    let chart = load_yaml_file(&chart_directory.join("Chart.yaml"))?;
+    let values = load_yaml_file(&chart_directory.join("values.yaml")).unwrap_or(Yaml::Null);
+
+    let templates_directory = chart_directory.join("templates");
+    let mut template_paths: Vec<_> = read_dir(&templates_directory)
+        .map_err(|err| format!("Failed to read {}: {}", templates_directory.display(), err))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "yaml" || extension == "yml"))
+        .collect();
+
+    template_paths.sort();
+
+    let mut parameter_paths: Vec<String> = vec![];
+    let mut objects = vec![];
+
+    for template_path in template_paths {
+        let contents = read_to_string(&template_path)
+            .map_err(|err| format!("Failed to read {}: {}", template_path.display(), err))?;
+
+        let mut error = None;
+        let mut placeholder = |action: &str, literal: bool| -> String {
+            let inner = ACTION.captures(action).and_then(|captures| captures.get(1)).map_or("", |inner| inner.as_str());
+
+            match VALUES_REFERENCE.captures(inner) {
+                Some(captures) => {
+                    let path = captures[1][1..].to_string();
+                    let name = parameter_name(&path);
+
+                    if !parameter_paths.contains(&path) {
+                        parameter_paths.push(path);
+                    }
+
+                    if literal { format!("$(({}))", name) } else { format!("$({})", name) }
+                }
+                None => {
+                    error.get_or_insert_with(|| format!(
+                        "{}: Unsupported template action \"{}\"; only .Values references can be converted.",
+                        template_path.display(),
+                        action,
+                    ));
+
+                    String::new()
+                }
+            }
+        };
+
+        let converted = STANDALONE_ACTION.replace_all(&contents, |captures: &Captures| {
+            format!("{}{}", &captures[1], placeholder(&captures[2], true))
+        }).into_owned();
+        let converted = ACTION.replace_all(&converted, |captures: &Captures| {
+            placeholder(&captures[0], false)
+        }).into_owned();
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let docs = load_yaml(&converted)
+            .map_err(|err| format!("{}: {}", template_path.display(), err))?;
+
+        objects.extend(docs.into_iter().filter(|doc| !doc.is_null()));
+    }
+
+    let parameters = parameter_paths.iter().map(|path| {
+        let default = path.split('.').fold(&values, |yaml, segment| &yaml[segment]);
+        let mut parameter = Hash::new();
+
+        parameter.insert(ystring("name"), ystring(&parameter_name(path)));
+        parameter.insert(ystring("description"), ystring(&format!("Helm value .Values.{}", path)));
+
+        let (parameter_type, value) = match *default {
+            Yaml::Boolean(value) => (Some("bool"), Some(value.to_string())),
+            Yaml::Integer(value) => (Some("int"), Some(value.to_string())),
+            Yaml::Real(ref value) | Yaml::String(ref value) => (Some("string"), Some(value.clone())),
+            _ => (None, None),
+        };
+
+        if let Some(parameter_type) = parameter_type {
+            parameter.insert(ystring("parameterType"), ystring(parameter_type));
+        }
+
+        parameter.insert(ystring("required"), Yaml::Boolean(value.is_none()));
+
+        if let Some(value) = value {
+            parameter.insert(ystring("value"), ystring(&value));
+        }
+
+        Yaml::Hash(parameter)
+    }).collect();
+
+    let mut metadata = Hash::new();
+
+    metadata.insert(ystring("name"), chart["name"].clone());
+
+    if let Some(description) = chart["description"].as_str() {
+        let mut annotations = Hash::new();
+
+        annotations.insert(ystring("description"), ystring(description));
+        metadata.insert(ystring("annotations"), Yaml::Hash(annotations));
+    }
+
+    let mut template = Hash::new();
+
+    template.insert(ystring("apiVersion"), ystring("v1"));
+    template.insert(ystring("kind"), ystring("Template"));
+    template.insert(ystring("metadata"), Yaml::Hash(metadata));
+    template.insert(ystring("objects"), Yaml::Array(objects));
+    template.insert(ystring("parameters"), Yaml::Array(parameters));
+
+    dump(vec![Yaml::Hash(template)])
+}
+
+fn load_yaml_file(path: &Path) -> Result<Yaml, String> {
+    let contents = read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let mut docs = YamlLoader::load_from_str(&contents).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    if docs.is_empty() {
+        return Ok(Yaml::Null);
+    }
+
+    Ok(docs.remove(0))
+}
+
+/// Converts a values path like `image.pullPolicy` to a parameter name like `IMAGE_PULL_POLICY`.
+fn parameter_name(path: &str) -> String {
+    let mut name = String::new();
+    let mut previous_lowercase = false;
+
+    for character in path.chars() {
+        if character == '.' {
+            name.push('_');
+            previous_lowercase = false;
+        } else {
+            if character.is_uppercase() && previous_lowercase {
+                name.push('_');
+            }
+
+            previous_lowercase = character.is_lowercase() || character.is_ascii_digit();
+            name.extend(character.to_uppercase());
+        }
+    }
+
+    name
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
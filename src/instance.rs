@@ -0,0 +1,143 @@
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use kubectl;
+use loader::load_yaml;
+use parameter::{ParameterValue, ParameterValues};
+use template::{Template, dump};
+
+/// The label naming the `TemplateInstance` an expanded object belongs to.
+pub const INSTANCE_LABEL: &str = "ktmpl.io/template-instance";
+
+/// The label naming the namespace of the `TemplateInstance` an expanded object belongs to.
+pub const INSTANCE_NAMESPACE_LABEL: &str = "ktmpl.io/template-instance-namespace";
+
+const API_VERSION: &str = "ktmpl.io/v1alpha1";
+
+/// The objects a `TemplateInstance` expands to.
+#[derive(Debug)]
+pub struct Expansion {
+    /// The template's processed objects, in the instance's namespace, labeled with the instance's
+    /// name and namespace, and owned by the instance.
+    pub objects: Vec<Yaml>,
+    /// The owner reference added to each object, which points to the instance.
+    pub owner_reference: Yaml,
+}
+
+/// Expands a `TemplateInstance` into the objects of its template, e.g. in a mutating admission
+/// webhook or a controller.
+///
+/// The instance gives parameter values in `spec.parameters`, as strings, numbers, or booleans.
+/// The template is a `Template` resource or template file, with `objects` and `parameters`;
+/// server-populated fields are ignored. Objects without a namespace are placed in the instance's
+/// namespace, and each object is labeled with `INSTANCE_LABEL` and `INSTANCE_NAMESPACE_LABEL`
+/// and given an owner reference to the instance, so it's garbage collected along with the
+/// instance. Both objects may be given as JSON, e.g. from an `AdmissionReview`, by loading them
+/// with `load_yaml`.
+///
+/// # Errors
+///
+/// Returns an error if the instance has no name or malformed parameters, the template can't be
+/// processed with the instance's parameters, or an object is in another namespace. Owner
+/// references can't cross namespaces.
+pub fn expand_instance(template: &Yaml, instance: &Yaml) -> Result<Expansion, String> {
+    let namespace = instance["metadata"]["namespace"].as_str().unwrap_or("default");
+    let name = instance["metadata"]["name"].as_str().ok_or("TemplateInstance has no name.")?;
+    let owner_reference = owner_reference(instance)?;
+
+    let mut template = template.clone();
+
+    kubectl::strip_server_fields(&mut template);
+
+    if let Yaml::Hash(ref mut hash) = template {
+        hash.entry(ystring("parameters")).or_insert_with(|| Yaml::Array(vec![]));
+    }
+
+    let mut template = Template::new(dump(vec![template])?, parameter_values(instance)?, None)?;
+
+    template.set_default_namespace(namespace);
+
+    let mut objects = load_yaml(&template.process()?).map_err(|err| err.to_string())?;
+
+    for object in objects.iter_mut() {
+        let metadata = match *object {
+            Yaml::Hash(ref mut hash) => hash.entry(ystring("metadata")).or_insert_with(|| Yaml::Hash(Hash::new())),
+            _ => return Err("Encountered a resource that is not a hash.".to_string()),
+        };
+        let metadata = match *metadata {
+            Yaml::Hash(ref mut metadata) => metadata,
+            _ => return Err("Encountered a resource with a non-hash \"metadata\" field.".to_string()),
+        };
+
+        match metadata.entry(ystring("namespace")).or_insert_with(|| ystring(namespace)).as_str() {
+            Some(object_namespace) if object_namespace == namespace => {}
+            _ => return Err(format!("Objects must be in the TemplateInstance's namespace, {}.", namespace)),
+        }
+
+        match *metadata.entry(ystring("labels")).or_insert_with(|| Yaml::Hash(Hash::new())) {
+            Yaml::Hash(ref mut labels) => {
+                labels.insert(ystring(INSTANCE_LABEL), ystring(name));
+                labels.insert(ystring(INSTANCE_NAMESPACE_LABEL), ystring(namespace));
+            }
+            _ => return Err("Encountered a resource with a non-hash \"metadata.labels\" field.".to_string()),
+        }
+
+        match *metadata.entry(ystring("ownerReferences")).or_insert_with(|| Yaml::Array(vec![])) {
+            Yaml::Array(ref mut owner_references) => {
+                owner_references.retain(|reference| reference["uid"] != owner_reference["uid"]);
+                owner_references.push(owner_reference.clone());
+            }
+            _ => return Err("Encountered a resource with a non-array \"metadata.ownerReferences\" field.".to_string()),
+        }
+    }
+
+    Ok(Expansion { objects, owner_reference })
+}
+
+/// Returns a controlling owner reference to a `TemplateInstance`.
+///
+/// # Errors
+///
+/// Returns an error if the instance has no name or UID.
+pub fn owner_reference(instance: &Yaml) -> Result<Yaml, String> {
+    let name = instance["metadata"]["name"].as_str().ok_or("TemplateInstance has no name.")?;
+    let uid = instance["metadata"]["uid"].as_str().ok_or("TemplateInstance has no UID.")?;
+    let mut owner_reference = Hash::new();
+
+    owner_reference.insert(ystring("apiVersion"), ystring(instance["apiVersion"].as_str().unwrap_or(API_VERSION)));
+    owner_reference.insert(ystring("blockOwnerDeletion"), Yaml::Boolean(true));
+    owner_reference.insert(ystring("controller"), Yaml::Boolean(true));
+    owner_reference.insert(ystring("kind"), ystring("TemplateInstance"));
+    owner_reference.insert(ystring("name"), ystring(name));
+    owner_reference.insert(ystring("uid"), ystring(uid));
+
+    Ok(Yaml::Hash(owner_reference))
+}
+
+fn parameter_values(instance: &Yaml) -> Result<ParameterValues, String> {
+    let mut parameter_values = ParameterValues::new();
+
+    let parameters = match instance["spec"]["parameters"] {
+        Yaml::Hash(ref parameters) => parameters,
+        Yaml::BadValue | Yaml::Null => return Ok(parameter_values),
+        _ => return Err("TemplateInstance's spec.parameters must be a hash.".to_string()),
+    };
+
+    for (name, value) in parameters {
+        let name = name.as_str().ok_or("Parameter names must be strings.")?;
+        let value = match *value {
+            Yaml::String(ref value) | Yaml::Real(ref value) => value.clone(),
+            Yaml::Integer(value) => value.to_string(),
+            Yaml::Boolean(value) => value.to_string(),
+            _ => return Err(format!("Parameter {} must be a string, number, or boolean.", name)),
+        };
+
+        parameter_values.insert(name.to_string(), ParameterValue::Plain(value));
+    }
+
+    Ok(parameter_values)
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
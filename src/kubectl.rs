@@ -0,0 +1,128 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use yaml::{Yaml, YamlLoader};
+
+/// Fetches a single object from the cluster via `kubectl`, in the given kubeconfig context or the
+/// current one, returning it as YAML.
+pub fn get(context: Option<&str>, kind: &str, namespace: &str, name: &str) -> Result<Yaml, String> {
+    let mut args = context_args(context);
+
+    args.extend(&["get", kind, name, "--namespace", namespace, "--output", "yaml"]);
+
+    let output = run(&args)?;
+
+    let mut docs = YamlLoader::load_from_str(&output).map_err(|err| err.to_string())?;
+
+    if docs.len() != 1 {
+        return Err(format!("kubectl returned an unexpected response for {}/{}/{}.", kind, namespace, name));
+    }
+
+    Ok(docs.remove(0))
+}
+
+/// Returns the arguments selecting a kubeconfig context, if one is given.
+pub fn context_args(context: Option<&str>) -> Vec<&str> {
+    match context {
+        Some(context) => vec!["--context", context],
+        None => vec![],
+    }
+}
+
+/// Runs `kubectl` with the given arguments, returning its standard output.
+pub fn run(args: &[&str]) -> Result<String, String> {
+    run_with_input(args, None)
+}
+
+/// Runs `kubectl` with the given arguments and standard input, returning its standard output.
+pub fn run_with_input(args: &[&str], input: Option<&str>) -> Result<String, String> {
+    debug!("Running kubectl {}", args.join(" "));
+
+    let mut child = Command::new("kubectl")
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run kubectl: {}", err))?;
+
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .expect("Failed to open kubectl's stdin.")
+            .write_all(input.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "kubectl {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    String::from_utf8(output.stdout).map_err(|err| err.to_string())
+}
+
+/// The cluster and default namespace of a kubeconfig context.
+#[derive(Clone, Debug)]
+pub struct KubeContext {
+    /// The name of the context's cluster.
+    pub cluster: String,
+    /// The context's default namespace, or "default" if it doesn't set one.
+    pub namespace: String,
+}
+
+/// Reads a context from the kubeconfig via `kubectl config view`.
+///
+/// # Errors
+///
+/// Returns an error if `kubectl` fails or the context does not exist.
+pub fn kube_context(name: &str) -> Result<KubeContext, String> {
+    let output = run(&["config", "view", "--minify", "--context", name, "--output", "yaml"])?;
+    let docs = YamlLoader::load_from_str(&output).map_err(|err| err.to_string())?;
+    let context = docs
+        .first()
+        .and_then(|config| config["contexts"].as_vec())
+        .and_then(|contexts| contexts.iter().find(|context| context["name"].as_str() == Some(name)))
+        .ok_or_else(|| format!("Context {} was not found in the kubeconfig.", name))?;
+
+    Ok(KubeContext {
+        cluster: context["context"]["cluster"].as_str().unwrap_or_default().to_string(),
+        namespace: context["context"]["namespace"].as_str().unwrap_or("default").to_string(),
+    })
+}
+
+/// Removes the fields the API server populates from an object fetched from the cluster, so it
+/// can be applied again.
+pub fn strip_server_fields(object: &mut Yaml) {
+    if let Yaml::Hash(ref mut hash) = *object {
+        hash.remove(&ystring("status"));
+
+        if let Some(&mut Yaml::Hash(ref mut metadata)) = hash.get_mut(&ystring("metadata")) {
+            for field in &["creationTimestamp", "generation", "managedFields", "resourceVersion", "selfLink", "uid"] {
+                metadata.remove(&ystring(field));
+            }
+
+            let mut empty_annotations = false;
+
+            if let Some(&mut Yaml::Hash(ref mut annotations)) = metadata.get_mut(&ystring("annotations")) {
+                annotations.remove(&ystring("kubectl.kubernetes.io/last-applied-configuration"));
+                annotations.remove(&ystring("deployment.kubernetes.io/revision"));
+                empty_annotations = annotations.is_empty();
+            }
+
+            if empty_annotations {
+                metadata.remove(&ystring("annotations"));
+            }
+        }
+    }
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
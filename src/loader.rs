@@ -0,0 +1,220 @@
+use std::borrow::Cow;
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::mem::replace;
+use std::str::Utf8Error;
+
+use serde_json::Value;
+use yaml::parser::{Event, MarkedEventReceiver, Parser};
+use yaml::scanner::{Marker, ScanError, TScalarStyle, TokenType};
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+/// Loads the YAML documents in a string, like `YamlLoader::load_from_str`, but keeping the
+/// representation of integers that would otherwise be re-emitted differently.
+///
+/// yaml-rust parses plain scalars such as `0755`, `0x1F`, and `+5` into integers, which are
+/// emitted as `755`, `31`, and `5`, changing what Kubernetes reads: a YAML 1.1 parser reads `0755`
+/// as the octal 493. Such scalars are loaded as `Yaml::Real` holding their original text, so
+/// they're emitted verbatim. Integers already in canonical form, e.g. `42` and `-1`, are loaded
+/// as `Yaml::Integer` as usual.
+///
+/// A leading byte order mark is ignored, and CRLF line endings are read as LF.
+pub fn load_yaml(source: &str) -> Result<Vec<Yaml>, ScanError> {
+    let source = normalize_text(source);
+    let mut loader = Loader::default();
+    let mut parser = Parser::new(source.chars());
+
+    parser.load(&mut loader, true)?;
+
+    Ok(loader.docs)
+}
+
+/// Loads a template or other document that may be stored as JSON, as Kubernetes manifests often
+/// are, returning its documents.
+///
+/// JSON is mostly YAML, but yaml-rust rejects some of JSON's string escapes, such as `\/` and
+/// UTF-16 surrogate pairs, so contents starting with `{` are parsed as JSON. If they aren't valid
+/// JSON they're loaded as YAML, since a YAML flow mapping also starts with `{`, and the JSON error
+/// is returned if they aren't valid YAML either. Other contents are loaded with `load_yaml`.
+///
+/// # Errors
+///
+/// Returns an error if the contents are neither valid JSON nor valid YAML.
+pub fn load_yaml_or_json(source: &str) -> Result<Vec<Yaml>, String> {
+    let source = normalize_text(source);
+    let source = source.as_ref();
+
+    if !source.trim_start().starts_with('{') {
+        return load_yaml(source).map_err(|err| err.to_string());
+    }
+
+    match serde_json::from_str(source) {
+        Ok(value) => Ok(vec![json_to_yaml(value)]),
+        Err(json_err) => load_yaml(source).map_err(|_| format!("Invalid JSON: {}", json_err)),
+    }
+}
+
+/// Decodes the contents of a template or parameter file as UTF-8, like `normalize_text`.
+///
+/// # Errors
+///
+/// Returns an error if the contents aren't valid UTF-8, giving the byte offset, line, and column of
+/// the first invalid byte, and the text around it with invalid bytes shown as U+FFFD.
+pub fn decode_text(bytes: Vec<u8>) -> Result<String, String> {
+    let text = String::from_utf8(bytes).map_err(|err| invalid_utf8(err.as_bytes(), err.utf8_error()))?;
+
+    match normalize_text(&text) {
+        Cow::Borrowed(normalized) if normalized.len() == text.len() => {}
+        normalized => return Ok(normalized.into_owned()),
+    }
+
+    Ok(text)
+}
+
+/// Removes a leading UTF-8 byte order mark, which some Windows editors add, and converts CRLF line
+/// endings to LF.
+pub fn normalize_text(text: &str) -> Cow<'_, str> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    if text.contains("\r\n") {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Describes where contents stop being valid UTF-8, previewing up to 24 bytes either side of the
+/// first invalid byte on its line.
+fn invalid_utf8(bytes: &[u8], error: Utf8Error) -> String {
+    let offset = error.valid_up_to();
+    let line_start = bytes[..offset].iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1);
+    let line_end = bytes[offset..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |index| offset + index);
+    let line = bytes[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1;
+    // The bytes before the offset are valid, so this counts their characters.
+    let column = String::from_utf8_lossy(&bytes[line_start..offset]).chars().count() + 1;
+    let mut preview_start = max(line_start, offset.saturating_sub(24));
+
+    // Start the preview on a character boundary.
+    while bytes[preview_start] & 0xC0 == 0x80 {
+        preview_start += 1;
+    }
+
+    let preview = String::from_utf8_lossy(&bytes[preview_start..min(line_end, offset + 24)]);
+
+    format!("Invalid UTF-8 at byte {} (line {}, column {}): {:?}", offset, line, column, preview)
+}
+
+/// Converts a JSON value to YAML. Numbers that aren't integers keep their text, like `load_yaml`.
+fn json_to_yaml(value: Value) -> Yaml {
+    match value {
+        Value::Null => Yaml::Null,
+        Value::Bool(value) => Yaml::Boolean(value),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Yaml::Integer(integer),
+            None => Yaml::Real(number.to_string()),
+        },
+        Value::String(value) => Yaml::String(value),
+        Value::Array(values) => Yaml::Array(values.into_iter().map(json_to_yaml).collect()),
+        Value::Object(object) => Yaml::Hash(
+            object.into_iter().map(|(key, value)| (Yaml::String(key), json_to_yaml(value))).collect(),
+        ),
+    }
+}
+
+#[derive(Default)]
+struct Loader {
+    docs: Vec<Yaml>,
+    /// The nodes being built, with their anchor IDs.
+    doc_stack: Vec<(Yaml, usize)>,
+    /// The pending key of each hash being built, or `BadValue` if the next node is a key.
+    key_stack: Vec<Yaml>,
+    anchor_map: BTreeMap<usize, Yaml>,
+}
+
+impl MarkedEventReceiver for Loader {
+    fn on_event(&mut self, event: &Event, _: Marker) {
+        match *event {
+            Event::DocumentEnd => match self.doc_stack.pop() {
+                Some((doc, _)) => self.docs.push(doc),
+                None => self.docs.push(Yaml::BadValue),
+            },
+            Event::SequenceStart(anchor_id) => self.doc_stack.push((Yaml::Array(vec![]), anchor_id)),
+            Event::MappingStart(anchor_id) => {
+                self.doc_stack.push((Yaml::Hash(Hash::new()), anchor_id));
+                self.key_stack.push(Yaml::BadValue);
+            }
+            Event::SequenceEnd => {
+                let node = self.doc_stack.pop().expect("Sequence ended without starting.");
+
+                self.insert_node(node);
+            }
+            Event::MappingEnd => {
+                self.key_stack.pop();
+
+                let node = self.doc_stack.pop().expect("Mapping ended without starting.");
+
+                self.insert_node(node);
+            }
+            Event::Scalar(ref value, style, anchor_id, ref tag) => {
+                let node = scalar(value, style, tag);
+
+                self.insert_node((node, anchor_id));
+            }
+            Event::Alias(anchor_id) => {
+                let node = self.anchor_map.get(&anchor_id).cloned().unwrap_or(Yaml::BadValue);
+
+                self.insert_node((node, 0));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Loader {
+    fn insert_node(&mut self, (node, anchor_id): (Yaml, usize)) {
+        if anchor_id > 0 {
+            self.anchor_map.insert(anchor_id, node.clone());
+        }
+
+        match self.doc_stack.last_mut() {
+            Some(&mut (Yaml::Array(ref mut array), _)) => array.push(node),
+            Some(&mut (Yaml::Hash(ref mut hash), _)) => {
+                let key = self.key_stack.last_mut().expect("Hash had no pending key.");
+
+                if key.is_badvalue() {
+                    *key = node;
+                } else {
+                    hash.insert(replace(key, Yaml::BadValue), node);
+                }
+            }
+            Some(_) => unreachable!("Only arrays and hashes contain nodes."),
+            None => self.doc_stack.push((node, anchor_id)),
+        }
+    }
+}
+
+fn scalar(value: &str, style: TScalarStyle, tag: &Option<TokenType>) -> Yaml {
+    if style != TScalarStyle::Plain {
+        return Yaml::String(value.to_string());
+    }
+
+    match *tag {
+        Some(TokenType::Tag(ref handle, ref suffix)) if handle == "!!" => match suffix.as_str() {
+            "bool" => value.parse().map(Yaml::Boolean).unwrap_or(Yaml::BadValue),
+            "int" => value.parse().map(Yaml::Integer).unwrap_or(Yaml::BadValue),
+            "float" => value.parse::<f64>().map(|_| Yaml::Real(value.to_string())).unwrap_or(Yaml::BadValue),
+            "null" if value == "~" || value == "null" => Yaml::Null,
+            "null" => Yaml::BadValue,
+            _ => Yaml::String(value.to_string()),
+        },
+        Some(TokenType::Tag(..)) => Yaml::String(value.to_string()),
+        _ => match Yaml::from_str(value) {
+            Yaml::Integer(integer) if integer.to_string() != value => Yaml::Real(value.to_string()),
+            node => node,
+        },
+    }
+}
@@ -0,0 +1,76 @@
+use base64::decode;
+use yaml::Yaml;
+
+use kubectl;
+use parameter::{ParameterValue, ParameterValues};
+
+const LOOKUP_PREFIX: &str = "lookup:";
+
+/// Resolves parameter values that reference live cluster objects.
+///
+/// Any plain value of the form `lookup:KIND/NAMESPACE/NAME#KEY` is replaced with the value found
+/// in the cluster using `kubectl`, in the given kubeconfig context or the current one. For
+/// ConfigMaps and Secrets, `KEY` names an entry in the object's `data` field (Secret data is
+/// Base64 decoded). For any other kind, `KEY` is a dot-separated path from the root of the
+/// object, e.g. `spec.clusterIP`.
+///
+/// # Errors
+///
+/// Returns an error if a lookup reference is malformed, `kubectl` fails to fetch the object, or
+/// the referenced key does not exist or is not a scalar value.
+pub fn resolve_lookups(parameter_values: &mut ParameterValues, context: Option<&str>) -> Result<(), String> {
+    for value in parameter_values.values_mut() {
+        let resolved = match *value {
+            ParameterValue::Plain(ref plain) if plain.starts_with(LOOKUP_PREFIX) => {
+                lookup(&plain[LOOKUP_PREFIX.len()..], context)?
+            }
+            _ => continue,
+        };
+
+        *value = ParameterValue::Plain(resolved);
+    }
+
+    Ok(())
+}
+
+fn lookup(reference: &str, context: Option<&str>) -> Result<String, String> {
+    debug!("Looking up {}", reference);
+
+    let (object_reference, key) = match reference.find('#') {
+        Some(index) => (&reference[..index], &reference[index + 1..]),
+        None => return Err(format!("Lookup \"{}\" must end with \"#KEY\".", reference)),
+    };
+
+    let parts: Vec<&str> = object_reference.split('/').collect();
+
+    if parts.len() != 3 || parts.iter().any(|part| part.is_empty()) || key.is_empty() {
+        return Err(format!("Lookup \"{}\" must be of the form KIND/NAMESPACE/NAME#KEY.", reference));
+    }
+
+    let kind = parts[0].to_lowercase();
+    let object = kubectl::get(context, &kind, parts[1], parts[2])?;
+
+    let value = match kind.as_str() {
+        "configmap" | "configmaps" | "cm" => scalar(&object["data"][key]),
+        "secret" | "secrets" => match scalar(&object["data"][key]) {
+            Some(encoded) => {
+                let decoded = decode(&encoded).map_err(|err| err.to_string())?;
+
+                Some(String::from_utf8(decoded).map_err(|err| err.to_string())?)
+            }
+            None => None,
+        },
+        _ => scalar(key.split('.').fold(&object, |yaml, segment| &yaml[segment])),
+    };
+
+    value.ok_or_else(|| format!("Lookup \"{}\" did not resolve to a scalar value.", reference))
+}
+
+fn scalar(yaml: &Yaml) -> Option<String> {
+    match *yaml {
+        Yaml::Boolean(ref value) => Some(format!("{}", value)),
+        Yaml::Integer(ref value) => Some(format!("{}", value)),
+        Yaml::Real(ref value) | Yaml::String(ref value) => Some(value.clone()),
+        _ => None,
+    }
+}
@@ -1,43 +1,271 @@
 extern crate clap;
 extern crate ktmpl;
+extern crate log;
+extern crate yaml_rust;
 
-use std::collections::{HashMap};
-use std::error::Error;
-use std::fs::File;
-use std::io::{Read, stdin};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, read, read_to_string, remove_file, symlink_metadata, write};
+use std::io::{BufWriter, Read, Write, stdin, stdout};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
 use std::process::exit;
+#[cfg(unix)]
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use clap::{App, AppSettings, Arg, Values};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand, Values};
+use log::{LevelFilter, Log, Metadata, Record, error, info, warn};
+use yaml_rust::Yaml;
 
 use ktmpl::{
+    ApplyOptions,
+    CiAnnotations,
+    CompiledTemplate,
+    ControlCharacters,
+    Error,
+    Outcome,
+    MergeOrder,
+    NullParameters,
+    OPERATOR_CRDS,
+    OperatorOptions,
+    Parameter,
+    ParameterType,
     ParameterValue,
     ParameterValues,
+    Progress,
+    ReleaseTemplate,
+    RemoteOptions,
     Secret,
     Secrets,
+    SignatureVerification,
+    SummaryFormat,
     Template,
-    parameter_values_from_file,
+    annotations,
+    apply,
+    apply_each,
+    capture_rollback,
+    catalog,
+    check_api_versions,
+    check_control_characters,
+    check_policy,
+    compose_to_template,
+    decode_text,
+    decrypt_parameter_values,
+    encrypt_parameter_file,
+    expand_glob,
+    extract,
+    fetch,
+    fetch_bytes,
+    format_annotations,
+    format_cpu,
+    format_memory,
+    format_outputs,
+    format_template,
+    helm_to_template,
+    import_dependency_values,
+    is_encrypted,
+    is_glob,
+    is_remote,
+    kube_context,
+    lint_template,
+    load_yaml,
+    parameter_usages,
+    parameter_values_from_str_merged,
+    parametrize,
+    reconcile_instances,
+    record_release,
+    release_manifest,
+    resolve_lookups,
+    rollback,
+    scan_secrets,
+    split_template_and_parameters,
+    summarize_resources,
+    summary_json,
+    template_graph,
+    template_parameters,
+    template_profile,
+    terraform_query,
+    terraform_result,
+    validate_crds,
+    validate_limits,
+    validate_references,
+    validate_unique,
+    verify_sha256,
+    verify_signature,
+    wait_for_ready,
 };
+#[cfg(unix)]
+use ktmpl::{Daemon, TemplateRegistry};
+
+/// The file a GitLab Code Quality report is written to, which is conventionally collected as a
+/// `codequality` report artifact.
+const GITLAB_REPORT: &str = "gl-code-quality-report.json";
+
+/// Why the program failed, which determines its exit code.
+enum Failure {
+    /// Invalid configuration: parameter files, parameter values, the template's structure, or
+    /// patches and overrides.
+    Config(String),
+    /// A file, URL, or cluster object could not be read.
+    Io(String),
+    /// The processed output, or the template's integrity, failed validation.
+    Validation(String),
+    /// The processed output violated a policy rule.
+    Policy(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> i32 {
+        match *self {
+            Failure::Config(_) => 2,
+            Failure::Validation(_) => 3,
+            Failure::Io(_) => 4,
+            Failure::Policy(_) => 5,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match *self {
+            Failure::Config(ref message)
+            | Failure::Io(ref message)
+            | Failure::Validation(ref message)
+            | Failure::Policy(ref message) => message,
+        }
+    }
+
+    /// Rewrites the failure's message, keeping its exit code.
+    fn map_message<F: FnOnce(String) -> String>(self, f: F) -> Self {
+        match self {
+            Failure::Config(message) => Failure::Config(f(message)),
+            Failure::Io(message) => Failure::Io(f(message)),
+            Failure::Validation(message) => Failure::Validation(f(message)),
+            Failure::Policy(message) => Failure::Policy(f(message)),
+        }
+    }
+}
+
+/// Problems with the template or its parameters are configuration failures, and problems with
+/// what processing it produced are validation failures.
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Parse(message) | Error::Parameter(message) => Failure::Config(message),
+            Error::Io(message) => Failure::Io(message),
+            Error::Secret(message) | Error::Process(message) | Error::Emit(message) => Failure::Validation(message),
+        }
+    }
+}
+
+/// Writes log records to stderr, either as plain text or as one JSON object per line.
+struct Logger {
+    json: bool,
+}
+
+static TEXT_LOGGER: Logger = Logger { json: false };
+static JSON_LOGGER: Logger = Logger { json: true };
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if self.json {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs_f64())
+                .unwrap_or(0.0);
+
+            eprintln!(
+                "{{\"timestamp\":{:.3},\"level\":\"{}\",\"target\":\"{}\",\"message\":\"{}\"}}",
+                timestamp,
+                record.level(),
+                json_escape(record.target()),
+                json_escape(&record.args().to_string()),
+            );
+        } else if record.level() <= log::Level::Warn {
+            eprintln!("{}: {}", level_label(record.level()), record.args());
+        } else {
+            eprintln!("{} [{}] {}", level_label(record.level()), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn level_label(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "Error",
+        log::Level::Warn => "Warning",
+        log::Level::Info => "Info",
+        log::Level::Debug => "Debug",
+        log::Level::Trace => "Trace",
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for character in s.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
 
 fn main() {
-    if let Err(error) = real_main() {
-        println!("Error: {}", error);
+    if let Err(failure) = real_main() {
+        error!("{}", failure.message());
 
-        exit(1);
+        exit(failure.exit_code());
     }
 }
 
-fn real_main() -> Result<(), String> {
+fn real_main() -> Result<(), Failure> {
     let matches = App::new("ktmpl")
         .version(env!("CARGO_PKG_VERSION"))
         .about("Produces a Kubernetes manifest from a parameterized template")
         .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .after_help(
+            "EXIT STATUS:\n    0    Success\n    1    Invalid command line arguments\n    \
+             2    Configuration error (parameters, template structure, patches, or overrides)\n    \
+             3    Validation failure\n    4    I/O error (files, URLs, or the cluster)\n    \
+             5    Policy violation"
+        )
         .setting(AppSettings::AllowLeadingHyphen)
         .arg(
             Arg::with_name("template")
-                .help("Path to the template file to be processed (use \"-\" to read from stdin)")
-                .required(true)
+                .help("Path, glob pattern matching one file, or URL of the template file to be processed (use \"-\" to read from stdin)")
+                .required_unless("terraform-external")
                 .index(1)
         )
+        .arg(
+            Arg::with_name("terraform-external")
+                .help(
+                    "Reads a Terraform external data source query from stdin, giving the template's path as \
+                     \"template\" and parameter values as the other keys, and writes the manifest as \
+                     {\"manifest\": ...}"
+                )
+                .next_line_help(true)
+                .long("terraform-external")
+        )
         .arg(
             Arg::with_name("parameter")
                 .help("Supplies a value for the named parameter")
@@ -46,6 +274,7 @@ fn real_main() -> Result<(), String> {
                 .short("p")
                 .multiple(true)
                 .takes_value(true)
+                .allow_hyphen_values(true)
                 .number_of_values(2)
                 .value_names(&["NAME", "VALUE"])
         )
@@ -57,12 +286,16 @@ fn real_main() -> Result<(), String> {
                 .short("b")
                 .multiple(true)
                 .takes_value(true)
+                .allow_hyphen_values(true)
                 .number_of_values(2)
                 .value_names(&["NAME", "VALUE"])
         )
         .arg(
             Arg::with_name("secret")
-                .help("A secret to Base64 encode after parameter interpolation")
+                .help(
+                    "A secret to Base64 encode after parameter interpolation; NAME and NAMESPACE may contain \
+                     placeholders, e.g. '$(APP_NAME)-creds'"
+                )
                 .next_line_help(true)
                 .long("secret")
                 .short("s")
@@ -71,9 +304,30 @@ fn real_main() -> Result<(), String> {
                 .number_of_values(2)
                 .value_names(&["NAME", "NAMESPACE"])
         )
+        .arg(
+            Arg::with_name("secret-name")
+                .help("A secret to Base64 encode after parameter interpolation, in whichever namespace it's in")
+                .next_line_help(true)
+                .long("secret-name")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("list-secrets")
+                .help(
+                    "Lists which Secret objects the --secret options encode, which they match but can't encode, \
+                     which they don't match, and which --secret options match nothing, instead of printing the \
+                     processed objects"
+                )
+                .next_line_help(true)
+                .long("list-secrets")
+                .conflicts_with("apply")
+        )
         .arg(
             Arg::with_name("parameter-file")
-                .help("Path to a YAML file with parameter values")
+                .help("Path or URL of a YAML file with parameter values, or a glob pattern matching files to load in order")
                 .next_line_help(true)
                 .long("parameter-file")
                 .short("f")
@@ -82,106 +336,2239 @@ fn real_main() -> Result<(), String> {
                 .number_of_values(1)
                 .value_names(&["PARAMETER_FILE"])
         )
-        .get_matches();
-
-    let mut values = HashMap::new();
-
-    if let Some(files) = matches.values_of("parameter-file") {
-        let params_from_file = parameter_files(files)?;
-
-        values.extend(params_from_file);
-    }
-
-    if let Some(parameters) = matches.values_of("parameter") {
-        values.extend(parameter_values(parameters, false));
-    }
-
-    if let Some(parameters) = matches.values_of("base64-parameter") {
-        let encoded_values = parameter_values(parameters, true);
-
-        values.extend(encoded_values);
-    }
-
-    let secrets = matches
-        .values_of("secret")
-        .and_then(|secrets| Some(secret_values(secrets)))
-        .or(None);
-
-    let filename = matches.value_of("template").expect("template wasn't provided");
-    let mut template_data = String::new();
-
-    if filename == "-" {
-        stdin().read_to_string(&mut template_data).map_err(|err| err.description().to_owned())?;
-    } else {
-        let mut file = File::open(filename).map_err(|err| err.description().to_owned())?;
-        file.read_to_string(&mut template_data).map_err(|err| err.description().to_owned())?;
-    }
-
-    let template = Template::new(template_data, values, secrets)?;
-
-    match template.process() {
-        Ok(manifests) => {
-            println!("{}", manifests);
-
-            Ok(())
-        }
-        Err(error) => Err(error),
-    }
-}
-
-fn parameter_files(mut param_files: Values) -> Result<ParameterValues, String> {
-    let mut parameter_values = ParameterValues::new();
-
-    loop {
-        if let Some(filename) = param_files.next() {
-            let values = parameter_values_from_file(&filename)?;
-
-            parameter_values.extend(values);
-        } else {
-            break;
-        }
-    }
-
-    Ok(parameter_values)
-}
-
-fn parameter_values(mut parameters: Values, base64_encoded: bool) -> ParameterValues {
-    let mut parameter_values = ParameterValues::new();
-
-    loop {
-        if let Some(name) = parameters.next() {
-            let value = parameters.next().expect("Parameter was missing its value.");
-
-            let parameter_value = if base64_encoded {
-                ParameterValue::Encoded(value.to_string())
-            } else {
-                ParameterValue::Plain(value.to_string())
-            };
-
-            parameter_values.insert(name.to_string(), parameter_value);
-        } else {
-            break;
-        }
-    }
-
-    parameter_values
-}
-
-fn secret_values(mut secret_parameters: Values) -> Secrets {
-    let mut secrets = Secrets::new();
-
-    loop {
-        if let Some(name) = secret_parameters.next() {
-            let namespace = secret_parameters.next().expect("Secret was missing its namespace.");
-
-            secrets.insert(Secret {
-                name: name.to_string(),
-                namespace: namespace.to_string(),
-            });
-        } else {
-            break;
-        }
+        .arg(
+            Arg::with_name("profile")
+                .help("Uses the values of the template's named profile as defaults for its parameters")
+                .next_line_help(true)
+                .long("profile")
+                .takes_value(true)
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("stdin-params")
+                .help(
+                    "Reads a parameter file from stdin along with the template, after a line \"--- # ktmpl:parameters\", \
+                     or as the \"parameters\" key of a document whose \"template\" key holds the template"
+                )
+                .next_line_help(true)
+                .long("stdin-params")
+                .conflicts_with("terraform-external")
+        )
+        .arg(
+            Arg::with_name("age-identity")
+                .help("Age identity file to decrypt parameter values encrypted with ktmpl encrypt-params")
+                .next_line_help(true)
+                .long("age-identity")
+                .short("i")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["IDENTITY_FILE"])
+        )
+        .arg(
+            Arg::with_name("merge-order")
+                .help("How values set by more than one document of a parameter file are merged")
+                .next_line_help(true)
+                .long("merge-order")
+                .takes_value(true)
+                .possible_values(&["first-wins", "last-wins", "error"])
+                .default_value("last-wins")
+                .value_names(&["ORDER"])
+        )
+        .arg(
+            Arg::with_name("control-characters")
+                .help("Whether supplied parameter values containing control characters are rejected or escaped")
+                .next_line_help(true)
+                .long("control-characters")
+                .takes_value(true)
+                .possible_values(&["reject", "escape"])
+                .default_value("reject")
+                .value_names(&["HANDLING"])
+        )
+        .arg(
+            Arg::with_name("multiline")
+                .help("Allows newlines in the value of the named parameter")
+                .next_line_help(true)
+                .long("multiline")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("interpolate-values")
+                .help("Resolves placeholders in supplied parameter values, e.g. -p CMD 'cd $(HOME)', instead of using the values as they are")
+                .next_line_help(true)
+                .long("interpolate-values")
+        )
+        .arg(
+            Arg::with_name("null-parameters")
+                .help("Whether placeholders for parameters without a value are an error, or are replaced with an empty string, kept as written, or replaced with null")
+                .next_line_help(true)
+                .long("null-parameters")
+                .takes_value(true)
+                .possible_values(&["error", "empty", "keep", "null"])
+                .default_value("error")
+                .value_names(&["HANDLING"])
+        )
+        .arg(
+            Arg::with_name("ca-bundle")
+                .help("Path to a PEM bundle of CA certificates for fetching remote files")
+                .next_line_help(true)
+                .long("ca-bundle")
+                .takes_value(true)
+                .value_names(&["CA_BUNDLE"])
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .help("Fetches remote files without reading or writing the local cache")
+                .next_line_help(true)
+                .long("no-cache")
+                .conflicts_with("refresh")
+        )
+        .arg(
+            Arg::with_name("refresh")
+                .help("Fetches remote files again, replacing their cached copies")
+                .next_line_help(true)
+                .long("refresh")
+        )
+        .arg(
+            Arg::with_name("template-sha256")
+                .help("Fails unless the template's SHA-256 digest matches the given hex digest")
+                .next_line_help(true)
+                .long("template-sha256")
+                .takes_value(true)
+                .value_names(&["HASH"])
+        )
+        .arg(
+            Arg::with_name("parameter-file-sha256")
+                .help("Fails unless the SHA-256 digest of FILE, a parameter file or URL given with --parameter-file, matches the given hex digest")
+                .next_line_help(true)
+                .long("parameter-file-sha256")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .requires("parameter-file")
+                .value_names(&["FILE=HASH"])
+        )
+        .arg(
+            Arg::with_name("compiled-cache")
+                .help("Loads the parsed template from the given file, writing it there if it's missing or stale")
+                .next_line_help(true)
+                .long("compiled-cache")
+                .takes_value(true)
+                .value_names(&["FILE"])
+        )
+        .arg(
+            Arg::with_name("verify-key")
+                .help("Verifies the template's detached signature against the given public key")
+                .next_line_help(true)
+                .long("verify-key")
+                .takes_value(true)
+                .conflicts_with("verify-keyless")
+                .value_names(&["KEY"])
+        )
+        .arg(
+            Arg::with_name("verify-keyless")
+                .help("Verifies the template's keyless signature bundle against a certificate identity")
+                .next_line_help(true)
+                .long("verify-keyless")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["IDENTITY", "OIDC_ISSUER"])
+        )
+        .arg(
+            Arg::with_name("signature")
+                .help("Path or URL of the template's detached signature (defaults to the template's location plus \".sig\")")
+                .next_line_help(true)
+                .long("signature")
+                .takes_value(true)
+                .value_names(&["SIGNATURE_FILE"])
+        )
+        .arg(
+            Arg::with_name("raw")
+                .help("Processes ordinary manifests without a Template wrapper, declaring a parameter for each value given")
+                .next_line_help(true)
+                .long("raw")
+                .conflicts_with_all(&["compiled-cache", "defer", "profile"])
+        )
+        .arg(
+            Arg::with_name("create-namespace")
+                .help("Adds a Namespace object for each namespace the processed objects are in that isn't among them")
+                .next_line_help(true)
+                .long("create-namespace")
+        )
+        .arg(
+            Arg::with_name("source-annotations")
+                .help("Annotates each of the template's objects with the file, lines, and index it came from, as ktmpl.io/source")
+                .next_line_help(true)
+                .long("source-annotations")
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .help("Interpolates this many of the template's objects at once, each on its own thread; requires ktmpl to be built with the parallel feature")
+                .next_line_help(true)
+                .long("jobs")
+                .short("j")
+                .takes_value(true)
+                .value_name("JOBS")
+        )
+        .arg(
+            Arg::with_name("rbac-for")
+                .help("Adds a ServiceAccount with this name, and a Role and RoleBinding granting it the rules in --rbac-spec")
+                .next_line_help(true)
+                .long("rbac-for")
+                .takes_value(true)
+                .value_names(&["NAME"])
+                .requires("rbac-spec")
+        )
+        .arg(
+            Arg::with_name("rbac-spec")
+                .help("Path to a YAML file of the namespace and rules for --rbac-for")
+                .next_line_help(true)
+                .long("rbac-spec")
+                .takes_value(true)
+                .value_names(&["SPEC_FILE"])
+                .requires("rbac-for")
+        )
+        .arg(
+            Arg::with_name("default-namespace")
+                .help("Namespace assumed for objects without one when matching secrets (defaults to \"default\")")
+                .next_line_help(true)
+                .long("default-namespace")
+                .takes_value(true)
+                .value_names(&["NAMESPACE"])
+        )
+        .arg(
+            Arg::with_name("kube-context")
+                .help("Kubeconfig context whose namespace and cluster are exposed as KUBE_NAMESPACE and KUBE_CLUSTER, and used by --lookup")
+                .next_line_help(true)
+                .long("kube-context")
+                .takes_value(true)
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("lookup")
+                .help("Resolves parameter values of the form lookup:KIND/NAMESPACE/NAME#KEY from the cluster")
+                .next_line_help(true)
+                .long("lookup")
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .help("Logs more detail to stderr (-v for progress, -vv for debugging, -vvv for tracing)")
+                .next_line_help(true)
+                .long("verbose")
+                .short("v")
+                .multiple(true)
+                .global(true)
+        )
+        .arg(
+            Arg::with_name("log-format")
+                .help("Format of log messages written to stderr")
+                .next_line_help(true)
+                .long("log-format")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .global(true)
+                .value_names(&["FORMAT"])
+        )
+        .arg(
+            Arg::with_name("ci-annotations")
+                .help(
+                    "Reports problems with the template as CI annotations: github writes workflow commands to \
+                     stdout, and gitlab writes a Code Quality report to gl-code-quality-report.json"
+                )
+                .next_line_help(true)
+                .long("ci-annotations")
+                .takes_value(true)
+                .possible_values(&["github", "gitlab"])
+                .value_names(&["FORMAT"])
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .help("Suppresses the processed output, for when only the exit status matters")
+                .next_line_help(true)
+                .long("quiet")
+                .short("q")
+        )
+        .arg(
+            Arg::with_name("defer")
+                .help("Leaves parameters whose names start with PREFIX unresolved, outputting a template for a later render")
+                .next_line_help(true)
+                .long("defer")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["PREFIX"])
+        )
+        .arg(
+            Arg::with_name("focus")
+                .help("Outputs only the objects matching a selector, e.g. kind=Deployment,name=web")
+                .next_line_help(true)
+                .long("focus")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["SELECTOR"])
+        )
+        .arg(
+            Arg::with_name("apply")
+                .help("Applies the processed objects to the cluster with kubectl instead of printing them")
+                .next_line_help(true)
+                .long("apply")
+                .conflicts_with_all(&["defer", "redact-secrets"])
+        )
+        .arg(
+            Arg::with_name("progress")
+                .help(
+                    "Applies objects one at a time, reporting each one's status and a summary on stderr, as a status \
+                     line (indicator), log lines (plain), or whichever suits stderr (auto)"
+                )
+                .next_line_help(true)
+                .long("progress")
+                .takes_value(true)
+                .possible_values(&["auto", "indicator", "plain"])
+                .requires("apply")
+                .value_names(&["STYLE"])
+        )
+        .arg(
+            Arg::with_name("server-side")
+                .help("Uses server-side apply")
+                .next_line_help(true)
+                .long("server-side")
+                .requires("apply")
+        )
+        .arg(
+            Arg::with_name("field-manager")
+                .help("Name of the field manager that owns the applied fields (defaults to \"ktmpl\" with --server-side)")
+                .next_line_help(true)
+                .long("field-manager")
+                .takes_value(true)
+                .requires("apply")
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("force-conflicts")
+                .help("Takes ownership of fields managed by others when using server-side apply")
+                .next_line_help(true)
+                .long("force-conflicts")
+                .requires("server-side")
+        )
+        .arg(
+            Arg::with_name("prune")
+                .help("Deletes objects matching --selector that ktmpl applied before but are no longer in the template")
+                .next_line_help(true)
+                .long("prune")
+                .requires_all(&["apply", "selector"])
+                .conflicts_with("focus")
+        )
+        .arg(
+            Arg::with_name("selector")
+                .help("Label selector identifying the objects applied from this template, used with --prune")
+                .next_line_help(true)
+                .long("selector")
+                .takes_value(true)
+                .requires("prune")
+                .value_names(&["SELECTOR"])
+        )
+        .arg(
+            Arg::with_name("rollback-bundle")
+                .help("Saves the prior state of the applied objects to a timestamped rollback bundle in DIRECTORY")
+                .next_line_help(true)
+                .long("rollback-bundle")
+                .takes_value(true)
+                .requires("apply")
+                .conflicts_with("focus")
+                .value_names(&["DIRECTORY"])
+        )
+        .arg(
+            Arg::with_name("record-release")
+                .help("Records the processed objects, with secrets redacted, and parameter values as a new revision of release NAME in a cluster ConfigMap")
+                .next_line_help(true)
+                .long("record-release")
+                .takes_value(true)
+                .value_names(&["NAME"])
+        )
+        .arg(
+            Arg::with_name("wait")
+                .help("Waits for applied Deployments, StatefulSets, DaemonSets, and Jobs to become ready")
+                .next_line_help(true)
+                .long("wait")
+                .requires("apply")
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .help("How long to wait for readiness, e.g. 90s, 5m (the default), or 1h")
+                .next_line_help(true)
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .value_names(&["DURATION"])
+        )
+        .arg(
+            Arg::with_name("migrate-api-versions")
+                .help(
+                    "Rewrites objects using deprecated API versions, e.g. extensions/v1beta1 Ingresses, to their \
+                     current equivalents where the conversion is mechanical"
+                )
+                .next_line_help(true)
+                .long("migrate-api-versions")
+        )
+        .arg(
+            Arg::with_name("redact-secrets")
+                .help("Replaces Secret data and sensitive parameter values with <redacted> in the output")
+                .next_line_help(true)
+                .long("redact-secrets")
+        )
+        .arg(
+            Arg::with_name("scan-secrets")
+                .help(
+                    "Fails if objects other than Secrets contain what look like credentials, such as tokens, private \
+                     keys, or random strings; annotate an object with ktmpl.io/scan-secrets: \"false\" to skip it"
+                )
+                .next_line_help(true)
+                .long("scan-secrets")
+        )
+        .arg(
+            Arg::with_name("summary")
+                .help("Prints a table of the processed objects' kinds, with their counts and requested CPU and memory, to stderr")
+                .next_line_help(true)
+                .long("summary")
+        )
+        .arg(
+            Arg::with_name("summary-format")
+                .help(
+                    "Format of the summary: \"table\" (the default) or \"json\", which also lists each workload's \
+                     replicas, requests, node selector, and tolerations for cost estimation"
+                )
+                .next_line_help(true)
+                .long("summary-format")
+                .takes_value(true)
+                .possible_values(&["json", "table"])
+                .requires("summary")
+                .value_names(&["FORMAT"])
+        )
+        .arg(
+            Arg::with_name("outputs")
+                .help("Writes the values of the template's declared outputs to OUTPUTS_FILE, as a YAML hash usable as a parameter file, or to stderr if it's \"-\"")
+                .next_line_help(true)
+                .long("outputs")
+                .takes_value(true)
+                .conflicts_with("list-secrets")
+                .value_names(&["OUTPUTS_FILE"])
+        )
+        .arg(
+            Arg::with_name("check-references")
+                .help(
+                    "Fails if the processed objects refer to ConfigMaps, Secrets, ServiceAccounts, or other objects \
+                     they don't include, or a Service selects none of their pods"
+                )
+                .next_line_help(true)
+                .long("check-references")
+        )
+        .arg(
+            Arg::with_name("external-reference")
+                .help(
+                    "Allows references to an object outside of the template with --check-references, e.g. \
+                     Secret/registry-auth or Secret/*"
+                )
+                .next_line_help(true)
+                .long("external-reference")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .requires("check-references")
+                .value_names(&["KIND/NAME"])
+        )
+        .arg(
+            Arg::with_name("target-version")
+                .help(
+                    "Kubernetes version the processed objects will be applied to, e.g. 1.29; APIs it deprecates \
+                     are warned about, and APIs it removes fail validation"
+                )
+                .next_line_help(true)
+                .long("target-version")
+                .takes_value(true)
+                .value_names(&["VERSION"])
+        )
+        .arg(
+            Arg::with_name("validate")
+                .help(
+                    "Validates the processed objects (\"crds\" checks custom resources against the cluster's CRDs, \
+                     \"duplicates\" checks that no two objects are the same resource, \"limits\" checks Kubernetes \
+                     length and size limits)"
+                )
+                .next_line_help(true)
+                .long("validate")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .possible_values(&["crds", "duplicates", "limits"])
+                .value_names(&["VALIDATION"])
+        )
+        .arg(
+            Arg::with_name("policy")
+                .help("Path to a YAML file of CEL policy rules every processed object must satisfy")
+                .next_line_help(true)
+                .long("policy")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["POLICY_FILE"])
+        )
+        .arg(
+            Arg::with_name("patch")
+                .help("Path to a file of strategic merge or JSON patches to apply to the processed objects")
+                .next_line_help(true)
+                .long("patch")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["PATCH_FILE"])
+        )
+        .arg(
+            Arg::with_name("overlay")
+                .help("Path to a file of partial objects to deep-merge onto the processed objects with the same kind and name, or add if there are none")
+                .next_line_help(true)
+                .long("overlay")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["OVERLAY_FILE"])
+        )
+        .arg(
+            Arg::with_name("set-path")
+                .help("Sets a value in the processed objects, e.g. 'objects[0].spec.replicas=5'")
+                .next_line_help(true)
+                .long("set-path")
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .value_names(&["PATH=VALUE"])
+        )
+        .arg(
+            Arg::with_name("replace")
+                .help("Replaces FROM with TO in every string value of the processed objects, e.g. 'docker.io/=registry.example.com/'")
+                .next_line_help(true)
+                .long("replace")
+                .multiple(true)
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .number_of_values(1)
+                .value_names(&["FROM=TO"])
+        )
+        .arg(
+            Arg::with_name("replace-regex")
+                .help("Treats the FROM of each --replace as a regex, whose TO can refer to its capture groups, e.g. $1")
+                .next_line_help(true)
+                .long("replace-regex")
+                .requires("replace")
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Renders templates for JSON requests on a Unix socket until stopped, keeping parsed templates in memory")
+                .arg(
+                    Arg::with_name("redact-secrets")
+                        .help("Replaces Secret data and sensitive parameter values with <redacted> in the output")
+                        .long("redact-secrets")
+                )
+                .arg(
+                    Arg::with_name("socket")
+                        .help("Path of the Unix socket to listen on, e.g. /run/ktmpl.sock")
+                        .long("socket")
+                        .required(true)
+                        .takes_value(true)
+                        .value_names(&["PATH"])
+                )
+                .arg(
+                    Arg::with_name("templates")
+                        .help("Directory of templates that requests can name, reloaded as they change")
+                        .long("templates")
+                        .takes_value(true)
+                        .value_names(&["DIRECTORY"])
+                )
+                .arg(
+                    Arg::with_name("watch-interval")
+                        .help("How often to check the --templates directory for changes, e.g. 5s or 1m [default: 2s]")
+                        .long("watch-interval")
+                        .requires("templates")
+                        .takes_value(true)
+                        .value_names(&["DURATION"])
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("encrypt-params")
+                .about("Encrypts the values of a parameter file with age, printing the result unless --write is given")
+                .arg(
+                    Arg::with_name("parameter-file")
+                        .help("Path of the parameter file")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("age-recipient")
+                        .help("Age public key to encrypt the values to, e.g. age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p")
+                        .next_line_help(true)
+                        .long("age-recipient")
+                        .short("r")
+                        .required(true)
+                        .multiple(true)
+                        .takes_value(true)
+                        .number_of_values(1)
+                        .value_names(&["RECIPIENT"])
+                )
+                .arg(
+                    Arg::with_name("write")
+                        .help("Rewrites the parameter file")
+                        .next_line_help(true)
+                        .long("write")
+                        .short("w")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("extract")
+                .about("Generates a template from objects in a cluster, e.g. deployment/web")
+                .arg(
+                    Arg::with_name("reference")
+                        .help("Objects to extract, of the form KIND/NAME")
+                        .required(true)
+                        .multiple(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .help("Namespace to fetch the objects from")
+                        .long("namespace")
+                        .short("n")
+                        .takes_value(true)
+                        .value_names(&["NAMESPACE"])
+                )
+                .arg(
+                    Arg::with_name("kube-context")
+                        .help("Kubeconfig context to fetch the objects from")
+                        .long("kube-context")
+                        .takes_value(true)
+                        .value_names(&["NAME"])
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats template files canonically, printing the result unless --write or --check is given")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Template files to format (read from stdin, one per line, when none are given)")
+                        .multiple(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("write")
+                        .help("Rewrites the files that aren't formatted")
+                        .next_line_help(true)
+                        .long("write")
+                        .short("w")
+                )
+                .arg(
+                    Arg::with_name("check")
+                        .help("Lists the files that aren't formatted, failing if there are any")
+                        .next_line_help(true)
+                        .long("check")
+                        .conflicts_with("write")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("from-compose")
+                .about("Generates a template from a Docker Compose file as a starting point")
+                .arg(
+                    Arg::with_name("compose-file")
+                        .help("Path of the Compose file (use \"-\" to read from stdin)")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("from-helm")
+                .about("Converts a Helm chart that only uses .Values interpolation into a template (experimental)")
+                .arg(
+                    Arg::with_name("chart")
+                        .help("Path of the chart directory")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Draws a graph of a template's parameters and objects, and how they refer to each other")
+                .arg(
+                    Arg::with_name("template")
+                        .help("Path or URL of the template file (use \"-\" to read from stdin)")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .help("Graph description language: dot for Graphviz, or mermaid")
+                        .next_line_help(true)
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "mermaid"])
+                        .default_value("dot")
+                        .value_names(&["FORMAT"])
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Checks template files for unused and undeclared parameters, e.g. from a pre-commit hook")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Template files to check (read from stdin, one per line, when none are given)")
+                        .multiple(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("changed")
+                        .help(
+                            "Treats the files as a list of changed files, e.g. from git diff --name-only, skipping \
+                             those that were deleted or aren't templates"
+                        )
+                        .next_line_help(true)
+                        .long("changed")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("operator")
+                .about("Renders and applies TemplateInstance resources in a cluster until stopped")
+                .arg(
+                    Arg::with_name("interval")
+                        .help("How often to reconcile TemplateInstances, e.g. 90s, 5m, or 1h [default: 30s]")
+                        .long("interval")
+                        .takes_value(true)
+                        .value_names(&["DURATION"])
+                )
+                .arg(
+                    Arg::with_name("kube-context")
+                        .help("Kubeconfig context to reconcile in")
+                        .long("kube-context")
+                        .takes_value(true)
+                        .value_names(&["NAME"])
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .help("Namespace to reconcile TemplateInstances in [default: all namespaces]")
+                        .long("namespace")
+                        .short("n")
+                        .takes_value(true)
+                        .value_names(&["NAMESPACE"])
+                )
+                .arg(
+                    Arg::with_name("once")
+                        .help("Reconciles once and exits")
+                        .long("once")
+                )
+                .arg(
+                    Arg::with_name("print-crds")
+                        .help("Prints the Template and TemplateInstance CustomResourceDefinitions and exits")
+                        .long("print-crds")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("params")
+                .about("Lists the parameters declared by a template")
+                .arg(
+                    Arg::with_name("template")
+                        .help("Path or URL of the template file (use \"-\" to read from stdin)")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("parametrize")
+                .about("Replaces a literal value with a parameter across a directory of templates")
+                .arg(
+                    Arg::with_name("value")
+                        .help("Literal value to replace")
+                        .long("value")
+                        .required(true)
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .value_names(&["VALUE"])
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("Name of the parameter to replace it with")
+                        .long("name")
+                        .required(true)
+                        .takes_value(true)
+                        .value_names(&["NAME"])
+                )
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Directory of templates to rewrite")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("catalog")
+                .about("Indexes the templates in a directory tree")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Lists the name, description, and parameter count of each template")
+                        .arg(
+                            Arg::with_name("directory")
+                                .help("Directory to scan for templates")
+                                .required(true)
+                                .index(1)
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Shows the details and parameters of a template in the catalog")
+                        .arg(
+                            Arg::with_name("directory")
+                                .help("Directory to scan for templates")
+                                .required(true)
+                                .index(1)
+                        )
+                        .arg(
+                            Arg::with_name("name")
+                                .help("Name of the template")
+                                .required(true)
+                                .index(2)
+                        )
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("release")
+                .about("Processes each template listed in a release manifest, after the templates it depends on, writing it to its output")
+                .arg(
+                    Arg::with_name("manifest")
+                        .help("Path of the release manifest")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("progress")
+                        .help(
+                            "Processes every template, skipping those that depend on one that failed, and reports \
+                             each one's status and a summary on stderr, as a status line (indicator), log lines \
+                             (plain), or whichever suits stderr (auto)"
+                        )
+                        .next_line_help(true)
+                        .long("progress")
+                        .takes_value(true)
+                        .possible_values(&["auto", "indicator", "plain"])
+                        .value_names(&["STYLE"])
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("rollback")
+                .about("Restores the objects saved in a rollback bundle and deletes the ones the apply created")
+                .arg(
+                    Arg::with_name("bundle")
+                        .help("Path of the rollback bundle")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::with_name("kube-context")
+                        .help("Kubeconfig context to roll back in")
+                        .long("kube-context")
+                        .takes_value(true)
+                        .value_names(&["NAME"])
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("usages")
+                .about("Lists where each parameter declared by a template is referred to")
+                .arg(
+                    Arg::with_name("template")
+                        .help("Path or URL of the template file (use \"-\" to read from stdin)")
+                        .required(true)
+                        .index(1)
+                )
+        )
+        .get_matches();
+
+    let logger = if matches.value_of("log-format") == Some("json") { &JSON_LOGGER } else { &TEXT_LOGGER };
+
+    log::set_logger(logger).expect("Failed to set the logger.");
+    log::set_max_level(match matches.occurrences_of("verbose") {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    });
+
+    match matches.subcommand() {
+        ("catalog", Some(catalog_matches)) => match catalog_matches.subcommand() {
+            ("list", Some(list_matches)) => list_catalog(list_matches),
+            ("show", Some(show_matches)) => show_catalog_entry(show_matches),
+            _ => unreachable!("clap requires a catalog subcommand"),
+        },
+        ("daemon", Some(daemon_matches)) => run_daemon(daemon_matches),
+        ("encrypt-params", Some(encrypt_matches)) => {
+            let filename = encrypt_matches.value_of("parameter-file").expect("parameter file wasn't provided");
+            let recipients: Vec<&str> = encrypt_matches
+                .values_of("age-recipient")
+                .expect("recipients weren't provided")
+                .collect();
+            let encrypted = encrypt_parameter_file(&read_file(filename)?, &recipients)
+                .map_err(|err| Failure::Config(format!("{}: {}", filename, err)))?;
+
+            if encrypt_matches.is_present("write") {
+                write(filename, encrypted).map_err(|err| Failure::Io(format!("Failed to write {}: {}", filename, err)))?;
+                info!("Encrypted {}", filename);
+            } else {
+                print!("{}", encrypted);
+            }
+
+            Ok(())
+        }
+        ("extract", Some(extract_matches)) => {
+            let references: Vec<&str> = extract_matches
+                .values_of("reference")
+                .expect("references weren't provided")
+                .collect();
+            let template = extract(
+                &references,
+                extract_matches.value_of("namespace"),
+                extract_matches.value_of("kube-context"),
+            ).map_err(Failure::Io)?;
+
+            println!("{}", template);
+
+            Ok(())
+        }
+        ("fmt", Some(fmt_matches)) => format_files(fmt_matches),
+        ("from-compose", Some(compose_matches)) => {
+            let compose_file = compose_matches.value_of("compose-file").expect("Compose file wasn't provided");
+            let contents = read_template(compose_file, None, &RemoteOptions::from_env())?;
+
+            println!("{}", compose_to_template(&contents).map_err(Failure::Config)?);
+
+            Ok(())
+        }
+        ("from-helm", Some(helm_matches)) => {
+            let chart = helm_matches.value_of("chart").expect("chart wasn't provided");
+
+            println!("{}", helm_to_template(Path::new(chart)).map_err(Failure::Config)?);
+
+            Ok(())
+        }
+        ("parametrize", Some(parametrize_matches)) => {
+            let rewritten = parametrize(
+                Path::new(parametrize_matches.value_of("directory").expect("directory wasn't provided")),
+                parametrize_matches.value_of("value").expect("value wasn't provided"),
+                parametrize_matches.value_of("name").expect("name wasn't provided"),
+            ).map_err(Failure::Io)?;
+
+            for path in rewritten {
+                println!("Rewrote {}", path.display());
+            }
+
+            Ok(())
+        }
+        ("graph", Some(graph_matches)) => {
+            let filename = template_path(graph_matches.value_of("template").expect("template wasn't provided"))?;
+            let template_data = read_template(&filename, None, &RemoteOptions::from_env())?;
+            let format = graph_matches
+                .value_of("format")
+                .expect("graph format wasn't provided")
+                .parse()
+                .map_err(Failure::Config)?;
+
+            println!("{}", template_graph(&template_data, format).map_err(Failure::Config)?);
+
+            Ok(())
+        }
+        ("lint", Some(lint_matches)) => lint_files(lint_matches),
+        ("operator", Some(operator_matches)) => run_operator(operator_matches),
+        ("params", Some(params_matches)) => list_parameters(params_matches),
+        ("release", Some(release_matches)) => process_release(release_matches),
+        ("rollback", Some(rollback_matches)) => roll_back(rollback_matches),
+        ("usages", Some(usages_matches)) => list_usages(usages_matches),
+        _ if matches.is_present("terraform-external") => render_terraform_external(&matches),
+        _ => {
+            let result = render(&matches);
+
+            if let Some(format) = matches.value_of("ci-annotations") {
+                let format = format.parse().map_err(Failure::Config)?;
+                let filename = matches.value_of("template").expect("template wasn't provided");
+                let filename = template_path(filename).unwrap_or_else(|_| filename.to_string());
+
+                report_annotations(format, &filename, result.as_ref().err())?;
+            }
+
+            result
+        }
+    }
+}
+
+/// Reports a failure to render a template as CI annotations. A GitLab report is written even when
+/// rendering succeeds, so a report from an earlier failure isn't left behind.
+fn report_annotations(format: CiAnnotations, filename: &str, failure: Option<&Failure>) -> Result<(), Failure> {
+    let source = if filename == "-" || is_remote(filename) { None } else { read_to_string(filename).ok() };
+    let annotations = failure
+        .map(|failure| annotations(failure.message(), source.as_deref()))
+        .unwrap_or_default();
+    let formatted = format_annotations(format, filename, &annotations);
+
+    match format {
+        CiAnnotations::GitHub => if !annotations.is_empty() {
+            println!("{}", formatted);
+        },
+        CiAnnotations::GitLab => write(GITLAB_REPORT, formatted + "\n")
+            .map_err(|err| Failure::Io(format!("Failed to write {}: {}", GITLAB_REPORT, err)))?,
+    }
+
+    Ok(())
+}
+
+fn render(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut remote_options = RemoteOptions::from_env();
+
+    if let Some(ca_bundle) = matches.value_of("ca-bundle") {
+        remote_options.ca_bundle = Some(ca_bundle.to_string());
+    }
+
+    if matches.is_present("no-cache") {
+        remote_options.cache_dir = None;
+    }
+
+    remote_options.refresh = matches.is_present("refresh");
+
+    let stdin_input = if matches.is_present("stdin-params") {
+        if matches.value_of("template") != Some("-") {
+            return Err(Failure::Config(
+                "--stdin-params requires the template to be read from stdin as \"-\".".to_string(),
+            ));
+        }
+
+        let input = read_stdin()?;
+        let (template_data, parameter_contents) = split_template_and_parameters(&input)
+            .map_err(|err| Failure::Config(format!("--stdin-params: {}", err)))?;
+
+        Some((template_data, parameter_contents))
+    } else {
+        None
+    };
+    let mut values = HashMap::new();
+
+    if matches.is_present("parameter-file") || stdin_input.is_some() {
+        let merge_order = matches
+            .value_of("merge-order")
+            .expect("merge order wasn't provided")
+            .parse()
+            .map_err(Failure::Config)?;
+        let mut params_from_file = match matches.values_of("parameter-file") {
+            Some(files) => parameter_files(files, merge_order, parameter_file_pins(matches)?, &remote_options)?,
+            None => ParameterValues::new(),
+        };
+
+        if let Some((_, ref parameter_contents)) = stdin_input {
+            params_from_file.extend(parameter_file_values("--stdin-params", parameter_contents, merge_order)?);
+        }
+
+        if let Some(identity_files) = matches.values_of("age-identity") {
+            let mut identities = String::new();
+
+            for identity_file in identity_files {
+                identities.push_str(&read_flag_file("age-identity", identity_file)?);
+                identities.push('\n');
+            }
+
+            decrypt_parameter_values(&mut params_from_file, &identities).map_err(Failure::Config)?;
+        }
+
+        let mut encrypted: Vec<&String> = params_from_file
+            .iter()
+            .filter(|&(_, value)| match *value {
+                ParameterValue::Plain(ref value) | ParameterValue::Encoded(ref value) => is_encrypted(value),
+            })
+            .map(|(name, _)| name)
+            .collect();
+
+        if !encrypted.is_empty() {
+            encrypted.sort();
+
+            return Err(Failure::Config(format!(
+                "Parameters are encrypted; pass --age-identity to decrypt them: {}.",
+                encrypted.iter().map(|name| name.as_str()).collect::<Vec<&str>>().join(", "),
+            )));
+        }
+
+        values.extend(params_from_file);
+    }
+
+    if let Some(parameters) = matches.values_of("parameter") {
+        values.extend(parameter_values("parameter", parameters, false)?);
+    }
+
+    if let Some(parameters) = matches.values_of("base64-parameter") {
+        let encoded_values = parameter_values("base64-parameter", parameters, true)?;
+
+        values.extend(encoded_values);
+    }
+
+    if matches.is_present("lookup") {
+        resolve_lookups(&mut values, matches.value_of("kube-context")).map_err(Failure::Io)?;
+    }
+
+    let control_characters: ControlCharacters = matches
+        .value_of("control-characters")
+        .expect("control character handling wasn't provided")
+        .parse()
+        .map_err(Failure::Config)?;
+    let multiline: Vec<&str> = matches.values_of("multiline").map(|names| names.collect()).unwrap_or_default();
+
+    check_control_characters(&mut values, control_characters, &multiline).map_err(Failure::Config)?;
+
+    if let Some(target_version) = matches.value_of("target-version") {
+        check_api_versions(&[], target_version).map_err(Failure::Config)?;
+    }
+
+    let secrets = if matches.is_present("secret") || matches.is_present("secret-name") {
+        Some(secret_values(matches)?)
+    } else {
+        None
+    };
+
+    let filename = template_path(matches.value_of("template").expect("template wasn't provided"))?;
+    let filename = filename.as_str();
+
+    let verification = if let Some(key) = matches.value_of("verify-key") {
+        Some(SignatureVerification::Key(key.to_string()))
+    } else {
+        match matches.values_of("verify-keyless").map(|keyless| keyless.collect::<Vec<&str>>()) {
+            Some(keyless) => match keyless.as_slice() {
+                [identity, issuer] => Some(SignatureVerification::Keyless {
+                    identity: identity.to_string(),
+                    issuer: issuer.to_string(),
+                }),
+                _ => return Err(Failure::Config(
+                    "--verify-keyless takes a certificate identity and an OIDC issuer.".to_string(),
+                )),
+            },
+            None => None,
+        }
+    };
+
+    if verification.is_some() && filename == "-" {
+        return Err(Failure::Config(
+            "Signatures can only be verified for templates read from a file or URL.".to_string()
+        ));
+    }
+
+    let template_data = match (stdin_input, verification) {
+        (Some((template_data, _)), _) => {
+            if let Some(hash) = matches.value_of("template-sha256") {
+                verify_sha256(template_data.as_bytes(), hash).map_err(Failure::Validation)?;
+            }
+
+            template_data
+        }
+        (None, Some(verification)) => read_signed_template(
+            filename,
+            matches.value_of("signature"),
+            &verification,
+            matches.value_of("template-sha256"),
+            &remote_options,
+        )?,
+        (None, None) => read_template(filename, matches.value_of("template-sha256"), &remote_options)?,
+    };
+
+    if let Some(profile) = matches.value_of("profile") {
+        let mut profile_values = template_profile(&template_data, profile).map_err(Failure::Config)?;
+
+        profile_values.extend(values);
+        values = profile_values;
+    }
+
+    let deferred_prefixes: Vec<String> = matches
+        .values_of("defer")
+        .map(|prefixes| prefixes.map(|prefix| prefix.to_string()).collect())
+        .unwrap_or_default();
+    let deferring = !deferred_prefixes.is_empty();
+    let mut template = match matches.value_of("compiled-cache") {
+        _ if matches.is_present("raw") => Template::from_manifests(template_data, values, secrets),
+        Some(cache) => Template::from_compiled_deferred(
+            compiled_template(cache, &template_data)?,
+            values,
+            secrets,
+            deferred_prefixes,
+        ),
+        None => Template::new_deferred(template_data, values, secrets, deferred_prefixes),
+    }?;
+
+    template.set_file_name(filename);
+
+    for warning in template.warnings() {
+        warn!("{}", warning);
+    }
+
+    if let Some(context_name) = matches.value_of("kube-context") {
+        template.set_kube_context(kube_context(context_name).map_err(Failure::Io)?);
+    }
+
+    if let Some(default_namespace) = matches.value_of("default-namespace") {
+        template.set_default_namespace(default_namespace);
+    }
+
+    template.set_create_namespaces(matches.is_present("create-namespace"));
+    template.set_migrate_api_versions(matches.is_present("migrate-api-versions"));
+    template.set_interpolate_supplied_values(matches.is_present("interpolate-values"));
+    template.set_null_parameters(null_parameters(matches)?);
+    template.set_redact_secrets(matches.is_present("redact-secrets"));
+    template.set_source_annotations(matches.is_present("source-annotations"));
+
+    if let Some(jobs) = matches.value_of("jobs") {
+        match jobs.parse::<usize>() {
+            Ok(jobs) if jobs > 0 => template.set_jobs(jobs),
+            _ => return Err(Failure::Config("--jobs must be a positive number.".to_string())),
+        }
+    }
+
+    if let Some(selectors) = matches.values_of("focus") {
+        for selector in selectors {
+            template.add_focus(selector)?;
+        }
+    }
+
+    if let Some(patch_files) = matches.values_of("patch") {
+        for patch_file in patch_files {
+            template
+                .add_patches(&read_flag_file("patch", patch_file)?)
+                .map_err(|err| Failure::from(err).map_message(|err| format!("--patch {}: {}", patch_file, err)))?;
+        }
+    }
+
+    if let Some(overlay_files) = matches.values_of("overlay") {
+        for overlay_file in overlay_files {
+            template
+                .add_overlays(&read_flag_file("overlay", overlay_file)?)
+                .map_err(|err| Failure::from(err).map_message(|err| format!("--overlay {}: {}", overlay_file, err)))?;
+        }
+    }
+
+    if let (Some(name), Some(spec_file)) = (matches.value_of("rbac-for"), matches.value_of("rbac-spec")) {
+        template
+            .add_rbac(name, &read_flag_file("rbac-spec", spec_file)?)
+            .map_err(|err| Failure::from(err).map_message(|err| format!("--rbac-spec {}: {}", spec_file, err)))?;
+    }
+
+    if let Some(overrides) = matches.values_of("set-path") {
+        for path_override in overrides {
+            template.add_override(path_override)?;
+        }
+    }
+
+    if let Some(replacements) = matches.values_of("replace") {
+        for replacement in replacements {
+            template.add_replacement(replacement, matches.is_present("replace-regex"))?;
+        }
+    }
+
+    let parameters: Vec<(String, String)> = template
+        .parameters()
+        .into_iter()
+        .filter_map(|parameter| parameter.value.as_ref().map(|value| {
+            let value = if parameter.is_sensitive() { "<redacted>".to_string() } else { value.clone() };
+
+            (parameter.name.clone(), value)
+        }))
+        .collect();
+    let sensitive_values: Vec<String> = template
+        .parameters()
+        .into_iter()
+        .filter(|parameter| parameter.is_sensitive())
+        .filter_map(|parameter| parameter.value.clone())
+        .filter(|value| !value.is_empty())
+        .collect();
+
+    if matches.is_present("list-secrets") {
+        let (_, report) = template.process_listing_secrets()?;
+        let object_row = |status: &str, (index, secret): (usize, Secret)| [
+            status.to_string(),
+            format!("objects[{}]", index),
+            secret.namespace.unwrap_or_default(),
+            secret.name,
+        ];
+        let mut rows = vec![["STATUS".to_string(), "OBJECT".to_string(), "NAMESPACE".to_string(), "NAME".to_string()]];
+
+        rows.extend(report.encoded.into_iter().map(|object| object_row("encoded", object)));
+        rows.extend(report.skipped.into_iter().map(|object| object_row("matched, no data", object)));
+        rows.extend(report.unspecified.into_iter().map(|object| object_row("not specified", object)));
+        rows.extend(report.unmatched.into_iter().map(|secret| [
+            "matched nothing".to_string(),
+            "-".to_string(),
+            secret.namespace.unwrap_or_else(|| "*".to_string()),
+            secret.name,
+        ]));
+
+        print_table(&rows);
+
+        return Ok(());
+    }
+
+    // Objects that nothing else inspects are streamed out rather than reloaded from the manifest.
+    let inspected = ["apply", "check-references", "outputs", "policy", "record-release", "scan-secrets", "summary", "target-version", "validate"];
+
+    if !inspected.iter().any(|name| matches.is_present(name)) {
+        if matches.is_present("quiet") {
+            template.process()?;
+        } else {
+            let stdout = stdout();
+            let mut sink = BufWriter::new(stdout.lock());
+
+            template.process_to(&mut sink)?;
+            writeln!(sink).and_then(|_| sink.flush()).map_err(|err| Failure::Io(err.to_string()))?;
+        }
+
+        return Ok(());
+    }
+
+    let (manifests, outputs) = template.process_with_outputs()?;
+    let mut objects = load_yaml(&manifests).map_err(|err| Failure::Validation(err.to_string()))?;
+
+    if deferring {
+        objects = objects[0]["objects"].as_vec().cloned().unwrap_or_default();
+    }
+
+    if let Some(validations) = matches.values_of("validate") {
+        for validation in validations {
+            match validation {
+                "crds" => validate_crds(&objects).map_err(Failure::Validation)?,
+                "duplicates" => validate_unique(&objects).map_err(Failure::Validation)?,
+                "limits" => validate_limits(&objects).map_err(Failure::Validation)?,
+                _ => unreachable!("clap restricts the validations"),
+            }
+        }
+    }
+
+    if let Some(target_version) = matches.value_of("target-version") {
+        for warning in check_api_versions(&objects, target_version).map_err(Failure::Validation)? {
+            warn!("{}", warning);
+        }
+    }
+
+    if matches.is_present("scan-secrets") {
+        scan_secrets(&objects).map_err(Failure::Validation)?;
+    }
+
+    if matches.is_present("check-references") {
+        let external: Vec<&str> = matches.values_of("external-reference").map(|values| values.collect()).unwrap_or_default();
+
+        validate_references(&objects, &external).map_err(Failure::Validation)?;
+    }
+
+    if let Some(policy_files) = matches.values_of("policy") {
+        for policy_file in policy_files {
+            check_policy(&read_flag_file("policy", policy_file)?, &objects).map_err(Failure::Policy)?;
+        }
+    }
+
+    if matches.is_present("apply") {
+        let server_side = matches.is_present("server-side");
+        let options = ApplyOptions {
+            context: matches.value_of("kube-context").map(|context| context.to_string()),
+            field_manager: matches
+                .value_of("field-manager")
+                .or(if server_side { Some("ktmpl") } else { None })
+                .map(|field_manager| field_manager.to_string()),
+            force_conflicts: matches.is_present("force-conflicts"),
+            prune_selector: matches.value_of("selector").map(|selector| selector.to_string()),
+            server_side,
+        };
+
+        if let Some(directory) = matches.value_of("rollback-bundle") {
+            let bundle = capture_rollback(&objects, &options).map_err(Failure::Io)?;
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+            let path = Path::new(directory).join(format!("rollback-{}.yml", timestamp));
+
+            create_dir_all(directory)
+                .and_then(|_| write(&path, bundle + "\n"))
+                .map_err(|err| Failure::Io(format!("Failed to write {}: {}", path.display(), err)))?;
+
+            info!("Saved rollback bundle {}", path.display());
+        }
+
+        let output = match matches.value_of("progress") {
+            Some(style) => {
+                let mut progress = Progress::new("applied", objects.len(), style.parse().map_err(Failure::Config)?);
+                let result = apply_each(&objects, &options, |object, outcome| {
+                    progress.report(&object_label(object), outcome)
+                });
+
+                progress.finish();
+
+                result
+            }
+            None => apply(&objects, &options),
+        }
+        .map_err(Failure::Io)?;
+
+        if !matches.is_present("quiet") {
+            print!("{}", output);
+        }
+
+        if matches.is_present("wait") {
+            let timeout = parse_duration(matches.value_of("timeout").unwrap_or("5m"))
+                .map_err(Failure::Config)?;
+            let output = wait_for_ready(&objects, timeout, &options).map_err(Failure::Validation)?;
+
+            if !matches.is_present("quiet") {
+                print!("{}", output);
+            }
+        }
+    } else if !matches.is_present("quiet") {
+        println!("{}", manifests);
+    }
+
+    if matches.is_present("summary") {
+        let format = matches.value_of("summary-format").unwrap_or("table").parse().map_err(Failure::Config)?;
+
+        match format {
+            SummaryFormat::Json => eprintln!("{}", summary_json(&objects).map_err(Failure::Validation)?),
+            SummaryFormat::Table => print_summary(&objects)?,
+        }
+    }
+
+    if let Some(outputs_file) = matches.value_of("outputs") {
+        let formatted = format_outputs(&outputs).map_err(Failure::Validation)?;
+
+        if outputs_file == "-" {
+            eprint!("{}", formatted);
+        } else {
+            write(outputs_file, formatted)
+                .map_err(|err| Failure::Io(format!("Failed to write {}: {}", outputs_file, err)))?;
+        }
+    }
+
+    if let Some(release) = matches.value_of("record-release") {
+        let options = ApplyOptions {
+            context: matches.value_of("kube-context").map(|context| context.to_string()),
+            ..ApplyOptions::default()
+        };
+        let sensitive_values: Vec<&str> = sensitive_values.iter().map(|value| value.as_str()).collect();
+        let revision = record_release(release, &objects, &sensitive_values, &parameters, Some(filename), &options)
+            .map_err(Failure::Io)?;
+
+        info!("Recorded revision {} of release {}", revision, release);
+    }
+
+    Ok(())
+}
+
+fn list_parameters(matches: &ArgMatches) -> Result<(), Failure> {
+    let filename = template_path(matches.value_of("template").expect("template wasn't provided"))?;
+    let template_data = read_template(&filename, None, &RemoteOptions::from_env())?;
+    let parameters = template_parameters(&template_data).map_err(Failure::Config)?;
+
+    print_parameters(parameters);
+
+    Ok(())
+}
+
+fn list_catalog(matches: &ArgMatches) -> Result<(), Failure> {
+    let directory = matches.value_of("directory").expect("directory wasn't provided");
+    let entries = catalog(Path::new(directory)).map_err(Failure::Io)?;
+
+    let mut rows = vec![
+        ["NAME", "PARAMETERS", "PATH", "DESCRIPTION"].map(|heading| heading.to_string()),
+    ];
+
+    for entry in entries {
+        rows.push([
+            entry.name,
+            entry.parameters.iter().filter(|parameter| !parameter.hidden).count().to_string(),
+            entry.path.display().to_string(),
+            entry.description.unwrap_or_default(),
+        ]);
+    }
+
+    print_table(&rows);
+
+    Ok(())
+}
+
+fn show_catalog_entry(matches: &ArgMatches) -> Result<(), Failure> {
+    let directory = matches.value_of("directory").expect("directory wasn't provided");
+    let name = matches.value_of("name").expect("name wasn't provided");
+    let entry = catalog(Path::new(directory))
+        .map_err(Failure::Io)?
+        .into_iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| Failure::Config(format!("No template named {} was found in {}.", name, directory)))?;
+
+    println!("Name:         {}", entry.name);
+    println!("Path:         {}", entry.path.display());
+    println!("Description:  {}", entry.description.unwrap_or_default());
+    println!();
+
+    print_parameters(entry.parameters);
+
+    Ok(())
+}
+
+/// Prints a table of parameters, leaving out hidden ones.
+fn print_parameters(parameters: Vec<Parameter>) {
+    let mut rows = vec![
+        ["NAME", "REQUIRED", "TYPE", "DEFAULT", "DESCRIPTION"].map(|heading| heading.to_string()),
+    ];
+
+    for parameter in parameters.into_iter().filter(|parameter| !parameter.hidden) {
+        rows.push([
+            parameter.name,
+            parameter.required.to_string(),
+            match parameter.parameter_type {
+                Some(ParameterType::Base64) => "base64",
+                Some(ParameterType::Bool) => "bool",
+                Some(ParameterType::Int) => "int",
+                Some(ParameterType::String) => "string",
+                None => "",
+            }.to_string(),
+            parameter.value.unwrap_or_default(),
+            parameter.description.unwrap_or_default(),
+        ]);
+    }
+
+    print_table(&rows);
+}
+
+/// Returns how `kubectl` refers to an object, e.g. `deployment/web`, for progress reports.
+fn object_label(object: &Yaml) -> String {
+    format!(
+        "{}/{}",
+        object["kind"].as_str().unwrap_or_default().to_lowercase(),
+        object["metadata"]["name"].as_str().unwrap_or_default(),
+    )
+}
+
+/// Prints a table summarizing objects by kind to stderr.
+fn print_summary(objects: &[Yaml]) -> Result<(), Failure> {
+    let summaries = summarize_resources(objects).map_err(Failure::Validation)?;
+    let mut rows = vec![["KIND".to_string(), "COUNT".to_string(), "CPU".to_string(), "MEMORY".to_string()]];
+    let (mut count, mut cpu, mut memory) = (0, 0, 0);
+
+    for summary in summaries {
+        count += summary.count;
+        cpu += summary.cpu_millicores;
+        memory += summary.memory_bytes;
+        rows.push([
+            summary.kind,
+            summary.count.to_string(),
+            format_cpu(summary.cpu_millicores),
+            format_memory(summary.memory_bytes),
+        ]);
+    }
+
+    rows.push(["Total".to_string(), count.to_string(), format_cpu(cpu), format_memory(memory)]);
+
+    for line in table_lines(&rows) {
+        eprintln!("{}", line);
+    }
+
+    if objects.iter().any(|object| object["kind"].as_str() == Some("DaemonSet")) {
+        eprintln!("DaemonSets' requests are per node.");
+    }
+
+    Ok(())
+}
+
+/// Prints rows with each column padded to its widest cell, except the last.
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    for line in table_lines(rows) {
+        println!("{}", line);
+    }
+}
+
+/// Returns the lines of rows with each column padded to its widest cell, except the last.
+fn table_lines<const N: usize>(rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = [0; N];
+
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths.iter())
+                .enumerate()
+                .map(|(index, (cell, width))| if index + 1 == N {
+                    cell.to_string()
+                } else {
+                    format!("{:<width$}", cell, width = width)
+                })
+                .collect();
+
+            cells.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+fn render_terraform_external(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut query = String::new();
+
+    stdin().read_to_string(&mut query).map_err(|err| Failure::Io(format!("Failed to read the query: {}", err)))?;
+
+    let (filename, mut values) = terraform_query(&query).map_err(Failure::Config)?;
+
+    if filename == "-" {
+        return Err(Failure::Config("The template can't be read from stdin, which holds the query.".to_string()));
+    }
+
+    let control_characters: ControlCharacters = matches
+        .value_of("control-characters")
+        .expect("control character handling wasn't provided")
+        .parse()
+        .map_err(Failure::Config)?;
+    let multiline: Vec<&str> = matches.values_of("multiline").map(|names| names.collect()).unwrap_or_default();
+
+    check_control_characters(&mut values, control_characters, &multiline).map_err(Failure::Config)?;
+
+    let template_data = read_template(&filename, None, &RemoteOptions::from_env())?;
+    let mut template = Template::new(template_data, values, None)?;
+
+    template.set_file_name(&filename);
+    template.set_interpolate_supplied_values(matches.is_present("interpolate-values"));
+    template.set_null_parameters(null_parameters(matches)?);
+
+    let manifests = template.process()?;
+
+    println!("{}", terraform_result(&manifests));
+
+    Ok(())
+}
+
+fn read_template(
+    filename: &str,
+    sha256: Option<&str>,
+    remote_options: &RemoteOptions,
+) -> Result<String, Failure> {
+    let template_data = if filename == "-" {
+        read_stdin()?
+    } else if is_remote(filename) {
+        fetch(filename, sha256, remote_options).map_err(Failure::Io)?
+    } else {
+        read_file(filename)?
+    };
+
+    if let Some(hash) = sha256 {
+        verify_sha256(template_data.as_bytes(), hash).map_err(Failure::Validation)?;
+    }
+
+    Ok(template_data)
+}
+
+/// Reads a template from a file or URL and verifies its signature, read from `signature` or the
+/// template's location plus ".sig", against the same bytes that are returned, so the template
+/// can't change between being verified and being rendered.
+fn read_signed_template(
+    filename: &str,
+    signature: Option<&str>,
+    verification: &SignatureVerification,
+    sha256: Option<&str>,
+    remote_options: &RemoteOptions,
+) -> Result<String, Failure> {
+    let signature = signature.map(|signature| signature.to_string()).unwrap_or_else(|| format!("{}.sig", filename));
+    let signature_bytes = if is_remote(&signature) {
+        fetch_bytes(&signature, None, remote_options).map_err(Failure::Io)?
+    } else {
+        read(&signature).map_err(|err| Failure::Io(format!("{}: {}", signature, err)))?
+    };
+    let bytes = if is_remote(filename) {
+        fetch_bytes(filename, sha256, remote_options).map_err(Failure::Io)?
+    } else {
+        read(filename).map_err(|err| Failure::Io(format!("{}: {}", filename, err)))?
+    };
+
+    verify_signature(&bytes, filename, &signature_bytes, verification).map_err(Failure::Validation)?;
+
+    if let Some(hash) = sha256 {
+        verify_sha256(&bytes, hash).map_err(Failure::Validation)?;
+    }
+
+    decode_text(bytes).map_err(|err| Failure::Io(format!("{}: {}", filename, err)))
+}
+
+/// Loads a compiled template from a cache file, or compiles the template and writes the cache if
+/// the file is missing, unreadable, or compiled from different contents.
+fn compiled_template(cache: &str, template_data: &str) -> Result<CompiledTemplate, Failure> {
+    let cached = read(cache).ok().and_then(|bytes| CompiledTemplate::from_bytes(&bytes).ok());
+
+    if let Some(compiled) = cached {
+        if compiled.is_compiled_from(template_data) {
+            return Ok(compiled);
+        }
+    }
+
+    let compiled = CompiledTemplate::compile(template_data).map_err(Failure::Config)?;
+
+    write(cache, compiled.to_bytes()).map_err(|err| Failure::Io(format!("Failed to write {}: {}", cache, err)))?;
+
+    Ok(compiled)
+}
+
+/// Expands a glob pattern given for a flag or argument into the files it matches, in order, so
+/// patterns work where the shell doesn't expand them. Paths that exist, URLs, and "-" are used as
+/// they are.
+fn expand_path(description: &str, path: &str) -> Result<Vec<String>, Failure> {
+    if path == "-" || is_remote(path) || !is_glob(path) || Path::new(path).exists() {
+        return Ok(vec![path.to_string()]);
+    }
+
+    let paths: Vec<String> = expand_glob(path).iter().map(|path| path.to_string_lossy().into_owned()).collect();
+
+    if paths.is_empty() {
+        return Err(Failure::Config(format!("{} {} matched no files.", description, path)));
+    }
+
+    Ok(paths)
+}
+
+/// Expands a template argument like `expand_path`, requiring it to match a single template.
+fn template_path(path: &str) -> Result<String, Failure> {
+    let mut paths = expand_path("Template", path)?;
+
+    if paths.len() > 1 {
+        return Err(Failure::Config(format!(
+            "Template {} matched {} files, but only one template can be processed: {}.",
+            path,
+            paths.len(),
+            paths.join(", "),
+        )));
+    }
+
+    Ok(paths.remove(0))
+}
+
+/// Reads a text file, ignoring a leading byte order mark and converting CRLF line endings to LF.
+fn read_file(filename: &str) -> Result<String, Failure> {
+    let bytes = read(filename).map_err(|err| Failure::Io(format!("{}: {}", filename, err)))?;
+
+    decode_text(bytes).map_err(|err| Failure::Io(format!("{}: {}", filename, err)))
+}
+
+/// Reads stdin as text, like `read_file`.
+fn read_stdin() -> Result<String, Failure> {
+    let mut bytes = vec![];
+
+    stdin().read_to_end(&mut bytes).map_err(|err| Failure::Io(err.to_string()))?;
+
+    decode_text(bytes).map_err(|err| Failure::Io(format!("stdin: {}", err)))
+}
+
+/// Reads a file named by a command line flag, naming the flag as well as the file in any error.
+fn read_flag_file(flag: &str, filename: &str) -> Result<String, Failure> {
+    read_file(filename).map_err(|failure| Failure::Io(format!("--{} {}", flag, failure.message())))
+}
+
+fn run_operator(matches: &ArgMatches) -> Result<(), Failure> {
+    if matches.is_present("print-crds") {
+        print!("{}", OPERATOR_CRDS);
+
+        return Ok(());
+    }
+
+    let interval = parse_duration(matches.value_of("interval").unwrap_or("30s")).map_err(Failure::Config)?;
+    let options = OperatorOptions {
+        context: matches.value_of("kube-context").map(|context| context.to_string()),
+        namespace: matches.value_of("namespace").map(|namespace| namespace.to_string()),
+    };
+
+    loop {
+        match reconcile_instances(&options) {
+            Ok(summaries) => for summary in summaries {
+                info!("{}", summary);
+            },
+            Err(err) if matches.is_present("once") => return Err(Failure::Io(err)),
+            Err(err) => error!("{}", err),
+        }
+
+        if matches.is_present("once") {
+            return Ok(());
+        }
+
+        sleep(interval);
+    }
+}
+
+/// Renders templates for requests on a Unix socket until stopped; see `Daemon::serve`. A socket
+/// left behind by a daemon that's no longer running is replaced.
+#[cfg(unix)]
+fn run_daemon(matches: &ArgMatches) -> Result<(), Failure> {
+    let socket = matches.value_of("socket").expect("socket wasn't provided");
+    let is_socket = symlink_metadata(socket).map(|metadata| metadata.file_type().is_socket()).unwrap_or(false);
+
+    if is_socket {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(Failure::Io(format!("Another process is already listening on {}.", socket)));
+        }
+
+        remove_file(socket).map_err(|err| Failure::Io(format!("Failed to remove {}: {}", socket, err)))?;
+    }
+
+    let listener =
+        UnixListener::bind(socket).map_err(|err| Failure::Io(format!("Failed to listen on {}: {}", socket, err)))?;
+    let mut daemon = Daemon::new();
+
+    daemon.set_redact_secrets(matches.is_present("redact-secrets"));
+
+    if let Some(directory) = matches.value_of("templates") {
+        let interval = parse_duration(matches.value_of("watch-interval").unwrap_or("2s")).map_err(Failure::Config)?;
+        let registry = Arc::new(TemplateRegistry::load(directory).map_err(Failure::Io)?);
+
+        info!("Loaded {} templates from {}", registry.names().len(), directory);
+        registry.clone().watch(interval);
+        daemon.set_registry(registry);
+    }
+    info!("Listening on {}", socket);
+    daemon.serve(listener);
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn run_daemon(_matches: &ArgMatches) -> Result<(), Failure> {
+    Err(Failure::Config("ktmpl daemon needs Unix sockets, which aren't available on this platform.".to_string()))
+}
+
+/// Processes the templates listed in a release manifest. Nothing is written unless all of them
+/// are processed successfully.
+fn process_release(matches: &ArgMatches) -> Result<(), Failure> {
+    let manifest = matches.value_of("manifest").expect("manifest wasn't provided");
+    let directory = Path::new(manifest).parent().unwrap_or_else(|| Path::new(""));
+    let relative = |path: &str| if is_remote(path) {
+        path.to_string()
+    } else {
+        directory.join(path).to_string_lossy().into_owned()
+    };
+    let entries = release_manifest(&read_file(manifest)?)
+        .map_err(|err| Failure::Config(format!("{}: {}", manifest, err)))?;
+    let remote_options = RemoteOptions::from_env();
+    let mut outputs: Vec<(Option<String>, String)> = vec![];
+    let mut processed_values: HashMap<String, ParameterValues> = HashMap::new();
+    let mut progress = match matches.value_of("progress") {
+        Some(style) => Some(Progress::new("rendered", entries.len(), style.parse().map_err(Failure::Config)?)),
+        None => None,
+    };
+    let mut unrendered: HashSet<String> = HashSet::new();
+    let mut first_failure = None;
+
+    for entry in entries {
+        let name = entry.name.clone();
+
+        if let Some(dependency) = entry.depends_on.iter().find(|dependency| unrendered.contains(*dependency)) {
+            if let Some(ref mut progress) = progress {
+                progress.report(&name, &Outcome::Skipped(format!("{} was not rendered", dependency)));
+            }
+
+            unrendered.insert(name);
+
+            continue;
+        }
+
+        let processed = release_entry(manifest, entry, &relative, &remote_options, &processed_values);
+        let (output, processed, exported) = match (processed, progress.as_mut()) {
+            (Ok(processed), _) => processed,
+            (Err(failure), None) => return Err(failure),
+            (Err(failure), Some(progress)) => {
+                progress.report(&name, &Outcome::Failed(failure.message().to_string()));
+                unrendered.insert(name);
+                first_failure.get_or_insert(failure);
+
+                continue;
+            }
+        };
+
+        if let Some(ref mut progress) = progress {
+            progress.report(&name, &Outcome::Done);
+        }
+
+        processed_values.insert(name, exported);
+
+        match outputs.iter_mut().find(|(existing, _)| *existing == output) {
+            Some((_, contents)) => contents.push_str(&processed),
+            None => outputs.push((output, processed)),
+        }
+    }
+
+    if let Some(ref mut progress) = progress {
+        progress.finish();
+    }
+
+    // Nothing is written unless every template rendered, so a release is never half updated.
+    if let Some(failure) = first_failure {
+        return Err(failure);
+    }
+
+    for (output, contents) in outputs {
+        match output {
+            Some(output) => {
+                if let Some(parent) = Path::new(&output).parent() {
+                    create_dir_all(parent)
+                        .map_err(|err| Failure::Io(format!("Failed to create {}: {}", parent.display(), err)))?;
+                }
+
+                write(&output, contents).map_err(|err| Failure::Io(format!("Failed to write {}: {}", output, err)))?;
+                info!("Wrote {}", output);
+            }
+            None => print!("{}", contents),
+        }
+    }
+
+    Ok(())
+}
+
+/// Processes a template of a release, returning where to write it, the processed template, and
+/// the values its dependents can refer to.
+fn release_entry(
+    manifest: &str,
+    mut entry: ReleaseTemplate,
+    relative: &dyn Fn(&str) -> String,
+    remote_options: &RemoteOptions,
+    processed_values: &HashMap<String, ParameterValues>,
+) -> Result<(Option<String>, String, ParameterValues), Failure> {
+    let name = entry.name.clone();
+    let context = |err| format!("{}: {}: {}", manifest, name, err);
+
+    import_dependency_values(&mut entry, processed_values).map_err(|err| Failure::Config(context(err)))?;
+
+    let filename = relative(&entry.template);
+    let mut values = ParameterValues::new();
+
+    for listed in &entry.parameter_files {
+        let parameter_file = relative(listed);
+        let pin = entry.parameter_file_sha256s.get(listed).map(String::as_str);
+        let contents = if is_remote(&parameter_file) {
+            fetch(&parameter_file, pin, remote_options)
+                .map_err(|err| Failure::Io(format!("{}: {}", parameter_file, err)))?
+        } else {
+            read_file(&parameter_file)?
+        };
+
+        if let Some(hash) = pin {
+            verify_sha256(contents.as_bytes(), hash)
+                .map_err(|err| Failure::Validation(format!("{}: {}", parameter_file, err)))?;
+        }
+
+        values.extend(parameter_file_values(&parameter_file, &contents, MergeOrder::LastWins)?);
+    }
+
+    values.extend(entry.parameters);
+
+    let supplied = values.clone();
+    let template_data = read_template(&filename, entry.template_sha256.as_deref(), remote_options)
+        .map_err(|failure| failure.map_message(context))?;
+    let mut template = Template::new(template_data, values, entry.secrets)
+        .map_err(|err| Failure::from(err).map_message(context))?;
+
+    template.set_file_name(&filename);
+
+    for warning in template.warnings() {
+        warn!("{}: {}: {}", manifest, name, warning);
+    }
+
+    // Dependents get the values as supplied, so Base64 values aren't encoded twice, and the
+    // template's outputs.
+    let mut exported: ParameterValues = template
+        .parameters()
+        .into_iter()
+        .filter_map(|parameter| {
+            let value = match supplied.get(&parameter.name) {
+                Some(value) => value.clone(),
+                None => ParameterValue::Plain(parameter.value.clone()?),
+            };
+
+            Some((parameter.name.clone(), value))
+        })
+        .collect();
+    let (mut processed, template_outputs) =
+        template.process_with_outputs().map_err(|err| Failure::from(err).map_message(context))?;
+
+    exported.extend(template_outputs.into_iter().map(|output| (output.name, ParameterValue::Plain(output.value))));
+
+    if !processed.ends_with('\n') {
+        processed.push('\n');
+    }
+
+    Ok((entry.output.as_deref().map(relative), processed, exported))
+}
+
+fn roll_back(matches: &ArgMatches) -> Result<(), Failure> {
+    let bundle = read_file(matches.value_of("bundle").expect("bundle wasn't provided"))?;
+    let options = ApplyOptions {
+        context: matches.value_of("kube-context").map(|context| context.to_string()),
+        ..ApplyOptions::default()
+    };
+
+    print!("{}", rollback(&bundle, &options).map_err(Failure::Io)?);
+
+    Ok(())
+}
+
+fn format_files(matches: &ArgMatches) -> Result<(), Failure> {
+    let check = matches.is_present("check");
+    let mut unformatted = 0;
+
+    for filename in file_list(matches)? {
+        let contents = read_file(&filename)?;
+        let formatted = format_template(&contents).map_err(|err| Failure::Config(format!("{}: {}", filename, err)))?;
+
+        if !check && !matches.is_present("write") {
+            print!("{}", formatted);
+            continue;
+        }
+
+        if formatted == contents {
+            continue;
+        }
+
+        unformatted += 1;
+
+        if check {
+            println!("{}", filename);
+        } else {
+            write(&filename, formatted).map_err(|err| Failure::Io(format!("Failed to write {}: {}", filename, err)))?;
+            info!("Formatted {}", filename);
+        }
+    }
+
+    if unformatted > 0 && check {
+        return Err(Failure::Validation(format!("Unformatted files: {}; run ktmpl fmt --write to fix them.", unformatted)));
+    }
+
+    Ok(())
+}
+
+fn lint_files(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut problem_count = 0;
+
+    for filename in file_list(matches)? {
+        let contents = if matches.is_present("changed") {
+            match read_file(&filename) {
+                Ok(ref contents) if is_template(contents) => contents.clone(),
+                _ => continue,
+            }
+        } else {
+            read_file(&filename)?
+        };
+
+        let problems = lint_template(&contents).unwrap_or_else(|err| vec![err]);
+
+        for problem in &problems {
+            println!("{}: {}", filename, problem);
+        }
+
+        problem_count += problems.len();
+    }
+
+    if problem_count > 0 {
+        return Err(Failure::Validation(format!("Problems found: {}.", problem_count)));
+    }
+
+    Ok(())
+}
+
+/// Returns the files given as arguments, or else listed on stdin one per line.
+fn file_list(matches: &ArgMatches) -> Result<Vec<String>, Failure> {
+    if let Some(files) = matches.values_of("file") {
+        let mut paths = vec![];
+
+        for file in files {
+            paths.extend(expand_path("File", file)?);
+        }
+
+        return Ok(paths);
+    }
+
+    let mut list = String::new();
+
+    stdin().read_to_string(&mut list).map_err(|err| Failure::Io(format!("Failed to read the file list: {}", err)))?;
+
+    Ok(list.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).map(|line| line.to_string()).collect())
+}
+
+/// Returns whether a file's contents are a template, rather than some other YAML or file.
+fn is_template(contents: &str) -> bool {
+    match load_yaml(contents) {
+        Ok(docs) => docs.len() == 1 && docs[0]["kind"].as_str() == Some("Template"),
+        Err(_) => false,
+    }
+}
+
+fn list_usages(matches: &ArgMatches) -> Result<(), Failure> {
+    let filename = template_path(matches.value_of("template").expect("template wasn't provided"))?;
+    let template_data = read_template(&filename, None, &RemoteOptions::from_env())?;
+
+    for usage in parameter_usages(&template_data).map_err(Failure::Config)? {
+        if usage.paths.is_empty() {
+            println!("{} (unreferenced)", usage.name);
+        } else {
+            println!("{}", usage.name);
+        }
+
+        for path in usage.paths {
+            println!("  {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads the values in a parameter file's contents, warning of parameters its documents set
+/// differently. Errors are prefixed with the source of the contents.
+fn parameter_file_values(source: &str, contents: &str, merge_order: MergeOrder) -> Result<ParameterValues, Failure> {
+    let (values, conflicts) = parameter_values_from_str_merged(contents, merge_order)
+        .map_err(|err| Failure::Config(format!("{}: {}", source, err)))?;
+
+    for conflict in conflicts {
+        let documents: Vec<String> = conflict.documents.iter().map(|number| number.to_string()).collect();
+
+        warn!(
+            "Parameter {} is set differently by documents {} of {}; using the value from document {}.",
+            conflict.name,
+            documents.join(", "),
+            source,
+            conflict.used,
+        );
+    }
+
+    Ok(values)
+}
+
+/// Reads the digests given with --parameter-file-sha256, by parameter file.
+fn parameter_file_pins(matches: &ArgMatches) -> Result<HashMap<String, String>, Failure> {
+    matches
+        .values_of("parameter-file-sha256")
+        .into_iter()
+        .flatten()
+        .map(|pin| match pin.rsplit_once('=') {
+            Some((filename, hash)) => Ok((filename.to_string(), hash.to_string())),
+            None => Err(Failure::Config(format!("--parameter-file-sha256 {} must be given as FILE=HASH.", pin))),
+        })
+        .collect()
+}
+
+/// Reads the parameter files matching the patterns given with --parameter-file, verifying those
+/// with pinned digests.
+fn parameter_files(
+    param_files: Values,
+    merge_order: MergeOrder,
+    pins: HashMap<String, String>,
+    remote_options: &RemoteOptions,
+) -> Result<ParameterValues, Failure> {
+    let mut parameter_values = ParameterValues::new();
+    let mut pinned = HashSet::new();
+
+    for pattern in param_files {
+        for filename in expand_path("--parameter-file", pattern)? {
+            let source = format!("--parameter-file {}", filename);
+            let pin = pins.get(&filename).map(String::as_str);
+            let contents = if is_remote(&filename) {
+                fetch(&filename, pin, remote_options).map_err(|err| Failure::Io(format!("{}: {}", source, err)))?
+            } else {
+                read_flag_file("parameter-file", &filename)?
+            };
+
+            if let Some(hash) = pin {
+                verify_sha256(contents.as_bytes(), hash)
+                    .map_err(|err| Failure::Validation(format!("{}: {}", source, err)))?;
+                pinned.insert(filename.clone());
+            }
+
+            parameter_values.extend(parameter_file_values(&source, &contents, merge_order)?);
+        }
+    }
+
+    // A pin that matches no parameter file, e.g. because of a typo, would silently verify nothing.
+    if let Some(filename) = pins.keys().find(|filename| !pinned.contains(*filename)) {
+        return Err(Failure::Config(format!("--parameter-file-sha256 {} is not a parameter file.", filename)));
+    }
+
+    Ok(parameter_values)
+}
+
+fn null_parameters(matches: &ArgMatches) -> Result<NullParameters, Failure> {
+    matches
+        .value_of("null-parameters")
+        .expect("null parameter handling wasn't provided")
+        .parse()
+        .map_err(Failure::Config)
+}
+
+/// Collects the values given by the occurrences of a flag taking a parameter's name and value.
+fn parameter_values(flag: &str, mut parameters: Values, base64_encoded: bool) -> Result<ParameterValues, Failure> {
+    let mut parameter_values = ParameterValues::new();
+
+    while let Some(name) = parameters.next() {
+        let value = parameters
+            .next()
+            .ok_or_else(|| Failure::Config(format!("--{} {} is missing its value.", flag, name)))?;
+
+        let parameter_value = if base64_encoded {
+            ParameterValue::Encoded(value.to_string())
+        } else {
+            ParameterValue::Plain(value.to_string())
+        };
+
+        parameter_values.insert(name.to_string(), parameter_value);
+    }
+
+    Ok(parameter_values)
+}
+
+/// Parses a duration given as a number of seconds, minutes, or hours, e.g. `90s`, `5m`, or `1h`.
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration \"{}\"; use e.g. 90s, 5m, or 1h.", duration);
+    let (number, multiplier) = match duration.chars().last() {
+        Some('s') => (&duration[..duration.len() - 1], 1),
+        Some('m') => (&duration[..duration.len() - 1], 60),
+        Some('h') => (&duration[..duration.len() - 1], 60 * 60),
+        _ => return Err(invalid()),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Reads the secrets given with --secret, which have a namespace, and --secret-name, which match
+/// secrets with the name in any namespace.
+fn secret_values(matches: &ArgMatches) -> Result<Secrets, Failure> {
+    let mut secrets = Secrets::new();
+
+    if let Some(mut values) = matches.values_of("secret") {
+        while let Some(name) = values.next() {
+            let namespace = values
+                .next()
+                .ok_or_else(|| Failure::Config(format!("--secret {} is missing its namespace.", name)))?;
+
+            secrets.insert(Secret {
+                name: name.to_string(),
+                namespace: Some(namespace.to_string()),
+            });
+        }
+    }
+
+    for name in matches.values_of("secret-name").into_iter().flatten() {
+        secrets.insert(Secret {
+            name: name.to_string(),
+            namespace: None,
+        });
     }
 
-    secrets
+    Ok(secrets)
 }
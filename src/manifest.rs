@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use regex::{Captures, Regex};
+use yaml::Yaml;
+
+use loader::load_yaml;
+use parameter::{ParameterValue, ParameterValues, parameter_values_from_yaml};
+use secret::{Secret, Secrets};
+
+/// A template to process as part of a release, as listed by a release manifest.
+#[derive(Debug)]
+pub struct ReleaseTemplate {
+    /// The name other templates refer to the template by, which defaults to the template's file
+    /// name without its extension.
+    pub name: String,
+    /// The names of the templates that must be processed before this one, whose parameter values
+    /// its inline parameter values can refer to.
+    pub depends_on: Vec<String>,
+    /// The path or URL of the template. Paths are relative to the manifest's directory.
+    pub template: String,
+    /// The hex-encoded SHA-256 digest the template must have, if it's pinned.
+    pub template_sha256: Option<String>,
+    /// The paths or URLs of parameter files, whose values are merged in order.
+    pub parameter_files: Vec<String>,
+    /// The hex-encoded SHA-256 digests the pinned parameter files must have, by path or URL as
+    /// listed in `parameter_files`.
+    pub parameter_file_sha256s: HashMap<String, String>,
+    /// Parameter values given inline, which take precedence over the parameter files' values.
+    pub parameters: ParameterValues,
+    /// The secrets to Base64 encode, if any were listed.
+    pub secrets: Option<Secrets>,
+    /// The path to write the processed template to, or `None` to write it to stdout. Templates
+    /// with the same output are written to it in order.
+    pub output: Option<String>,
+}
+
+/// Parses a release manifest, which lists templates to process in one run, returning them in the
+/// order they must be processed.
+///
+/// A manifest is a hash with a `templates` array. Each entry has a `template` path or URL, and
+/// optionally a `name`, `templateSha256` (the template's pinned digest), `dependsOn` (names of
+/// other templates), `parameterFiles` (paths or URLs, or hashes with a `file` and its pinned
+/// `sha256`), `parameters` (a hash of names to string values), `secrets` (names, or hashes with a
+/// `name` and a `namespace`), and an `output` path:
+///
+/// ```yaml
+/// templates:
+///   - template: "database.yml"
+///   - template: "https://example.com/templates/web.yml"
+///     templateSha256: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+///     dependsOn: ["database"]
+///     parameterFiles:
+///       - "production.yml"
+///       - file: "https://example.com/shared.yml"
+///         sha256: "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752"
+///     parameters:
+///       REPLICAS: "3"
+///       DATABASE_PASSWORD: "$(database.PASSWORD)"
+///     secrets: ["web-credentials"]
+///     output: "manifests/web.yml"
+/// ```
+///
+/// Templates are processed after the templates they depend on, and otherwise in the order
+/// listed. See `import_dependency_values` for referring to the values of a dependency's
+/// parameters and outputs.
+///
+/// # Errors
+///
+/// Returns an error if the manifest isn't a single YAML document of this form, if two templates
+/// have the same name, or if a template depends on an unknown template or, indirectly, on itself.
+pub fn release_manifest(contents: &str) -> Result<Vec<ReleaseTemplate>, String> {
+    let mut docs = load_yaml(contents).map_err(|err| err.to_string())?;
+
+    if docs.len() != 1 {
+        return Err("Release manifests must contain exactly one YAML document.".to_string());
+    }
+
+    let doc = docs.remove(0);
+    let entries = match doc["templates"].as_vec() {
+        Some(entries) if !entries.is_empty() => entries,
+        _ => return Err("Key \"templates\" must be present and must be a non-empty array.".to_string()),
+    };
+
+    let templates = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| release_template(entry).map_err(|err| format!("templates[{}]: {}", index, err)))
+        .collect::<Result<Vec<ReleaseTemplate>, String>>()?;
+
+    for (index, template) in templates.iter().enumerate() {
+        if templates[..index].iter().any(|other| other.name == template.name) {
+            return Err(format!(
+                "templates[{}]: Another template is named {}; give one of them a different \"name\".",
+                index,
+                template.name,
+            ));
+        }
+
+        for dependency in &template.depends_on {
+            if !templates.iter().any(|other| other.name == *dependency) {
+                return Err(format!("templates[{}]: Depends on unknown template {}.", index, dependency));
+            }
+        }
+    }
+
+    dependency_order(templates)
+}
+
+/// Replaces references to the parameter values of the templates a template depends on, e.g.
+/// `$(database.PASSWORD)`, in its inline parameter values, given the parameter and output values
+/// of each processed template by name. A value that's only a reference takes the referenced value as it
+/// is, so a Base64 parameter's value stays encoded. References to templates it doesn't depend on
+/// are left as they are.
+///
+/// # Errors
+///
+/// Returns an error if a dependency hasn't been processed or has no value for the parameter.
+pub fn import_dependency_values(
+    template: &mut ReleaseTemplate,
+    processed: &HashMap<String, ParameterValues>,
+) -> Result<(), String> {
+    lazy_static! {
+        static ref REFERENCE: Regex = Regex::new(r"\$\(([^()\s]+)\)").expect("Failed to compile regex.");
+    }
+
+    let depends_on = &template.depends_on;
+    let lookup = |name: &str, reference: &str| -> Result<Option<&ParameterValue>, String> {
+        let dependency = depends_on.iter().find(|dependency| reference.starts_with(&format!("{}.", dependency)));
+
+        match dependency {
+            Some(dependency) => {
+                let parameter = &reference[dependency.len() + 1..];
+
+                match processed.get(dependency).and_then(|values| values.get(parameter)) {
+                    Some(value) => Ok(Some(value)),
+                    None => Err(format!(
+                        "Parameter {} refers to $({}), but template {} has no value for {}.",
+                        name,
+                        reference,
+                        dependency,
+                        parameter,
+                    )),
+                }
+            }
+            None => Ok(None),
+        }
+    };
+
+    for (name, value) in template.parameters.iter_mut() {
+        let text = match *value {
+            ParameterValue::Plain(ref text) | ParameterValue::Encoded(ref text) => text.clone(),
+        };
+
+        if let Some(captures) = REFERENCE.captures(&text).filter(|captures| captures[0] == *text) {
+            if let Some(imported) = lookup(name, &captures[1])? {
+                *value = imported.clone();
+            }
+
+            continue;
+        }
+
+        let mut error = None;
+        let imported = REFERENCE.replace_all(&text, |captures: &Captures| match lookup(name, &captures[1]) {
+            Ok(Some(&ParameterValue::Plain(ref imported))) | Ok(Some(&ParameterValue::Encoded(ref imported))) => {
+                imported.clone()
+            }
+            Ok(None) => captures[0].to_string(),
+            Err(err) => {
+                error = Some(err);
+
+                captures[0].to_string()
+            }
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        let imported = imported.into_owned();
+
+        match *value {
+            ParameterValue::Plain(ref mut text) | ParameterValue::Encoded(ref mut text) => *text = imported,
+        }
+    }
+
+    Ok(())
+}
+
+/// Orders templates after the templates they depend on, keeping the listed order otherwise.
+fn dependency_order(mut templates: Vec<ReleaseTemplate>) -> Result<Vec<ReleaseTemplate>, String> {
+    let mut ordered: Vec<ReleaseTemplate> = vec![];
+
+    while !templates.is_empty() {
+        let ready = templates.iter().position(|template| {
+            template.depends_on.iter().all(|dependency| ordered.iter().any(|other| other.name == *dependency))
+        });
+
+        match ready {
+            Some(index) => ordered.push(templates.remove(index)),
+            None => {
+                let names: Vec<&str> = templates.iter().map(|template| template.name.as_str()).collect();
+
+                return Err(format!("Templates depend on each other in a cycle: {}.", names.join(", ")));
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+fn release_template(entry: &Yaml) -> Result<ReleaseTemplate, String> {
+    let template = match entry["template"].as_str() {
+        Some(template) => template.to_string(),
+        None => return Err("Key \"template\" must be present and must be a string.".to_string()),
+    };
+    let name = match entry["name"] {
+        Yaml::BadValue => Path::new(&template)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| template.clone()),
+        Yaml::String(ref name) => name.clone(),
+        _ => return Err("Key \"name\" must be a string.".to_string()),
+    };
+    let depends_on = match entry["dependsOn"] {
+        Yaml::BadValue => vec![],
+        Yaml::Array(ref names) => names
+            .iter()
+            .map(|name| name.as_str().map(|name| name.to_string()))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| "Key \"dependsOn\" must be an array of template names.".to_string())?,
+        _ => return Err("Key \"dependsOn\" must be an array of template names.".to_string()),
+    };
+    let template_sha256 = match entry["templateSha256"] {
+        Yaml::BadValue => None,
+        Yaml::String(ref hash) => Some(hash.clone()),
+        _ => return Err("Key \"templateSha256\" must be a string.".to_string()),
+    };
+    let mut parameter_files = vec![];
+    let mut parameter_file_sha256s = HashMap::new();
+
+    match entry["parameterFiles"] {
+        Yaml::BadValue => {}
+        Yaml::Array(ref files) => {
+            for file in files {
+                let (file, sha256) = parameter_file(file)?;
+
+                if let Some(sha256) = sha256 {
+                    parameter_file_sha256s.insert(file.clone(), sha256);
+                }
+
+                parameter_files.push(file);
+            }
+        }
+        _ => return Err("Key \"parameterFiles\" must be an array.".to_string()),
+    }
+
+    let parameters = match entry["parameters"] {
+        Yaml::BadValue => ParameterValues::new(),
+        ref parameters => parameter_values_from_yaml(parameters.clone())
+            .map_err(|err| format!("Key \"parameters\" must be a hash of parameter values: {}", err))?,
+    };
+    let secrets = match entry["secrets"] {
+        Yaml::BadValue => None,
+        Yaml::Array(ref secrets) => Some(secrets.iter().map(secret).collect::<Result<Secrets, String>>()?),
+        _ => return Err("Key \"secrets\" must be an array.".to_string()),
+    };
+    let output = match entry["output"] {
+        Yaml::BadValue => None,
+        Yaml::String(ref output) if output == "-" => None,
+        Yaml::String(ref output) => Some(output.clone()),
+        _ => return Err("Key \"output\" must be a string.".to_string()),
+    };
+
+    Ok(ReleaseTemplate {
+        name,
+        depends_on,
+        template,
+        template_sha256,
+        parameter_files,
+        parameter_file_sha256s,
+        parameters,
+        secrets,
+        output,
+    })
+}
+
+fn parameter_file(yaml: &Yaml) -> Result<(String, Option<String>), String> {
+    match *yaml {
+        Yaml::String(ref file) => Ok((file.clone(), None)),
+        Yaml::Hash(_) => match (yaml["file"].as_str(), yaml["sha256"].as_str()) {
+            (Some(file), Some(sha256)) => Ok((file.to_string(), Some(sha256.to_string()))),
+            _ => Err("Parameter files given as hashes must have a \"file\" and a \"sha256\".".to_string()),
+        },
+        _ => Err("Parameter files must be paths, or hashes with a \"file\" and a \"sha256\".".to_string()),
+    }
+}
+
+fn secret(yaml: &Yaml) -> Result<Secret, String> {
+    match *yaml {
+        Yaml::String(ref name) => Ok(Secret {
+            name: name.clone(),
+            namespace: None,
+        }),
+        Yaml::Hash(_) => match yaml["name"].as_str() {
+            Some(name) => Ok(Secret {
+                name: name.to_string(),
+                namespace: yaml["namespace"].as_str().map(|namespace| namespace.to_string()),
+            }),
+            None => Err("Secrets must have a \"name\".".to_string()),
+        },
+        _ => Err("Secrets must be names, or hashes with a \"name\" and a \"namespace\".".to_string()),
+    }
+}
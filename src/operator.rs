@@ -0,0 +1,171 @@
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use apply::{ApplyOptions, apply};
+use instance::{INSTANCE_LABEL, INSTANCE_NAMESPACE_LABEL, expand_instance};
+use kubectl;
+use loader::load_yaml;
+use template::dump;
+
+/// The CustomResourceDefinitions of the `Template` and `TemplateInstance` resources reconciled by
+/// `reconcile_instances`.
+pub const OPERATOR_CRDS: &str = include_str!("../operator/crds.yml");
+
+const FIELD_MANAGER: &str = "ktmpl-operator";
+
+/// Options for reconciling `TemplateInstance` resources.
+#[derive(Debug, Default)]
+pub struct OperatorOptions {
+    /// The kubeconfig context to reconcile in, or `None` for the current context.
+    pub context: Option<String>,
+    /// The namespace to reconcile instances in, or `None` for all namespaces.
+    pub namespace: Option<String>,
+}
+
+/// Renders and applies every `TemplateInstance` in the cluster whose instance or template changed
+/// since it was last reconciled, returning a summary of each instance reconciled.
+///
+/// A `TemplateInstance` names a `Template` in its namespace with `spec.templateRef.name`. Its
+/// objects are expanded with `expand_instance`, so they're owned by the instance and deleted along
+/// with it, and applied with server-side apply. Objects applied for the instance that the template no
+/// longer renders are pruned. The outcome is recorded in the instance's `status`, whose `phase`
+/// is `Ready` or `Failed`. See `OPERATOR_CRDS` for the resources' schemas.
+///
+/// # Errors
+///
+/// Returns an error if the instances can't be listed. Failures to reconcile an instance are
+/// recorded in its status and summary instead.
+pub fn reconcile_instances(options: &OperatorOptions) -> Result<Vec<String>, String> {
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["get", "templateinstances.ktmpl.io", "--output", "yaml"]);
+
+    match options.namespace {
+        Some(ref namespace) => args.extend(["--namespace", namespace]),
+        None => args.push("--all-namespaces"),
+    }
+
+    let list = load_yaml(&kubectl::run(&args)?).map_err(|err| err.to_string())?;
+    let mut summaries = vec![];
+
+    for instance in list.first().and_then(|list| list["items"].as_vec()).unwrap_or(&vec![]) {
+        let namespace = instance["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = instance["metadata"]["name"].as_str().unwrap_or_default();
+        let template = fetch_template(instance, namespace, options);
+
+        if let Ok(ref template) = template {
+            if is_up_to_date(instance, template) {
+                continue;
+            }
+        }
+
+        let result = template.and_then(|template| {
+            let objects = expand_instance(&template, instance)?.objects;
+            let apply_options = ApplyOptions {
+                context: options.context.clone(),
+                field_manager: Some(FIELD_MANAGER.to_string()),
+                force_conflicts: true,
+                prune_selector: Some(format!("{}={},{}={}", INSTANCE_LABEL, name, INSTANCE_NAMESPACE_LABEL, namespace)),
+                server_side: true,
+            };
+
+            apply(&objects, &apply_options)?;
+
+            Ok((template, objects))
+        });
+
+        let (status, summary) = match result {
+            Ok((template, objects)) => {
+                let references: Vec<Yaml> = objects
+                    .iter()
+                    .map(|object| ystring(&format!(
+                        "{}/{}",
+                        object["kind"].as_str().unwrap_or_default(),
+                        object["metadata"]["name"].as_str().unwrap_or_default(),
+                    )))
+                    .collect();
+                let message = format!("Applied {} objects.", references.len());
+                let mut status = status(instance, "Ready", &message);
+
+                status.insert(ystring("objects"), Yaml::Array(references));
+                status.insert(
+                    ystring("templateResourceVersion"),
+                    template["metadata"]["resourceVersion"].clone(),
+                );
+
+                (status, format!("{}/{}: {}", namespace, name, message))
+            }
+            Err(err) => (status(instance, "Failed", &err), format!("{}/{}: Failed: {}", namespace, name, err)),
+        };
+
+        update_status(namespace, name, status, options)?;
+
+        summaries.push(summary);
+    }
+
+    Ok(summaries)
+}
+
+fn fetch_template(instance: &Yaml, namespace: &str, options: &OperatorOptions) -> Result<Yaml, String> {
+    let template_name = instance["spec"]["templateRef"]["name"]
+        .as_str()
+        .ok_or("TemplateInstance must name a Template in spec.templateRef.name.")?;
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["get", "templates.ktmpl.io", template_name, "--namespace", namespace, "--output", "yaml"]);
+
+    load_yaml(&kubectl::run(&args)?)
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("kubectl returned nothing for Template {}.", template_name))
+}
+
+/// Returns whether an instance was successfully reconciled against its current generation and
+/// the template's current version.
+fn is_up_to_date(instance: &Yaml, template: &Yaml) -> bool {
+    let status = &instance["status"];
+
+    status["phase"].as_str() == Some("Ready")
+        && status["observedGeneration"].as_i64() == instance["metadata"]["generation"].as_i64()
+        && status["templateResourceVersion"].as_str() == template["metadata"]["resourceVersion"].as_str()
+}
+
+fn status(instance: &Yaml, phase: &str, message: &str) -> Hash {
+    let mut status = Hash::new();
+
+    status.insert(ystring("message"), ystring(message));
+    status.insert(ystring("observedGeneration"), instance["metadata"]["generation"].clone());
+    status.insert(ystring("phase"), ystring(phase));
+
+    status
+}
+
+fn update_status(namespace: &str, name: &str, status: Hash, options: &OperatorOptions) -> Result<(), String> {
+    let mut patch = Hash::new();
+
+    patch.insert(ystring("status"), Yaml::Hash(status));
+
+    let patch = dump(vec![Yaml::Hash(patch)])?;
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend([
+        "patch",
+        "templateinstances.ktmpl.io",
+        name,
+        "--namespace",
+        namespace,
+        "--subresource",
+        "status",
+        "--type",
+        "merge",
+        "--patch",
+        &patch,
+    ]);
+
+    kubectl::run(&args).map(|_| ())
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
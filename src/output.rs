@@ -0,0 +1,134 @@
+use yaml::Yaml;
+use yaml::yaml::Hash;
+
+use emitter::emit;
+use parameter::ParamMap;
+use path::Path;
+use processor::process_yaml;
+
+/// A value exported by a template, as declared in its `outputs`, for later pipeline steps or
+/// dependent templates to consume.
+#[derive(Debug, PartialEq)]
+pub struct TemplateOutput {
+    /// The output's name.
+    pub name: String,
+    /// The output's value.
+    pub value: String,
+}
+
+/// A template's declaration of an output, whose value is either interpolated from parameters or
+/// taken from the processed objects.
+#[derive(Clone, Debug)]
+pub struct OutputSpec {
+    name: String,
+    source: OutputSource,
+}
+
+#[derive(Clone, Debug)]
+enum OutputSource {
+    Path(Path),
+    Value(String),
+}
+
+impl OutputSpec {
+    /// Parses the `outputs` of a template document, if it has any.
+    ///
+    /// Each output has a `name` and either a `value`, a string whose placeholders are interpolated
+    /// like an object's, or a `path` selecting a value within the processed objects, e.g.
+    /// `objects[?(@.kind=="Service")].metadata.name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `outputs` isn't an array of such hashes, or two outputs have the same
+    /// name.
+    pub fn parse_all(document: &Yaml) -> Result<Vec<Self>, String> {
+        let specs = match document["outputs"] {
+            Yaml::BadValue => return Ok(vec![]),
+            Yaml::Array(ref specs) => specs,
+            _ => return Err("Key \"outputs\" must be an array.".to_string()),
+        };
+        let mut outputs: Vec<OutputSpec> = vec![];
+
+        for (index, spec) in specs.iter().enumerate() {
+            let output = OutputSpec::parse(spec).map_err(|err| format!("outputs[{}]: {}", index, err))?;
+
+            if outputs.iter().any(|other| other.name == output.name) {
+                return Err(format!("Output {} is declared more than once.", output.name));
+            }
+
+            outputs.push(output);
+        }
+
+        Ok(outputs)
+    }
+
+    fn parse(spec: &Yaml) -> Result<Self, String> {
+        let name = match spec["name"].as_str() {
+            Some(name) => name.to_string(),
+            None => return Err("Outputs must have a \"name\".".to_string()),
+        };
+        let source = match (&spec["value"], &spec["path"]) {
+            (Yaml::String(value), Yaml::BadValue) => OutputSource::Value(value.clone()),
+            (Yaml::BadValue, Yaml::String(path)) => OutputSource::Path(Path::parse(path)?),
+            _ => return Err(format!("Output {} must have either a \"value\" or a \"path\" string.", name)),
+        };
+
+        Ok(OutputSpec { name, source })
+    }
+
+    /// Evaluates the output against the processed objects and the parameters' values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value's placeholders can't be interpolated, or a path doesn't select
+    /// exactly one string, number, or boolean.
+    pub fn evaluate(&self, objects: &[Yaml], parameters: &ParamMap) -> Result<TemplateOutput, String> {
+        let context = |err: String| format!("Output {}: {}", self.name, err);
+        let value = match self.source {
+            OutputSource::Value(ref value) => {
+                let mut value = Yaml::String(value.clone());
+                let value = process_yaml(&mut value, parameters).map_err(context)?.unwrap_or(value);
+
+                scalar(&value)
+            }
+            OutputSource::Path(ref path) => match path.values(objects)[..] {
+                [value] => scalar(value),
+                [] => return Err(context("Path did not match any value.".to_string())),
+                ref values => return Err(context(format!("Path matched {} values rather than one.", values.len()))),
+            },
+        };
+
+        match value {
+            Some(value) => Ok(TemplateOutput { name: self.name.clone(), value }),
+            None => Err(context("Value must be a string, number, or boolean.".to_string())),
+        }
+    }
+}
+
+/// Formats outputs as a YAML hash of names to values, which can be given to a dependent template
+/// as a parameter file.
+///
+/// # Errors
+///
+/// Returns an error if the outputs can't be emitted.
+pub fn format_outputs(outputs: &[TemplateOutput]) -> Result<String, String> {
+    let hash: Hash = outputs
+        .iter()
+        .map(|output| (Yaml::String(output.name.clone()), Yaml::String(output.value.clone())))
+        .collect();
+    let mut formatted = String::new();
+
+    emit(&Yaml::Hash(hash), &mut formatted)?;
+    formatted.push('\n');
+
+    Ok(formatted)
+}
+
+fn scalar(value: &Yaml) -> Option<String> {
+    match *value {
+        Yaml::String(ref value) | Yaml::Real(ref value) => Some(value.clone()),
+        Yaml::Integer(value) => Some(value.to_string()),
+        Yaml::Boolean(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
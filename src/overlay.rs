@@ -0,0 +1,89 @@
+use yaml::Yaml;
+
+use loader::load_yaml;
+use patch::strategic_merge;
+
+/// A partial object deep-merged onto the processed objects with the same kind and name, or added
+/// as a new object if there are none.
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    kind: String,
+    name: String,
+    namespace: Option<String>,
+    object: Yaml,
+}
+
+impl Overlay {
+    /// Loads overlays from the contents of an overlay file, one per YAML document.
+    ///
+    /// Each overlay is a partial object with a `kind` and `metadata.name`, and optionally a
+    /// `metadata.namespace`, identifying the objects it's merged onto.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents aren't valid YAML, or an overlay doesn't identify the
+    /// objects it's merged onto.
+    pub fn from_str(contents: &str) -> Result<Vec<Self>, String> {
+        let docs = load_yaml(contents).map_err(|err| err.to_string())?;
+        let mut overlays = vec![];
+
+        for (index, doc) in docs.into_iter().enumerate() {
+            let kind = match doc["kind"].as_str() {
+                Some(kind) => kind.to_string(),
+                None => return Err(format!("Overlay {} must have a \"kind\" field.", index + 1)),
+            };
+            let name = match doc["metadata"]["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => return Err(format!("Overlay {} must have a \"metadata.name\" field.", index + 1)),
+            };
+
+            overlays.push(Overlay {
+                kind,
+                name,
+                namespace: doc["metadata"]["namespace"].as_str().map(|namespace| namespace.to_string()),
+                object: doc,
+            });
+        }
+
+        Ok(overlays)
+    }
+
+    /// Merges the overlay onto every object matching its kind, name, and namespace (if given),
+    /// like a strategic merge patch: hashes are merged recursively, null values delete keys, and
+    /// lists of hashes with a well-known merge key, such as containers, are merged by that key.
+    /// If no object matches, the overlay is added as a new object, so it must be a complete one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no object matches and the overlay has no `apiVersion`, as it's then
+    /// likely meant for an object that's misnamed rather than to be added.
+    pub fn apply(&self, objects: &mut Vec<Yaml>) -> Result<(), String> {
+        let mut matched = false;
+
+        for object in objects.iter_mut() {
+            if object["kind"].as_str() != Some(&self.kind)
+                || object["metadata"]["name"].as_str() != Some(&self.name)
+                || self.namespace.is_some() && object["metadata"]["namespace"].as_str() != self.namespace.as_deref()
+            {
+                continue;
+            }
+
+            strategic_merge(object, &self.object);
+            matched = true;
+        }
+
+        if !matched {
+            if self.object["apiVersion"].as_str().is_none() {
+                return Err(format!(
+                    "Overlay for {} {} did not match any objects, and can't be added without an \"apiVersion\".",
+                    self.kind,
+                    self.name,
+                ));
+            }
+
+            objects.push(self.object.clone());
+        }
+
+        Ok(())
+    }
+}
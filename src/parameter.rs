@@ -1,32 +1,99 @@
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
+use std::slice::Iter;
 use std::str::FromStr;
 
-use base64::encode;
+use base64::{decode, encode};
+use regex::Regex;
 use yaml::{Yaml, YamlLoader};
 
-#[derive(Debug)]
+use emitter::emit;
+use error::Error;
+use loader::{decode_text, load_yaml, normalize_text};
+
+/// The fields of a parameter's declaration that are read; others are ignored.
+pub const PARAMETER_FIELDS: [&str; 12] = [
+    "aliases",
+    "allowedValues",
+    "description",
+    "displayName",
+    "hidden",
+    "name",
+    "parameterType",
+    "pattern",
+    "required",
+    "sensitive",
+    "transform",
+    "value",
+];
+
+/// A parameter declared by a template.
+#[derive(Clone, Debug)]
 pub struct Parameter {
+    /// Former names of the parameter, under which values are still accepted with a warning, so
+    /// renaming a parameter doesn't break the pipelines that supply it.
+    pub aliases: Vec<String>,
+    /// The values that may be supplied for the parameter, after any transforms, or any value if
+    /// empty.
+    pub allowed_values: Vec<String>,
+    /// A description of the parameter.
     pub description: Option<String>,
+    /// A human-readable name for the parameter.
     pub display_name: Option<String>,
+    /// Whether the parameter should be left out of parameter listings. Hidden parameters can
+    /// still be given values.
+    pub hidden: bool,
+    /// The name used to refer to the parameter in the template. Names are case-sensitive, start
+    /// with a letter or an underscore, and contain only letters, in any script, digits,
+    /// underscores, hyphens, and dots.
     pub name: String,
+    /// The type of value the parameter expects.
     pub parameter_type: Option<ParameterType>,
+    /// A regular expression that supplied values must match in full, after any transforms.
+    pub pattern: Option<String>,
+    /// Whether a value must be supplied for the parameter.
     pub required: bool,
+    /// Whether the parameter's value should be treated as confidential, e.g. redacted from
+    /// previews.
+    pub sensitive: bool,
+    /// The transforms applied, in order, to a plain text value supplied for the parameter.
+    pub transforms: Vec<Transform>,
+    /// The parameter's value: the user-supplied value if there is one, otherwise the default
+    /// value declared in the template.
     pub value: Option<String>,
 }
 
-#[derive(Debug, PartialEq)]
+/// The type of value a parameter expects.
+#[derive(Clone, Debug, PartialEq)]
 pub enum ParameterType {
+    /// A value that will be Base64 encoded if it's not already.
     Base64,
+    /// A boolean value.
     Bool,
+    /// An integer value.
     Int,
+    /// A string value.
     String,
 }
 
+/// A normalization of a supplied parameter value, declared by the parameter's `transform`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    /// Removes leading and trailing whitespace.
+    Trim,
+    /// Converts the value to lower case.
+    Lower,
+    /// Converts the value to upper case.
+    Upper,
+    /// Base64 encodes the value.
+    B64Enc,
+    /// Base64 decodes the value, which must decode to UTF-8 text.
+    B64Dec,
+}
+
 /// The user-supplied value of a template parameter, either plain text or Base64-encoded.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ParameterValue {
     /// A plain text parameter value.
     Plain(String),
@@ -34,37 +101,225 @@ pub enum ParameterValue {
     Encoded(String),
 }
 
-pub type ParamMap = HashMap<String, Parameter>;
+/// How values given to the same parameter by different documents of a parameter file are merged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeOrder {
+    /// The value from the first document that sets the parameter is used.
+    FirstWins,
+    /// The value from the last document that sets the parameter is used.
+    LastWins,
+    /// Conflicting values are an error.
+    ErrorOnConflict,
+}
+
+/// How supplied parameter values containing control characters are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlCharacters {
+    /// Values containing control characters are an error.
+    Reject,
+    /// Control characters are replaced with escape sequences, e.g. `\r`, with a warning.
+    Escape,
+}
+
+/// How placeholders for declared parameters without a value are interpolated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NullParameters {
+    /// Placeholders for parameters without a value are an error.
+    #[default]
+    Error,
+    /// Placeholders are replaced with an empty string.
+    Empty,
+    /// Placeholders are left as written.
+    Keep,
+    /// Placeholders are replaced with `~`, which is a YAML null if it's the whole value.
+    Null,
+}
+
+/// A parameter given different values by more than one document of a parameter file.
+#[derive(Debug)]
+pub struct ParameterConflict {
+    /// The name of the parameter.
+    pub name: String,
+    /// The one-based numbers of the documents that set the parameter, in order.
+    pub documents: Vec<usize>,
+    /// The number of the document whose value was used.
+    pub used: usize,
+}
+
+/// A template's parameters, looked up by name and kept in declaration order.
+#[derive(Clone, Debug, Default)]
+pub struct ParamMap {
+    /// The position of each parameter in `parameters`, by name.
+    index: HashMap<String, usize>,
+    interpolate_supplied: bool,
+    null_parameters: NullParameters,
+    parameters: Vec<Parameter>,
+    supplied: HashSet<String>,
+}
+
+/// The line separating a template from a parameter file when both are read from one stream.
+pub const PARAMETERS_MARKER: &str = "--- # ktmpl:parameters";
 
 /// A map of parameter names to user-supplied values of the parameters.
 pub type ParameterValues = HashMap<String, ParameterValue>;
 
 /// Loads `ParameterValues` from a file.
-pub fn parameter_values_from_file(file_path: &str) -> Result<ParameterValues, String> {
-    let mut file = File::open(file_path).map_err(|err| err.description().to_owned())?;
+///
+/// # Errors
+///
+/// Returns `Error::Io` if the file can't be read, `Error::Parse` if it isn't UTF-8, and otherwise
+/// errors like `parameter_values_from_str`. A leading byte order mark and CRLF line endings are
+/// accepted.
+pub fn parameter_values_from_file(file_path: &str) -> Result<ParameterValues, Error> {
+    info!("Loading parameter file {}", file_path);
+
+    let mut file = File::open(file_path).map_err(|err| Error::Io(format!("{}: {}", file_path, err)))?;
+
+    let mut bytes = vec![];
+    file.read_to_end(&mut bytes).map_err(|err| Error::Io(format!("{}: {}", file_path, err)))?;
 
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).map_err(|err| err.description().to_owned())?;
+    let contents = decode_text(bytes).map_err(|err| Error::Parse(format!("{}: {}", file_path, err)))?;
 
     parameter_values_from_str(&contents)
 }
 
 /// Loads `ParameterValues` from the raw contents of a parameter file.
-pub fn parameter_values_from_str(contents: &str) -> Result<ParameterValues, String> {
-    let docs = YamlLoader::load_from_str(&contents)
-        .map_err(|err| err.description().to_owned())?;
+///
+/// If the file contains multiple YAML documents, values from later documents replace values
+/// from earlier ones.
+///
+/// # Errors
+///
+/// Returns an error like `parameter_values_from_str_merged`.
+pub fn parameter_values_from_str(contents: &str) -> Result<ParameterValues, Error> {
+    parameter_values_from_str_merged(contents, MergeOrder::LastWins).map(|(values, _)| values)
+}
+
+/// Loads `ParameterValues` from the raw contents of a parameter file, merging the values from
+/// multiple YAML documents in the given order and reporting any parameters they disagree on.
+///
+/// # Errors
+///
+/// Returns `Error::Parse` if the contents are not a valid parameter file, or `Error::Parameter`
+/// if documents give conflicting values and the merge order is `MergeOrder::ErrorOnConflict`.
+pub fn parameter_values_from_str_merged(
+    contents: &str,
+    merge_order: MergeOrder,
+) -> Result<(ParameterValues, Vec<ParameterConflict>), Error> {
+    let docs = YamlLoader::load_from_str(&normalize_text(contents))
+        .map_err(|err| Error::Parse(err.to_string()))?;
 
     let mut parameter_values = ParameterValues::new();
+    let mut sources: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut conflicting = vec![];
+
+    for (index, doc) in docs.into_iter().enumerate() {
+        for (name, value) in parameter_values_from_yaml(doc)? {
+            sources.entry(name.clone()).or_default().push(index + 1);
 
-    for doc in docs {
-        parameter_values.extend(parameter_values_from_yaml(doc)?);
+            match parameter_values.get(&name) {
+                Some(existing) if *existing == value => continue,
+                Some(_) => {
+                    if !conflicting.contains(&name) {
+                        conflicting.push(name.clone());
+                    }
+
+                    if merge_order == MergeOrder::FirstWins {
+                        continue;
+                    }
+                }
+                None => {}
+            }
+
+            parameter_values.insert(name, value);
+        }
     }
 
-    Ok(parameter_values)
+    conflicting.sort();
+
+    let conflicts: Vec<ParameterConflict> = conflicting
+        .into_iter()
+        .map(|name| {
+            let documents = sources.remove(&name).unwrap_or_default();
+            let used = match merge_order {
+                MergeOrder::FirstWins => documents[0],
+                _ => documents[documents.len() - 1],
+            };
+
+            ParameterConflict { name, documents, used }
+        })
+        .collect();
+
+    if merge_order == MergeOrder::ErrorOnConflict && !conflicts.is_empty() {
+        let names: Vec<&str> = conflicts.iter().map(|conflict| conflict.name.as_str()).collect();
+
+        return Err(Error::Parameter(format!(
+            "Parameter file documents give conflicting values for: {}.",
+            names.join(", ")
+        )));
+    }
+
+    Ok((parameter_values, conflicts))
+}
+
+/// Splits a stream containing both a template and a parameter file, returning the contents of
+/// each.
+///
+/// The stream is either the template followed by the parameter file, separated by a line
+/// containing `PARAMETERS_MARKER`, or a single document with a `template` key holding the template
+/// and a `parameters` key holding the parameter values.
+///
+/// # Errors
+///
+/// Returns an error if the stream has no marker and isn't a document with a `template` key, or
+/// if it isn't valid YAML.
+pub fn split_template_and_parameters(contents: &str) -> Result<(String, String), String> {
+    let mut offset = 0;
+
+    for line in contents.split_inclusive('\n') {
+        if line.trim_end() == PARAMETERS_MARKER {
+            return Ok((contents[..offset].to_string(), contents[offset + line.len()..].to_string()));
+        }
+
+        offset += line.len();
+    }
+
+    let mut docs = load_yaml(contents).map_err(|err| err.to_string())?;
+
+    match docs.pop() {
+        Some(Yaml::Hash(mut hash)) if docs.is_empty() && hash.contains_key(&Yaml::String("template".to_string())) => {
+            let template = hash.remove(&Yaml::String("template".to_string()));
+            let parameters = hash.remove(&Yaml::String("parameters".to_string())).unwrap_or(Yaml::Null);
+            let mut template_contents = String::new();
+            let mut parameter_contents = String::new();
+
+            match template {
+                Some(template @ Yaml::Hash(_)) => emit(&template, &mut template_contents)?,
+                _ => return Err("Key \"template\" must be a hash.".to_string()),
+            }
+
+            match parameters {
+                Yaml::Hash(_) => emit(&parameters, &mut parameter_contents)?,
+                Yaml::Null => {}
+                _ => return Err("Key \"parameters\" must be a hash.".to_string()),
+            }
+
+            Ok((template_contents, parameter_contents))
+        }
+        _ => Err(format!(
+            "The template and parameter values must be separated by a line \"{}\", or given as the \
+             \"template\" and \"parameters\" keys of one document.",
+            PARAMETERS_MARKER,
+        )),
+    }
 }
 
 /// Loads `ParameterValues` from a YAML document in the format of a parameter file.
-pub fn parameter_values_from_yaml(yaml: Yaml) -> Result<ParameterValues, String> {
+///
+/// # Errors
+///
+/// Returns `Error::Parse` if the document isn't a hash of parameter names to strings.
+pub fn parameter_values_from_yaml(yaml: Yaml) -> Result<ParameterValues, Error> {
     let mut parameter_values = ParameterValues::new();
 
     match yaml {
@@ -79,21 +334,136 @@ pub fn parameter_values_from_yaml(yaml: Yaml) -> Result<ParameterValues, String>
                                     ParameterValue::Plain(value_string.to_string()),
                                 );
                             }
-                            _ => return Err("Parameter values in parameter files must be strings.".to_string()),
+                            _ => return Err(Error::Parse(
+                                "Parameter values in parameter files must be strings.".to_string(),
+                            )),
                         }
                     }
-                    _ => return Err(
+                    _ => return Err(Error::Parse(
                         "Parameters names in parameter files must be strings.".to_string()
-                    ),
+                    )),
                 }
             }
         }
-        _ => return Err("YAML documents in parameter files must be hashes.".to_string()),
+        _ => return Err(Error::Parse("YAML documents in parameter files must be hashes.".to_string())),
     }
 
     Ok(parameter_values)
 }
 
+/// Checks supplied parameter values for characters that would silently corrupt or break the
+/// rendered manifest: control characters (including NUL and carriage returns), the byte order
+/// mark, and the Unicode line and paragraph separators. Tabs are always allowed, and newlines are
+/// allowed in the values of the parameters named in `multiline`.
+///
+/// Base64 encoded values are checked as the text they decode to, and re-encoded if they're
+/// escaped. Encoded values that aren't valid Base64 or don't decode to UTF-8 text, such as binary
+/// keys, are left as they are. Default values declared in the template are not checked. Values are
+/// already valid UTF-8, as invalid UTF-8 is rejected when the values are read.
+///
+/// # Errors
+///
+/// Returns an error naming the parameter and the character's position if a value contains such a
+/// character and `handling` is `ControlCharacters::Reject`.
+pub fn check_control_characters(
+    parameter_values: &mut ParameterValues,
+    handling: ControlCharacters,
+    multiline: &[&str],
+) -> Result<(), String> {
+    let mut names: Vec<String> = parameter_values.keys().cloned().collect();
+
+    names.sort();
+
+    for name in names {
+        let allow_newlines = multiline.contains(&name.as_str());
+        let value = match parameter_values.get_mut(&name) {
+            Some(value) => value,
+            None => continue,
+        };
+        let text = match *value {
+            ParameterValue::Plain(ref text) => text.clone(),
+            ParameterValue::Encoded(ref encoded) => {
+                match decode(encoded).ok().and_then(|bytes| String::from_utf8(bytes).ok()) {
+                    Some(text) => text,
+                    None => continue,
+                }
+            }
+        };
+        let forbidden = |character: char| match character {
+            '\t' => false,
+            '\n' => !allow_newlines,
+            '\u{feff}' | '\u{2028}' | '\u{2029}' => true,
+            _ => character.is_control(),
+        };
+
+        let (position, character) = match text.chars().enumerate().find(|&(_, character)| forbidden(character)) {
+            Some(found) => found,
+            None => continue,
+        };
+
+        match handling {
+            ControlCharacters::Reject => return Err(format!(
+                "Parameter {} contains the control character U+{:04X} at position {}{}.",
+                name,
+                character as u32,
+                position,
+                if character == '\n' { "; use --multiline to allow newlines" } else { "" },
+            )),
+            ControlCharacters::Escape => {
+                warn!("Escaping control characters in the value of parameter {}", name);
+
+                let escaped: String = text.chars().map(|character| match character {
+                    '\n' if forbidden(character) => "\\n".to_string(),
+                    '\r' => "\\r".to_string(),
+                    '\0' => "\\0".to_string(),
+                    character if forbidden(character) => format!("\\u{:04x}", character as u32),
+                    character => character.to_string(),
+                }).collect();
+
+                *value = match *value {
+                    ParameterValue::Plain(_) => ParameterValue::Plain(escaped),
+                    ParameterValue::Encoded(_) => ParameterValue::Encoded(encode(escaped.as_bytes())),
+                };
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_parameter_name(name: &str) -> Result<(), String> {
+    lazy_static! {
+        static ref PARAMETER_NAME: Regex = Regex::new(r"^[\p{L}_][\p{L}\p{N}_.-]*$").expect("Failed to compile regex.");
+    }
+
+    if PARAMETER_NAME.is_match(name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Parameter name {:?} is invalid: names must start with a letter or an underscore and contain only letters, \
+            digits, underscores, hyphens, and dots.",
+            name,
+        ))
+    }
+}
+
+/// Returns a boolean, number, or string as the text of a parameter value.
+fn scalar_value(yaml: &Yaml) -> Option<String> {
+    match *yaml {
+        Yaml::Boolean(ref value)  => Some(format!("{}", value)),
+        Yaml::Integer(ref value) => Some(format!("{}", value)),
+        Yaml::Real(ref value) => Some(value.clone()),
+        Yaml::String(ref value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Compiles a parameter's pattern so that it only matches whole values.
+fn pattern_regex(name: &str, pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|err| format!("Parameter {}'s pattern {} is not a valid regex: {}", name, pattern, err))
+}
+
 fn maybe_base64_encode(parameter_type: &Option<ParameterType>, user_value: &ParameterValue) -> String {
     if parameter_type.is_none() || parameter_type.as_ref().unwrap() != &ParameterType::Base64 {
         return match *user_value {
@@ -108,8 +478,257 @@ fn maybe_base64_encode(parameter_type: &Option<ParameterType>, user_value: &Para
     }
 }
 
+impl ParamMap {
+    pub fn new() -> Self {
+        ParamMap::default()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Parameter> {
+        self.index.get(name).map(|&index| &self.parameters[index])
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Parameter> {
+        match self.index.get(name) {
+            Some(&index) => Some(&mut self.parameters[index]),
+            None => None,
+        }
+    }
+
+    /// Adds a parameter after the existing ones, or replaces the parameter with the same name
+    /// in place.
+    pub fn insert(&mut self, parameter: Parameter) {
+        match self.index.get(&parameter.name) {
+            Some(&index) => self.parameters[index] = parameter,
+            None => {
+                self.index.insert(parameter.name.clone(), self.parameters.len());
+                self.parameters.push(parameter);
+            }
+        }
+    }
+
+    /// Returns whether placeholders in the values users supply are resolved like those in
+    /// default values.
+    pub fn interpolate_supplied(&self) -> bool {
+        self.interpolate_supplied
+    }
+
+    /// Returns whether a parameter's value is used as it is, without resolving the placeholders
+    /// in it: it was supplied by the user, and `interpolate_supplied` isn't set.
+    pub fn is_literal(&self, name: &str) -> bool {
+        !self.interpolate_supplied && self.supplied.contains(name)
+    }
+
+    /// Returns how placeholders for parameters without a value are interpolated.
+    pub fn null_parameters(&self) -> NullParameters {
+        self.null_parameters
+    }
+
+    /// Removes a parameter, keeping the others in order.
+    pub fn remove(&mut self, name: &str) {
+        if let Some(index) = self.index.remove(name) {
+            self.parameters.remove(index);
+
+            for position in self.index.values_mut().filter(|position| **position > index) {
+                *position -= 1;
+            }
+        }
+    }
+
+    pub fn set_interpolate_supplied(&mut self, interpolate_supplied: bool) {
+        self.interpolate_supplied = interpolate_supplied;
+    }
+
+    pub fn set_null_parameters(&mut self, null_parameters: NullParameters) {
+        self.null_parameters = null_parameters;
+    }
+
+    /// Records that a parameter's value was supplied by the user; see `is_literal`.
+    pub fn set_supplied(&mut self, name: &str) {
+        self.supplied.insert(name.to_string());
+    }
+
+    pub fn values(&self) -> Iter<'_, Parameter> {
+        self.parameters.iter()
+    }
+}
+
 impl Parameter {
-    pub fn new(yaml: &Yaml, user_values: &ParameterValues) -> Result<Self, String> {
+    /// Returns whether the parameter's value is confidential: it's declared `sensitive` or has a
+    /// "base64" `parameterType`.
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive || self.parameter_type == Some(ParameterType::Base64)
+    }
+
+    /// Parses a parameter's declaration, using the user-supplied value for the parameter if there
+    /// is one, otherwise its default value. A value supplied under one of the parameter's
+    /// `aliases` is used if none is supplied under its name. A supplied plain text value is
+    /// normalized by the parameter's `transform`, a transform's name or an array of them, e.g.
+    /// `[trim, lower]`, and must then be valid for the parameter's `parameterType`, e.g. `42` for
+    /// an int or `true` or `false` for a bool, and be one of its `allowedValues` and match its
+    /// `pattern` in full, if it has them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parameter`, naming the parameter and its type, if the declaration is
+    /// invalid, if the parameter is required but has neither a user-supplied nor a default value,
+    /// if one of its transforms fails, or if the supplied value isn't of its type, isn't allowed,
+    /// or doesn't match its pattern.
+    pub fn new(yaml: &Yaml, user_values: &ParameterValues) -> Result<Self, Error> {
+        let mut parameter = Parameter::from_spec(yaml).map_err(Error::Parameter)?;
+        let alias_value = parameter.aliases.iter().find_map(|alias| user_values.get(alias));
+
+        if let Some(user_value) = user_values.get(&parameter.name) {
+            debug!("Parameter {} uses the supplied value", parameter.name);
+
+            parameter.value = Some(parameter.supplied_value(user_value).map_err(Error::Parameter)?);
+        } else if let Some(user_value) = alias_value {
+            debug!("Parameter {} uses the value supplied under a former name", parameter.name);
+
+            parameter.value = Some(parameter.supplied_value(user_value).map_err(Error::Parameter)?);
+        } else if parameter.value.is_some() {
+            debug!("Parameter {} uses its default value", parameter.name);
+        } else if parameter.required {
+            return Err(Error::Parameter(format!(
+                "Parameter {} required and must be {}",
+                parameter.display_name.unwrap_or(parameter.name),
+                parameter.parameter_type.map(|pt| pt.description()).unwrap_or("base64, bool, int, or string")
+            )));
+        }
+
+        Ok(parameter)
+    }
+
+    /// Returns the value of the parameter for a supplied value, after applying its transforms to
+    /// a plain text value and Base64 encoding it if the parameter's type is "base64". Values
+    /// supplied already Base64 encoded are used as they are, though it's their decoded text that
+    /// is checked against the parameter's type, allowed values, and pattern.
+    fn supplied_value(&self, user_value: &ParameterValue) -> Result<String, String> {
+        let user_value = match *user_value {
+            ParameterValue::Plain(ref value) => {
+                let mut value = value.clone();
+
+                for transform in &self.transforms {
+                    value = transform
+                        .apply(&value)
+                        .map_err(|err| format!("Parameter {} can't be transformed: {}", self.name, err))?;
+                }
+
+                self.check_value(&value)?;
+
+                ParameterValue::Plain(value)
+            }
+            ParameterValue::Encoded(ref value) => {
+                let checked_as_text = match self.parameter_type {
+                    Some(ParameterType::Bool) | Some(ParameterType::Int) => true,
+                    Some(ParameterType::Base64) => {
+                        decode(value).map_err(|err| {
+                            format!(
+                                "Parameter {} must be base64, but its value isn't valid Base64: {}",
+                                self.name,
+                                err,
+                            )
+                        })?;
+
+                        false
+                    }
+                    _ => false,
+                };
+
+                if checked_as_text || !self.allowed_values.is_empty() || self.pattern.is_some() {
+                    let decoded = Transform::B64Dec
+                        .apply(value)
+                        .map_err(|err| format!("Parameter {}'s value can't be checked: {}", self.name, err))?;
+
+                    self.check_value(&decoded)?;
+                }
+
+                user_value.clone()
+            }
+        };
+
+        Ok(maybe_base64_encode(&self.parameter_type, &user_value))
+    }
+
+    /// Checks that a supplied value is valid for the parameter's type, and is one of its allowed
+    /// values and matches its pattern in full, if it has them. The value is left out of errors if
+    /// the parameter is sensitive.
+    fn check_value(&self, value: &str) -> Result<(), String> {
+        let quoted = if self.is_sensitive() { String::new() } else { format!(" {:?}", value) };
+        let valid_for_type = match self.parameter_type {
+            Some(ParameterType::Bool) => value == "true" || value == "false",
+            Some(ParameterType::Int) => value.parse::<i64>().is_ok(),
+            _ => true,
+        };
+
+        if !valid_for_type {
+            return Err(format!(
+                "Parameter {} must be {}, but its value{} isn't.",
+                self.name,
+                self.parameter_type.as_ref().map_or("", ParameterType::description),
+                quoted,
+            ));
+        }
+
+        if !self.allowed_values.is_empty() && !self.allowed_values.iter().any(|allowed| allowed == value) {
+            let (last, others) = self.allowed_values.split_last().expect("allowed values are empty");
+            let options = match others.len() {
+                0 => last.clone(),
+                1 => format!("{} or {}", others[0], last),
+                _ => format!("{}, or {}", others.join(", "), last),
+            };
+
+            return Err(format!(
+                "Parameter {} ({}) must be {}, but its value{} isn't.",
+                self.name,
+                self.parameter_type.as_ref().unwrap_or(&ParameterType::String).description(),
+                options,
+                quoted,
+            ));
+        }
+
+        if let Some(ref pattern) = self.pattern {
+            if !pattern_regex(&self.name, pattern)?.is_match(value) {
+                return Err(format!(
+                    "Parameter {} must match the pattern {}, but its value{} doesn't.",
+                    self.name,
+                    pattern,
+                    quoted,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a parameter's declaration, using its default value (if any) as its value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the declaration has no name, an invalid name or alias, or an invalid
+    /// parameter type, allowed values, or pattern.
+    pub fn from_spec(yaml: &Yaml) -> Result<Self, String> {
+        let aliases = match yaml["aliases"] {
+            Yaml::BadValue => vec![],
+            Yaml::Array(ref aliases) => aliases
+                .iter()
+                .map(|alias| alias.as_str().map(|alias| alias.to_string()))
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(|| "Parameter aliases must be an array of names.".to_string())?,
+            _ => return Err("Parameter aliases must be an array of names.".to_string()),
+        };
+        let allowed_values = match yaml["allowedValues"] {
+            Yaml::BadValue => vec![],
+            Yaml::Array(ref values) if !values.is_empty() => values
+                .iter()
+                .map(scalar_value)
+                .collect::<Option<Vec<String>>>()
+                .ok_or_else(|| "Parameter allowedValues must be a non-empty array of values.".to_string())?,
+            _ => return Err("Parameter allowedValues must be a non-empty array of values.".to_string()),
+        };
         let description = match yaml["description"] {
             Yaml::String(ref description) => Some(description.clone()),
             _ => None,
@@ -118,51 +737,132 @@ impl Parameter {
             Yaml::String(ref description) => Some(description.clone()),
             _ => None,
         };
+        let hidden = yaml["hidden"].as_bool().unwrap_or(false);
         let name = match yaml["name"] {
             Yaml::String(ref name) => name.clone(),
             _ => return Err("Parameters must have a \"name\" field.".to_owned()),
         };
+
+        check_parameter_name(&name)?;
+
+        for alias in &aliases {
+            check_parameter_name(alias)?;
+        }
+
         let parameter_type = match yaml["parameterType"].as_str() {
-            Some(ref parameter_type) => Some(parameter_type.parse()?),
+            Some(parameter_type) => Some(parameter_type.parse()?),
             None => None,
         };
-        let required = yaml["required"].as_bool().unwrap_or(false);
-        let value = match user_values.get(&name) {
-            Some(user_value) => Some(maybe_base64_encode(&parameter_type, &user_value)),
-            None => match yaml["value"] {
-                Yaml::Boolean(ref value)  => Some(format!("{}", value)),
-                Yaml::Integer(ref value) => Some(format!("{}", value)),
-                Yaml::String(ref value) => Some(value.clone()),
-                _ => if required {
-                    return Err(
-                        format!(
-                            "Parameter {} required and must be {}",
-                            display_name.unwrap_or(name),
-                            parameter_type.map(|pt| match pt {
-                                ParameterType::Base64 => "base64",
-                                ParameterType::Bool => "a bool",
-                                ParameterType::Int => "an int",
-                                ParameterType::String => "a string"
-                            }).unwrap_or("base64, bool, int, or string")
-                        )
-                    )
-                } else {
-                    None
-                },
+        let pattern = match yaml["pattern"] {
+            Yaml::BadValue => None,
+            Yaml::String(ref pattern) => {
+                pattern_regex(&name, pattern)?;
+
+                Some(pattern.clone())
             }
+            _ => return Err(format!("Parameter {}'s pattern must be a string.", name)),
+        };
+        let required = yaml["required"].as_bool().unwrap_or(false);
+        let sensitive = yaml["sensitive"].as_bool().unwrap_or(false);
+        let transforms = match yaml["transform"] {
+            Yaml::BadValue => vec![],
+            Yaml::String(ref transform) => vec![transform.parse()?],
+            Yaml::Array(ref transforms) => transforms
+                .iter()
+                .map(|transform| transform.as_str().unwrap_or_default().parse())
+                .collect::<Result<Vec<Transform>, String>>()?,
+            _ => return Err("transform must be a transform's name or an array of them.".to_owned()),
         };
+        let value = scalar_value(&yaml["value"]);
 
         Ok(Parameter {
-            description: description,
-            display_name: display_name,
-            name: name,
-            parameter_type: parameter_type,
-            required: required,
-            value: value,
+            aliases,
+            allowed_values,
+            description,
+            display_name,
+            hidden,
+            name,
+            parameter_type,
+            pattern,
+            required,
+            sensitive,
+            transforms,
+            value,
         })
     }
 }
 
+impl Transform {
+    /// Applies the transform to a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transform is `B64Dec` and the value isn't Base64-encoded UTF-8.
+    pub fn apply(&self, value: &str) -> Result<String, String> {
+        match *self {
+            Transform::Trim => Ok(value.trim().to_string()),
+            Transform::Lower => Ok(value.to_lowercase()),
+            Transform::Upper => Ok(value.to_uppercase()),
+            Transform::B64Enc => Ok(encode(value.as_bytes())),
+            Transform::B64Dec => decode(value)
+                .map_err(|err| err.to_string())
+                .and_then(|decoded| String::from_utf8(decoded).map_err(|err| err.to_string()))
+                .map_err(|err| format!("The value isn't Base64-encoded UTF-8: {}.", err)),
+        }
+    }
+}
+
+impl FromStr for ControlCharacters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(ControlCharacters::Reject),
+            "escape" => Ok(ControlCharacters::Escape),
+            _ => Err("Control character handling must be reject or escape.".to_owned()),
+        }
+    }
+}
+
+impl FromStr for NullParameters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(NullParameters::Error),
+            "empty" => Ok(NullParameters::Empty),
+            "keep" => Ok(NullParameters::Keep),
+            "null" => Ok(NullParameters::Null),
+            _ => Err("Null parameter handling must be error, empty, keep, or null.".to_owned()),
+        }
+    }
+}
+
+impl FromStr for MergeOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first-wins" => Ok(MergeOrder::FirstWins),
+            "last-wins" => Ok(MergeOrder::LastWins),
+            "error" => Ok(MergeOrder::ErrorOnConflict),
+            _ => Err("Merge order must be first-wins, last-wins, or error.".to_owned()),
+        }
+    }
+}
+
+impl ParameterType {
+    /// Describes a value of the type, e.g. "an int".
+    fn description(&self) -> &'static str {
+        match *self {
+            ParameterType::Base64 => "base64",
+            ParameterType::Bool => "a bool",
+            ParameterType::Int => "an int",
+            ParameterType::String => "a string",
+        }
+    }
+}
+
 impl FromStr for ParameterType {
     type Err = String;
 
@@ -176,3 +876,18 @@ impl FromStr for ParameterType {
         }
     }
 }
+
+impl FromStr for Transform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trim" => Ok(Transform::Trim),
+            "lower" => Ok(Transform::Lower),
+            "upper" => Ok(Transform::Upper),
+            "b64enc" => Ok(Transform::B64Enc),
+            "b64dec" => Ok(Transform::B64Dec),
+            _ => Err("transform must be trim, lower, upper, b64enc, or b64dec.".to_owned()),
+        }
+    }
+}
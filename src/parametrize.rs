@@ -0,0 +1,106 @@
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use catalog::template_paths;
+use loader::load_yaml;
+use template::dump;
+
+/// Replaces a literal value with a parameter reference across the templates in a directory tree,
+/// returning the paths of the templates that were rewritten.
+///
+/// Only scalars in `objects` that are exactly equal to the value are replaced: strings become
+/// `$(NAME)` and numbers and booleans become `$((NAME))`, so they keep their type. A parameter
+/// named `name` with the value as its default is declared in each rewritten template that doesn't
+/// already declare it. Files that are not a single YAML document of kind `Template` are skipped.
+/// Rewritten templates are re-emitted, so comments and formatting are not preserved.
+///
+/// # Errors
+///
+/// Returns an error if a directory or template can't be read or written.
+pub fn parametrize(directory: &Path, value: &str, name: &str) -> Result<Vec<PathBuf>, String> {
+    let mut rewritten = vec![];
+
+    for path in template_paths(directory)? {
+        let contents = read_to_string(&path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let mut docs = match load_yaml(&contents) {
+            Ok(docs) => docs,
+            Err(_) => continue,
+        };
+
+        if docs.len() != 1 || docs[0]["kind"].as_str() != Some("Template") {
+            continue;
+        }
+
+        let template = &mut docs[0];
+
+        let replaced = match get_mut(template, "objects") {
+            Some(objects) => replace(objects, value, name),
+            None => 0,
+        };
+
+        if replaced == 0 {
+            continue;
+        }
+
+        let declared = template["parameters"]
+            .as_vec()
+            .is_some_and(|parameters| parameters.iter().any(|parameter| parameter["name"].as_str() == Some(name)));
+
+        if !declared {
+            let mut parameter = Hash::new();
+
+            parameter.insert(ystring("name"), ystring(name));
+            parameter.insert(ystring("required"), Yaml::Boolean(false));
+            parameter.insert(ystring("value"), ystring(value));
+
+            if let Yaml::Hash(ref mut template) = *template {
+                let parameters = template.entry(ystring("parameters")).or_insert(Yaml::Array(vec![]));
+
+                match *parameters {
+                    Yaml::Array(ref mut parameters) => parameters.push(Yaml::Hash(parameter)),
+                    _ => return Err(format!("{}: \"parameters\" must be an array.", path.display())),
+                }
+            }
+        }
+
+        let manifest = dump(docs)?;
+
+        write(&path, format!("{}\n", manifest)).map_err(|err| format!("Failed to write {}: {}", path.display(), err))?;
+
+        rewritten.push(path);
+    }
+
+    Ok(rewritten)
+}
+
+/// Replaces scalars equal to `value` with references to the parameter, returning the number
+/// replaced.
+fn replace(yaml: &mut Yaml, value: &str, name: &str) -> usize {
+    let reference = match *yaml {
+        Yaml::String(ref string) if string == value => format!("$({})", name),
+        Yaml::Real(ref real) if real == value => format!("$(({}))", name),
+        Yaml::Integer(integer) if integer.to_string() == value => format!("$(({}))", name),
+        Yaml::Boolean(boolean) if boolean.to_string() == value => format!("$(({}))", name),
+        Yaml::Array(ref mut array) => return array.iter_mut().map(|yaml| replace(yaml, value, name)).sum(),
+        Yaml::Hash(ref mut hash) => return hash.iter_mut().map(|(_, yaml)| replace(yaml, value, name)).sum(),
+        _ => return 0,
+    };
+
+    *yaml = Yaml::String(reference);
+
+    1
+}
+
+fn get_mut<'a>(yaml: &'a mut Yaml, key: &str) -> Option<&'a mut Yaml> {
+    match *yaml {
+        Yaml::Hash(ref mut hash) => hash.get_mut(&ystring(key)),
+        _ => None,
+    }
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
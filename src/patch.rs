@@ -0,0 +1,407 @@
+use yaml::Yaml;
+use yaml::yaml::{Array, Hash};
+
+use loader::load_yaml;
+
+/// A patch applied to processed objects matching its kind and name.
+#[derive(Clone, Debug)]
+pub struct Patch {
+    kind: String,
+    name: String,
+    namespace: Option<String>,
+    patch: PatchType,
+}
+
+#[derive(Clone, Debug)]
+enum PatchType {
+    Json(Vec<JsonOperation>),
+    StrategicMerge(Yaml),
+}
+
+#[derive(Clone, Debug)]
+struct JsonOperation {
+    op: String,
+    path: Vec<String>,
+    from: Option<Vec<String>>,
+    value: Option<Yaml>,
+}
+
+impl Patch {
+    /// Loads patches from the contents of a patch file.
+    ///
+    /// Each YAML document in the file is either a strategic merge patch, a partial object with a
+    /// `kind` and `metadata.name` identifying the object it should be merged into, or an RFC
+    /// 6902 JSON patch, a hash with a `target` (`kind`, `name`, and optionally `namespace`) and a
+    /// `patch` array of operations.
+    pub fn from_str(contents: &str) -> Result<Vec<Self>, String> {
+        let docs = load_yaml(contents).map_err(|err| err.to_string())?;
+        let mut patches = vec![];
+
+        for doc in docs {
+            if let Some(operations) = doc["patch"].as_vec() {
+                let target = &doc["target"];
+                let kind = match target["kind"].as_str() {
+                    Some(kind) => kind.to_string(),
+                    None => return Err("JSON patches must have a \"target.kind\" field.".to_string()),
+                };
+                let name = match target["name"].as_str() {
+                    Some(name) => name.to_string(),
+                    None => return Err("JSON patches must have a \"target.name\" field.".to_string()),
+                };
+                let operations = operations
+                    .iter()
+                    .map(JsonOperation::new)
+                    .collect::<Result<Vec<JsonOperation>, String>>()?;
+
+                patches.push(Patch {
+                    kind,
+                    name,
+                    namespace: target["namespace"].as_str().map(|ns| ns.to_string()),
+                    patch: PatchType::Json(operations),
+                });
+
+                continue;
+            }
+
+            let kind = match doc["kind"].as_str() {
+                Some(kind) => kind.to_string(),
+                None => return Err("Patches must have a \"kind\" field.".to_string()),
+            };
+            let name = match doc["metadata"]["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => return Err("Patches must have a \"metadata.name\" field.".to_string()),
+            };
+            let namespace = doc["metadata"]["namespace"].as_str().map(|ns| ns.to_string());
+
+            patches.push(Patch {
+                kind,
+                name,
+                namespace,
+                patch: PatchType::StrategicMerge(doc),
+            });
+        }
+
+        Ok(patches)
+    }
+
+    /// Applies the patch to every object matching its kind, name, and namespace (if specified).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no object matched the patch, or if a JSON patch operation failed.
+    pub fn apply(&self, objects: &mut [Yaml]) -> Result<(), String> {
+        let mut matched = false;
+
+        for object in objects.iter_mut() {
+            if object["kind"].as_str() != Some(&self.kind)
+                || object["metadata"]["name"].as_str() != Some(&self.name)
+            {
+                continue;
+            }
+
+            if let Some(ref namespace) = self.namespace {
+                if object["metadata"]["namespace"].as_str() != Some(namespace) {
+                    continue;
+                }
+            }
+
+            match self.patch {
+                PatchType::Json(ref operations) => {
+                    let mut patched = object.clone();
+
+                    for operation in operations {
+                        operation.apply(&mut patched).map_err(|err| {
+                            format!("JSON patch for {} {} failed: {}", self.kind, self.name, err)
+                        })?;
+                    }
+
+                    *object = patched;
+                }
+                PatchType::StrategicMerge(ref patch) => strategic_merge(object, patch),
+            }
+
+            matched = true;
+        }
+
+        if matched {
+            Ok(())
+        } else {
+            Err(format!("Patch for {} {} did not match any objects.", self.kind, self.name))
+        }
+    }
+}
+
+impl JsonOperation {
+    fn new(yaml: &Yaml) -> Result<Self, String> {
+        let op = match yaml["op"].as_str() {
+            Some(op @ "add") | Some(op @ "remove") | Some(op @ "replace") | Some(op @ "move")
+            | Some(op @ "copy") | Some(op @ "test") => op.to_string(),
+            _ => return Err(
+                "JSON patch operations must have an \"op\" of add, remove, replace, move, copy, or test."
+                    .to_string()
+            ),
+        };
+        let path = match yaml["path"].as_str() {
+            Some(path) => pointer(path)?,
+            None => return Err("JSON patch operations must have a \"path\" field.".to_string()),
+        };
+        let from = match yaml["from"].as_str() {
+            Some(from) => Some(pointer(from)?),
+            None if op == "move" || op == "copy" => {
+                return Err(format!("JSON patch {} operations must have a \"from\" field.", op));
+            }
+            None => None,
+        };
+        let value = match yaml["value"] {
+            Yaml::BadValue if op == "add" || op == "replace" || op == "test" => {
+                return Err(format!("JSON patch {} operations must have a \"value\" field.", op));
+            }
+            Yaml::BadValue => None,
+            ref value => Some(value.clone()),
+        };
+
+        Ok(JsonOperation {
+            op,
+            path,
+            from,
+            value,
+        })
+    }
+
+    fn apply(&self, object: &mut Yaml) -> Result<(), String> {
+        let value = || self.value.clone().expect("JSON patch operation is missing its value.");
+        let from = || self.from.as_ref().expect("JSON patch operation is missing its source.");
+
+        match self.op.as_str() {
+            "add" => add(object, &self.path, value()),
+            "remove" => remove(object, &self.path).map(|_| ()),
+            "replace" => {
+                remove(object, &self.path)?;
+                add(object, &self.path, value())
+            }
+            "move" => {
+                let moved = remove(object, from())?;
+                add(object, &self.path, moved)
+            }
+            "copy" => {
+                let copied = match get(object, from()) {
+                    Some(copied) => copied.clone(),
+                    None => return Err(format!("path /{} does not exist", from().join("/"))),
+                };
+                add(object, &self.path, copied)
+            }
+            _ => if get(object, &self.path) == self.value.as_ref() {
+                Ok(())
+            } else {
+                Err(format!("test of /{} did not match", self.path.join("/")))
+            },
+        }
+    }
+}
+
+/// Merges `patch` into `target` using a subset of Kubernetes strategic merge patch semantics.
+///
+/// Hashes are merged recursively and null values delete keys. Lists of hashes with a well-known
+/// merge key (e.g. `name` for containers) are merged element by element; other lists are
+/// replaced. The `$patch: replace` and `$patch: delete` directives are honored.
+pub fn strategic_merge(target: &mut Yaml, patch: &Yaml) {
+    match (target, patch) {
+        (&mut Yaml::Hash(ref mut target_hash), Yaml::Hash(patch_hash)) => {
+            if directive(patch_hash) == Some("replace") {
+                let mut replacement = patch_hash.clone();
+
+                replacement.remove(&ystring("$patch"));
+                *target_hash = replacement;
+
+                return;
+            }
+
+            merge_hash(target_hash, patch_hash);
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn merge_hash(target: &mut Hash, patch: &Hash) {
+    for (key, patch_value) in patch {
+        if *key == ystring("$patch") {
+            continue;
+        }
+
+        if patch_value.is_null() {
+            target.remove(key);
+            continue;
+        }
+
+        let merge_key = key.as_str().and_then(merge_key);
+
+        match (target.get_mut(key), patch_value, merge_key) {
+            (
+                Some(&mut Yaml::Array(ref mut target_array)),
+                Yaml::Array(patch_array),
+                Some(merge_key),
+            ) => merge_array(target_array, patch_array, merge_key),
+            (Some(target_value), _, _) => strategic_merge(target_value, patch_value),
+            (None, _, _) => {
+                target.insert(key.clone(), without_directives(patch_value));
+            }
+        }
+    }
+}
+
+fn merge_array(target: &mut Array, patch: &Array, merge_key: &[&str]) {
+    for patch_item in patch {
+        let patch_hash = match *patch_item {
+            Yaml::Hash(ref patch_hash) => patch_hash,
+            _ => {
+                target.push(patch_item.clone());
+                continue;
+            }
+        };
+
+        let key = match merge_key.iter().find(|key| patch_hash.contains_key(&ystring(key))) {
+            Some(key) => ystring(key),
+            None => {
+                target.push(patch_item.clone());
+                continue;
+            }
+        };
+
+        let position = target.iter().position(|item| match *item {
+            Yaml::Hash(ref hash) => hash.get(&key) == patch_hash.get(&key),
+            _ => false,
+        });
+
+        match (position, directive(patch_hash)) {
+            (Some(position), Some("delete")) => {
+                target.remove(position);
+            }
+            (Some(position), _) => strategic_merge(&mut target[position], patch_item),
+            (None, Some("delete")) => {}
+            (None, _) => target.push(without_directives(patch_item)),
+        }
+    }
+}
+
+fn merge_key(field: &str) -> Option<&'static [&'static str]> {
+    match field {
+        "containers" | "initContainers" | "ephemeralContainers" | "env" | "volumes"
+        | "imagePullSecrets" => Some(&["name"]),
+        "volumeMounts" | "volumeDevices" => Some(&["mountPath", "devicePath"]),
+        "ports" => Some(&["containerPort", "port"]),
+        "hostAliases" => Some(&["ip"]),
+        _ => None,
+    }
+}
+
+fn directive(hash: &Hash) -> Option<&str> {
+    hash.get(&ystring("$patch")).and_then(|value| value.as_str())
+}
+
+fn without_directives(yaml: &Yaml) -> Yaml {
+    match *yaml {
+        Yaml::Hash(ref hash) => Yaml::Hash(
+            hash.iter()
+                .filter(|&(key, _)| *key != ystring("$patch"))
+                .map(|(key, value)| (key.clone(), without_directives(value)))
+                .collect()
+        ),
+        Yaml::Array(ref array) => Yaml::Array(array.iter().map(without_directives).collect()),
+        _ => yaml.clone(),
+    }
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
+
+fn pointer(path: &str) -> Result<Vec<String>, String> {
+    if path.is_empty() {
+        return Ok(vec![]);
+    }
+
+    if !path.starts_with('/') {
+        return Err(format!("JSON pointer \"{}\" must start with \"/\".", path));
+    }
+
+    Ok(path[1..].split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+fn get<'a>(yaml: &'a Yaml, tokens: &[String]) -> Option<&'a Yaml> {
+    let mut current = yaml;
+
+    for token in tokens {
+        current = match *current {
+            Yaml::Hash(ref hash) => hash.get(&ystring(token))?,
+            Yaml::Array(ref array) => array.get(token.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn get_mut<'a>(yaml: &'a mut Yaml, tokens: &[String]) -> Option<&'a mut Yaml> {
+    let mut current = yaml;
+
+    for token in tokens {
+        current = match *current {
+            Yaml::Hash(ref mut hash) => hash.get_mut(&ystring(token))?,
+            Yaml::Array(ref mut array) => array.get_mut(token.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn add(yaml: &mut Yaml, tokens: &[String], value: Yaml) -> Result<(), String> {
+    let (last, parent_tokens) = match tokens.split_last() {
+        Some(split) => split,
+        None => {
+            *yaml = value;
+
+            return Ok(());
+        }
+    };
+
+    let missing = || format!("path /{} does not exist", parent_tokens.join("/"));
+
+    match *get_mut(yaml, parent_tokens).ok_or_else(missing)? {
+        Yaml::Hash(ref mut hash) => {
+            hash.insert(ystring(last), value);
+        }
+        Yaml::Array(ref mut array) => {
+            let index = if last == "-" {
+                array.len()
+            } else {
+                last.parse::<usize>().map_err(|_| format!("\"{}\" is not an array index", last))?
+            };
+
+            if index > array.len() {
+                return Err(format!("array index {} is out of bounds", index));
+            }
+
+            array.insert(index, value);
+        }
+        _ => return Err(missing()),
+    }
+
+    Ok(())
+}
+
+fn remove(yaml: &mut Yaml, tokens: &[String]) -> Result<Yaml, String> {
+    let missing = || format!("path /{} does not exist", tokens.join("/"));
+    let (last, parent_tokens) = tokens.split_last().ok_or_else(missing)?;
+
+    let removed = match *get_mut(yaml, parent_tokens).ok_or_else(missing)? {
+        Yaml::Hash(ref mut hash) => hash.remove(&ystring(last)),
+        Yaml::Array(ref mut array) => match last.parse::<usize>() {
+            Ok(index) if index < array.len() => Some(array.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    removed.ok_or_else(missing)
+}
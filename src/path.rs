@@ -0,0 +1,255 @@
+use yaml::Yaml;
+
+/// A parsed path expression selecting values within a template's objects.
+///
+/// Paths start with `objects`, followed by an object selector and any number of hash keys and
+/// array indices, e.g. `objects[0].metadata.name`, `objects[*].metadata.namespace`, or
+/// `objects[?(@.kind=="Deployment")].spec.replicas`.
+#[derive(Clone, Debug)]
+pub struct Path {
+    selector: Selector,
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Selector {
+    All,
+    Index(usize),
+    Filter(Vec<Segment>, String),
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Index(usize),
+    Key(String),
+}
+
+/// A path paired with the value to set at that path.
+#[derive(Clone, Debug)]
+pub struct PathOverride {
+    path: Path,
+    value: Yaml,
+}
+
+impl Path {
+    /// Parses a path expression.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid path \"{}\".", expression);
+
+        if !expression.starts_with("objects[") {
+            return Err(invalid());
+        }
+
+        let rest = &expression["objects".len()..];
+        let close = closing_bracket(rest).ok_or_else(invalid)?;
+        let selector_source = &rest[1..close];
+
+        let selector = if selector_source == "*" {
+            Selector::All
+        } else if selector_source.starts_with("?(@.") && selector_source.ends_with(')') {
+            let filter = &selector_source[4..selector_source.len() - 1];
+            let equals = filter.find("==").ok_or_else(invalid)?;
+            let value = filter[equals + 2..].trim().trim_matches('"').to_string();
+
+            Selector::Filter(parse_segments(filter[..equals].trim()).ok_or_else(invalid)?, value)
+        } else {
+            Selector::Index(selector_source.parse().map_err(|_| invalid())?)
+        };
+
+        let segments_source = &rest[close + 1..];
+        let segments = if segments_source.is_empty() {
+            vec![]
+        } else if segments_source.starts_with('.') || segments_source.starts_with('[') {
+            parse_segments(segments_source.trim_start_matches('.')).ok_or_else(invalid)?
+        } else {
+            return Err(invalid());
+        };
+
+        Ok(Path {
+            selector,
+            segments,
+        })
+    }
+
+    /// Returns the indices of the objects matched by the path's selector.
+    pub fn select(&self, objects: &[Yaml]) -> Vec<usize> {
+        match self.selector {
+            Selector::All => (0..objects.len()).collect(),
+            Selector::Index(index) => if index < objects.len() { vec![index] } else { vec![] },
+            Selector::Filter(ref segments, ref expected) => objects
+                .iter()
+                .enumerate()
+                .filter(|&(_, object)| match get(object, segments) {
+                    Some(Yaml::String(value)) => value == expected,
+                    Some(&Yaml::Integer(value)) => value.to_string() == *expected,
+                    Some(&Yaml::Boolean(value)) => value.to_string() == *expected,
+                    _ => false,
+                })
+                .map(|(index, _)| index)
+                .collect(),
+        }
+    }
+
+    /// Returns the values at the path within the objects matched by its selector.
+    pub fn values<'a>(&self, objects: &'a [Yaml]) -> Vec<&'a Yaml> {
+        self.select(objects)
+            .into_iter()
+            .filter_map(|index| get(&objects[index], &self.segments))
+            .collect()
+    }
+}
+
+impl PathOverride {
+    /// Parses an override of the form `PATH=VALUE`. The value is parsed as a YAML scalar, so
+    /// `5` becomes an integer while `"5"` stays a string.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let mut depth = 0;
+        let mut in_quotes = false;
+        let mut equals = None;
+
+        for (index, character) in expression.char_indices() {
+            match character {
+                '"' => in_quotes = !in_quotes,
+                '[' if !in_quotes => depth += 1,
+                ']' if !in_quotes => depth -= 1,
+                '=' if !in_quotes && depth == 0 => {
+                    equals = Some(index);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let equals = equals.ok_or_else(|| {
+            format!("Override \"{}\" must be of the form PATH=VALUE.", expression)
+        })?;
+
+        let value = &expression[equals + 1..];
+        let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            Yaml::String(value[1..value.len() - 1].to_string())
+        } else {
+            Yaml::from_str(value)
+        };
+
+        Ok(PathOverride {
+            path: Path::parse(&expression[..equals])?,
+            value,
+        })
+    }
+
+    /// Sets the value in every object matched by the path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path matches no objects, or traverses a value that is not a hash
+    /// or array, or an array index that is out of bounds.
+    pub fn apply(&self, objects: &mut [Yaml]) -> Result<(), String> {
+        let indices = self.path.select(objects);
+
+        if indices.is_empty() {
+            return Err("Override path did not match any objects.".to_string());
+        }
+
+        for index in indices {
+            set(&mut objects[index], &self.path.segments, self.value.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn closing_bracket(source: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_quotes = false;
+
+    for (index, character) in source.char_indices() {
+        match character {
+            '"' => in_quotes = !in_quotes,
+            '[' if !in_quotes => depth += 1,
+            ']' if !in_quotes => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn parse_segments(source: &str) -> Option<Vec<Segment>> {
+    let mut segments = vec![];
+
+    for part in source.split('.') {
+        let (key, mut indices) = match part.find('[') {
+            Some(bracket) => (&part[..bracket], &part[bracket..]),
+            None => (part, ""),
+        };
+
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        } else if indices.is_empty() {
+            return None;
+        }
+
+        while !indices.is_empty() {
+            let close = indices.find(']')?;
+
+            if !indices.starts_with('[') {
+                return None;
+            }
+
+            segments.push(Segment::Index(indices[1..close].parse().ok()?));
+            indices = &indices[close + 1..];
+        }
+    }
+
+    Some(segments)
+}
+
+fn get<'a>(yaml: &'a Yaml, segments: &[Segment]) -> Option<&'a Yaml> {
+    let mut current = yaml;
+
+    for segment in segments {
+        current = match (segment, current) {
+            (Segment::Key(key), Yaml::Hash(hash)) => hash.get(&Yaml::String(key.clone()))?,
+            (&Segment::Index(index), Yaml::Array(array)) => array.get(index)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn set(yaml: &mut Yaml, segments: &[Segment], value: Yaml) -> Result<(), String> {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => {
+            *yaml = value;
+
+            return Ok(());
+        }
+    };
+
+    let child = match (segment, yaml) {
+        (Segment::Key(key), &mut Yaml::Hash(ref mut hash)) => {
+            let key = Yaml::String(key.clone());
+
+            if !hash.contains_key(&key) {
+                hash.insert(key.clone(), Yaml::Hash(Default::default()));
+            }
+
+            hash.get_mut(&key).expect("Failed to insert key.")
+        }
+        (&Segment::Index(index), &mut Yaml::Array(ref mut array)) => match array.get_mut(index) {
+            Some(child) => child,
+            None => return Err(format!("Override path index {} is out of bounds.", index)),
+        },
+        _ => return Err("Override path traverses a value that is not a hash or array.".to_string()),
+    };
+
+    set(child, rest, value)
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use cel::{Context, Program, Value};
+use cel::objects::{Key, Map};
+use yaml::{Yaml, YamlLoader};
+
+/// Checks processed objects against a set of policy rules.
+///
+/// `rules` is the contents of a YAML policy file: a list of hashes, each with a `name` and a CEL
+/// `expression`, and optionally a `message`. Every expression is evaluated once for each object,
+/// which is available to the expression as the variable `object`. A rule is violated when its
+/// expression evaluates to anything other than `true`.
+///
+/// # Errors
+///
+/// Returns an error if the policy file is malformed, a rule's expression fails to compile, or
+/// listing every rule violation found in the objects.
+pub fn check_policy(rules: &str, objects: &[Yaml]) -> Result<(), String> {
+    let docs = YamlLoader::load_from_str(rules).map_err(|err| err.to_string())?;
+    let mut programs = vec![];
+
+    for doc in &docs {
+        let rules = match doc.as_vec() {
+            Some(rules) => rules,
+            None => return Err("YAML documents in policy files must be arrays.".to_string()),
+        };
+
+        for rule in rules {
+            let name = match rule["name"].as_str() {
+                Some(name) => name,
+                None => return Err("Policy rules must have a \"name\" field.".to_string()),
+            };
+            let expression = match rule["expression"].as_str() {
+                Some(expression) => expression,
+                None => return Err(format!("Policy rule {} must have an \"expression\" field.", name)),
+            };
+            let program = Program::compile(expression)
+                .map_err(|err| format!("Policy rule {} failed to compile: {}", name, err))?;
+
+            programs.push((name, rule["message"].as_str().unwrap_or(expression), program));
+        }
+    }
+
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let mut context = Context::default();
+
+        context.add_variable_from_value("object", cel_value(object));
+
+        for &(name, message, ref program) in &programs {
+            match program.execute(&context) {
+                Ok(Value::Bool(true)) => {}
+                Ok(_) => violations.push(format!("objects[{}]: {}: {}", index, name, message)),
+                Err(err) => violations.push(format!("objects[{}]: {}: {}", index, name, err)),
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Policy rules were violated:\n{}", violations.join("\n")))
+    }
+}
+
+fn cel_value(yaml: &Yaml) -> Value {
+    match *yaml {
+        Yaml::Array(ref array) => Value::List(Arc::new(array.iter().map(cel_value).collect())),
+        Yaml::Boolean(value) => Value::Bool(value),
+        Yaml::Hash(ref hash) => {
+            let mut map = HashMap::new();
+
+            for (key, value) in hash {
+                let key = match *key {
+                    Yaml::String(ref key) => Key::String(Arc::new(key.clone())),
+                    Yaml::Integer(key) => Key::Int(key),
+                    Yaml::Boolean(key) => Key::Bool(key),
+                    _ => continue,
+                };
+
+                map.insert(key, cel_value(value));
+            }
+
+            Value::Map(Map { map: Arc::new(map) })
+        }
+        Yaml::Integer(value) => Value::Int(value),
+        Yaml::Real(_) => yaml.as_f64().map(Value::Float).unwrap_or(Value::Null),
+        Yaml::String(ref value) => Value::String(Arc::new(value.clone())),
+        _ => Value::Null,
+    }
+}
@@ -1,75 +1,453 @@
+use std::collections::HashMap;
+use std::mem::take;
+
 use yaml::Yaml;
 use yaml::yaml::{Array, Hash};
-use regex::{Captures, Regex};
+use regex::Regex;
 
-use parameter::ParamMap;
+use loader::load_yaml;
+use parameter::{NullParameters, ParamMap};
 
-pub fn process_yaml(yaml: &mut Yaml, parameters: &ParamMap) -> Option<Yaml> {
-    match yaml {
-        &mut Yaml::Array(ref mut array) => process_array(array, parameters),
-        &mut Yaml::Hash(ref mut hash) => process_hash(hash, parameters),
-        &mut Yaml::String(ref mut string) => process_string(string, parameters),
-        _ => None,
+/// Interpolates parameter values into the placeholders in a YAML value, returning a replacement
+/// for the value if it's a string that must change type.
+///
+/// # Errors
+///
+/// Returns an error if a parameter's value can't be converted to the type a typed literal
+/// placeholder, such as `$((MODE as int))`, asks for.
+pub fn process_yaml(yaml: &mut Yaml, parameters: &ParamMap) -> Result<Option<Yaml>, String> {
+    match *yaml {
+        Yaml::Array(ref mut array) => process_array(array, parameters),
+        Yaml::Hash(ref mut hash) => process_hash(hash, parameters),
+        Yaml::String(ref mut string) => process_string(string, parameters),
+        _ => Ok(None),
     }
 }
 
-fn process_array(array: &mut Array, parameters: &ParamMap) -> Option<Yaml> {
-    for value in array {
-        match process_yaml(value, parameters) {
-            Some(new_value) => *value = new_value,
-            _ => {},
+fn process_array(array: &mut Array, parameters: &ParamMap) -> Result<Option<Yaml>, String> {
+    let mut items = Vec::with_capacity(array.len());
+
+    for mut value in array.drain(..) {
+        if let Some(spliced) = splice(&value, parameters)? {
+            items.extend(spliced);
+
+            continue;
         }
+
+        if let Some(new_value) = process_yaml(&mut value, parameters)? {
+            value = new_value;
+        }
+
+        items.push(value);
+    }
+
+    *array = items;
+
+    Ok(None)
+}
+
+/// Returns the items to splice into a sequence in place of an item `$((NAME...))`, parsed from
+/// the value of the parameter as a YAML sequence, e.g. extra environment variables. Returns
+/// `None` if the item isn't a splice of a declared parameter, which leaves it as it is.
+fn splice(value: &Yaml, parameters: &ParamMap) -> Result<Option<Array>, String> {
+    lazy_static! {
+        static ref SPLICE: Regex =
+            Regex::new(r"^\$\(\(\s*([^\s()]+)\.\.\.\s*\)\)$").expect("Failed to compile regex.");
     }
 
-    None
+    let string = match *value {
+        Yaml::String(ref string) => string,
+        _ => return Ok(None),
+    };
+    let name = match SPLICE.captures(string) {
+        Some(captures) => captures.get(1).expect("Failed to extract regex capture group.").as_str(),
+        None => return Ok(None),
+    };
+    let value = match parameters.get(name).map(|parameter| &parameter.value) {
+        Some(Some(value)) => value,
+        Some(None) => match parameters.null_parameters() {
+            NullParameters::Error => {
+                return Err(unresolvable_message(string, Unresolvable::NoValue(name.to_string())));
+            }
+            NullParameters::Keep => return Ok(None),
+            NullParameters::Empty | NullParameters::Null => return Ok(Some(vec![])),
+        },
+        None => return Ok(None),
+    };
+    let (resolved, _) = resolve(&tokenize(value), parameters, 1).map_err(|err| unresolvable_message(string, err))?;
+    let not_sequence = |details: String| {
+        format!("Parameter {} must be a YAML sequence to be spliced with {}{}.", name, string, details)
+    };
+
+    match load_yaml(&resolved).map_err(|err| not_sequence(format!(": {}", err)))?.into_iter().next() {
+        Some(Yaml::Array(items)) => Ok(Some(items)),
+        Some(Yaml::Null) | None => Ok(Some(vec![])),
+        Some(_) => Err(not_sequence(String::new())),
+    }
 }
 
-fn process_hash(hash: &mut Hash, parameters: &ParamMap) -> Option<Yaml> {
-    for (_, value) in hash {
-        match process_yaml(value, parameters) {
-            Some(new_value) => *value = new_value,
-            _ => {},
+fn process_hash(hash: &mut Hash, parameters: &ParamMap) -> Result<Option<Yaml>, String> {
+    for value in hash.values_mut() {
+        if let Some(new_value) = process_yaml(value, parameters)? {
+            *value = new_value;
         }
     }
 
-    None
+    Ok(None)
 }
 
-fn process_string(string: &mut String, parameters: &ParamMap) -> Option<Yaml> {
-    lazy_static! {
-        static ref LITERAL_INTERPOLATION: Regex = Regex::new(
-            r"\$\({2}([^\)]*)\){2}"
-        ).expect("Failed to compile regex.");
+/// The maximum depth of placeholders within placeholders, counting placeholders in the values
+/// they resolve to, which bounds both nesting, e.g. `$(SERVICE_$(TIER)_NAME)`, and chains of
+/// values that refer to other parameters.
+const MAX_RESOLUTION_DEPTH: usize = 10;
+
+/// The maximum depth of placeholders within placeholders that are tokenized; deeper ones are
+/// text. This only guards against exhausting the stack, as resolution fails at a lower depth.
+const MAX_TOKENIZATION_DEPTH: usize = 100;
+
+/// Why placeholders couldn't be resolved.
+enum Unresolvable {
+    /// Placeholders are nested more than `MAX_RESOLUTION_DEPTH` deep.
+    TooDeep,
+    /// The named parameter has no value, and that's an error.
+    NoValue(String),
+}
+
+lazy_static! {
+    static ref TYPED_NAME: Regex = Regex::new(
+        r"^\s*(\S+)\s+as\s+(int|str|bool|float)\s*$"
+    ).expect("Failed to compile regex.");
+}
+
+lazy_static! {
+    static ref INT: Regex = Regex::new(
+        r"^[-+]?(0[xX][0-9a-fA-F]+|0[oO][0-7]+|[0-9]+)$"
+    ).expect("Failed to compile regex.");
+}
+
+/// Interpolates parameter values into the placeholders in a string as text, without parsing the
+/// result as YAML, e.g. to interpolate a parameter's default value.
+///
+/// # Errors
+///
+/// Returns an error if a placeholder names a parameter without a value and the `ParamMap`'s
+/// `NullParameters` is `Error`, or placeholders are nested too deeply.
+pub fn interpolate_string(string: &str, parameters: &ParamMap) -> Result<String, String> {
+    resolve(&tokenize(string), parameters, 0)
+        .map(|(resolved, _)| resolved)
+        .map_err(|err| unresolvable_message(string, err))
+}
+
+/// Collects the names of any placeholders remaining in a processed YAML value.
+pub fn unresolved_placeholders(yaml: &Yaml, names: &mut Vec<String>) {
+    match *yaml {
+        Yaml::Array(ref array) => {
+            for value in array {
+                unresolved_placeholders(value, names);
+            }
+        }
+        Yaml::Hash(ref hash) => {
+            for value in hash.values() {
+                unresolved_placeholders(value, names);
+            }
+        }
+        Yaml::String(ref string) => names.extend(placeholder_names(string)),
+        _ => {}
     }
+}
 
-    lazy_static! {
-        static ref STRING_INTERPOLATION: Regex = Regex::new(
-            r"\$\(([^\)]*)\)"
-        ).expect("Failed to compile regex.");
+/// Returns the names of the parameters referred to by placeholders in a string, in order.
+///
+/// Only the innermost placeholders of nested ones are included, e.g. `TIER` for
+/// `$(SERVICE_$(TIER)_NAME)`, as the outer names aren't known until those are resolved.
+pub fn placeholder_names(string: &str) -> Vec<String> {
+    fn collect(tokens: &[Token], names: &mut Vec<String>) {
+        for token in tokens {
+            if let Token::Placeholder { ref contents, .. } = *token {
+                match contents.as_slice() {
+                    [Token::Text(ref name)] => names.push(split_type(name.trim_end_matches("...")).0.to_string()),
+                    [] => names.push(String::new()),
+                    _ => collect(contents, names),
+                }
+            }
+        }
+    }
+
+    let mut names = vec![];
+
+    collect(&tokenize(string), &mut names);
+
+    names
+}
+
+/// A piece of a string to be interpolated.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Text that's copied as is.
+    Text(String),
+    /// A placeholder, `$(...)`, or `$((...))` if it's `literal`. Its contents are usually a
+    /// parameter name, but may contain nested placeholders.
+    Placeholder { literal: bool, contents: Vec<Token> },
+}
+
+/// Splits a string into text and placeholders.
+///
+/// A placeholder's contents run to its matching closing parenthesis, so they may contain
+/// balanced parentheses and nested placeholders. `$$(` is an escape for a literal `$(`, which
+/// is not a placeholder. A `$(` without a matching closing parenthesis is text.
+fn tokenize(string: &str) -> Vec<Token> {
+    Tokenizer { depth: 0, input: string, placeholders: HashMap::new() }.tokens(0, None).0
+}
+
+struct Tokenizer<'a> {
+    /// The number of placeholders being tokenized.
+    depth: usize,
+    input: &'a str,
+    /// The placeholder starting at each position that has been tokenized, with the position
+    /// after it, or `None` if it's unterminated. This keeps tokenizing linear in the number of
+    /// `$(`, however they're nested.
+    placeholders: HashMap<usize, Option<(Token, usize)>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Tokenizes from `start` until `closing` at the top level, or the end of the input if
+    /// `closing` is `None`. Returns the tokens and the position after `closing`, or `None` if
+    /// `closing` wasn't found.
+    fn tokens(&mut self, start: usize, closing: Option<&str>) -> (Vec<Token>, Option<usize>) {
+        let mut tokens = vec![];
+        let mut text = String::new();
+        let mut depth = 0;
+        let mut position = start;
+
+        while position < self.input.len() {
+            let rest = &self.input[position..];
+
+            if rest.starts_with("$$(") {
+                text.push_str("$(");
+                position += 3;
+
+                continue;
+            }
+
+            if rest.starts_with("$(") {
+                if let Some((placeholder, end)) = self.placeholder(position) {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(take(&mut text)));
+                    }
+
+                    tokens.push(placeholder);
+                    position = end;
+
+                    continue;
+                }
+            }
+
+            if let Some(closing) = closing {
+                if depth == 0 && rest.starts_with(closing) {
+                    if !text.is_empty() {
+                        tokens.push(Token::Text(text));
+                    }
+
+                    return (tokens, Some(position + closing.len()));
+                }
+            }
+
+            let character = rest.chars().next().expect("Position was not before the end of the input.");
+
+            match character {
+                '(' => depth += 1,
+                ')' if depth > 0 => depth -= 1,
+                _ => {}
+            }
+
+            text.push(character);
+            position += character.len_utf8();
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+
+        (tokens, if closing.is_none() { Some(position) } else { None })
     }
 
-    let interpolate = |captures: &Captures| -> String {
-        let key = captures.get(1).expect("Failed to extract regex capture group.");
+    /// Tokenizes the placeholder starting at `start`, returning it and the position after it.
+    fn placeholder(&mut self, start: usize) -> Option<(Token, usize)> {
+        if let Some(placeholder) = self.placeholders.get(&start) {
+            return placeholder.clone();
+        }
 
-        match parameters.get(key.as_str()) {
-            Some(parameter) => parameter.value.clone().unwrap_or("~".to_owned()),
-            None => captures.get(0).expect("Failed to extract regex match.").as_str().to_owned(),
+        if self.depth == MAX_TOKENIZATION_DEPTH {
+            return None;
         }
+
+        let literal = self.input[start..].starts_with("$((");
+        let (open, closing) = if literal { (3, "))") } else { (2, ")") };
+
+        self.depth += 1;
+
+        let placeholder = match self.tokens(start + open, Some(closing)) {
+            (contents, Some(end)) => Some((Token::Placeholder { literal, contents }, end)),
+            (_, None) => None,
+        };
+
+        self.depth -= 1;
+
+        self.placeholders.insert(start, placeholder.clone());
+
+        placeholder
+    }
+}
+
+/// Splits a placeholder's contents, e.g. `MODE as int`, into the parameter name and the type, if
+/// any.
+fn split_type(contents: &str) -> (&str, Option<&str>) {
+    match TYPED_NAME.captures(contents) {
+        Some(captures) => (
+            captures.get(1).expect("Failed to extract regex capture group.").as_str(),
+            Some(captures.get(2).expect("Failed to extract regex capture group.").as_str()),
+        ),
+        None => (contents, None),
+    }
+}
+
+/// Converts a parameter's value to the scalar of the given type.
+///
+/// Integers keep their representation, so `0644` is emitted as written and read by Kubernetes as
+/// an octal mode.
+fn typed_value(name: &str, value: Option<&String>, scalar_type: &str) -> Result<Yaml, String> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Yaml::Null),
     };
+    let invalid = || {
+        let description = match scalar_type {
+            "int" => "an integer",
+            "bool" => "a boolean",
+            _ => "a number",
+        };
+
+        format!("Parameter {} must be {} for $(({} as {})), not \"{}\".", name, description, name, scalar_type, value)
+    };
+
+    match scalar_type {
+        "str" => Ok(Yaml::String(value.clone())),
+        "int" if INT.is_match(value) => match value.parse() {
+            Ok(integer) if value == &format!("{}", integer) => Ok(Yaml::Integer(integer)),
+            _ => Ok(Yaml::Real(value.clone())),
+        },
+        "bool" => value.parse().map(Yaml::Boolean).map_err(|_| invalid()),
+        "float" => value.parse::<f64>().map(|_| Yaml::Real(value.clone())).map_err(|_| invalid()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Resolves tokens to a string, returning it and whether any placeholder was resolved.
+///
+/// Placeholders for undeclared parameters are left in place, with any nested placeholders
+/// resolved. Placeholders in the values parameters resolve to are resolved in turn, unless the
+/// `ParamMap` says the value is literal, i.e. supplied by the user, and
+/// placeholders for parameters without a value are resolved as the `ParamMap`'s
+/// `NullParameters` says.
+fn resolve(tokens: &[Token], parameters: &ParamMap, depth: usize) -> Result<(String, bool), Unresolvable> {
+    if depth > MAX_RESOLUTION_DEPTH {
+        return Err(Unresolvable::TooDeep);
+    }
 
-    let replacement = LITERAL_INTERPOLATION.replace_all(string, &interpolate);
+    let mut resolved = String::new();
+    let mut changed = false;
 
-    let contains_literal_replacement = &replacement != string;
+    for token in tokens {
+        match *token {
+            Token::Text(ref text) => resolved.push_str(text),
+            Token::Placeholder { literal, ref contents } => {
+                let (contents, contents_changed) = resolve(contents, parameters, depth + 1)?;
+                let name = split_type(&contents).0;
+                let value = match parameters.get(name) {
+                    Some(parameter) => match (parameter.value.as_ref(), parameters.null_parameters()) {
+                        (Some(value), _) => Some(value.as_str()),
+                        (None, NullParameters::Error) => return Err(Unresolvable::NoValue(parameter.name.clone())),
+                        (None, NullParameters::Empty) => Some(""),
+                        (None, NullParameters::Keep) => None,
+                        (None, NullParameters::Null) => Some("~"),
+                    },
+                    None => None,
+                };
 
-    let final_replacement = STRING_INTERPOLATION.replace_all(&replacement, &interpolate);
+                match value {
+                    Some(value) if parameters.is_literal(name) => {
+                        resolved.push_str(value);
+                        changed = true;
+                    }
+                    Some(value) => {
+                        resolved.push_str(&resolve(&tokenize(value), parameters, depth + 1)?.0);
+                        changed = true;
+                    }
+                    None => {
+                        resolved.push_str(if literal { "$((" } else { "$(" });
+                        resolved.push_str(&contents);
+                        resolved.push_str(if literal { "))" } else { ")" });
+                        changed |= contents_changed;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((resolved, changed))
+}
+
+/// Describes why the placeholders in a string couldn't be resolved.
+fn unresolvable_message(string: &str, err: Unresolvable) -> String {
+    match err {
+        Unresolvable::TooDeep => format!(
+            "Placeholders in {:?} are nested more than {} levels deep, or a parameter's value refers to itself.",
+            string,
+            MAX_RESOLUTION_DEPTH,
+        ),
+        Unresolvable::NoValue(name) => format!(
+            "Parameter {} has no value; give it one, or use --null-parameters.",
+            name,
+        ),
+    }
+}
+
+fn process_string(string: &mut String, parameters: &ParamMap) -> Result<Option<Yaml>, String> {
+    let unresolvable = |err| unresolvable_message(string, err);
+    let tokens = tokenize(string);
+
+    if let [Token::Placeholder { literal: true, ref contents }] = tokens.as_slice() {
+        let (contents, _) = resolve(contents, parameters, 1).map_err(unresolvable)?;
+
+        if let (name, Some(scalar_type)) = split_type(&contents) {
+            if let Some(parameter) = parameters.get(name) {
+                let value = match parameter.value {
+                    Some(ref value) if parameters.is_literal(name) => Some(value.clone()),
+                    Some(ref value) => Some(resolve(&tokenize(value), parameters, 1).map_err(unresolvable)?.0),
+                    None => match parameters.null_parameters() {
+                        NullParameters::Error => return Err(unresolvable(Unresolvable::NoValue(name.to_string()))),
+                        NullParameters::Empty => Some(String::new()),
+                        NullParameters::Keep => return Ok(None),
+                        NullParameters::Null => None,
+                    },
+                };
+
+                return typed_value(name, value.as_ref(), scalar_type).map(Some);
+            }
+        }
+    }
 
-    let contains_string_replacement = &final_replacement != &replacement;
+    let (resolved, changed) = resolve(&tokens, parameters, 0).map_err(unresolvable)?;
+    let only_literal_placeholders = tokens.iter().all(|token| match *token {
+        Token::Placeholder { literal, .. } => literal,
+        Token::Text(_) => true,
+    });
 
-    if !contains_literal_replacement && !contains_string_replacement {
-        None
-    } else if contains_literal_replacement && !contains_string_replacement {
-        Some(Yaml::from_str(&final_replacement))
+    if resolved == *string {
+        Ok(None)
+    } else if changed && only_literal_placeholders {
+        Ok(Some(Yaml::from_str(&resolved)))
     } else {
-        Some(Yaml::String(final_replacement.to_string()))
+        Ok(Some(Yaml::String(resolved)))
     }
 }
@@ -0,0 +1,141 @@
+use std::io::{IsTerminal, Stderr, Write, stderr};
+use std::str::FromStr;
+
+/// How the progress of a bulk operation is shown.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProgressStyle {
+    /// A status line that is rewritten as each item finishes, for terminals. Failures are kept
+    /// on lines of their own.
+    Indicator,
+    /// A log line for each item, for CI and other non-interactive environments.
+    Plain,
+}
+
+impl ProgressStyle {
+    /// Returns `Indicator` if stderr is a terminal, and `Plain` otherwise.
+    pub fn detect() -> Self {
+        if stderr().is_terminal() {
+            ProgressStyle::Indicator
+        } else {
+            ProgressStyle::Plain
+        }
+    }
+}
+
+impl FromStr for ProgressStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ProgressStyle::detect()),
+            "indicator" => Ok(ProgressStyle::Indicator),
+            "plain" => Ok(ProgressStyle::Plain),
+            _ => Err("Progress style must be auto, indicator, or plain.".to_owned()),
+        }
+    }
+}
+
+/// What happened to one item of a bulk operation.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Outcome {
+    /// The item was rendered, or applied.
+    Done,
+    /// The item was left alone, e.g. because it was unchanged or a template it depends on failed.
+    Skipped(String),
+    /// The item failed, with the error.
+    Failed(String),
+}
+
+/// Reports the status of each item of a bulk operation, such as the objects of an apply or the
+/// templates of a release, followed by a summary of how many were done, skipped, and failed.
+#[derive(Debug)]
+pub struct Progress<W: Write = Stderr> {
+    action: &'static str,
+    done: usize,
+    failed: usize,
+    skipped: usize,
+    style: ProgressStyle,
+    total: usize,
+    writer: W,
+}
+
+impl Progress<Stderr> {
+    /// Creates a reporter writing to stderr for `total` items. `action` is the past tense of what
+    /// is done to each item, e.g. "rendered" or "applied".
+    pub fn new(action: &'static str, total: usize, style: ProgressStyle) -> Self {
+        Progress::with_writer(action, total, style, stderr())
+    }
+}
+
+impl<W: Write> Progress<W> {
+    /// Creates a reporter writing to the given writer; see `new`.
+    pub fn with_writer(action: &'static str, total: usize, style: ProgressStyle, writer: W) -> Self {
+        Progress {
+            action,
+            done: 0,
+            failed: 0,
+            skipped: 0,
+            style,
+            total,
+            writer,
+        }
+    }
+
+    /// Reports what happened to an item.
+    pub fn report(&mut self, item: &str, outcome: &Outcome) {
+        let status = match *outcome {
+            Outcome::Done => {
+                self.done += 1;
+
+                self.action.to_string()
+            }
+            Outcome::Skipped(ref reason) => {
+                self.skipped += 1;
+
+                format!("skipped ({})", reason)
+            }
+            Outcome::Failed(ref err) => {
+                self.failed += 1;
+
+                format!("failed: {}", err)
+            }
+        };
+        let finished = self.done + self.skipped + self.failed;
+        let line = format!("[{}/{}] {} {}", finished, self.total, item, status);
+
+        // Reporting progress must never fail the operation itself.
+        let _ = match self.style {
+            ProgressStyle::Indicator if matches!(*outcome, Outcome::Failed(_)) => {
+                writeln!(self.writer, "\r\x1b[K{}", line)
+            }
+            ProgressStyle::Indicator => write!(self.writer, "\r\x1b[K{}", line),
+            ProgressStyle::Plain => writeln!(self.writer, "{}", line),
+        };
+        let _ = self.writer.flush();
+    }
+
+    /// Returns whether any item failed.
+    pub fn has_failures(&self) -> bool {
+        self.failed > 0
+    }
+
+    /// Returns the summary of the items reported so far, e.g. "3 rendered, 1 skipped, 0 failed".
+    pub fn summary(&self) -> String {
+        format!("{} {}, {} skipped, {} failed", self.done, self.action, self.skipped, self.failed)
+    }
+
+    /// Writes the summary, replacing the status line of the indicator.
+    pub fn finish(&mut self) {
+        let summary = self.summary();
+        let _ = match self.style {
+            ProgressStyle::Indicator => writeln!(self.writer, "\r\x1b[K{}", summary),
+            ProgressStyle::Plain => writeln!(self.writer, "{}", summary),
+        };
+        let _ = self.writer.flush();
+    }
+
+    /// Returns the writer, e.g. to inspect what was written to a buffer.
+    pub fn into_writer(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,219 @@
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+use loader::load_yaml;
+use parameter::ParamMap;
+use processor::process_yaml;
+
+/// The API group of Roles and RoleBindings.
+const RBAC_API_GROUP: &str = "rbac.authorization.k8s.io";
+
+/// A ServiceAccount for a workload, and the Role and RoleBinding granting it access to the
+/// resources in its namespace that it needs.
+#[derive(Clone, Debug)]
+pub struct Rbac {
+    name: String,
+    spec: Yaml,
+}
+
+impl Rbac {
+    /// Loads the spec for a ServiceAccount with the given name from the contents of an RBAC spec
+    /// file, a hash with `rules` and optionally a `namespace`, `automountToken`, and `token`:
+    ///
+    /// ```yaml
+    /// namespace: "$(NAMESPACE)"
+    /// automountToken: false
+    /// token: true
+    /// rules:
+    ///   - "get,list,watch configmaps,secrets"
+    ///   - "get,list deployments.apps"
+    ///   - apiGroups: ["batch"]
+    ///     resources: ["jobs"]
+    ///     verbs: ["create"]
+    /// ```
+    ///
+    /// A rule is either a Role's policy rule, or a string of comma-separated verbs and
+    /// comma-separated resources, each qualified by its API group unless it's in the core group.
+    /// `automountToken: false` stops the ServiceAccount's token being mounted into pods by
+    /// default, and `token: true` adds a long-lived token Secret for the ServiceAccount, named
+    /// after it with a `-token` suffix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents aren't a single YAML document of this form.
+    pub fn from_str(name: &str, contents: &str) -> Result<Self, String> {
+        let mut docs = load_yaml(contents).map_err(|err| err.to_string())?;
+
+        if docs.len() != 1 || docs[0].as_hash().is_none() {
+            return Err("RBAC specs must contain exactly one YAML document, which must be a hash.".to_string());
+        }
+
+        let spec = docs.remove(0);
+
+        match spec["rules"] {
+            Yaml::Array(ref rules) if !rules.is_empty() => {}
+            _ => return Err("Key \"rules\" must be present and must be a non-empty array.".to_string()),
+        }
+
+        match spec["namespace"] {
+            Yaml::BadValue | Yaml::String(_) => {}
+            _ => return Err("Key \"namespace\" must be a string.".to_string()),
+        }
+
+        for key in &["automountToken", "token"] {
+            match spec[*key] {
+                Yaml::BadValue | Yaml::Boolean(_) => {}
+                _ => return Err(format!("Key \"{}\" must be true or false.", key)),
+            }
+        }
+
+        Ok(Rbac {
+            name: name.to_string(),
+            spec,
+        })
+    }
+
+    /// Returns the ServiceAccount, Role, and RoleBinding, and the token Secret if the spec asks
+    /// for one, after interpolating the spec with the template's parameters. The objects are in
+    /// the spec's namespace, or the default namespace if it doesn't have one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the spec can't be interpolated, or a rule isn't a hash or a string of
+    /// verbs and resources.
+    pub fn objects(&self, default_namespace: &str, parameters: &ParamMap) -> Result<Vec<Yaml>, String> {
+        let mut spec = self.spec.clone();
+
+        if let Some(processed) = process_yaml(&mut spec, parameters)? {
+            spec = processed;
+        }
+
+        let namespace = spec["namespace"].as_str().unwrap_or(default_namespace).to_string();
+        let mut rules = vec![];
+
+        for (index, rule) in spec["rules"].as_vec().cloned().unwrap_or_default().into_iter().enumerate() {
+            match rule {
+                Yaml::Hash(_) => rules.push(rule),
+                Yaml::String(ref rule) => {
+                    rules.extend(shorthand_rules(rule).map_err(|err| format!("rules[{}]: {}", index, err))?)
+                }
+                _ => return Err(format!("rules[{}]: Rules must be hashes or strings of verbs and resources.", index)),
+            }
+        }
+
+        let mut service_account = object("v1", "ServiceAccount", metadata(&self.name, &namespace));
+
+        if let Some(automount) = spec["automountToken"].as_bool() {
+            insert(&mut service_account, "automountServiceAccountToken", Yaml::Boolean(automount));
+        }
+
+        let mut role = object(&format!("{}/v1", RBAC_API_GROUP), "Role", metadata(&self.name, &namespace));
+
+        insert(&mut role, "rules", Yaml::Array(rules));
+
+        let mut role_binding =
+            object(&format!("{}/v1", RBAC_API_GROUP), "RoleBinding", metadata(&self.name, &namespace));
+        let mut role_ref = Hash::new();
+        let mut subject = Hash::new();
+
+        role_ref.insert(ystring("apiGroup"), ystring(RBAC_API_GROUP));
+        role_ref.insert(ystring("kind"), ystring("Role"));
+        role_ref.insert(ystring("name"), ystring(&self.name));
+        subject.insert(ystring("kind"), ystring("ServiceAccount"));
+        subject.insert(ystring("name"), ystring(&self.name));
+        subject.insert(ystring("namespace"), ystring(&namespace));
+        insert(&mut role_binding, "roleRef", Yaml::Hash(role_ref));
+        insert(&mut role_binding, "subjects", Yaml::Array(vec![Yaml::Hash(subject)]));
+
+        let mut objects = vec![service_account, role, role_binding];
+
+        if spec["token"].as_bool() == Some(true) {
+            let mut token_metadata = metadata(&format!("{}-token", self.name), &namespace);
+            let mut annotations = Hash::new();
+
+            annotations.insert(ystring("kubernetes.io/service-account.name"), ystring(&self.name));
+            token_metadata.insert(ystring("annotations"), Yaml::Hash(annotations));
+
+            let mut token = object("v1", "Secret", token_metadata);
+
+            insert(&mut token, "type", ystring("kubernetes.io/service-account-token"));
+            objects.push(token);
+        }
+
+        Ok(objects)
+    }
+}
+
+/// Parses a rule like "get,list deployments.apps,pods" into a policy rule for each API group its
+/// resources are in, in the order they're first given.
+fn shorthand_rules(rule: &str) -> Result<Vec<Yaml>, String> {
+    let parts: Vec<&str> = rule.split_whitespace().collect();
+
+    if parts.len() != 2 {
+        return Err(format!(
+            "Rule \"{}\" must be comma-separated verbs and comma-separated resources, e.g. \"get,list pods\".",
+            rule,
+        ));
+    }
+
+    let verbs: Vec<Yaml> = parts[0].split(',').filter(|verb| !verb.is_empty()).map(ystring).collect();
+    let mut groups: Vec<(String, Vec<Yaml>)> = vec![];
+
+    for resource in parts[1].split(',').filter(|resource| !resource.is_empty()) {
+        let (resource, group) = match resource.find('.') {
+            Some(index) => (&resource[..index], &resource[index + 1..]),
+            None => (resource, ""),
+        };
+
+        match groups.iter_mut().find(|(name, _)| name == group) {
+            Some(&mut (_, ref mut resources)) => resources.push(ystring(resource)),
+            None => groups.push((group.to_string(), vec![ystring(resource)])),
+        }
+    }
+
+    if verbs.is_empty() || groups.is_empty() {
+        return Err(format!("Rule \"{}\" must have at least one verb and one resource.", rule));
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(group, resources)| {
+            let mut policy_rule = Hash::new();
+
+            policy_rule.insert(ystring("apiGroups"), Yaml::Array(vec![Yaml::String(group)]));
+            policy_rule.insert(ystring("resources"), Yaml::Array(resources));
+            policy_rule.insert(ystring("verbs"), Yaml::Array(verbs.clone()));
+
+            Yaml::Hash(policy_rule)
+        })
+        .collect())
+}
+
+fn metadata(name: &str, namespace: &str) -> Hash {
+    let mut metadata = Hash::new();
+
+    metadata.insert(ystring("name"), ystring(name));
+    metadata.insert(ystring("namespace"), ystring(namespace));
+
+    metadata
+}
+
+fn object(api_version: &str, kind: &str, metadata: Hash) -> Yaml {
+    let mut object = Hash::new();
+
+    object.insert(ystring("apiVersion"), ystring(api_version));
+    object.insert(ystring("kind"), ystring(kind));
+    object.insert(ystring("metadata"), Yaml::Hash(metadata));
+
+    Yaml::Hash(object)
+}
+
+fn insert(object: &mut Yaml, key: &str, value: Yaml) {
+    if let Yaml::Hash(ref mut hash) = *object {
+        hash.insert(ystring(key), value);
+    }
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
@@ -0,0 +1,43 @@
+use yaml::Yaml;
+
+const REDACTED: &str = "<redacted>";
+
+/// Replaces the data values of Secret objects, and any occurrence of the given sensitive values
+/// in other string values, with a placeholder.
+pub fn redact(objects: &mut [Yaml], sensitive_values: &[&str]) {
+    for object in objects.iter_mut() {
+        let is_secret = object["kind"].as_str() == Some("Secret");
+
+        if let Yaml::Hash(ref mut hash) = *object {
+            for (key, value) in hash.iter_mut() {
+                match key.as_str() {
+                    Some("data") | Some("stringData") if is_secret => {
+                        if let Yaml::Hash(ref mut data) = *value {
+                            for data_value in data.values_mut() {
+                                *data_value = Yaml::String(REDACTED.to_string());
+                            }
+                        }
+                    }
+                    _ => redact_values(value, sensitive_values),
+                }
+            }
+        }
+    }
+}
+
+fn redact_values(yaml: &mut Yaml, sensitive_values: &[&str]) {
+    match *yaml {
+        Yaml::Array(ref mut array) => for value in array {
+            redact_values(value, sensitive_values);
+        },
+        Yaml::Hash(ref mut hash) => for value in hash.values_mut() {
+            redact_values(value, sensitive_values);
+        },
+        Yaml::String(ref mut string) => for sensitive_value in sensitive_values {
+            if string.contains(sensitive_value) {
+                *string = string.replace(sensitive_value, REDACTED);
+            }
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,117 @@
+use yaml::Yaml;
+
+/// Kinds of objects whose pods are selected by a `Service`'s selector.
+const WORKLOAD_KINDS: [&str; 7] = [
+    "DaemonSet",
+    "Deployment",
+    "Job",
+    "Pod",
+    "ReplicaSet",
+    "ReplicationController",
+    "StatefulSet",
+];
+
+/// A reference from one object to another by name.
+#[derive(Debug)]
+pub struct Reference {
+    /// The kind of the object referred to.
+    pub kind: &'static str,
+    /// The name of the object referred to.
+    pub name: String,
+    /// How the object is referred to, e.g. `envFrom` or `volume`.
+    pub via: &'static str,
+    /// Whether the reference is marked `optional`, so the object needn't exist.
+    pub optional: bool,
+}
+
+/// Returns the objects an object refers to by name: the `ConfigMap`s, `Secret`s,
+/// `PersistentVolumeClaim`s, and `ServiceAccount` its pods use, an `Ingress`'s backend
+/// `Service`s, and a `HorizontalPodAutoscaler`'s target.
+pub fn references(object: &Yaml) -> Vec<Reference> {
+    let mut references = vec![];
+    let mut add = |kind: &'static str, node: &Yaml, key: &str, via: &'static str| {
+        if let Some(name) = node[key].as_str() {
+            references.push(Reference {
+                kind,
+                name: name.to_string(),
+                via,
+                optional: node["optional"].as_bool() == Some(true),
+            });
+        }
+    };
+
+    match object["kind"].as_str().unwrap_or_default() {
+        "HorizontalPodAutoscaler" => {
+            let target = &object["spec"]["scaleTargetRef"];
+
+            if let Some(kind) = WORKLOAD_KINDS.iter().find(|kind| target["kind"].as_str() == Some(**kind)) {
+                add(kind, target, "name", "scaleTarget");
+            }
+        }
+        "Ingress" => {
+            add("Service", &object["spec"]["defaultBackend"]["service"], "name", "backend");
+
+            for rule in object["spec"]["rules"].as_vec().unwrap_or(&vec![]) {
+                for path in rule["http"]["paths"].as_vec().unwrap_or(&vec![]) {
+                    add("Service", &path["backend"]["service"], "name", "backend");
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let pod_spec = pod_spec(object);
+
+    add("ServiceAccount", pod_spec, "serviceAccountName", "serviceAccount");
+
+    for volume in pod_spec["volumes"].as_vec().unwrap_or(&vec![]) {
+        add("ConfigMap", &volume["configMap"], "name", "volume");
+        add("Secret", &volume["secret"], "secretName", "volume");
+        add("PersistentVolumeClaim", &volume["persistentVolumeClaim"], "claimName", "volume");
+
+        for source in volume["projected"]["sources"].as_vec().unwrap_or(&vec![]) {
+            add("ConfigMap", &source["configMap"], "name", "volume");
+            add("Secret", &source["secret"], "name", "volume");
+        }
+    }
+
+    for containers in &["initContainers", "containers"] {
+        for container in pod_spec[*containers].as_vec().unwrap_or(&vec![]) {
+            for source in container["envFrom"].as_vec().unwrap_or(&vec![]) {
+                add("ConfigMap", &source["configMapRef"], "name", "envFrom");
+                add("Secret", &source["secretRef"], "name", "envFrom");
+            }
+
+            for variable in container["env"].as_vec().unwrap_or(&vec![]) {
+                add("ConfigMap", &variable["valueFrom"]["configMapKeyRef"], "name", "env");
+                add("Secret", &variable["valueFrom"]["secretKeyRef"], "name", "env");
+            }
+        }
+    }
+
+    references
+}
+
+/// Returns whether a `Service`'s selector matches the pods of a workload.
+pub fn selects(service: &Yaml, workload: &Yaml) -> bool {
+    let selector = match service["spec"]["selector"].as_hash() {
+        Some(selector) if !selector.is_empty() => selector,
+        _ => return false,
+    };
+    let labels = match workload["kind"].as_str().unwrap_or_default() {
+        "Pod" => &workload["metadata"]["labels"],
+        kind if WORKLOAD_KINDS.contains(&kind) => &workload["spec"]["template"]["metadata"]["labels"],
+        _ => return false,
+    };
+
+    selector.iter().all(|(key, value)| key.as_str().is_some_and(|key| labels[key] == *value))
+}
+
+/// Returns the spec of the pods an object creates, or of the object itself if it's a pod.
+pub fn pod_spec(object: &Yaml) -> &Yaml {
+    match object["kind"].as_str().unwrap_or_default() {
+        "Pod" => &object["spec"],
+        "CronJob" => &object["spec"]["jobTemplate"]["spec"]["template"]["spec"],
+        _ => &object["spec"]["template"]["spec"],
+    }
+}
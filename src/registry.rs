@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs::{metadata, read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{JoinHandle, sleep, spawn};
+use std::time::{Duration, SystemTime};
+
+use catalog::template_paths;
+use compiled::CompiledTemplate;
+use loader::decode_text;
+
+/// The parsed templates in a directory tree, by their `metadata.name`, which can be shared between
+/// threads rendering concurrently and kept up to date as the files change.
+///
+/// Templates are found like `catalog`: files with a `.yml` or `.yaml` extension holding a single
+/// document of kind `Template`. Files that can't be parsed or have no `metadata.name` are skipped
+/// with a warning, or keep the version loaded before they changed, and if two templates have the
+/// same name, the first by path is used.
+///
+/// Renderers get a template with `get` and create a `Template` from a copy of it with
+/// `Template::from_compiled`, which is much faster than parsing it again.
+#[derive(Debug)]
+pub struct TemplateRegistry {
+    directory: PathBuf,
+    files: Mutex<HashMap<PathBuf, RegisteredFile>>,
+    templates: RwLock<HashMap<String, Arc<CompiledTemplate>>>,
+}
+
+/// What was loaded from a file, and when it was last modified.
+#[derive(Debug)]
+struct RegisteredFile {
+    length: u64,
+    modified: Option<SystemTime>,
+    template: Option<(String, Arc<CompiledTemplate>)>,
+}
+
+impl TemplateRegistry {
+    /// Loads the templates in a directory tree.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory can't be read.
+    pub fn load<P: AsRef<Path>>(directory: P) -> Result<Self, String> {
+        let registry = TemplateRegistry {
+            directory: directory.as_ref().to_path_buf(),
+            files: Mutex::new(HashMap::new()),
+            templates: RwLock::new(HashMap::new()),
+        };
+
+        registry.refresh()?;
+
+        Ok(registry)
+    }
+
+    /// Returns the template with the given name, if there is one. The template stays valid after
+    /// its file changes; later calls return the new version.
+    pub fn get(&self, name: &str) -> Option<Arc<CompiledTemplate>> {
+        self.templates.read().expect("template registry lock was poisoned").get(name).cloned()
+    }
+
+    /// Returns the names of the templates, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> =
+            self.templates.read().expect("template registry lock was poisoned").keys().cloned().collect();
+
+        names.sort();
+
+        names
+    }
+
+    /// Scans the directory tree again, parsing new files and those whose size or modification
+    /// time changed, and forgetting files that were removed. Returns whether any template was
+    /// added, changed, or removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory can't be read, in which case the templates are left as
+    /// they were.
+    pub fn refresh(&self) -> Result<bool, String> {
+        let paths = template_paths(&self.directory)?;
+        let mut files = self.files.lock().expect("template registry lock was poisoned");
+        let mut changed = files.keys().any(|path| paths.binary_search(path).is_err());
+
+        files.retain(|path, _| paths.binary_search(path).is_ok());
+
+        for path in &paths {
+            let (length, modified) = match metadata(path) {
+                Ok(metadata) => (metadata.len(), metadata.modified().ok()),
+                Err(_) => (0, None),
+            };
+
+            if let Some(file) = files.get(path) {
+                if file.length == length && file.modified == modified && modified.is_some() {
+                    continue;
+                }
+            }
+
+            let template = load_template(path, files.get(path).and_then(|file| file.template.as_ref()));
+
+            changed |= match files.get(path) {
+                Some(file) => !same_template(file.template.as_ref(), template.as_ref()),
+                None => true,
+            };
+            files.insert(path.clone(), RegisteredFile { length, modified, template });
+        }
+
+        if !changed {
+            return Ok(false);
+        }
+
+        let mut templates = HashMap::new();
+
+        for path in &paths {
+            if let Some((ref name, ref compiled)) = files[path].template {
+                if templates.contains_key(name) {
+                    warn!("Ignoring {}: another template is already named {}.", path.display(), name);
+                } else {
+                    templates.insert(name.clone(), compiled.clone());
+                }
+            }
+        }
+
+        *self.templates.write().expect("template registry lock was poisoned") = templates;
+
+        Ok(true)
+    }
+
+    /// Refreshes the registry every `interval` on a new thread, logging what changed, until the
+    /// registry is dropped.
+    pub fn watch(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let registry = Arc::downgrade(&self);
+
+        drop(self);
+
+        spawn(move || loop {
+            sleep(interval);
+
+            let registry = match registry.upgrade() {
+                Some(registry) => registry,
+                None => return,
+            };
+
+            match registry.refresh() {
+                Ok(true) => info!("Reloaded the templates in {}", registry.directory.display()),
+                Ok(false) => {}
+                Err(err) => error!("{}", err),
+            }
+        })
+    }
+}
+
+/// Parses a template file, reusing the template loaded from it before if its contents haven't
+/// changed. Returns nothing if it isn't a template, or the template loaded before if it can't be
+/// read or parsed, so a file saved partway through an edit doesn't remove it.
+fn load_template(
+    path: &Path,
+    previous: Option<&(String, Arc<CompiledTemplate>)>,
+) -> Option<(String, Arc<CompiledTemplate>)> {
+    let contents = match read(path).map_err(|err| err.to_string()).and_then(decode_text) {
+        Ok(contents) => contents,
+        Err(err) => {
+            warn!("Skipping {}: {}", path.display(), err);
+
+            return previous.cloned();
+        }
+    };
+
+    if let Some(previous) = previous {
+        if previous.1.is_compiled_from(&contents) {
+            return Some(previous.clone());
+        }
+    }
+
+    let compiled = match CompiledTemplate::compile(&contents) {
+        Ok(compiled) => compiled,
+        Err(err) => {
+            warn!("Skipping {}: {}", path.display(), err);
+
+            return previous.cloned();
+        }
+    };
+
+    if compiled.document()["kind"].as_str() != Some("Template") {
+        return None;
+    }
+
+    match compiled.document()["metadata"]["name"].as_str() {
+        Some(name) => Some((name.to_string(), Arc::new(compiled))),
+        None => {
+            warn!("Skipping {}: Templates must have a metadata.name.", path.display());
+
+            None
+        }
+    }
+}
+
+fn same_template(a: Option<&(String, Arc<CompiledTemplate>)>, b: Option<&(String, Arc<CompiledTemplate>)>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.0 == b.0 && Arc::ptr_eq(&a.1, &b.1),
+        (None, None) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::encode;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use yaml::yaml::Hash;
+use yaml::{Yaml, YamlLoader};
+
+use apply::ApplyOptions;
+use kubectl;
+use redact::redact;
+use template::dump;
+
+const RELEASE_LABEL: &str = "ktmpl.io/release";
+const REVISION_LABEL: &str = "ktmpl.io/revision";
+
+/// Records a rendered release in the cluster as a ConfigMap, returning its revision number.
+///
+/// The ConfigMap is named `ktmpl-release.NAME.vREVISION` and labeled with the release name and
+/// revision, where the revision is one more than the latest recorded revision of the release. It
+/// holds the gzipped manifest under `binaryData.manifest.gz`, and the parameter values, the
+/// template file, and the time of recording under `data`.
+///
+/// Secret data and the given sensitive values are redacted from the recorded manifest, as with
+/// `Template::set_redact_secrets`. Parameter values are recorded as given, so sensitive ones
+/// should be redacted by the caller.
+///
+/// # Errors
+///
+/// Returns an error if `kubectl` fails to list previous revisions or create the ConfigMap.
+pub fn record_release(
+    name: &str,
+    objects: &[Yaml],
+    sensitive_values: &[&str],
+    parameters: &[(String, String)],
+    template_file: Option<&str>,
+    options: &ApplyOptions,
+) -> Result<u64, String> {
+    let revision = latest_revision(name, options)? + 1;
+
+    let mut objects = objects.to_vec();
+
+    redact(&mut objects, sensitive_values);
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+
+    encoder.write_all(dump(objects)?.as_bytes()).map_err(|err| err.to_string())?;
+
+    let compressed = encoder.finish().map_err(|err| err.to_string())?;
+
+    let mut parameter_values = Hash::new();
+
+    for (parameter_name, value) in parameters {
+        parameter_values.insert(ystring(parameter_name), ystring(value));
+    }
+
+    let mut labels = Hash::new();
+
+    labels.insert(ystring(RELEASE_LABEL), ystring(name));
+    labels.insert(ystring(REVISION_LABEL), ystring(&revision.to_string()));
+
+    let mut metadata = Hash::new();
+
+    metadata.insert(ystring("labels"), Yaml::Hash(labels));
+    metadata.insert(ystring("name"), ystring(&format!("ktmpl-release.{}.v{}", name, revision)));
+
+    let mut binary_data = Hash::new();
+
+    binary_data.insert(ystring("manifest.gz"), ystring(&encode(&compressed)));
+
+    let recorded_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let mut data = Hash::new();
+
+    data.insert(ystring("parameters"), ystring(&dump(vec![Yaml::Hash(parameter_values)])?));
+    data.insert(ystring("recordedAt"), ystring(&recorded_at.to_string()));
+
+    if let Some(template_file) = template_file {
+        data.insert(ystring("template"), ystring(template_file));
+    }
+
+    let mut config_map = Hash::new();
+
+    config_map.insert(ystring("apiVersion"), ystring("v1"));
+    config_map.insert(ystring("binaryData"), Yaml::Hash(binary_data));
+    config_map.insert(ystring("data"), Yaml::Hash(data));
+    config_map.insert(ystring("kind"), ystring("ConfigMap"));
+    config_map.insert(ystring("metadata"), Yaml::Hash(metadata));
+
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["create", "--filename", "-"]);
+
+    kubectl::run_with_input(&args, Some(&dump(vec![Yaml::Hash(config_map)])?))?;
+
+    Ok(revision)
+}
+
+fn latest_revision(name: &str, options: &ApplyOptions) -> Result<u64, String> {
+    let selector = format!("{}={}", RELEASE_LABEL, name);
+    let mut args = kubectl::context_args(options.context.as_deref());
+
+    args.extend(["get", "configmaps", "--selector", &selector, "--output", "yaml"]);
+
+    let listed = YamlLoader::load_from_str(&kubectl::run(&args)?).map_err(|err| err.to_string())?;
+
+    Ok(listed
+        .first()
+        .and_then(|list| list["items"].as_vec())
+        .unwrap_or(&vec![])
+        .iter()
+        .filter_map(|item| item["metadata"]["labels"][REVISION_LABEL].as_str())
+        .filter_map(|revision| revision.parse().ok())
+        .max()
+        .unwrap_or(0))
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
@@ -0,0 +1,207 @@
+use std::env::var;
+use std::fs::{create_dir_all, metadata, read, write};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use checksum::{sha256_hex, verify_sha256};
+use loader::decode_text;
+
+/// Options for fetching templates and parameter files from remote URLs.
+#[derive(Debug, Default)]
+pub struct RemoteOptions {
+    /// A bearer token sent in the `Authorization` header.
+    pub bearer_token: Option<String>,
+    /// A username and password sent using HTTP basic authentication.
+    pub basic_auth: Option<(String, String)>,
+    /// Path to a PEM bundle of CA certificates used to verify the server.
+    pub ca_bundle: Option<String>,
+    /// Directory in which fetched files are cached, or `None` to disable caching.
+    pub cache_dir: Option<PathBuf>,
+    /// How long a cached file is used before it is fetched again.
+    pub cache_ttl: Duration,
+    /// Whether to ignore cached files, fetching and caching them again.
+    pub refresh: bool,
+}
+
+impl RemoteOptions {
+    /// Creates options from the environment.
+    ///
+    /// `KTMPL_BEARER_TOKEN`, `KTMPL_BASIC_AUTH_USERNAME` and `KTMPL_BASIC_AUTH_PASSWORD`, and
+    /// `KTMPL_CA_BUNDLE` set the corresponding options. Proxies configured with the standard
+    /// `HTTPS_PROXY`, `HTTP_PROXY`, and `NO_PROXY` variables are honored when fetching.
+    ///
+    /// Fetched files are cached for an hour under `$XDG_CACHE_HOME/ktmpl`, or `~/.cache/ktmpl`
+    /// if `XDG_CACHE_HOME` is not set.
+    pub fn from_env() -> Self {
+        let basic_auth = match (var("KTMPL_BASIC_AUTH_USERNAME"), var("KTMPL_BASIC_AUTH_PASSWORD")) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            _ => None,
+        };
+
+        RemoteOptions {
+            bearer_token: var("KTMPL_BEARER_TOKEN").ok(),
+            basic_auth,
+            ca_bundle: var("KTMPL_CA_BUNDLE").ok(),
+            cache_dir: var("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|_| var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .ok()
+                .map(|cache_home| cache_home.join("ktmpl")),
+            cache_ttl: Duration::from_secs(60 * 60),
+            refresh: false,
+        }
+    }
+}
+
+/// Returns whether a template or parameter file path refers to a remote URL.
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Fetches the contents of a remote URL, using the local cache when possible, and decodes them
+/// like a local file with `decode_text`.
+///
+/// # Errors
+///
+/// Returns an error if the contents couldn't be fetched, as with `fetch_bytes`, or aren't valid
+/// UTF-8.
+pub fn fetch(url: &str, sha256: Option<&str>, options: &RemoteOptions) -> Result<String, String> {
+    fetch_bytes(url, sha256, options).and_then(|bytes| decode_text(bytes).map_err(|err| format!("{}: {}", url, err)))
+}
+
+/// Fetches the contents of a remote URL as they were served, using the local cache when possible,
+/// e.g. to verify a signature over the exact bytes.
+///
+/// Cache entries are keyed by the URL and the pinned SHA-256 digest, if any. A cached file whose
+/// content matches a pinned digest never expires; otherwise it is used until it is older than
+/// the cache TTL. If fetching fails, a stale cache entry is used instead, so templates that
+/// have been fetched before can be rendered offline.
+///
+/// # Errors
+///
+/// Returns an error if `curl` could not be run or the request failed or returned an error status,
+/// and there was no cached copy to fall back to.
+pub fn fetch_bytes(url: &str, sha256: Option<&str>, options: &RemoteOptions) -> Result<Vec<u8>, String> {
+    let cache_path = options.cache_dir.as_ref().map(|cache_dir| {
+        cache_dir.join(sha256_hex(format!("{}\n{}", url, sha256.unwrap_or("")).as_bytes()))
+    });
+
+    let cached = match cache_path {
+        Some(ref cache_path) if !options.refresh => read(cache_path).ok(),
+        _ => None,
+    };
+
+    if let (Some(cache_path), Some(contents)) = (&cache_path, &cached) {
+        let fresh = match sha256 {
+            Some(sha256) => verify_sha256(contents, sha256).is_ok(),
+            None => metadata(cache_path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .map(|age| age < options.cache_ttl)
+                .unwrap_or(false),
+        };
+
+        if fresh {
+            debug!("Using cached copy of {} from {}", url, cache_path.display());
+
+            return Ok(contents.clone());
+        }
+    }
+
+    let contents = match fetch_uncached(url, options) {
+        Ok(contents) => contents,
+        Err(error) => return match cached {
+            Some(contents) => {
+                warn!("{}; using a cached copy.", error);
+
+                Ok(contents)
+            }
+            None => Err(error),
+        },
+    };
+
+    if let Some(cache_path) = cache_path {
+        if let Some(cache_dir) = cache_path.parent() {
+            let _ = create_dir_all(cache_dir).and_then(|_| write(&cache_path, &contents));
+        }
+    }
+
+    Ok(contents)
+}
+
+fn fetch_uncached(url: &str, options: &RemoteOptions) -> Result<Vec<u8>, String> {
+    info!("Fetching {}", url);
+
+    let config = curl_config(options)?;
+    let mut command = Command::new("curl");
+
+    command.args(["--silent", "--show-error", "--fail", "--location", "--config", "-"]);
+
+    if let Some(ref ca_bundle) = options.ca_bundle {
+        command.arg("--cacert").arg(ca_bundle);
+    }
+
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run curl: {}", err))?;
+
+    child
+        .stdin
+        .take()
+        .expect("Failed to open curl's stdin.")
+        .write_all(config.as_bytes())
+        .map_err(|err| err.to_string())?;
+
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Fetching {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Returns the curl config file, read from stdin, that holds the credentials in the options.
+///
+/// Credentials are passed in a config file to keep them out of the process list.
+///
+/// # Errors
+///
+/// Returns an error if a credential contains a line break, which would end its line of the config
+/// file and let the rest of the value set other curl options.
+pub fn curl_config(options: &RemoteOptions) -> Result<String, String> {
+    let mut config = String::new();
+
+    if let Some(ref token) = options.bearer_token {
+        config.push_str(&format!("header = \"Authorization: Bearer {}\"\n", escape("bearer token", token)?));
+    }
+
+    if let Some((ref username, ref password)) = options.basic_auth {
+        config.push_str(&format!(
+            "user = \"{}:{}\"\n",
+            escape("basic auth username", username)?,
+            escape("basic auth password", password)?,
+        ));
+    }
+
+    Ok(config)
+}
+
+fn escape(name: &str, value: &str) -> Result<String, String> {
+    if value.contains(['\n', '\r']) {
+        return Err(format!("The {} must not contain line breaks.", name));
+    }
+
+    Ok(value.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,70 @@
+use regex::{NoExpand, Regex, escape};
+use yaml::Yaml;
+
+/// A find-and-replace applied to every string value in the processed objects, e.g. to point the
+/// images of templates that can't be changed at another registry.
+#[derive(Clone, Debug)]
+pub struct Replacement {
+    pattern: Regex,
+    replacement: String,
+    expand: bool,
+}
+
+impl Replacement {
+    /// Parses a replacement of the form `FROM=TO`, e.g. `docker.io/=registry.example.com/`.
+    ///
+    /// `FROM` is matched literally unless `regex` is true, in which case it's a regular
+    /// expression and `TO` can refer to its capture groups, e.g. `$1` or `${name}`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the expression has no `=`, if `FROM` is empty, or if it's not a valid
+    /// regular expression.
+    pub fn parse(expression: &str, regex: bool) -> Result<Self, String> {
+        let equals = match expression.find('=') {
+            Some(equals) if equals > 0 => equals,
+            _ => return Err(format!("Replacement \"{}\" must take the form FROM=TO.", expression)),
+        };
+        let from = &expression[..equals];
+        let pattern = if regex {
+            Regex::new(from).map_err(|err| format!("Replacement \"{}\" is not a valid regex: {}", expression, err))?
+        } else {
+            Regex::new(&escape(from)).expect("Failed to compile escaped regex.")
+        };
+
+        Ok(Replacement {
+            pattern,
+            replacement: expression[equals + 1..].to_string(),
+            expand: regex,
+        })
+    }
+
+    /// Replaces every match in the string values of the objects, returning how many string
+    /// values changed. Hash keys are left as they are.
+    pub fn apply(&self, objects: &mut [Yaml]) -> usize {
+        objects.iter_mut().map(|object| self.replace(object)).sum()
+    }
+
+    fn replace(&self, yaml: &mut Yaml) -> usize {
+        match *yaml {
+            Yaml::Array(ref mut array) => array.iter_mut().map(|value| self.replace(value)).sum(),
+            Yaml::Hash(ref mut hash) => hash.values_mut().map(|value| self.replace(value)).sum(),
+            Yaml::String(ref mut string) => {
+                let replaced = if self.expand {
+                    self.pattern.replace_all(string, self.replacement.as_str())
+                } else {
+                    self.pattern.replace_all(string, NoExpand(&self.replacement))
+                };
+
+                if replaced == *string {
+                    return 0;
+                }
+
+                *string = replaced.into_owned();
+
+                1
+            }
+            _ => 0,
+        }
+    }
+}
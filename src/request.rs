@@ -0,0 +1,52 @@
+use serde_json::Value;
+
+use parameter::{ParameterValue, ParameterValues};
+use secret::{Secret, Secrets};
+
+/// Reads the parameter values from the `parameters` object of a JSON render request, as taken by
+/// `render_request` and `Daemon::render_request`. Values may be strings, numbers, or booleans.
+pub fn request_parameter_values(parameters: &Value) -> Result<ParameterValues, String> {
+    let mut parameter_values = ParameterValues::new();
+
+    let parameters = match *parameters {
+        Value::Null => return Ok(parameter_values),
+        Value::Object(ref parameters) => parameters,
+        _ => return Err("\"parameters\" must be an object.".to_string()),
+    };
+
+    for (name, value) in parameters {
+        let value = match *value {
+            Value::String(ref value) => value.clone(),
+            Value::Number(ref value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            _ => return Err(format!("Parameter {} must be a string, number, or boolean.", name)),
+        };
+
+        parameter_values.insert(name.clone(), ParameterValue::Plain(value));
+    }
+
+    Ok(parameter_values)
+}
+
+/// Reads the secrets from the `secrets` array of a JSON render request, each an object with a
+/// `name` and an optional `namespace`.
+pub fn request_secrets(secrets: &Value) -> Result<Option<Secrets>, String> {
+    let secrets = match *secrets {
+        Value::Null => return Ok(None),
+        Value::Array(ref secrets) => secrets,
+        _ => return Err("\"secrets\" must be an array.".to_string()),
+    };
+
+    secrets
+        .iter()
+        .map(|secret| match (secret["name"].as_str(), &secret["namespace"]) {
+            (Some(name), Value::Null) => Ok(Secret { name: name.to_string(), namespace: None }),
+            (Some(name), Value::String(namespace)) => Ok(Secret {
+                name: name.to_string(),
+                namespace: Some(namespace.clone()),
+            }),
+            _ => Err("Secrets must have a \"name\" string and an optional \"namespace\" string.".to_string()),
+        })
+        .collect::<Result<Secrets, String>>()
+        .map(Some)
+}
@@ -0,0 +1,152 @@
+use base64::decode;
+use regex::Regex;
+use yaml::Yaml;
+
+/// The annotation that excludes an object from scanning when set to "false". It's left on the
+/// object, rather than taken as a directive, so the processed objects can be scanned.
+pub const SCAN_ANNOTATION: &str = "ktmpl.io/scan-secrets";
+
+/// The shortest token considered for its randomness.
+const MIN_TOKEN_LENGTH: usize = 20;
+
+/// How far below the greatest entropy possible for its length, in bits per character, a random
+/// token's entropy may be.
+const ENTROPY_MARGIN: f64 = 1.0;
+
+/// The entropy, in bits per character, at which a token of any length may be random.
+const LONG_TOKEN_ENTROPY: f64 = 4.5;
+
+/// The fraction of adjacent characters in a random token that switch between upper case, lower
+/// case, digits, and punctuation, which is lower in identifiers made of words.
+const MIN_CLASS_CHANGES: f64 = 0.4;
+
+/// Checks that objects other than Secrets don't contain what look like credentials, such as a
+/// password interpolated into a ConfigMap or a container's environment.
+///
+/// Strings are flagged if they match a known credential pattern, such as an AWS access key ID, a
+/// private key, a GitHub or Slack token, or a URL with a password, or if they contain a token of at
+/// least 20 letters, digits, and Base64 punctuation, mixing upper and lower case letters and
+/// digits, whose entropy and changes of case suggest it's random. Certificates, which are public,
+/// are ignored. An object is excluded from scanning by annotating it with
+/// `ktmpl.io/scan-secrets: "false"`.
+///
+/// # Errors
+///
+/// Returns an error listing the path of each string that looks like a secret.
+pub fn scan_secrets(objects: &[Yaml]) -> Result<(), String> {
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        if object["kind"].as_str() == Some("Secret")
+            || object["metadata"]["annotations"][SCAN_ANNOTATION].as_str() == Some("false")
+        {
+            continue;
+        }
+
+        scan(object, &format!("objects[{}]", index), &mut violations);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Objects contain what look like secrets outside of Secrets:\n{}", violations.join("\n")))
+    }
+}
+
+fn scan(node: &Yaml, path: &str, violations: &mut Vec<String>) {
+    match *node {
+        Yaml::Array(ref items) => {
+            for (index, item) in items.iter().enumerate() {
+                scan(item, &format!("{}[{}]", path, index), violations);
+            }
+        }
+        Yaml::Hash(ref hash) => {
+            for (key, value) in hash {
+                if let Some(key) = key.as_str() {
+                    scan(value, &format!("{}.{}", path, key), violations);
+                }
+            }
+        }
+        Yaml::String(ref value) => {
+            if let Some(description) = secret_description(value) {
+                violations.push(format!("{}: looks like {}.", path, description));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns a description of the secret a string looks like, if it looks like one.
+fn secret_description(value: &str) -> Option<&'static str> {
+    lazy_static! {
+        static ref PATTERNS: Vec<(Regex, &'static str)> = [
+            (r"\b(AKIA|ASIA)[0-9A-Z]{16}\b", "an AWS access key ID"),
+            (r"-----BEGIN [A-Z ]*PRIVATE KEY-----", "a private key"),
+            (r"\b(gh[oprsu]_[0-9A-Za-z]{36,}|github_pat_[0-9A-Za-z_]{22,})", "a GitHub token"),
+            (r"\bglpat-[0-9A-Za-z_-]{20,}", "a GitLab token"),
+            (r"\bxox[aboprs]-[0-9A-Za-z-]{10,}", "a Slack token"),
+            (r"\bAIza[0-9A-Za-z_-]{35}", "a Google API key"),
+            (r"\b[rs]k_live_[0-9A-Za-z]{24,}", "a Stripe API key"),
+            (r"\beyJ[0-9A-Za-z_-]{10,}\.eyJ[0-9A-Za-z_-]{10,}\.[0-9A-Za-z_-]{10,}", "a JSON web token"),
+        ]
+            .iter()
+            .map(|&(pattern, description)| (Regex::new(pattern).expect("Failed to compile regex."), description))
+            .collect();
+        static ref URL_PASSWORD: Regex =
+            Regex::new(r"[A-Za-z][0-9A-Za-z+.-]*://[^/\s:@]+:([^/\s@]+)@").expect("Failed to compile regex.");
+        static ref CERTIFICATE: Regex =
+            Regex::new(r"(?s)-----BEGIN CERTIFICATE-----.*?-----END CERTIFICATE-----").expect("Failed to compile regex.");
+        static ref TOKEN: Regex = Regex::new(r"[0-9A-Za-z+/=_-]+").expect("Failed to compile regex.");
+    }
+
+    if let Some(&(_, description)) = PATTERNS.iter().find(|&(pattern, _)| pattern.is_match(value)) {
+        return Some(description);
+    }
+
+    // Passwords may be Kubernetes references to environment variables, e.g. $(DB_PASSWORD).
+    if URL_PASSWORD.captures_iter(value).any(|captures| !captures[1].starts_with("$(")) {
+        return Some("a URL with a password");
+    }
+
+    let value = CERTIFICATE.replace_all(value, "");
+
+    if TOKEN.find_iter(&value).any(|token| is_random(token.as_str())) {
+        return Some("a random token");
+    }
+
+    None
+}
+
+/// Returns whether a token looks randomly generated, and isn't a Base64-encoded certificate.
+fn is_random(token: &str) -> bool {
+    if token.len() < MIN_TOKEN_LENGTH
+        || !token.contains(|c: char| c.is_ascii_uppercase())
+        || !token.contains(|c: char| c.is_ascii_lowercase())
+        || !token.contains(|c: char| c.is_ascii_digit())
+    {
+        return false;
+    }
+
+    let mut counts = [0usize; 128];
+
+    for byte in token.bytes() {
+        counts[byte as usize] += 1;
+    }
+
+    let length = token.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / length;
+
+            -probability * probability.log2()
+        })
+        .sum();
+    let class = |byte: u8| (byte.is_ascii_uppercase(), byte.is_ascii_lowercase(), byte.is_ascii_digit());
+    let class_changes = token.as_bytes().windows(2).filter(|pair| class(pair[0]) != class(pair[1])).count();
+
+    entropy >= (length.log2() - ENTROPY_MARGIN).min(LONG_TOKEN_ENTROPY)
+        && class_changes as f64 / (length - 1.0) >= MIN_CLASS_CHANGES
+        && !decode(token).is_ok_and(|decoded| decoded.starts_with(b"-----BEGIN CERTIFICATE-----"))
+}
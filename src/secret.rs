@@ -4,12 +4,12 @@ use std::collections::HashSet;
 ///
 /// If a set of these values is passed to a `Template`, all of the secret's data values will be
 /// Base64 encoded after interpolation of parameters.
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Secret {
     /// The name of the secret.
     pub name: String,
-    /// The namespace of the secret.
-    pub namespace: String,
+    /// The namespace of the secret, or `None` to match a secret with this name in any namespace.
+    pub namespace: Option<String>,
 }
 
 /// A set of Kubernetes secrets.
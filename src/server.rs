@@ -0,0 +1,136 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::Value;
+use tokio::task::{JoinHandle, spawn_blocking};
+
+use loader::load_yaml;
+use parameter::{ControlCharacters, check_control_characters};
+use request::{request_parameter_values, request_secrets};
+use template::Template;
+use validate::validate_limits;
+
+/// Options for the HTTP render handler.
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// The largest request body accepted, in bytes. Larger requests are rejected with 413 Payload
+    /// Too Large.
+    pub max_body_bytes: usize,
+    /// Whether to replace the values of sensitive parameters in the output; see
+    /// `Template::set_redact_secrets`.
+    pub redact_secrets: bool,
+    /// Whether to reject rendered objects that exceed Kubernetes' limits; see `validate_limits`.
+    pub validate_limits: bool,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        ServerOptions {
+            max_body_bytes: 2 * 1024 * 1024,
+            redact_secrets: true,
+            validate_limits: true,
+        }
+    }
+}
+
+/// Returns a router that renders templates on `POST /render` with `render_handler`.
+pub fn router(options: ServerOptions) -> Router {
+    let max_body_bytes = options.max_body_bytes;
+
+    Router::new()
+        .route("/render", post(render_handler))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .with_state(options)
+}
+
+/// An axum handler that renders the template in a JSON request body; see `render_request` for its
+/// form.
+///
+/// Responds with the rendered manifests as `application/yaml`, 400 Bad Request if the body is not
+/// JSON, or 422 Unprocessable Entity if the template can't be rendered. Errors have a JSON body
+/// of the form `{"error": "..."}`. Templates are rendered on Tokio's blocking thread pool, so large
+/// templates don't stall other requests.
+pub fn render_handler(State(options): State<ServerOptions>, body: Bytes) -> Rendering {
+    Rendering(spawn_blocking(move || render_body(&body, &options)))
+}
+
+/// A template being rendered by `render_handler`, which resolves to the response.
+#[derive(Debug)]
+pub struct Rendering(JoinHandle<Response>);
+
+impl Future for Rendering {
+    type Output = Response;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Response> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            result.unwrap_or_else(|err| error_response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+        })
+    }
+}
+
+fn render_body(body: &[u8], options: &ServerOptions) -> Response {
+    match serde_json::from_slice(body) {
+        Ok(request) => match render_request(&request, options) {
+            Ok(manifests) => ([(CONTENT_TYPE, "application/yaml")], manifests + "\n").into_response(),
+            Err(err) => error_response(StatusCode::UNPROCESSABLE_ENTITY, err),
+        },
+        Err(err) => error_response(StatusCode::BAD_REQUEST, format!("Request body is not valid JSON: {}", err)),
+    }
+}
+
+/// Renders a template from a JSON request, returning the manifests.
+///
+/// The request is an object with the template's YAML as `template`, an optional `parameters`
+/// object of parameter names and values, and an optional `secrets` array of objects with a
+/// `name` and an optional `namespace`, e.g.:
+///
+/// ```json
+/// {
+///   "template": "---\nkind: Template\n...",
+///   "parameters": {"REPLICA_COUNT": 2},
+///   "secrets": [{"name": "credentials"}]
+/// }
+/// ```
+///
+/// Parameter values may be strings, numbers, or booleans. As with the command line utility,
+/// values containing control characters are rejected.
+///
+/// # Errors
+///
+/// Returns an error if the request is malformed, the template can't be processed, or the
+/// processed objects exceed Kubernetes' limits and `validate_limits` is set.
+pub fn render_request(request: &Value, options: &ServerOptions) -> Result<String, String> {
+    let template_contents = request["template"].as_str().ok_or("Request must have a \"template\" string.")?;
+    let mut parameter_values = request_parameter_values(&request["parameters"])?;
+    let secrets = request_secrets(&request["secrets"])?;
+
+    check_control_characters(&mut parameter_values, ControlCharacters::Reject, &[])?;
+
+    let mut template = Template::new(template_contents.to_string(), parameter_values, secrets)?;
+
+    template.set_redact_secrets(options.redact_secrets);
+
+    let manifests = template.process()?;
+
+    if options.validate_limits {
+        validate_limits(&load_yaml(&manifests).map_err(|err| err.to_string())?)?;
+    }
+
+    Ok(manifests)
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    let mut body = serde_json::Map::new();
+
+    body.insert("error".to_string(), Value::String(error));
+
+    (status, Json(Value::Object(body))).into_response()
+}
@@ -0,0 +1,119 @@
+use std::env::temp_dir;
+use std::fs::{OpenOptions, remove_file};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the detached signature of a template should be verified.
+#[derive(Debug)]
+pub enum SignatureVerification {
+    /// Verify against a public key, given as a path or any key reference `cosign` understands.
+    Key(String),
+    /// Verify a keyless signature against the identity and OIDC issuer of its certificate.
+    Keyless {
+        /// The identity (e.g. email address or workflow URL) the certificate was issued to.
+        identity: String,
+        /// The OIDC issuer that authenticated the identity.
+        issuer: String,
+    },
+}
+
+/// Verifies a detached signature of a template's contents using `cosign verify-blob`.
+///
+/// The contents are passed to `cosign` on stdin, so the bytes verified are exactly the bytes that
+/// will be rendered, even if the file they were read from changes. The signature is written to a
+/// temporary file for the duration of the check. For keyless verification, `signature` is expected
+/// to be a bundle written by `cosign sign-blob --bundle`.
+///
+/// # Errors
+///
+/// Returns an error if `cosign` could not be run or the signature is not valid.
+pub fn verify_signature(
+    contents: &[u8],
+    name: &str,
+    signature: &[u8],
+    verification: &SignatureVerification,
+) -> Result<(), String> {
+    debug!("Verifying the signature of {} with cosign", name);
+
+    let signature_path = write_signature(signature)?;
+    let result = run_cosign(contents, name, &signature_path, verification);
+
+    let _ = remove_file(&signature_path);
+
+    result
+}
+
+fn run_cosign(
+    contents: &[u8],
+    name: &str,
+    signature_path: &Path,
+    verification: &SignatureVerification,
+) -> Result<(), String> {
+    let mut command = Command::new("cosign");
+
+    command.arg("verify-blob");
+
+    match *verification {
+        SignatureVerification::Key(ref key) => {
+            command.args(["--key", key]).arg("--signature").arg(signature_path);
+        }
+        SignatureVerification::Keyless { ref identity, ref issuer } => {
+            command.arg("--bundle").arg(signature_path).args([
+                "--certificate-identity",
+                identity,
+                "--certificate-oidc-issuer",
+                issuer,
+            ]);
+        }
+    }
+
+    let mut child = command
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run cosign: {}", err))?;
+
+    // cosign may exit before reading everything if its arguments are rejected, so a failed write
+    // is reported through its exit status instead.
+    let _ = child.stdin.take().expect("Failed to open cosign's stdin.").write_all(contents);
+
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Signature verification of {} failed: {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Writes a signature to a new file only the current user can read, returning its path.
+fn write_signature(signature: &[u8]) -> Result<PathBuf, String> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or(0);
+    let path = temp_dir().join(format!("ktmpl-{}-{}.sig", process::id(), nanos));
+    let mut options = OpenOptions::new();
+
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    if let Err(err) = file.write_all(signature) {
+        let _ = remove_file(&path);
+
+        return Err(format!("{}: {}", path.display(), err));
+    }
+
+    Ok(path)
+}
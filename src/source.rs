@@ -0,0 +1,75 @@
+use yaml::yaml::Hash;
+use yaml::Yaml;
+
+/// The annotation recording the template file, object index, and lines an object came from. Like
+/// `ktmpl.io/scan-secrets`, it's left on the object rather than taken as a directive.
+pub const SOURCE_ANNOTATION: &str = "ktmpl.io/source";
+
+/// Returns the one-based first and last lines of each object in a block-style template's
+/// `objects` sequence, leaving out trailing blank and comment lines.
+pub fn object_line_ranges(template: &str) -> Vec<(usize, usize)> {
+    let mut lines = template.lines().enumerate().skip_while(|&(_, line)| !line.starts_with("objects:"));
+    let mut item_indent = None;
+    let mut ranges: Vec<(usize, usize)> = vec![];
+
+    if lines.next().is_none() {
+        return ranges;
+    }
+
+    for (number, line) in lines {
+        let content = line.trim_start();
+
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - content.len();
+
+        if indent == 0 && !content.starts_with('-') {
+            break;
+        }
+
+        let is_item = (content == "-" || content.starts_with("- "))
+            && match item_indent {
+                Some(item_indent) => item_indent == indent,
+                None => {
+                    item_indent = Some(indent);
+
+                    true
+                }
+            };
+
+        if is_item {
+            ranges.push((number + 1, number + 1));
+        } else if let Some(range) = ranges.last_mut() {
+            range.1 = number + 1;
+        }
+    }
+
+    ranges
+}
+
+/// Records where an object came from in its `ktmpl.io/source` annotation, e.g.
+/// `web.yml:12-30 objects[1]`, or `web.yml objects[1]` if its lines aren't known.
+pub fn annotate_source(object: &mut Yaml, file_name: Option<&str>, index: usize, lines: Option<(usize, usize)>) {
+    let location = match lines {
+        Some((first, last)) => format!("{}:{}-{}", file_name.unwrap_or("template"), first, last),
+        None => file_name.unwrap_or("template").to_string(),
+    };
+
+    if let Yaml::Hash(ref mut object) = *object {
+        let metadata = object.entry(ystring("metadata")).or_insert_with(|| Yaml::Hash(Hash::new()));
+
+        if let Yaml::Hash(ref mut metadata) = *metadata {
+            let annotations = metadata.entry(ystring("annotations")).or_insert_with(|| Yaml::Hash(Hash::new()));
+
+            if let Yaml::Hash(ref mut annotations) = *annotations {
+                annotations.insert(ystring(SOURCE_ANNOTATION), Yaml::String(format!("{} objects[{}]", location, index)));
+            }
+        }
+    }
+}
+
+fn ystring(s: &str) -> Yaml {
+    Yaml::String(s.to_string())
+}
@@ -0,0 +1,350 @@
+use std::str::FromStr;
+
+use yaml::Yaml;
+
+use reference::pod_spec;
+use terraform::json_escape;
+
+/// Binary suffixes of Kubernetes quantities and their multipliers.
+const BINARY_SUFFIXES: [(&str, f64); 6] = [
+    ("Ki", 1024.0),
+    ("Mi", 1_048_576.0),
+    ("Gi", 1_073_741_824.0),
+    ("Ti", 1_099_511_627_776.0),
+    ("Pi", 1_125_899_906_842_624.0),
+    ("Ei", 1_152_921_504_606_846_976.0),
+];
+
+/// Decimal suffixes of Kubernetes quantities and their multipliers.
+const DECIMAL_SUFFIXES: [(&str, f64); 7] = [
+    ("m", 1e-3),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("P", 1e15),
+    ("E", 1e18),
+];
+
+/// The number of objects of a kind, and the resources their pods request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KindSummary {
+    /// The kind of the objects.
+    pub kind: String,
+    /// How many objects there are of the kind.
+    pub count: usize,
+    /// The CPU requested by the objects' pods, in millicores.
+    pub cpu_millicores: u64,
+    /// The memory requested by the objects' pods, in bytes.
+    pub memory_bytes: u64,
+}
+
+/// The pods a workload creates and the resources they request, for pricing them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkloadSummary {
+    /// The kind of the workload, e.g. `Deployment`.
+    pub kind: String,
+    /// The name of the workload.
+    pub name: Option<String>,
+    /// The namespace of the workload.
+    pub namespace: Option<String>,
+    /// How many pods the workload creates: its `replicas`, or a Job's `parallelism`.
+    pub replicas: u64,
+    /// Whether the workload creates a pod on each node, as a DaemonSet does, rather than
+    /// `replicas` pods.
+    pub per_node: bool,
+    /// The CPU each pod requests, in millicores.
+    pub cpu_millicores: u64,
+    /// The memory each pod requests, in bytes.
+    pub memory_bytes: u64,
+    /// The pods' `nodeSelector`, or `Null` if they have none.
+    pub node_selector: Yaml,
+    /// The pods' `tolerations`, or `Null` if they have none.
+    pub tolerations: Yaml,
+}
+
+/// A format for summaries of objects.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SummaryFormat {
+    /// A table of kinds, for people.
+    Table,
+    /// A JSON report of kinds and workloads, for cost estimation tools. See `summary_json`.
+    Json,
+}
+
+/// Summarizes objects by kind, in order of each kind's first object, with the CPU and memory
+/// their pods request.
+///
+/// A pod requests the sum of its containers' requests, or its largest init container's request
+/// if that's greater. Workloads request that for each of their `replicas`, Jobs and CronJobs for
+/// each of their `parallelism`, and DaemonSets for one pod, i.e. per node.
+///
+/// # Errors
+///
+/// Returns an error listing each request that isn't a Kubernetes quantity.
+pub fn summarize_resources(objects: &[Yaml]) -> Result<Vec<KindSummary>, String> {
+    let mut summaries: Vec<KindSummary> = vec![];
+
+    for (object, workload) in objects.iter().zip(summarize_workloads(objects)?) {
+        let kind = object["kind"].as_str().unwrap_or("(none)");
+        let position = summaries.iter().position(|summary| summary.kind == kind).unwrap_or_else(|| {
+            summaries.push(KindSummary { kind: kind.to_string(), count: 0, cpu_millicores: 0, memory_bytes: 0 });
+
+            summaries.len() - 1
+        });
+        let summary = &mut summaries[position];
+
+        summary.count += 1;
+
+        if let Some(workload) = workload {
+            summary.cpu_millicores += workload.cpu_millicores * workload.replicas;
+            summary.memory_bytes += workload.memory_bytes * workload.replicas;
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Returns a JSON report of objects' kinds and workloads, so external tools can estimate what
+/// they'll cost to run.
+///
+/// The report has `kinds`, as given by `summarize_resources`; `workloads`, one for each object
+/// that creates pods, with the `replicas` of them it creates (or `perNode` if it creates one on
+/// each node), their `podRequests`, the total `requests` of its replicas, and the pods'
+/// `nodeSelector` and `tolerations`, for mapping them to instance types; and the `total`
+/// requests. Requests are given in `cpuMillicores` and `memoryBytes`.
+///
+/// # Errors
+///
+/// Returns an error listing each request that isn't a Kubernetes quantity.
+pub fn summary_json(objects: &[Yaml]) -> Result<String, String> {
+    let requests = |cpu: u64, memory: u64| format!("{{\"cpuMillicores\":{},\"memoryBytes\":{}}}", cpu, memory);
+    let kinds = summarize_resources(objects)?;
+    let workloads: Vec<WorkloadSummary> = summarize_workloads(objects)?.into_iter().flatten().collect();
+    let total = format!(
+        "{{\"count\":{},\"requests\":{}}}",
+        objects.len(),
+        requests(
+            kinds.iter().map(|kind| kind.cpu_millicores).sum(),
+            kinds.iter().map(|kind| kind.memory_bytes).sum(),
+        ),
+    );
+    let kinds: Vec<String> = kinds
+        .iter()
+        .map(|kind| format!(
+            "{{\"kind\":\"{}\",\"count\":{},\"requests\":{}}}",
+            json_escape(&kind.kind),
+            kind.count,
+            requests(kind.cpu_millicores, kind.memory_bytes),
+        ))
+        .collect();
+    let workloads: Vec<String> = workloads
+        .iter()
+        .map(|workload| {
+            let string = |value: &Option<String>| value.as_ref().map_or("null".to_string(), |value| {
+                format!("\"{}\"", json_escape(value))
+            });
+
+            format!(
+                "{{\"kind\":\"{}\",\"name\":{},\"namespace\":{},\"replicas\":{},\"perNode\":{},\
+                 \"podRequests\":{},\"requests\":{},\"nodeSelector\":{},\"tolerations\":{}}}",
+                json_escape(&workload.kind),
+                string(&workload.name),
+                string(&workload.namespace),
+                workload.replicas,
+                workload.per_node,
+                requests(workload.cpu_millicores, workload.memory_bytes),
+                requests(workload.cpu_millicores * workload.replicas, workload.memory_bytes * workload.replicas),
+                json(&workload.node_selector),
+                json(&workload.tolerations),
+            )
+        })
+        .collect();
+
+    Ok(format!(
+        "{{\"kinds\":[{}],\"workloads\":[{}],\"total\":{}}}",
+        kinds.join(","),
+        workloads.join(","),
+        total,
+    ))
+}
+
+/// Summarizes the workload each object is, if it creates pods.
+///
+/// # Errors
+///
+/// Returns an error listing each request that isn't a Kubernetes quantity.
+pub fn summarize_workloads(objects: &[Yaml]) -> Result<Vec<Option<WorkloadSummary>>, String> {
+    let mut workloads = vec![];
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let kind = object["kind"].as_str().unwrap_or_default();
+        let replicas = match kind {
+            "Deployment" | "ReplicaSet" | "ReplicationController" | "StatefulSet" => {
+                object["spec"]["replicas"].as_i64().unwrap_or(1)
+            }
+            "Job" => object["spec"]["parallelism"].as_i64().unwrap_or(1),
+            "CronJob" => object["spec"]["jobTemplate"]["spec"]["parallelism"].as_i64().unwrap_or(1),
+            "DaemonSet" | "Pod" => 1,
+            _ => {
+                workloads.push(None);
+
+                continue;
+            }
+        };
+        let pod_spec = pod_spec(object);
+        let (cpu, memory) = pod_requests(pod_spec, &format!("objects[{}]", index), &mut violations);
+
+        workloads.push(Some(WorkloadSummary {
+            kind: kind.to_string(),
+            name: object["metadata"]["name"].as_str().map(|name| name.to_string()),
+            namespace: object["metadata"]["namespace"].as_str().map(|namespace| namespace.to_string()),
+            replicas: replicas.max(0) as u64,
+            per_node: kind == "DaemonSet",
+            cpu_millicores: cpu,
+            memory_bytes: memory,
+            node_selector: if pod_spec["nodeSelector"].is_badvalue() { Yaml::Null } else { pod_spec["nodeSelector"].clone() },
+            tolerations: if pod_spec["tolerations"].is_badvalue() { Yaml::Null } else { pod_spec["tolerations"].clone() },
+        }));
+    }
+
+    if violations.is_empty() {
+        Ok(workloads)
+    } else {
+        Err(format!("Objects request resources that aren't quantities:\n{}", violations.join("\n")))
+    }
+}
+
+/// Formats millicores of CPU as a Kubernetes quantity, e.g. `2` or `1500m`.
+pub fn format_cpu(millicores: u64) -> String {
+    if millicores.is_multiple_of(1000) {
+        (millicores / 1000).to_string()
+    } else {
+        format!("{}m", millicores)
+    }
+}
+
+/// Formats bytes of memory in the largest binary unit they amount to one of, e.g. `768Mi` or
+/// `1.5Gi`.
+pub fn format_memory(bytes: u64) -> String {
+    match BINARY_SUFFIXES.iter().rev().find(|&&(_, multiplier)| bytes as f64 >= multiplier) {
+        Some(&(suffix, multiplier)) => {
+            let amount = format!("{:.1}", bytes as f64 / multiplier);
+
+            format!("{}{}", amount.trim_end_matches(".0"), suffix)
+        }
+        None => bytes.to_string(),
+    }
+}
+
+impl FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(SummaryFormat::Json),
+            "table" => Ok(SummaryFormat::Table),
+            _ => Err("Summary format must be table or json.".to_owned()),
+        }
+    }
+}
+
+/// Converts a YAML node to JSON.
+fn json(node: &Yaml) -> String {
+    match *node {
+        Yaml::Array(ref items) => format!("[{}]", items.iter().map(json).collect::<Vec<String>>().join(",")),
+        Yaml::Boolean(value) => value.to_string(),
+        Yaml::Hash(ref hash) => {
+            let entries: Vec<String> = hash
+                .iter()
+                .map(|(key, value)| {
+                    let key = match *key {
+                        Yaml::String(ref key) | Yaml::Real(ref key) => key.clone(),
+                        Yaml::Integer(key) => key.to_string(),
+                        Yaml::Boolean(key) => key.to_string(),
+                        _ => "null".to_string(),
+                    };
+
+                    format!("\"{}\":{}", json_escape(&key), json(value))
+                })
+                .collect();
+
+            format!("{{{}}}", entries.join(","))
+        }
+        Yaml::Integer(value) => value.to_string(),
+        Yaml::Real(ref value) => match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => number.to_string(),
+            _ => format!("\"{}\"", json_escape(value)),
+        },
+        Yaml::String(ref value) => format!("\"{}\"", json_escape(value)),
+        _ => "null".to_string(),
+    }
+}
+
+/// Returns the CPU, in millicores, and memory, in bytes, a pod requests.
+fn pod_requests(pod_spec: &Yaml, path: &str, violations: &mut Vec<String>) -> (u64, u64) {
+    let mut requests = (0, 0);
+
+    for containers in &["containers", "initContainers"] {
+        let mut total = (0, 0);
+
+        for (index, container) in pod_spec[*containers].as_vec().unwrap_or(&vec![]).iter().enumerate() {
+            let path = format!("{}.{}[{}].resources.requests", path, containers, index);
+            let container_requests = &container["resources"]["requests"];
+            let cpu = request(&container_requests["cpu"], 1000.0, &format!("{}.cpu", path), violations);
+            let memory = request(&container_requests["memory"], 1.0, &format!("{}.memory", path), violations);
+
+            if *containers == "containers" {
+                total = (total.0 + cpu, total.1 + memory);
+            } else {
+                total = (total.0.max(cpu), total.1.max(memory));
+            }
+        }
+
+        requests = (requests.0.max(total.0), requests.1.max(total.1));
+    }
+
+    requests
+}
+
+/// Returns a requested quantity in units of `1 / scale`, rounded up.
+fn request(quantity: &Yaml, scale: f64, path: &str, violations: &mut Vec<String>) -> u64 {
+    let amount = match *quantity {
+        Yaml::BadValue | Yaml::Null => return 0,
+        Yaml::Integer(amount) => Some(amount as f64),
+        Yaml::Real(ref amount) | Yaml::String(ref amount) => parse_quantity(amount),
+        _ => None,
+    };
+
+    match amount {
+        // Rounded to a millionth first, so that e.g. 0.1 CPU doesn't come to 101 millicores.
+        Some(amount) if amount >= 0.0 => ((amount * scale * 1e6).round() / 1e6).ceil() as u64,
+        _ => {
+            let quantity = match *quantity {
+                Yaml::String(ref quantity) => quantity.clone(),
+                ref quantity => format!("{:?}", quantity),
+            };
+
+            violations.push(format!("{}: {} isn't a Kubernetes quantity.", path, quantity));
+
+            0
+        }
+    }
+}
+
+/// Parses a Kubernetes quantity, e.g. `500m`, `1.5`, `128Mi`, or `1e3`.
+fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let (number, multiplier) = BINARY_SUFFIXES
+        .iter()
+        .chain(DECIMAL_SUFFIXES.iter())
+        .find(|&&(suffix, _)| quantity.ends_with(suffix))
+        .map_or((quantity, 1.0), |&(suffix, multiplier)| (&quantity[..quantity.len() - suffix.len()], multiplier));
+
+    if number.is_empty() || !number.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '+') {
+        return None;
+    }
+
+    number.parse::<f64>().ok().map(|number| number * multiplier)
+}
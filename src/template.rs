@@ -1,130 +1,1618 @@
-use std::error::Error;
+use std::fs::read;
+use std::io::{Read, Write};
+use std::mem::take;
+use std::path::Path;
+use std::slice::from_mut;
+use std::time::{Duration, Instant};
 
 use base64::encode;
+#[cfg(feature = "parallel")]
+use rayon::ThreadPoolBuilder;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use yaml::yaml::Hash;
-use yaml::{EmitError, Yaml, YamlEmitter, YamlLoader};
+use yaml::Yaml;
 
-use parameter::{ParamMap, Parameter, ParameterValues};
-use processor::process_yaml;
+use compiled::CompiledTemplate;
+use condition::{apply_conditions, condition};
+use deprecation::{deprecated_apis, migrate_api_version};
+use directive::take_directives;
+use emitter::emit;
+use error::Error;
+use kubectl::KubeContext;
+use loader::{decode_text, load_yaml_or_json};
+use output::{OutputSpec, TemplateOutput};
+use overlay::Overlay;
+use parameter::{NullParameters, PARAMETER_FIELDS, ParamMap, Parameter, ParameterValues, parameter_values_from_yaml};
+use patch::Patch;
+use path::PathOverride;
+use processor::{interpolate_string, placeholder_names, process_yaml, unresolved_placeholders};
+use rbac::Rbac;
+use redact::redact;
+use replace::Replacement;
 use secret::{Secret, Secrets};
+use source::{annotate_source, object_line_ranges};
+use warning::{WarningKind, Warnings};
 
 /// A Kubernetes manifest template and the values for each of its parameters.
+#[derive(Clone, Debug)]
+pub struct Template {
+    create_namespaces: bool,
+    default_namespace: String,
+    deferred_parameters: Vec<Yaml>,
+    deferred_prefixes: Vec<String>,
+    document: Yaml,
+    file_name: Option<String>,
+    focus: Vec<Vec<(String, String)>>,
+    jobs: usize,
+    kube_context: Option<KubeContext>,
+    load_time: Duration,
+    migrate_api_versions: bool,
+    object_lines: Vec<(usize, usize)>,
+    objects: Vec<Yaml>,
+    outputs: Vec<OutputSpec>,
+    overlays: Vec<Overlay>,
+    overrides: Vec<PathOverride>,
+    param_map: ParamMap,
+    patches: Vec<Patch>,
+    rbac: Vec<Rbac>,
+    redact_secrets: bool,
+    replacements: Vec<Replacement>,
+    secrets: Option<Secrets>,
+    source_annotations: bool,
+    warnings: Warnings,
+}
+
+impl Template {
+    /// Creates a new template.
+    ///
+    /// # Parameters
+    ///
+    /// * template_contents: The YAML template file's contents.
+    /// * parameter_values: A map of the template's parameters and the user-supplied values for
+    ///   each.
+    /// * secrets: A list of Kubernetes secrets whose data keys should be Base64 encoded after
+    ///   parameter interpolation. Secrets without a namespace match secrets with the same name in
+    ///   any namespace. Their names and namespaces may contain placeholders, e.g.
+    ///   `$(APP_NAME)-creds`, which are interpolated with the template's parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    ///
+    /// * There was more than one YAML document present in the template contents.
+    /// * The YAML document did not contain an "objects" key or it was not an array value.
+    /// * One of the objects is not a hash, e.g. because of a mistake in its indentation.
+    /// * The YAML document did not contain a "parameters" key or it was not an array value.
+    /// * One of the parameters doesn't have a "name" key.
+    /// * Two of the parameters have the same name.
+    /// * One of the parameters specifies an invalid "parameterType", "allowedValues", or "pattern".
+    /// * A supplied value isn't one of its parameter's "allowedValues" or doesn't match its
+    ///   "pattern".
+    /// * One of the parameters requires a value which wasn't supplied.
+    /// * Any of the provided secrets were not found in the template.
+    /// * There was an error in the structure of a secret that prevented its data from being Base64
+    ///   encoded.
+    ///
+    /// Problems with the template's contents or structure are `Error::Parse`, and problems with
+    /// its parameters, such as a missing required value, are `Error::Parameter`.
+    pub fn new(
+        template_contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Error> {
+        Template::new_deferred(template_contents, parameter_values, secrets, vec![])
+    }
+
+    /// Creates a new template from a template file, like `Template::new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file can't be read, `Error::Parse` if it isn't UTF-8, and
+    /// otherwise errors like `Template::new`. A leading byte order mark and CRLF line endings are
+    /// accepted.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let bytes = read(path).map_err(|err| Error::Io(format!("{}: {}", path.display(), err)))?;
+        let template_contents =
+            decode_text(bytes).map_err(|err| Error::Parse(format!("{}: {}", path.display(), err)))?;
+
+        Template::new(template_contents, parameter_values, secrets)
+    }
+
+    /// Creates a new template from the contents of a reader, e.g. stdin, like `Template::new`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if reading fails, `Error::Parse` if the contents aren't UTF-8, and
+    /// otherwise errors like `Template::new`.
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Error> {
+        let mut bytes = vec![];
+
+        reader.read_to_end(&mut bytes).map_err(|err| Error::Io(err.to_string()))?;
+
+        Template::new(decode_text(bytes).map_err(Error::Parse)?, parameter_values, secrets)
+    }
+
+    /// Creates a new template whose parameters with any of the given name prefixes are left
+    /// unresolved, to be supplied by a later render.
+    ///
+    /// Deferred parameters don't need values, and their placeholders are left in the processed
+    /// objects. If any prefixes are given, processing is strict: any other placeholder that
+    /// doesn't name a declared parameter is an error. The processed result is a template
+    /// containing the processed objects and the declarations of the deferred parameters, so it
+    /// can be processed again with the remaining values. Secrets containing deferred placeholders
+    /// should only be encoded by the final render.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `Template::new`.
+    pub fn new_deferred(
+        template_contents: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+        deferred_prefixes: Vec<String>,
+    ) -> Result<Self, Error> {
+        let started = Instant::now();
+        let doc = load(&template_contents).map_err(Error::Parse)?;
+        let object_lines = object_line_ranges(&template_contents);
+
+        drop(template_contents);
+
+        let mut template = Template::from_document(doc, parameter_values, secrets, deferred_prefixes, started)?;
+
+        // Line ranges are only trusted if one was found for each object, e.g. not for JSON.
+        if object_lines.len() == template.objects.len() {
+            template.object_lines = object_lines;
+        }
+
+        Ok(template)
+    }
+
+    /// Creates a new template from one compiled ahead of time, without parsing it again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `Template::new`.
+    pub fn from_compiled(
+        compiled: CompiledTemplate,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Error> {
+        Template::from_compiled_deferred(compiled, parameter_values, secrets, vec![])
+    }
+
+    /// Creates a new template from one compiled ahead of time, deferring parameters like
+    /// `Template::new_deferred`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `Template::new`.
+    pub fn from_compiled_deferred(
+        compiled: CompiledTemplate,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+        deferred_prefixes: Vec<String>,
+    ) -> Result<Self, Error> {
+        let started = Instant::now();
+
+        Template::from_document(compiled.into_document(), parameter_values, secrets, deferred_prefixes, started)
+    }
+
+    /// Creates a new template from ordinary Kubernetes manifests containing placeholders, without
+    /// a `Template` wrapper.
+    ///
+    /// Each YAML document in the manifests is an object, and each supplied parameter value
+    /// declares a parameter of the same name. Placeholders naming parameters without a value are
+    /// left as they are, like undeclared placeholders in a template, so references to container
+    /// environment variables such as `$(POD_NAME)` survive.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifests aren't valid YAML or JSON, if one of the objects is not a
+    /// hash, if a parameter name is invalid, or if any of the provided secrets were not found.
+    pub fn from_manifests(
+        manifests: String,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+    ) -> Result<Self, Error> {
+        let started = Instant::now();
+        let objects: Vec<Yaml> = load_yaml_or_json(&manifests)
+            .map_err(Error::Parse)?
+            .into_iter()
+            .filter(|object| !object.is_null() && !object.is_badvalue())
+            .collect();
+        let mut names: Vec<&String> = parameter_values.keys().collect();
+
+        names.sort();
+
+        let parameters = names
+            .into_iter()
+            .map(|name| {
+                let mut parameter = Hash::new();
+
+                parameter.insert(ystring("name"), ystring(name));
+
+                Yaml::Hash(parameter)
+            })
+            .collect();
+        let mut doc = Hash::new();
+
+        drop(manifests);
+        doc.insert(ystring("kind"), ystring("Template"));
+        doc.insert(ystring("objects"), Yaml::Array(objects));
+        doc.insert(ystring("parameters"), Yaml::Array(parameters));
+
+        Template::from_document(Yaml::Hash(doc), parameter_values, secrets, vec![], started)
+    }
+
+    fn from_document(
+        mut doc: Yaml,
+        parameter_values: ParameterValues,
+        secrets: Option<Secrets>,
+        deferred_prefixes: Vec<String>,
+        started: Instant,
+    ) -> Result<Self, Error> {
+        let objects = match doc {
+            Yaml::Hash(ref mut hash) => hash.remove(&ystring("objects")),
+            _ => None,
+        };
+        let template_objects = match objects {
+            Some(Yaml::Array(objects)) => objects,
+            _ => return Err(Error::Parse("Key \"objects\" must be present and must be an array.".to_owned()))
+        };
+
+        check_objects_are_hashes(&template_objects).map_err(Error::Parse)?;
+
+        let outputs = OutputSpec::parse_all(&doc).map_err(Error::Parse)?;
+
+        let parameter_specs = match doc["parameters"].as_vec() {
+            Some(parameter_specs) => parameter_specs,
+            None => return Err(Error::Parse("Key \"parameters\" must be present and must be an array.".to_owned()))
+        };
+
+        check_duplicate_parameters(parameter_specs).map_err(Error::Parameter)?;
+
+        let mut warnings = Warnings::new();
+        let (param_map, deferred_parameters) =
+            parameter_map(&doc, &parameter_values, &deferred_prefixes, &mut warnings)?;
+
+        Ok(Template {
+            create_namespaces: false,
+            default_namespace: "default".to_string(),
+            deferred_parameters,
+            deferred_prefixes,
+            document: doc,
+            file_name: None,
+            focus: vec![],
+            jobs: 1,
+            kube_context: None,
+            load_time: started.elapsed(),
+            migrate_api_versions: false,
+            object_lines: vec![],
+            objects: template_objects,
+            outputs,
+            overlays: vec![],
+            overrides: vec![],
+            param_map,
+            patches: vec![],
+            rbac: vec![],
+            redact_secrets: false,
+            replacements: vec![],
+            secrets,
+            source_annotations: false,
+            warnings,
+        })
+    }
+
+    /// Adds an override that sets a value in the processed objects, after parameter interpolation.
+    ///
+    /// The expression takes the form `PATH=VALUE`, where `PATH` selects objects and a location
+    /// within them, e.g. `objects[?(@.kind=="Deployment")].spec.replicas=5` or
+    /// `objects[0].metadata.name=web`. Missing hash keys along the path are created.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the expression is not a valid override.
+    pub fn add_override(&mut self, expression: &str) -> Result<(), Error> {
+        self.overrides.push(PathOverride::parse(expression).map_err(Error::Parse)?);
+
+        Ok(())
+    }
+
+    /// Adds a find-and-replace of the form `FROM=TO` to apply to the string values of the
+    /// processed objects, after any overrides, e.g. to rewrite the registry of every image. See
+    /// `Replacement::parse` for how `FROM` is matched.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the expression is not a valid replacement.
+    pub fn add_replacement(&mut self, expression: &str, regex: bool) -> Result<(), Error> {
+        self.replacements.push(Replacement::parse(expression, regex).map_err(Error::Parse)?);
+
+        Ok(())
+    }
+
+    /// Adds patches to apply to the processed objects, after parameter interpolation and before
+    /// any overrides.
+    ///
+    /// The contents may contain multiple YAML documents, each either a strategic merge patch (a
+    /// partial object whose `kind` and `metadata.name` identify the object it is merged into) or
+    /// an RFC 6902 JSON patch (a hash with a `target` identifying the object by `kind` and `name`,
+    /// and a `patch` array of operations).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the contents are not valid YAML or a patch does not identify its
+    /// target object.
+    pub fn add_patches(&mut self, patch_contents: &str) -> Result<(), Error> {
+        self.patches.extend(Patch::from_str(patch_contents).map_err(Error::Parse)?);
+
+        Ok(())
+    }
+
+    /// Adds overlays to merge onto the processed objects, after patches and before any overrides.
+    ///
+    /// The contents may contain multiple YAML documents, each a partial object whose `kind` and
+    /// `metadata.name` identify the objects it's deep-merged onto, like a strategic merge patch.
+    /// An overlay that matches no object is added as a new object, so environment-specific
+    /// objects can be supplied alongside changes to the template's.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the contents are not valid YAML or an overlay does not identify
+    /// the objects it's merged onto.
+    pub fn add_overlays(&mut self, overlay_contents: &str) -> Result<(), Error> {
+        self.overlays.extend(Overlay::from_str(overlay_contents).map_err(Error::Parse)?);
+
+        Ok(())
+    }
+
+    /// Adds a ServiceAccount with the given name, and a Role and RoleBinding granting it the rules
+    /// in the contents of an RBAC spec file, after the processed objects. The spec may contain
+    /// placeholders, which are interpolated with the template's parameters. See `Rbac::from_str`
+    /// for the spec's form.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the contents are not a valid RBAC spec.
+    pub fn add_rbac(&mut self, name: &str, spec_contents: &str) -> Result<(), Error> {
+        self.rbac.push(Rbac::from_str(name, spec_contents).map_err(Error::Parse)?);
+
+        Ok(())
+    }
+
+    /// Returns the template's declared parameters and their values, in declaration order.
+    /// Parameters deferred with `new_deferred` are not included.
+    pub fn parameters(&self) -> Vec<&Parameter> {
+        self.param_map.values().collect()
+    }
+
+    /// Adds a selector limiting the output to matching objects, e.g. `kind=Deployment,name=web`.
+    ///
+    /// Selectors are comma-separated `kind`, `name`, and `namespace` criteria that must all match
+    /// an object after processing. With multiple selectors, objects matching any of them are
+    /// output. All objects are still processed, so parameters are validated as usual.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Parse` if the selector is malformed or uses an unknown key.
+    pub fn add_focus(&mut self, selector: &str) -> Result<(), Error> {
+        let mut criteria = vec![];
+
+        for criterion in selector.split(',').map(str::trim).filter(|criterion| !criterion.is_empty()) {
+            let (key, value) = criterion.split_once('=').ok_or_else(|| {
+                Error::Parse(format!("Focus criterion \"{}\" must be of the form KEY=VALUE.", criterion))
+            })?;
+
+            match key {
+                "kind" | "name" | "namespace" => criteria.push((key.to_string(), value.to_string())),
+                _ => return Err(Error::Parse(format!("Focus key \"{}\" must be kind, name, or namespace.", key))),
+            }
+        }
+
+        if criteria.is_empty() {
+            return Err(Error::Parse("Focus selectors must not be empty.".to_string()));
+        }
+
+        self.focus.push(criteria);
+
+        Ok(())
+    }
+
+    /// Sets how many objects the processing methods interpolate at once, each on its own thread.
+    /// Objects are still emitted in order, and an error is reported for the first object that
+    /// fails. Defaults to 1. Only has an effect if the `parallel` feature is enabled.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs;
+    }
+
+    /// Sets the kubeconfig context being rendered for.
+    ///
+    /// The context's default namespace and cluster name are available to the template as the
+    /// built-in parameters `KUBE_NAMESPACE` and `KUBE_CLUSTER`, and the namespace is used in
+    /// place of "default" when matching secrets without a `metadata.namespace`.
+    pub fn set_kube_context(&mut self, kube_context: KubeContext) {
+        self.default_namespace = kube_context.namespace.clone();
+        self.kube_context = Some(kube_context);
+    }
+
+    /// Sets whether a Namespace object is added, before the processed objects, for each namespace
+    /// they're in that isn't among them, like `helm install --create-namespace`.
+    ///
+    /// Objects without a `metadata.namespace`, other than well-known cluster-scoped kinds, are in
+    /// the default namespace (see `set_default_namespace`). The namespaces every cluster has, such
+    /// as "default" and "kube-system", aren't added.
+    pub fn set_create_namespaces(&mut self, create_namespaces: bool) {
+        self.create_namespaces = create_namespaces;
+    }
+
+    /// Sets whether each of the template's objects is annotated with where it came from, as
+    /// `ktmpl.io/source: FILE:FIRST-LAST objects[INDEX]`, so an object in the cluster can be
+    /// traced back to the template. The lines are left out if they aren't known, e.g. for
+    /// templates that aren't block-style YAML, and the file is "template" if it isn't set (see
+    /// `set_file_name`). Objects added by overlays, RBAC, or `set_create_namespaces` aren't
+    /// annotated.
+    pub fn set_source_annotations(&mut self, source_annotations: bool) {
+        self.source_annotations = source_annotations;
+    }
+
+    /// Sets the namespace assumed for objects without a `metadata.namespace` when matching
+    /// secrets and creating namespaces. Defaults to "default", or the namespace of the kubeconfig
+    /// context if one is set.
+    pub fn set_default_namespace(&mut self, default_namespace: &str) {
+        self.default_namespace = default_namespace.to_string();
+    }
+
+    /// Sets the name of the file the template was read from, available to the template as the
+    /// built-in parameter `KTMPL_TEMPLATE_FILE`.
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_name = Some(file_name.to_string());
+    }
+
+    /// Sets whether objects using deprecated API versions are rewritten to their current
+    /// equivalents, where the conversion is mechanical, after patches and overrides are applied.
+    /// See `migrate_api_version` for the conversions made.
+    pub fn set_migrate_api_versions(&mut self, migrate_api_versions: bool) {
+        self.migrate_api_versions = migrate_api_versions;
+    }
+
+    /// Sets whether placeholders in supplied parameter values are resolved, e.g. so `-p CMD
+    /// 'cd $(HOME)'` refers to the HOME parameter. By default supplied values are used as they
+    /// are, and only placeholders in the template's default values are resolved.
+    pub fn set_interpolate_supplied_values(&mut self, interpolate_supplied_values: bool) {
+        self.param_map.set_interpolate_supplied(interpolate_supplied_values);
+    }
+
+    /// Sets how placeholders for parameters without a value, i.e. optional parameters with neither
+    /// a supplied nor a default value, are interpolated. By default they're an error.
+    pub fn set_null_parameters(&mut self, null_parameters: NullParameters) {
+        self.param_map.set_null_parameters(null_parameters);
+    }
+
+    /// Sets whether sensitive values are redacted from the processed template.
+    ///
+    /// When enabled, the data values of Secret objects, and any occurrences of the values of
+    /// parameters declared as `sensitive` or with a "base64" `parameterType`, are replaced with
+    /// `<redacted>`, so the output can be shared safely. Secrets are still matched and encoded
+    /// as usual before being redacted.
+    pub fn set_redact_secrets(&mut self, redact_secrets: bool) {
+        self.redact_secrets = redact_secrets;
+    }
+
+    /// Interpolates the parameters' values into the YAML template, returning the results.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the processed template was not valid YAML, if any placeholders were
+    /// left unresolved when deferring parameters, if any patch, override, or overlay without an
+    /// `apiVersion` did not match the processed objects, if any object couldn't be migrated to a
+    /// current API version, or if any specified secrets could not be found and Base64 encoded.
+    ///
+    /// Besides the declared parameters, objects can refer to the built-in parameters
+    /// `KTMPL_TEMPLATE_NAME` (the template's `metadata.name`), `KTMPL_TEMPLATE_FILE` (see
+    /// `set_file_name`), and `KTMPL_OBJECT_INDEX` (the object's zero-based position in the
+    /// template's objects). A declared parameter with the same name takes precedence.
+    ///
+    /// Objects can also carry processing directives as annotations, which are removed from the
+    /// output: `ktmpl.io/param-default` gives `NAME=VALUE` defaults, one per line, for declared
+    /// parameters that have no value, and `ktmpl.io/required` lists parameters that must have a
+    /// value for the object. Both apply only to the annotated object.
+    ///
+    /// Sections of objects can be included or dropped by a bool parameter, with a hash key of
+    /// `$(if NAME)` or `$(unless NAME)`; see `apply_conditions`.
+    ///
+    /// A sequence item `$((NAME...))` is replaced by the items of the parameter's value, parsed as a
+    /// YAML sequence, e.g. `[--verbose]`, so callers can append to what the template defines.
+    ///
+    /// Problems with parameters' values are `Error::Parameter`, secrets that weren't found are
+    /// `Error::Secret`, patches, overlays, and overrides that didn't apply are `Error::Process`,
+    /// and failures to emit the objects are `Error::Emit`.
+    pub fn process(self) -> Result<String, Error> {
+        self.process_timed().map(|(manifest, _)| manifest)
+    }
+
+    /// Processes a copy of the template with other parameter values, leaving the template as it
+    /// is, so a template parsed once can be rendered for several environments.
+    ///
+    /// The values replace those the template was created with, and the template's other settings,
+    /// such as its secrets, patches, and deferred parameters, apply as they would to `process`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`, or if a required parameter has no
+    /// value or a value is invalid for its parameter's type, which is `Error::Parameter`.
+    pub fn render(&self, parameter_values: &ParameterValues) -> Result<String, Error> {
+        let mut warnings = Warnings::new();
+        let (mut param_map, _) =
+            parameter_map(&self.document, parameter_values, &self.deferred_prefixes, &mut warnings)?;
+        let mut template = self.clone();
+
+        param_map.set_interpolate_supplied(self.param_map.interpolate_supplied());
+        param_map.set_null_parameters(self.param_map.null_parameters());
+        template.param_map = param_map;
+        template.warnings = warnings;
+
+        template.process()
+    }
+
+    /// Processes the template like `process`, also returning how long each phase of rendering
+    /// took. The load phase is measured when the template is created.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`.
+    pub fn process_timed(self) -> Result<(String, RenderTiming), Error> {
+        let load = self.load_time;
+        let started = Instant::now();
+        let docs = self.process_objects(None, None, None)?;
+        let process = started.elapsed();
+        let started = Instant::now();
+        let manifest = dump(docs).map_err(Error::Emit)?;
+        let emit = started.elapsed();
+
+        Ok((manifest, RenderTiming { load, process, emit }))
+    }
+
+    /// Processes the template like `process`, also returning which Secret objects were Base64
+    /// encoded and which specified secrets matched none, rather than failing if any didn't.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`, except that specified secrets
+    /// needn't be found.
+    pub fn process_listing_secrets(self) -> Result<(String, SecretReport), Error> {
+        let mut report = SecretReport::default();
+        let docs = self.process_objects(Some(&mut report), None, None)?;
+
+        Ok((dump(docs).map_err(Error::Emit)?, report))
+    }
+
+    /// Processes the template like `process`, writing each object to a sink as soon as it's
+    /// interpolated, so neither the processed objects nor the manifest are held in memory at
+    /// once. Like `process`, the output has no trailing newline.
+    ///
+    /// Objects are only streamed when each can be processed on its own. If the template has
+    /// secrets to encode, deferred parameters, patches, overlays, overrides, focus selectors,
+    /// RBAC, or namespaces to create, which need all of the processed objects, or is set to use
+    /// more than one job, all of the objects are processed before any are written, so processing
+    /// errors leave the sink untouched. Otherwise, a processing error can leave the objects before
+    /// the failing one written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`, or if writing to the sink fails.
+    pub fn process_to<W: Write>(self, sink: &mut W) -> Result<(), Error> {
+        let mut manifest = String::new();
+
+        self.process_each(|index, object| write_object(index, object, &mut manifest, sink))
+    }
+
+    /// Processes the template like `process_to`, passing each processed object to `each` as a YAML
+    /// document of its own as soon as it's interpolated, e.g. to send the objects to a client while
+    /// the rest are processed. Objects are streamed under the same conditions as `process_to`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`, or the error returned by `each`,
+    /// which stops processing, as an `Error::Emit`.
+    pub fn process_each_document<F>(self, mut each: F) -> Result<(), Error>
+    where
+        F: FnMut(String) -> Result<(), String>,
+    {
+        self.process_each(|_, object| {
+            let mut document = String::new();
+
+            emit(object, &mut document)?;
+            each(document)
+        })
+    }
+
+    /// Processes the template, passing each processed object and its index to `each`, as soon as
+    /// it's interpolated if the objects can be streamed; see `process_to`.
+    fn process_each<F>(mut self, mut each: F) -> Result<(), Error>
+    where
+        F: FnMut(usize, &Yaml) -> Result<(), String>,
+    {
+        let streamable = self.secrets.is_none()
+            && self.deferred_prefixes.is_empty()
+            && self.patches.is_empty()
+            && self.overlays.is_empty()
+            && self.overrides.is_empty()
+            && self.focus.is_empty()
+            && self.rbac.is_empty()
+            && !self.create_namespaces
+            && self.jobs == 1;
+
+        if !streamable {
+            for (index, object) in self.process_objects(None, None, None)?.iter().enumerate() {
+                each(index, object).map_err(Error::Emit)?;
+            }
+
+            return Ok(());
+        }
+
+        self.add_builtins();
+
+        let declares_object_index = self.param_map.contains_key("KTMPL_OBJECT_INDEX");
+
+        for (index, mut object) in take(&mut self.objects).into_iter().enumerate() {
+            interpolate_object(&mut self.param_map, index, &mut object, declares_object_index)?;
+
+            if self.source_annotations {
+                annotate_source(&mut object, self.file_name.as_deref(), index, self.object_lines.get(index).cloned());
+            }
+
+            for replacement in &self.replacements {
+                replacement.apply(from_mut(&mut object));
+            }
+
+            if self.migrate_api_versions {
+                migrate_object(index, &mut object)?;
+            }
+
+            if self.redact_secrets {
+                redact(from_mut(&mut object), &sensitive_values(&self.param_map));
+            }
+
+            each(index, &object).map_err(Error::Emit)?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes the template like `process`, returning each processed object as a YAML document
+    /// of its own, e.g. to send the objects separately.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`.
+    pub fn process_documents(self) -> Result<Vec<String>, Error> {
+        self.process_objects(None, None, None)?
+            .iter()
+            .map(|object| {
+                let mut document = String::new();
+
+                emit(object, &mut document).map_err(Error::Emit)?;
+
+                Ok(document)
+            })
+            .collect()
+    }
+
+    /// Processes the template like `process`, returning the processed objects as YAML values
+    /// rather than emitting them, e.g. to change them programmatically without parsing the
+    /// output again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`.
+    pub fn process_into_yaml(self) -> Result<Vec<Yaml>, Error> {
+        self.process_objects(None, None, None)
+    }
+
+    /// Processes the template like `process`, also returning the values of the outputs it declares.
+    ///
+    /// A template's `outputs` each have a `name` and either a `value`, a string whose placeholders
+    /// are interpolated like an object's, e.g. `$(APP_NAME)-db`, or a `path` selecting a value
+    /// within the processed objects before any are redacted, e.g.
+    /// `objects[?(@.kind=="Service")].spec.ports[0].port`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`, or if an output's value can't be
+    /// interpolated or its path doesn't select exactly one string, number, or boolean, which is
+    /// `Error::Process`.
+    pub fn process_with_outputs(self) -> Result<(String, Vec<TemplateOutput>), Error> {
+        let mut outputs = vec![];
+        let docs = self.process_objects(None, Some(&mut outputs), None)?;
+
+        Ok((dump(docs).map_err(Error::Emit)?, outputs))
+    }
+
+    /// Processes the template like `process`, also returning the warnings found loading and
+    /// processing it: values supplied for undeclared parameters, values supplied under parameters'
+    /// former names, unknown fields in parameters' declarations, and objects using deprecated API
+    /// versions that weren't migrated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error under the same conditions as `process`.
+    pub fn process_with_warnings(self) -> Result<(String, Warnings), Error> {
+        let mut warnings = Warnings::new();
+        let docs = self.process_objects(None, None, Some(&mut warnings))?;
+
+        Ok((dump(docs).map_err(Error::Emit)?, warnings))
+    }
+
+    /// Returns the warnings found loading the template, about its parameters and the values
+    /// supplied for them. See `process_with_warnings` for those found processing it as well.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    /// Adds the built-in parameters that are the same for every object.
+    fn add_builtins(&mut self) {
+        let template_name = self.document["metadata"]["name"].as_str().map(|name| name.to_string());
+
+        add_builtin(&mut self.param_map, "KTMPL_TEMPLATE_NAME", template_name);
+        add_builtin(&mut self.param_map, "KTMPL_TEMPLATE_FILE", self.file_name.clone());
+
+        if let Some(ref kube_context) = self.kube_context {
+            add_builtin(&mut self.param_map, "KUBE_NAMESPACE", Some(kube_context.namespace.clone()));
+            add_builtin(&mut self.param_map, "KUBE_CLUSTER", Some(kube_context.cluster.clone()));
+        }
+    }
+
+    /// Interpolates the parameters into each of the template's objects, on several threads if
+    /// more than one job is set and the `parallel` feature is enabled.
+    fn interpolate_objects(&mut self) -> Result<(), Error> {
+        let declares_object_index = self.param_map.contains_key("KTMPL_OBJECT_INDEX");
+        let file_name = self.file_name.as_deref();
+        let object_lines = &self.object_lines;
+        let source_annotations = self.source_annotations;
+        let interpolate = |param_map: &mut ParamMap, index: usize, object: &mut Yaml| {
+            interpolate_object(param_map, index, object, declares_object_index)?;
+
+            if source_annotations {
+                annotate_source(object, file_name, index, object_lines.get(index).cloned());
+            }
+
+            Ok(())
+        };
+
+        #[cfg(feature = "parallel")]
+        {
+            if self.jobs > 1 {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(self.jobs)
+                    .build()
+                    .map_err(|err| Error::Process(format!("Failed to start {} jobs: {}", self.jobs, err)))?;
+                let param_map = &self.param_map;
+                let objects = &mut self.objects;
+                // Each thread interpolates with its own copy of the parameters, as directives
+                // and KTMPL_OBJECT_INDEX change them for each object.
+                let results: Vec<Result<(), Error>> = pool.install(|| {
+                    objects
+                        .par_iter_mut()
+                        .enumerate()
+                        .map_init(
+                            || param_map.clone(),
+                            |param_map, (index, object)| interpolate(param_map, index, object),
+                        )
+                        .collect()
+                });
+
+                results.into_iter().collect::<Result<(), Error>>()?;
+
+                // Leave the parameters as interpolating the objects in turn would.
+                if !declares_object_index && !self.objects.is_empty() {
+                    let last_index = (self.objects.len() - 1).to_string();
+
+                    add_builtin(&mut self.param_map, "KTMPL_OBJECT_INDEX", Some(last_index));
+                }
+
+                return Ok(());
+            }
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            if self.jobs > 1 {
+                warn!("ktmpl was built without the parallel feature, so objects are processed one at a time.");
+            }
+        }
+
+        for (index, object) in self.objects.iter_mut().enumerate() {
+            interpolate(&mut self.param_map, index, object)?;
+        }
+
+        Ok(())
+    }
+
+    /// Processes the objects, returning the documents to emit. If given a secret report, fills it
+    /// in rather than failing when specified secrets aren't found. If given a list of outputs,
+    /// fills it in with the values of the template's outputs.
+    fn process_objects(
+        mut self,
+        secret_report: Option<&mut SecretReport>,
+        outputs: Option<&mut Vec<TemplateOutput>>,
+        warnings: Option<&mut Warnings>,
+    ) -> Result<Vec<Yaml>, Error> {
+        self.add_builtins();
+        self.interpolate_objects()?;
+
+        if !self.deferred_prefixes.is_empty() {
+            let mut unresolved = vec![];
+
+            for object in &self.objects {
+                unresolved_placeholders(object, &mut unresolved);
+            }
+
+            unresolved.retain(|name| {
+                !self.deferred_prefixes.iter().any(|prefix| name.starts_with(prefix.as_str()))
+            });
+            unresolved.sort();
+            unresolved.dedup();
+
+            if !unresolved.is_empty() {
+                return Err(Error::Parameter(format!("Unresolved placeholders: {}.", unresolved.join(", "))));
+            }
+        }
+
+        for patch in &self.patches {
+            patch.apply(&mut self.objects).map_err(Error::Process)?;
+        }
+
+        for overlay in &self.overlays {
+            overlay.apply(&mut self.objects).map_err(Error::Process)?;
+        }
+
+        for path_override in &self.overrides {
+            path_override.apply(&mut self.objects).map_err(Error::Process)?;
+        }
+
+        for replacement in &self.replacements {
+            let replaced = replacement.apply(&mut self.objects);
+
+            debug!("Replacement changed {} string values", replaced);
+        }
+
+        if self.migrate_api_versions {
+            for (index, object) in self.objects.iter_mut().enumerate() {
+                migrate_object(index, object)?;
+            }
+        }
+
+        for warning in deprecated_apis(&self.objects) {
+            self.warnings.push(WarningKind::Deprecated, warning);
+        }
+
+        for rbac in &self.rbac {
+            let objects = rbac.objects(&self.default_namespace, &self.param_map).map_err(Error::Process)?;
+
+            self.objects.extend(objects);
+        }
+
+        if self.create_namespaces {
+            let namespaces = missing_namespaces(&self.objects, &self.default_namespace);
+
+            self.objects.splice(0..0, namespaces.into_iter().map(|namespace| namespace_object(&namespace)));
+        }
+
+        if self.secrets.is_some() || secret_report.is_some() {
+            let secrets = interpolate_secrets(self.secrets.take().unwrap_or_default(), &self.param_map)
+                .map_err(Error::Secret)?;
+            let mut report = SecretReport::default();
+            let mut secrets_encoded = Secrets::new();
+            let mut secrets_skipped = Secrets::new();
+
+            for (index, object) in self.objects.iter_mut().enumerate() {
+                match maybe_base64_encode_secret(&secrets, &self.default_namespace, object).map_err(Error::Secret)? {
+                    SecretMatch::Encoded(secret, matched) => {
+                        report.encoded.push((index, secret));
+                        secrets_encoded.extend(matched);
+                    }
+                    SecretMatch::Skipped(secret, matched) => {
+                        report.skipped.push((index, secret));
+                        secrets_skipped.extend(matched);
+                    }
+                    SecretMatch::Unspecified(secret) => report.unspecified.push((index, secret)),
+                    SecretMatch::NotSecret => {}
+                }
+            }
+
+            if secret_report.is_none() && secrets_encoded.len() != secrets.len() {
+                return Err(Error::Secret(
+                    "Not all secrets specified were found; use --list-secrets to see which.".to_string(),
+                ));
+            }
+
+            report.unmatched = secrets
+                .into_iter()
+                .filter(|secret| !secrets_encoded.contains(secret) && !secrets_skipped.contains(secret))
+                .collect();
+            report.unmatched.sort_by(|a, b| (&a.name, &a.namespace).cmp(&(&b.name, &b.namespace)));
+
+            if let Some(secret_report) = secret_report {
+                *secret_report = report;
+            }
+        }
+
+        if let Some(outputs) = outputs {
+            for output in &self.outputs {
+                outputs.push(output.evaluate(&self.objects, &self.param_map).map_err(Error::Process)?);
+            }
+        }
+
+        if self.redact_secrets {
+            redact(&mut self.objects, &sensitive_values(&self.param_map));
+        }
+
+        if !self.focus.is_empty() {
+            let focus = &self.focus;
+
+            self.objects.retain(|object| focus.iter().any(|criteria| {
+                criteria.iter().all(|(key, value)| {
+                    let actual = match key.as_str() {
+                        "kind" => object["kind"].as_str(),
+                        "namespace" => object["metadata"]["namespace"].as_str(),
+                        _ => object["metadata"]["name"].as_str(),
+                    };
+
+                    actual == Some(value.as_str())
+                })
+            }));
+
+            if self.objects.is_empty() {
+                return Err(Error::Process("No objects matched the focus selectors.".to_string()));
+            }
+        }
+
+        if let Some(warnings) = warnings {
+            *warnings = take(&mut self.warnings);
+        }
+
+        if self.deferred_prefixes.is_empty() {
+            Ok(self.objects)
+        } else {
+            if let Yaml::Hash(ref mut hash) = self.document {
+                let deferred_names: Vec<Yaml> = self.deferred_parameters
+                    .iter()
+                    .map(|parameter_spec| parameter_spec["name"].clone())
+                    .collect();
+
+                // Profiles may only set the parameters still declared.
+                if let Some(&mut Yaml::Hash(ref mut profiles)) = hash.get_mut(&ystring("profiles")) {
+                    for profile in profiles.values_mut() {
+                        if let Yaml::Hash(ref mut values) = *profile {
+                            values.retain(|name, _| deferred_names.contains(name));
+                        }
+                    }
+                }
+
+                hash.insert(ystring("objects"), Yaml::Array(self.objects));
+                hash.insert(ystring("parameters"), Yaml::Array(self.deferred_parameters));
+            }
+
+            Ok(vec![self.document])
+        }
+    }
+}
+
+/// Which Secret objects were Base64 encoded when processing a template, as returned by
+/// `Template::process_listing_secrets`. Objects are given by index, with their name and
+/// namespace, which is the default namespace if they have none.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct SecretReport {
+    /// Secret objects that matched a specified secret and were encoded.
+    pub encoded: Vec<(usize, Secret)>,
+    /// Secret objects that matched a specified secret but have no `data` to encode, e.g. because
+    /// they only have `stringData`.
+    pub skipped: Vec<(usize, Secret)>,
+    /// Secret objects that matched no specified secret, which are left as they are.
+    pub unspecified: Vec<(usize, Secret)>,
+    /// Specified secrets that matched no Secret object, by name and namespace.
+    pub unmatched: Vec<Secret>,
+}
+
+/// What became of an object when encoding secrets.
+enum SecretMatch {
+    /// The object isn't a Secret.
+    NotSecret,
+    /// The Secret matched no specified secret.
+    Unspecified(Secret),
+    /// The Secret matched specified secrets, given second, and its data was encoded.
+    Encoded(Secret, Vec<Secret>),
+    /// The Secret matched specified secrets, given second, but had no data to encode.
+    Skipped(Secret, Vec<Secret>),
+}
+
+/// How long each phase of rendering a template took, as returned by `Template::process_timed`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderTiming {
+    /// Parsing the template and resolving its parameters' values.
+    pub load: Duration,
+    /// Interpolating parameters and applying directives, patches, overlays, overrides, and
+    /// secrets.
+    pub process: Duration,
+    /// Emitting the processed objects as YAML.
+    pub emit: Duration,
+}
+
+impl RenderTiming {
+    /// The total time spent rendering.
+    pub fn total(&self) -> Duration {
+        self.load + self.process + self.emit
+    }
+}
+
+/// Returns the parameters declared by a template, in declaration order, with their default values.
+///
+/// Unlike `Template::new`, this does not require values for required parameters, so it can be
+/// used to describe a template's parameters before any values are known.
+///
+/// # Errors
+///
+/// Returns an error if the template is not a single YAML document with a "parameters" array, or
+/// any of the parameters' declarations are invalid.
+pub fn template_parameters(template_contents: &str) -> Result<Vec<Parameter>, String> {
+    let doc = load(template_contents)?;
+    let parameter_specs = match doc["parameters"].as_vec() {
+        Some(parameter_specs) => parameter_specs,
+        None => return Err("Key \"parameters\" must be present and must be an array.".to_owned())
+    };
+
+    check_duplicate_parameters(parameter_specs)?;
+
+    let mut param_map = ParamMap::new();
+
+    for parameter_spec in parameter_specs {
+        param_map.insert(Parameter::from_spec(parameter_spec)?);
+    }
+
+    interpolate_parameters(&mut param_map, doc["metadata"]["name"].as_str().map(|name| name.to_string()), |_| false)?;
+
+    Ok(param_map.values().cloned().collect())
+}
+
+/// Returns the parameter values of one of a template's profiles, the named presets of values
+/// under its "profiles" key, e.g. `profiles: {small: {REPLICAS: "1"}, large: {REPLICAS: "10"}}`.
+///
+/// A profile is a layer of defaults: its values take precedence over the default values in the
+/// parameters' declarations, and supplied values take precedence over its values.
+///
+/// # Errors
+///
+/// Returns an error if the template doesn't have the profile, if the profile isn't a hash of
+/// strings, or if it sets a parameter the template doesn't declare.
+pub fn template_profile(template_contents: &str, profile: &str) -> Result<ParameterValues, String> {
+    let doc = load(template_contents)?;
+    let values = match doc["profiles"][profile] {
+        Yaml::BadValue => {
+            let profiles: Vec<&str> = doc["profiles"]
+                .as_hash()
+                .map(|profiles| profiles.keys().filter_map(|name| name.as_str()).collect())
+                .unwrap_or_default();
+
+            return Err(if profiles.is_empty() {
+                format!("Profile {} not found; the template has no profiles.", profile)
+            } else {
+                format!("Profile {} not found; the template's profiles are {}.", profile, profiles.join(", "))
+            });
+        }
+        ref values => parameter_values_from_yaml(values.clone()).map_err(|err| format!("Profile {}: {}", profile, err))?,
+    };
+    let parameters = template_parameters(template_contents)?;
+    let mut undeclared: Vec<&str> = values
+        .keys()
+        .filter(|name| !parameters.iter().any(|parameter| parameter.name == **name))
+        .map(|name| name.as_str())
+        .collect();
+
+    if !undeclared.is_empty() {
+        undeclared.sort();
+
+        return Err(format!("Profile {} sets undeclared parameters: {}.", profile, undeclared.join(", ")));
+    }
+
+    Ok(values)
+}
+
+/// Where a declared parameter is referred to within a template's objects.
 #[derive(Debug)]
-pub struct Template {
-    objects: Vec<Yaml>,
-    param_map: ParamMap,
-    secrets: Option<Secrets>,
+pub struct ParameterUsage {
+    /// The name of the parameter.
+    pub name: String,
+    /// The paths of the values that refer to the parameter, e.g. `objects[0].metadata.name`, in
+    /// the order they appear in the template. Empty if the parameter is never referred to.
+    pub paths: Vec<String>,
 }
 
-impl Template {
-    /// Creates a new template.
-    ///
-    /// # Parameters
-    ///
-    /// * template_contents: The YAML template file's contents.
-    /// * parameter_values: A map of the template's parameters and the user-supplied values for
-    ///   each.
-    /// * secrets: A list of Kubernetes secrets whose data keys should be Base64 encoded after
-    ///   parameter interpolation.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if:
-    ///
-    /// * There was more than one YAML document present in the template contents.
-    /// * The YAML document did not contain an "objects" key or it was not an array value.
-    /// * The YAML document did not contain a "parameters" key or it was not an array value.
-    /// * One of the parameters doesn't have a "name" key.
-    /// * One of the parameters specifies an invalid "parameterType".
-    /// * One of the parameters requires a value which wasn't supplied.
-    /// * Any of the provided secrets were not found in the template.
-    /// * There was an error in the structure of a secret that prevented its data from being Base64
-    /// encoded.
-    pub fn new(
-        template_contents: String,
-        parameter_values: ParameterValues,
-        secrets: Option<Secrets>,
-    ) -> Result<Self, String> {
-        let docs = YamlLoader::load_from_str(&template_contents)
-            .map_err(|err| err.description().to_owned())?;
+/// Returns where each parameter declared by a template is referred to, in declaration order.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as `template_parameters`.
+pub fn parameter_usages(template_contents: &str) -> Result<Vec<ParameterUsage>, String> {
+    let references = template_references(&load(template_contents)?);
+
+    Ok(template_parameters(template_contents)?
+        .into_iter()
+        .map(|parameter| {
+            let mut paths = vec![];
+
+            for (name, path) in &references {
+                if *name == parameter.name && !paths.contains(path) {
+                    paths.push(path.clone());
+                }
+            }
+
+            ParameterUsage { name: parameter.name, paths }
+        })
+        .collect())
+}
+
+/// Kinds of objects that aren't in a namespace, which don't need one created for them.
+const CLUSTER_SCOPED_KINDS: [&str; 12] = [
+    "APIService",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "CustomResourceDefinition",
+    "IngressClass",
+    "MutatingWebhookConfiguration",
+    "Namespace",
+    "PersistentVolume",
+    "PriorityClass",
+    "RuntimeClass",
+    "StorageClass",
+    "ValidatingWebhookConfiguration",
+];
+
+/// Namespaces every cluster has, which are never created.
+const SYSTEM_NAMESPACES: [&str; 4] = ["default", "kube-node-lease", "kube-public", "kube-system"];
+
+/// The parameters `Template` provides without a declaration.
+const BUILTIN_PARAMETERS: [&str; 5] = [
+    "KTMPL_OBJECT_INDEX",
+    "KTMPL_TEMPLATE_FILE",
+    "KTMPL_TEMPLATE_NAME",
+    "KUBE_CLUSTER",
+    "KUBE_NAMESPACE",
+];
+
+/// Checks a template for likely mistakes, returning a description of each problem found: declared
+/// parameters that are never referred to, and references to parameters that aren't declared or
+/// built in.
+///
+/// # Errors
+///
+/// Returns an error under the same conditions as `template_parameters`.
+pub fn lint_template(template_contents: &str) -> Result<Vec<String>, String> {
+    let references = template_references(&load(template_contents)?);
+    let parameters = template_parameters(template_contents)?;
+    let mut problems = vec![];
+
+    for parameter in &parameters {
+        if !references.iter().any(|(name, _)| *name == parameter.name) {
+            problems.push(format!("Parameter {} is declared but never referred to.", parameter.name));
+        }
+    }
 
-        if docs.len() != 1 {
-            return Err("Only one YAML document can be present in the template.".to_owned());
+    for (name, path) in &references {
+        if !BUILTIN_PARAMETERS.contains(&name.as_str())
+            && !parameters.iter().any(|parameter| parameter.name == *name)
+        {
+            problems.push(format!("{}: refers to undeclared parameter {}.", path, name));
         }
+    }
 
-        let doc = &docs[0];
+    Ok(problems)
+}
 
-        let mut template_objects = vec![];
-        let objects = match doc["objects"].as_vec() {
-            Some(objects) => objects,
-            None => return Err("Key \"objects\" must be present and must be an array.".to_owned())
-        };
+/// Returns the parameters referred to by a template's objects and outputs, with the path of each
+/// reference.
+fn template_references(doc: &Yaml) -> Vec<(String, String)> {
+    let mut references = vec![];
 
-        for object in objects {
-            template_objects.push(object.clone());
+    if let Some(objects) = doc["objects"].as_vec() {
+        for (index, object) in objects.iter().enumerate() {
+            collect_references(object, format!("objects[{}]", index), &mut references);
         }
+    }
 
-        let mut param_map = ParamMap::new();
-        let parameter_specs = match doc["parameters"].as_vec() {
-            Some(parameter_specs) => parameter_specs,
-            None => return Err("Key \"parameters\" must be present and must be an array.".to_owned())
+    if let Some(outputs) = doc["outputs"].as_vec() {
+        for (index, output) in outputs.iter().enumerate() {
+            collect_references(&output["value"], format!("outputs[{}].value", index), &mut references);
+        }
+    }
+
+    references
+}
+
+fn collect_references(yaml: &Yaml, path: String, references: &mut Vec<(String, String)>) {
+    match *yaml {
+        Yaml::Array(ref array) => for (index, value) in array.iter().enumerate() {
+            collect_references(value, format!("{}[{}]", path, index), references);
+        },
+        Yaml::Hash(ref hash) => for (key, value) in hash {
+            let key_path = match key.as_str() {
+                Some(key) if !key.contains(['.', '[', ']']) => format!("{}.{}", path, key),
+                Some(key) => format!("{}[{:?}]", path, key),
+                None => continue,
+            };
+
+            if let Some((name, _)) = key.as_str().and_then(condition) {
+                references.push((name.to_string(), key_path.clone()));
+            }
+
+            collect_references(value, key_path, references);
+        },
+        Yaml::String(ref string) => for name in placeholder_names(string) {
+            references.push((name, path.clone()));
+        },
+        _ => {}
+    }
+}
+
+fn check_duplicate_parameters(parameter_specs: &[Yaml]) -> Result<(), String> {
+    for (index, parameter_spec) in parameter_specs.iter().enumerate() {
+        let name = match parameter_spec["name"].as_str() {
+            Some(name) => name,
+            None => continue,
         };
 
-        for parameter_spec in parameter_specs {
-            let parameter = Parameter::new(parameter_spec, &parameter_values)?;
+        let earlier = parameter_specs[..index]
+            .iter()
+            .position(|earlier_spec| earlier_spec["name"].as_str() == Some(name));
 
-            param_map.insert(parameter.name.clone(), parameter);
+        if let Some(earlier) = earlier {
+            return Err(format!(
+                "Parameter {} is declared more than once: {} and {}.",
+                name,
+                describe_parameter_spec(earlier, &parameter_specs[earlier]),
+                describe_parameter_spec(index, parameter_spec),
+            ));
         }
 
-        Ok(Template {
-            objects: template_objects,
-            param_map: param_map,
-            secrets: secrets,
-        })
+        for alias in parameter_spec["aliases"].as_vec().map(Vec::as_slice).unwrap_or_default() {
+            let taken = parameter_specs.iter().enumerate().any(|(other_index, other_spec)| {
+                let other_aliases = other_spec["aliases"].as_vec();
+
+                other_spec["name"] == *alias
+                    || other_index != index && other_aliases.is_some_and(|aliases| aliases.contains(alias))
+            });
+
+            if taken {
+                return Err(format!(
+                    "Parameter {} has the alias {}, which is also the name or an alias of another parameter.",
+                    name,
+                    alias.as_str().unwrap_or_default(),
+                ));
+            }
+        }
     }
 
-    /// Interpolates the parameters' values into the YAML template, returning the results.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the processed template was not valid YAML, or if any specified secrets
-    /// could not be found and Base64 encoded.
-    pub fn process(mut self) -> Result<String, String> {
-        let mut secrets_encoded = 0;
+    Ok(())
+}
+
+fn describe_parameter_spec(index: usize, parameter_spec: &Yaml) -> String {
+    let mut details = vec![];
+
+    for key in &["description", "required", "parameterType", "value"] {
+        match parameter_spec[*key] {
+            Yaml::String(ref value) => details.push(format!("{}: {:?}", key, value)),
+            Yaml::Boolean(value) => details.push(format!("{}: {}", key, value)),
+            Yaml::Integer(value) => details.push(format!("{}: {}", key, value)),
+            Yaml::Real(ref value) => details.push(format!("{}: {}", key, value)),
+            _ => {}
+        }
+    }
+
+    if details.is_empty() {
+        format!("parameters[{}]", index)
+    } else {
+        format!("parameters[{}] ({})", index, details.join(", "))
+    }
+}
 
-        for object in self.objects.iter_mut() {
-            process_yaml(object, &self.param_map);
+/// Returns the parameters a template declares, with the given values, and the specs of the
+/// parameters deferred by any of the given name prefixes.
+fn parameter_map(
+    document: &Yaml,
+    parameter_values: &ParameterValues,
+    deferred_prefixes: &[String],
+    warnings: &mut Warnings,
+) -> Result<(ParamMap, Vec<Yaml>), Error> {
+    let mut param_map = ParamMap::new();
+    let mut deferred_parameters = vec![];
+    let parameter_specs = document["parameters"].as_vec().map(Vec::as_slice).unwrap_or_default();
 
-            if let Some(ref secrets) = self.secrets {
-                if maybe_base64_encode_secret(secrets, object)? {
-                    secrets_encoded += 1;
+    parameter_warnings(parameter_specs, parameter_values, warnings);
+
+    for parameter_spec in parameter_specs {
+        let deferred = parameter_spec["name"].as_str().is_some_and(|name| {
+            deferred_prefixes.iter().any(|prefix| name.starts_with(prefix.as_str()))
+        });
+
+        if deferred {
+            deferred_parameters.push(parameter_spec.clone());
+
+            continue;
+        }
+
+        let parameter = Parameter::new(parameter_spec, parameter_values)?;
+
+        param_map.insert(parameter);
+    }
+
+    let template_name = document["metadata"]["name"].as_str().map(|name| name.to_string());
+
+    let is_supplied = |parameter: &Parameter| {
+        parameter_values.contains_key(&parameter.name)
+            || parameter.aliases.iter().any(|alias| parameter_values.contains_key(alias))
+    };
+
+    interpolate_parameters(&mut param_map, template_name, is_supplied).map_err(Error::Parameter)?;
+
+    let supplied: Vec<String> = param_map
+        .values()
+        .filter(|parameter| is_supplied(parameter))
+        .map(|parameter| parameter.name.clone())
+        .collect();
+
+    for name in supplied {
+        param_map.set_supplied(&name);
+    }
+
+    Ok((param_map, deferred_parameters))
+}
+
+/// Records warnings for unknown fields in parameters' declarations, values supplied under
+/// parameters' former names, and values supplied for undeclared parameters.
+fn parameter_warnings(parameter_specs: &[Yaml], parameter_values: &ParameterValues, warnings: &mut Warnings) {
+    let mut declared = vec![];
+
+    for (index, parameter_spec) in parameter_specs.iter().enumerate() {
+        let name = parameter_spec["name"].as_str().unwrap_or_default();
+
+        if let Yaml::Hash(ref fields) = *parameter_spec {
+            for field in fields.keys().filter_map(Yaml::as_str) {
+                if !PARAMETER_FIELDS.contains(&field) {
+                    warnings.push(
+                        WarningKind::IgnoredField,
+                        format!("parameters[{}] ({}): Ignoring unknown field \"{}\".", index, name, field),
+                    );
                 }
             }
         }
 
-        if let Some(ref secrets) = self.secrets {
-            if secrets_encoded != secrets.len() {
-                return Err("Not all secrets specified were found.".to_string());
+        let aliases: Vec<&str> = parameter_spec["aliases"]
+            .as_vec()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(Yaml::as_str)
+            .collect();
+        let mut supplied = parameter_values.contains_key(name);
+
+        for alias in aliases.iter().filter(|alias| parameter_values.contains_key(**alias)) {
+            if supplied {
+                warnings.push(
+                    WarningKind::IgnoredField,
+                    format!("Parameter {} was renamed to {}; ignoring the value given for {}.", alias, name, alias),
+                );
+            } else {
+                warnings.push(
+                    WarningKind::Deprecated,
+                    format!("Parameter {} was renamed to {}; give its value as {} instead.", alias, name, name),
+                );
+                supplied = true;
+            }
+        }
+
+        declared.push(name);
+        declared.extend(aliases);
+    }
+
+    let mut unused: Vec<&String> =
+        parameter_values.keys().filter(|name| !declared.contains(&name.as_str())).collect();
+
+    unused.sort();
+
+    for name in unused {
+        warnings.push(
+            WarningKind::UnusedParameter,
+            format!("A value was given for parameter {}, which the template doesn't declare.", name),
+        );
+    }
+}
+
+/// Interpolates the placeholders in parameters' default values, descriptions, and display names,
+/// so a default can be e.g. `$(APP_NAME)-data`. A default is interpolated after the defaults it
+/// refers to, and supplied values are left as they are. Besides other parameters, placeholders
+/// can refer to `KTMPL_TEMPLATE_NAME`; the placeholders of parameters without a value, and of
+/// other built-in parameters, are left for processing the objects.
+fn interpolate_parameters<F>(
+    param_map: &mut ParamMap,
+    template_name: Option<String>,
+    is_supplied: F,
+) -> Result<(), String>
+where
+    F: Fn(&Parameter) -> bool,
+{
+    let mut references = param_map.clone();
+
+    references.set_null_parameters(NullParameters::Keep);
+    add_builtin(&mut references, "KTMPL_TEMPLATE_NAME", template_name);
+
+    let mut pending: Vec<String> = param_map
+        .values()
+        .filter(|parameter| !is_supplied(parameter))
+        .filter(|parameter| parameter.value.as_ref().is_some_and(|value| !placeholder_names(value).is_empty()))
+        .map(|parameter| parameter.name.clone())
+        .collect();
+
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|name| {
+            let value = references.get(name).and_then(|parameter| parameter.value.as_deref()).unwrap_or_default();
+
+            placeholder_names(value).iter().all(|reference| !pending.contains(reference))
+        });
+        let name = match ready {
+            Some(index) => pending.remove(index),
+            None => {
+                return Err(format!(
+                    "The default values of parameters {} refer to each other in a cycle.",
+                    pending.join(", "),
+                ));
+            }
+        };
+        let value = references.get(&name).and_then(|parameter| parameter.value.clone()).unwrap_or_default();
+        let value = interpolate_string(&value, &references).map_err(|err| format!("Parameter {}: {}", name, err))?;
+
+        if let Some(parameter) = references.get_mut(&name) {
+            parameter.value = Some(value.clone());
+        }
+
+        if let Some(parameter) = param_map.get_mut(&name) {
+            parameter.value = Some(value);
+        }
+    }
+
+    let names: Vec<String> = param_map.values().map(|parameter| parameter.name.clone()).collect();
+
+    for name in names {
+        if let Some(parameter) = param_map.get_mut(&name) {
+            for text in parameter.description.iter_mut().chain(parameter.display_name.iter_mut()) {
+                *text = interpolate_string(text, &references).map_err(|err| format!("Parameter {}: {}", name, err))?;
             }
         }
+    }
+
+    Ok(())
+}
+
+fn add_builtin(param_map: &mut ParamMap, name: &str, value: Option<String>) {
+    if value.is_none() || param_map.contains_key(name) {
+        return;
+    }
+
+    param_map.insert(Parameter {
+        aliases: vec![],
+        allowed_values: vec![],
+        description: None,
+        display_name: None,
+        hidden: true,
+        name: name.to_string(),
+        parameter_type: None,
+        pattern: None,
+        required: false,
+        sensitive: false,
+        transforms: vec![],
+        value,
+    });
+}
+
+fn load(template_contents: &str) -> Result<Yaml, String> {
+    let mut docs = load_yaml_or_json(template_contents)?;
+
+    if docs.len() != 1 {
+        return Err("Only one YAML document can be present in the template.".to_owned());
+    }
+
+    Ok(docs.remove(0))
+}
 
-        dump(self.objects)
+/// Checks that every object is a hash, as an object that's a string or an array is usually an
+/// indentation mistake, e.g. a missing `:` or an extra `-`, that would otherwise be emitted as is.
+fn check_objects_are_hashes(objects: &[Yaml]) -> Result<(), String> {
+    let problems: Vec<String> = objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| {
+            let (kind, snippet) = match *object {
+                Yaml::Hash(_) => return None,
+                Yaml::Array(_) => {
+                    let mut emitted = String::new();
+
+                    emit(object, &mut emitted).ok()?;
+
+                    ("an array", emitted.split_whitespace().skip(1).collect::<Vec<&str>>().join(" "))
+                }
+                Yaml::String(ref string) => ("a string", format!("{:?}", string)),
+                Yaml::Null => ("empty", String::new()),
+                Yaml::Integer(integer) => ("a number", integer.to_string()),
+                Yaml::Real(ref real) => ("a number", real.clone()),
+                Yaml::Boolean(boolean) => ("a boolean", boolean.to_string()),
+                _ => ("invalid", String::new()),
+            };
+            let snippet = if snippet.chars().count() > 40 {
+                format!(" {}...", snippet.chars().take(40).collect::<String>())
+            } else if snippet.is_empty() {
+                snippet
+            } else {
+                format!(" {}", snippet)
+            };
+
+            Some(format!("objects[{}]: Object is {}{}; check its indentation.", index, kind, snippet))
+        })
+        .collect();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Objects must be hashes:\n{}", problems.join("\n")))
     }
+}
 
+/// Interpolates parameter values into the placeholders in secrets' names and namespaces, e.g.
+/// `$(APP_NAME)-creds`, so they match the processed objects.
+fn interpolate_secrets(secrets: Secrets, param_map: &ParamMap) -> Result<Secrets, String> {
+    let interpolate = |value: String| {
+        let mut yaml = Yaml::String(value.clone());
+
+        if let Some(new_value) = process_yaml(&mut yaml, param_map)? {
+            yaml = new_value;
+        }
+
+        match yaml {
+            Yaml::String(interpolated) => Ok(interpolated),
+            _ => Err(format!("Secret {} must be interpolated as a string.", value)),
+        }
+    };
+
+    secrets
+        .into_iter()
+        .map(|secret| Ok(Secret {
+            name: interpolate(secret.name)?,
+            namespace: secret.namespace.map(&interpolate).transpose()?,
+        }))
+        .collect()
 }
 
-fn maybe_base64_encode_secret(secrets: &Secrets, object: &mut Yaml)
--> Result<bool, String> {
-    let mut hash = match object {
+/// Base64 encodes the data of an object if it's one of the given secrets, returning what became
+/// of it.
+fn maybe_base64_encode_secret(secrets: &Secrets, default_namespace: &str, object: &mut Yaml)
+-> Result<SecretMatch, String> {
+    let hash = match object {
         &mut Yaml::Hash(ref mut hash) => hash,
-        _ => return Ok(false),
+        _ => return Ok(SecretMatch::NotSecret),
     };
 
     if let Some(kind) = hash.get(&Yaml::String("kind".to_string())) {
         match kind {
-            &Yaml::String(ref kind_string) => {
+            Yaml::String(kind_string) => {
                 if kind_string != "Secret" {
-                    return Ok(false);
+                    return Ok(SecretMatch::NotSecret);
                 }
             }
             _ => return Err(
@@ -136,7 +1624,7 @@ fn maybe_base64_encode_secret(secrets: &Secrets, object: &mut Yaml)
     }
 
     let metadata = match hash.get(&Yaml::String("metadata".to_string())) {
-        Some(&Yaml::Hash(ref metadata)) => metadata.clone(),
+        Some(Yaml::Hash(metadata)) => metadata.clone(),
         Some(_) => return Err(
             "Encountered a resource with a non-hash \"metadata\" field.".to_string()
         ),
@@ -146,7 +1634,7 @@ fn maybe_base64_encode_secret(secrets: &Secrets, object: &mut Yaml)
     };
 
     let name = match metadata.get(&ystring("name")) {
-        Some(&Yaml::String(ref name)) => name.to_string(),
+        Some(Yaml::String(name)) => name.to_string(),
         Some(_) => return Err(
             "Encountered a resource with a non-string \"metadata.name\" field.".to_string()
         ),
@@ -156,34 +1644,34 @@ fn maybe_base64_encode_secret(secrets: &Secrets, object: &mut Yaml)
     };
 
     let namespace = match metadata.get(&ystring("namespace")) {
-        Some(&Yaml::String(ref namespace)) => namespace.to_string(),
+        Some(Yaml::String(namespace)) => namespace.to_string(),
         Some(_) => return Err(
             "Encountered a resource with a non-string \"metadata.namespace\" field.".to_string()
         ),
-        None => "default".to_string(),
+        None => default_namespace.to_string(),
     };
 
-    let secret = Secret {
-        name: name,
-        namespace: namespace,
-    };
+    let secret = Secret { name: name.clone(), namespace: Some(namespace.clone()) };
+    let matched: Vec<Secret> = vec![
+        Secret { name: name.clone(), namespace: Some(namespace) },
+        Secret { name, namespace: None },
+    ].into_iter().filter(|secret| secrets.contains(secret)).collect();
 
-    if secrets.contains(&secret) {
-        if let Some(data) = hash.get_mut(&ystring("data")) {
-            match data {
-                &mut Yaml::Hash(ref mut data_hash) => {
-                    base64_encode_secret_data(data_hash)?;
-                    return Ok(true);
-                }
-                _ => return Err(
-                    "Encountered secret with non-hash \"data\" field.".to_string()
-                ),
-            }
-        }
+    if matched.is_empty() {
+        return Ok(SecretMatch::Unspecified(secret));
     }
 
-    return Ok(false);
+    match hash.get_mut(&ystring("data")) {
+        Some(&mut Yaml::Hash(ref mut data_hash)) => {
+            base64_encode_secret_data(data_hash)?;
+
+            Ok(SecretMatch::Encoded(secret, matched))
+        }
+        Some(_) => Err("Encountered secret with non-hash \"data\" field.".to_string()),
+        None => Ok(SecretMatch::Skipped(secret, matched)),
+    }
 }
+
 fn base64_encode_secret_data(data: &mut Hash) -> Result<(), String> {
     for (_, value) in data.iter_mut() {
         let encoded = match value {
@@ -197,27 +1685,161 @@ fn base64_encode_secret_data(data: &mut Hash) -> Result<(), String> {
     Ok(())
 }
 
-fn dump(objects: Vec<Yaml>) -> Result<String, String> {
-    let mut manifests = String::new();
-    let last = objects.len() - 1;
+/// Serializes objects as a stream of YAML documents.
+///
+/// The keys of every hash, including the `data` of Secrets and ConfigMaps, are emitted in sorted
+/// order regardless of their order in the template, so rendering the same input always produces
+/// the same output. This relies on yaml-rust's `Hash` being a `BTreeMap`, so its
+/// `preserve_order` feature must not be enabled.
+pub fn dump(objects: Vec<Yaml>) -> Result<String, String> {
+    let mut manifests = vec![];
 
-    for (i, object) in objects.iter().enumerate() {
-        {
-            let mut emitter = YamlEmitter::new(&mut manifests);
-            emitter.dump(&object).map_err(|error| {
-                match error {
-                    EmitError::FmtError(error) => format!("{}", error),
-                    EmitError::BadHashmapKey => "Bad hashmap key in YAML structure.".to_owned(),
-                }
-            })?;
+    dump_to(&objects, &mut manifests)?;
+
+    String::from_utf8(manifests).map_err(|err| err.to_string())
+}
+
+/// Writes objects to a sink as YAML documents, like `dump`, emitting one object at a time so only
+/// a single object's YAML is held in memory.
+///
+/// # Errors
+///
+/// Returns an error if an object can't be emitted or writing to the sink fails.
+pub fn dump_to<W: Write>(objects: &[Yaml], sink: &mut W) -> Result<(), String> {
+    let mut manifest = String::new();
+
+    for (index, object) in objects.iter().enumerate() {
+        write_object(index, object, &mut manifest, sink)?;
+    }
+
+    Ok(())
+}
+
+/// Writes an object to a sink as a YAML document, using `manifest` as a buffer. Documents after
+/// the first are separated from the one before by a newline.
+fn write_object<W: Write>(index: usize, object: &Yaml, manifest: &mut String, sink: &mut W) -> Result<(), String> {
+    manifest.clear();
+
+    if index > 0 {
+        manifest.push('\n');
+    }
+
+    emit(object, manifest)?;
+
+    sink.write_all(manifest.as_bytes()).map_err(|err| format!("Failed to write manifests: {}", err))
+}
+
+/// Interpolates the parameters into one of the template's objects, applying its directives and
+/// conditions.
+fn interpolate_object(
+    param_map: &mut ParamMap,
+    index: usize,
+    object: &mut Yaml,
+    declares_object_index: bool,
+) -> Result<(), Error> {
+    if !declares_object_index {
+        param_map.remove("KTMPL_OBJECT_INDEX");
+        add_builtin(param_map, "KTMPL_OBJECT_INDEX", Some(index.to_string()));
+    }
+
+    let directives = take_directives(object)
+        .map_err(|err| Error::Parse(format!("objects[{}]: {}", index, err)))?;
+    let mut defaulted = vec![];
+
+    for (name, value) in directives.defaults {
+        match param_map.get_mut(&name) {
+            Some(parameter) => if parameter.value.is_none() {
+                parameter.value = Some(value);
+                defaulted.push(name);
+            },
+            None => return Err(Error::Parameter(
+                format!("objects[{}]: Default given for undeclared parameter {}.", index, name)
+            )),
+        }
+    }
+
+    for name in &directives.required {
+        if param_map.get(name).and_then(|parameter| parameter.value.as_ref()).is_none() {
+            return Err(Error::Parameter(
+                format!("objects[{}]: Parameter {} is required by this object.", index, name)
+            ));
+        }
+    }
+
+    apply_conditions(object, param_map)
+        .map_err(|err| Error::Parameter(format!("objects[{}]: {}", index, err)))?;
+    process_yaml(object, param_map)
+        .map_err(|err| Error::Parameter(format!("objects[{}]: {}", index, err)))?;
+
+    for name in defaulted {
+        if let Some(parameter) = param_map.get_mut(&name) {
+            parameter.value = None;
         }
+    }
+
+    Ok(())
+}
+
+/// Returns the non-empty values of the sensitive parameters, to redact.
+fn sensitive_values(param_map: &ParamMap) -> Vec<&str> {
+    param_map
+        .values()
+        .filter(|parameter| parameter.is_sensitive())
+        .filter_map(|parameter| parameter.value.as_deref())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
 
-        if i != last {
-            manifests.push_str("\n");
+/// Migrates an object from a deprecated API version to its replacement, if it has one.
+fn migrate_object(index: usize, object: &mut Yaml) -> Result<(), Error> {
+    let api_version = object["apiVersion"].as_str().unwrap_or_default().to_string();
+
+    let migrated = migrate_api_version(object)
+        .map_err(|err| Error::Process(format!("objects[{}]: {}", index, err)))?;
+
+    if let Some(migrated) = migrated {
+        info!("Migrated objects[{}] from {} to {}", index, api_version, migrated);
+    }
+
+    Ok(())
+}
+
+/// Returns the namespaces the objects are in that aren't among them, in order of first use.
+fn missing_namespaces(objects: &[Yaml], default_namespace: &str) -> Vec<String> {
+    let mut namespaces: Vec<String> = vec![];
+
+    for object in objects {
+        let kind = object["kind"].as_str().unwrap_or_default();
+        let namespace = match object["metadata"]["namespace"].as_str() {
+            Some(namespace) => namespace,
+            None if CLUSTER_SCOPED_KINDS.contains(&kind) => continue,
+            None => default_namespace,
+        };
+
+        if !SYSTEM_NAMESPACES.contains(&namespace) && !namespaces.iter().any(|existing| existing == namespace) {
+            namespaces.push(namespace.to_string());
         }
     }
 
-    Ok(manifests)
+    namespaces.retain(|namespace| {
+        !objects.iter().any(|object| {
+            object["kind"].as_str() == Some("Namespace") && object["metadata"]["name"].as_str() == Some(namespace)
+        })
+    });
+
+    namespaces
+}
+
+fn namespace_object(name: &str) -> Yaml {
+    let mut metadata = Hash::new();
+    let mut object = Hash::new();
+
+    metadata.insert(ystring("name"), ystring(name));
+    object.insert(ystring("apiVersion"), ystring("v1"));
+    object.insert(ystring("kind"), ystring("Namespace"));
+    object.insert(ystring("metadata"), Yaml::Hash(metadata));
+
+    Yaml::Hash(object)
 }
 
 fn ystring(s: &str) -> Yaml {
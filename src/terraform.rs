@@ -0,0 +1,74 @@
+use std::fmt::Write;
+
+use yaml::Yaml;
+
+use loader::load_yaml;
+use parameter::{ParameterValue, ParameterValues};
+
+/// Parses the query a Terraform `external` data source sends a program on stdin, returning the
+/// template path and parameter values it gives.
+///
+/// The query is a JSON object of strings. Its `template` key is the path or URL of the template
+/// to render, and every other key is a parameter name with its value, e.g.
+/// `{"template": "web.yml", "REPLICAS": "3"}`.
+///
+/// # Errors
+///
+/// Returns an error if the query is not a JSON object of strings or has no `template` key.
+pub fn terraform_query(query: &str) -> Result<(String, ParameterValues), String> {
+    let docs = load_yaml(query).map_err(|err| format!("Query is not valid JSON: {}", err))?;
+    let query = match docs.first() {
+        Some(Yaml::Hash(query)) => query,
+        _ => return Err("Query must be a JSON object.".to_string()),
+    };
+
+    let mut template = None;
+    let mut parameter_values = ParameterValues::new();
+
+    for (key, value) in query {
+        let key = key.as_str().ok_or("Query keys must be strings.")?;
+        let value = match *value {
+            Yaml::String(ref value) | Yaml::Real(ref value) => value.clone(),
+            Yaml::Integer(value) => value.to_string(),
+            Yaml::Boolean(value) => value.to_string(),
+            _ => return Err(format!("Query value for \"{}\" must be a string.", key)),
+        };
+
+        if key == "template" {
+            template = Some(value);
+        } else {
+            parameter_values.insert(key.to_string(), ParameterValue::Plain(value));
+        }
+    }
+
+    let template = template.ok_or("Query must give the template's path as \"template\".")?;
+
+    Ok((template, parameter_values))
+}
+
+/// Formats a rendered manifest as the JSON object a Terraform `external` data source expects,
+/// with the manifest as `manifest`.
+pub fn terraform_result(manifest: &str) -> String {
+    format!("{{\"manifest\":\"{}\"}}", json_escape(manifest))
+}
+
+/// Escapes a string for use within a JSON string literal.
+pub fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for character in s.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                write!(escaped, "\\u{:04x}", character as u32).expect("Writing to a String can't fail.");
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,482 @@
+use base64::decode;
+use yaml::{Yaml, YamlLoader};
+
+use kubectl;
+use reference::{references, selects};
+
+/// The maximum length of a DNS label, and of label values and label name segments.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// The maximum length of a DNS subdomain, and of most object names and label prefixes.
+const MAX_NAME_LENGTH: usize = 253;
+
+/// The maximum total size of an object's annotations, in bytes.
+const MAX_ANNOTATIONS_SIZE: usize = 256 * 1024;
+
+/// The maximum size of the data in a Secret or ConfigMap, in bytes.
+const MAX_DATA_SIZE: usize = 1024 * 1024;
+
+/// Kinds whose names must be DNS labels rather than DNS subdomains.
+const LABEL_NAMED_KINDS: [&str; 2] = ["Namespace", "Service"];
+
+/// Validates objects against well-known Kubernetes limits, so they fail before reaching the API
+/// server.
+///
+/// Names are limited to 253 characters (63 for Namespaces and Services) and namespaces to 63.
+/// Labels, including those in selectors and pod templates, are limited to 63-character
+/// values and names with an optional prefix of up to 253 characters. An object's annotations
+/// are limited to 256KiB in total, and the data of a Secret or ConfigMap (after Base64 decoding)
+/// to 1MiB.
+///
+/// # Errors
+///
+/// Returns an error listing every value that exceeds a limit, with its path.
+pub fn validate_limits(objects: &[Yaml]) -> Result<(), String> {
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let path = format!("objects[{}]", index);
+        let kind = object["kind"].as_str().unwrap_or_default();
+        let max_name_length = if LABEL_NAMED_KINDS.contains(&kind) { MAX_LABEL_LENGTH } else { MAX_NAME_LENGTH };
+
+        if let Some(name) = object["metadata"]["name"].as_str() {
+            check_length(name, max_name_length, "name", &format!("{}.metadata.name", path), &mut violations);
+        }
+
+        if let Some(namespace) = object["metadata"]["namespace"].as_str() {
+            check_length(
+                namespace,
+                MAX_LABEL_LENGTH,
+                "namespace",
+                &format!("{}.metadata.namespace", path),
+                &mut violations,
+            );
+        }
+
+        if let Some(annotations) = object["metadata"]["annotations"].as_hash() {
+            let size: usize = annotations
+                .iter()
+                .map(|(key, value)| key.as_str().map_or(0, str::len) + value.as_str().map_or(0, str::len))
+                .sum();
+
+            if size > MAX_ANNOTATIONS_SIZE {
+                violations.push(format!(
+                    "{}.metadata.annotations: annotations are {} bytes; the limit is {}.",
+                    path,
+                    size,
+                    MAX_ANNOTATIONS_SIZE,
+                ));
+            }
+        }
+
+        if kind == "Secret" || kind == "ConfigMap" {
+            let size = data_size(object, kind == "Secret");
+
+            if size > MAX_DATA_SIZE {
+                violations.push(format!("{}: {} data is {} bytes; the limit is {}.", path, kind, size, MAX_DATA_SIZE));
+            }
+        }
+
+        check_labels(object, &path, &mut violations);
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Objects exceed Kubernetes limits:\n{}", violations.join("\n")))
+    }
+}
+
+/// Validates that no two objects are the same Kubernetes resource, which usually means an object
+/// was copied without being renamed. Applied together, the later object would silently replace
+/// the earlier one.
+///
+/// Objects are the same resource if they have the same API group, kind, namespace, and name. The
+/// API group is compared rather than the full API version, since the same object can be read and
+/// written through each version of its group. Objects without a namespace are taken to be in the
+/// same one.
+///
+/// # Errors
+///
+/// Returns an error listing each duplicate object with the index of the object it duplicates.
+pub fn validate_unique(objects: &[Yaml]) -> Result<(), String> {
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let name = match object["metadata"]["name"].as_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let original = objects[..index].iter().position(|earlier| identity(earlier) == identity(object));
+
+        if let Some(original) = original {
+            let namespace = match object["metadata"]["namespace"].as_str() {
+                Some(namespace) => format!(" in namespace {}", namespace),
+                None => String::new(),
+            };
+
+            violations.push(format!(
+                "objects[{}]: {} {}{} duplicates objects[{}].",
+                index,
+                object["kind"].as_str().unwrap_or("object"),
+                name,
+                namespace,
+                original,
+            ));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Objects are duplicated:\n{}", violations.join("\n")))
+    }
+}
+
+/// Returns what identifies an object as a Kubernetes resource: its API group, kind, namespace,
+/// and name.
+fn identity(object: &Yaml) -> (Option<&str>, Option<&str>, Option<&str>, Option<&str>) {
+    (
+        object["apiVersion"].as_str().and_then(|version| version.rsplit_once('/')).map(|(group, _)| group),
+        object["kind"].as_str(),
+        object["metadata"]["namespace"].as_str(),
+        object["metadata"]["name"].as_str(),
+    )
+}
+
+/// Validates that the objects' references to each other resolve, so broken wiring fails before
+/// it's deployed.
+///
+/// Each `ConfigMap`, `Secret`, `PersistentVolumeClaim`, and `ServiceAccount` a pod spec refers to
+/// must be among the objects, in the same namespace, as must each `Ingress` backend `Service` and
+/// `HorizontalPodAutoscaler` target. References marked `optional` are skipped. Each `Service`
+/// with a selector must select the pods of one of the objects. Objects that exist outside of the
+/// template are allowed with `external`, as `KIND/NAME` or `KIND/*`, e.g. `Secret/registry-auth`,
+/// and a `Service` allowed this way needn't select any of the objects. The `default`
+/// ServiceAccount is always allowed. Objects without a namespace are taken to be in the same one.
+///
+/// # Errors
+///
+/// Returns an error listing each reference that doesn't resolve.
+pub fn validate_references(objects: &[Yaml], external: &[&str]) -> Result<(), String> {
+    let is_external = |kind: &str, name: &str| {
+        (kind, name) == ("ServiceAccount", "default")
+            || external.iter().any(|allowed| match allowed.split_once('/') {
+                Some((allowed_kind, allowed_name)) => allowed_kind == kind && (allowed_name == "*" || allowed_name == name),
+                None => false,
+            })
+    };
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let namespace = object["metadata"]["namespace"].as_str();
+        let description = format!(
+            "{} {}",
+            object["kind"].as_str().unwrap_or("object"),
+            object["metadata"]["name"].as_str().unwrap_or_default(),
+        );
+
+        for reference in references(object) {
+            let resolves = reference.optional
+                || is_external(reference.kind, &reference.name)
+                || objects.iter().any(|target| {
+                    target["kind"].as_str() == Some(reference.kind)
+                        && target["metadata"]["name"].as_str() == Some(&reference.name)
+                        && target["metadata"]["namespace"].as_str() == namespace
+                });
+
+            if !resolves {
+                violations.push(format!(
+                    "objects[{}]: {} refers to {} {} ({}), which isn't among the objects.",
+                    index,
+                    description,
+                    reference.kind,
+                    reference.name,
+                    reference.via,
+                ));
+            }
+        }
+
+        let has_selector = object["spec"]["selector"].as_hash().is_some_and(|selector| !selector.is_empty());
+
+        if object["kind"].as_str() == Some("Service")
+            && has_selector
+            && !is_external("Service", object["metadata"]["name"].as_str().unwrap_or_default())
+            && !objects.iter().any(|target| selects(object, target) && target["metadata"]["namespace"].as_str() == namespace)
+        {
+            violations.push(format!("objects[{}]: {}'s selector matches none of the objects' pods.", index, description));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Objects have unresolved references:\n{}", violations.join("\n")))
+    }
+}
+
+/// Validates custom resources against the schemas of the CRDs installed in the cluster.
+///
+/// The CRDs are fetched with `kubectl`. Objects whose API group is not served by a CRD are
+/// ignored, so built-in resources pass through untouched.
+///
+/// # Errors
+///
+/// Returns an error if the CRDs could not be fetched, or listing every schema violation found in
+/// the objects.
+pub fn validate_crds(objects: &[Yaml]) -> Result<(), String> {
+    let output = kubectl::run(&["get", "customresourcedefinitions", "--output", "yaml"])?;
+    let docs = YamlLoader::load_from_str(&output).map_err(|err| err.to_string())?;
+
+    match docs.first().and_then(|doc| doc["items"].as_vec()) {
+        Some(crds) => validate_against_crds(objects, crds),
+        None => Err("kubectl returned an unexpected response for CRDs.".to_string()),
+    }
+}
+
+/// Validates custom resources against the schemas of the given CRDs, like `validate_crds`.
+///
+/// # Errors
+///
+/// Returns an error listing every schema violation found in the objects.
+pub fn validate_against_crds(objects: &[Yaml], crds: &[Yaml]) -> Result<(), String> {
+    let mut violations = vec![];
+
+    for (index, object) in objects.iter().enumerate() {
+        let (group, version) = match object["apiVersion"].as_str() {
+            Some(api_version) => match api_version.find('/') {
+                Some(slash) => (&api_version[..slash], &api_version[slash + 1..]),
+                None => continue,
+            },
+            None => continue,
+        };
+        let kind = match object["kind"].as_str() {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        let crd = crds.iter().find(|crd| {
+            crd["spec"]["group"].as_str() == Some(group)
+                && crd["spec"]["names"]["kind"].as_str() == Some(kind)
+        });
+
+        if let Some(crd) = crd {
+            match crd_schema(crd, version) {
+                Some(schema) => {
+                    let path = format!("objects[{}]", index);
+
+                    check_properties(
+                        object,
+                        schema,
+                        &path,
+                        &["apiVersion", "kind", "metadata"],
+                        &mut violations,
+                    );
+                }
+                None => violations.push(format!(
+                    "objects[{}]: {}/{} {} is not served by its CRD.",
+                    index,
+                    group,
+                    version,
+                    kind,
+                )),
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Custom resources failed validation:\n{}", violations.join("\n")))
+    }
+}
+
+fn crd_schema<'a>(crd: &'a Yaml, version: &str) -> Option<&'a Yaml> {
+    let versions = crd["spec"]["versions"].as_vec();
+    let served = versions.and_then(|versions| {
+        versions.iter().find(|v| v["name"].as_str() == Some(version))
+    });
+
+    match served {
+        Some(served) => match served["schema"]["openAPIV3Schema"] {
+            Yaml::BadValue => Some(&crd["spec"]["validation"]["openAPIV3Schema"]),
+            ref schema => Some(schema),
+        },
+        None => if crd["spec"]["version"].as_str() == Some(version) {
+            Some(&crd["spec"]["validation"]["openAPIV3Schema"])
+        } else {
+            None
+        },
+    }
+}
+
+fn check(value: &Yaml, schema: &Yaml, path: &str, violations: &mut Vec<String>) {
+    if schema.is_badvalue() || value.is_null() && schema["nullable"].as_bool() == Some(true) {
+        return;
+    }
+
+    if schema["x-kubernetes-int-or-string"].as_bool() == Some(true) {
+        match *value {
+            Yaml::Integer(_) | Yaml::String(_) => {}
+            _ => violations.push(format!("{}: expected an integer or a string.", path)),
+        }
+
+        return;
+    }
+
+    if let Some(allowed) = schema["enum"].as_vec() {
+        if !allowed.contains(value) {
+            violations.push(format!("{}: value is not one of the allowed values.", path));
+        }
+    }
+
+    match schema["type"].as_str() {
+        Some("object") => match *value {
+            Yaml::Hash(_) => check_properties(value, schema, path, &[], violations),
+            _ => violations.push(format!("{}: expected an object.", path)),
+        },
+        Some("array") => match *value {
+            Yaml::Array(ref items) => for (index, item) in items.iter().enumerate() {
+                check(item, &schema["items"], &format!("{}[{}]", path, index), violations);
+            },
+            _ => violations.push(format!("{}: expected an array.", path)),
+        },
+        Some("string") if value.as_str().is_none() => {
+            violations.push(format!("{}: expected a string.", path));
+        }
+        Some("integer") if value.as_i64().is_none() => {
+            violations.push(format!("{}: expected an integer.", path));
+        }
+        Some("number") => match *value {
+            Yaml::Integer(_) | Yaml::Real(_) => {}
+            _ => violations.push(format!("{}: expected a number.", path)),
+        },
+        Some("boolean") if value.as_bool().is_none() => {
+            violations.push(format!("{}: expected a boolean.", path));
+        }
+        _ => {}
+    }
+}
+
+fn check_properties(
+    value: &Yaml,
+    schema: &Yaml,
+    path: &str,
+    ignored: &[&str],
+    violations: &mut Vec<String>,
+) {
+    let hash = match *value {
+        Yaml::Hash(ref hash) => hash,
+        _ => return,
+    };
+
+    if let Some(required) = schema["required"].as_vec() {
+        for key in required {
+            if let Some(key) = key.as_str() {
+                if !hash.contains_key(&Yaml::String(key.to_string())) {
+                    violations.push(format!("{}.{}: required field is missing.", path, key));
+                }
+            }
+        }
+    }
+
+    let properties = schema["properties"].as_hash();
+    let additional = &schema["additionalProperties"];
+    let preserve_unknown = schema["x-kubernetes-preserve-unknown-fields"].as_bool() == Some(true);
+
+    for (key, child) in hash {
+        let key = match key.as_str() {
+            Some(key) => key,
+            None => continue,
+        };
+
+        if ignored.contains(&key) {
+            continue;
+        }
+
+        let child_path = format!("{}.{}", path, key);
+
+        match properties.and_then(|properties| properties.get(&Yaml::String(key.to_string()))) {
+            Some(child_schema) => check(child, child_schema, &child_path, violations),
+            None => match *additional {
+                Yaml::Hash(_) => check(child, additional, &child_path, violations),
+                Yaml::Boolean(true) => {}
+                _ => if !preserve_unknown && properties.is_some() {
+                    violations.push(format!("{}: unknown field.", child_path));
+                },
+            },
+        }
+    }
+}
+
+fn check_length(value: &str, limit: usize, description: &str, path: &str, violations: &mut Vec<String>) {
+    let length = value.chars().count();
+
+    if length > limit {
+        violations.push(format!("{}: {} is {} characters; the limit is {}.", path, description, length, limit));
+    }
+}
+
+/// Checks every `labels` and `matchLabels` hash within a value, and every `selector` hash of
+/// strings, such as a Service's.
+fn check_labels(value: &Yaml, path: &str, violations: &mut Vec<String>) {
+    match *value {
+        Yaml::Hash(ref hash) => for (key, child) in hash {
+            let key = match key.as_str() {
+                Some(key) => key,
+                None => continue,
+            };
+            let child_path = format!("{}.{}", path, key);
+            let labels = match (key, child) {
+                ("labels", Yaml::Hash(labels)) | ("matchLabels", Yaml::Hash(labels)) => Some(labels),
+                ("selector", Yaml::Hash(labels)) if labels.values().all(|value| value.as_str().is_some()) => {
+                    Some(labels)
+                }
+                _ => None,
+            };
+
+            match labels {
+                Some(labels) => {
+                    for (label, label_value) in labels {
+                        let label = label.as_str().unwrap_or_default();
+                        let label_path = format!("{}.{}", child_path, label);
+                        let (prefix, name) = match label.rfind('/') {
+                            Some(slash) => (Some(&label[..slash]), &label[slash + 1..]),
+                            None => (None, label),
+                        };
+
+                        if let Some(prefix) = prefix {
+                            check_length(prefix, MAX_NAME_LENGTH, "label prefix", &label_path, violations);
+                        }
+
+                        check_length(name, MAX_LABEL_LENGTH, "label name", &label_path, violations);
+
+                        if let Some(label_value) = label_value.as_str() {
+                            check_length(label_value, MAX_LABEL_LENGTH, "label value", &label_path, violations);
+                        }
+                    }
+                }
+                None => check_labels(child, &child_path, violations),
+            }
+        },
+        Yaml::Array(ref items) => for (index, item) in items.iter().enumerate() {
+            check_labels(item, &format!("{}[{}]", path, index), violations);
+        },
+        _ => {}
+    }
+}
+
+/// Returns the size in bytes of a Secret's or ConfigMap's data, decoding Base64-encoded values.
+fn data_size(object: &Yaml, secret: bool) -> usize {
+    let size = |key: &str, encoded: bool| -> usize {
+        object[key].as_hash().map_or(0, |data| data.values().filter_map(Yaml::as_str).map(|value| {
+            if encoded {
+                decode(value).map_or(value.len(), |decoded| decoded.len())
+            } else {
+                value.len()
+            }
+        }).sum())
+    };
+
+    size("data", secret) + size("stringData", false) + size("binaryData", true)
+}
@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::slice::Iter;
+
+/// What a warning is about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// A value was supplied for a parameter the template doesn't declare.
+    UnusedParameter,
+    /// The template or its values use something deprecated, such as a parameter's former name or
+    /// a deprecated API version.
+    Deprecated,
+    /// Part of the template or its values was ignored, such as an unknown field in a parameter's
+    /// declaration.
+    IgnoredField,
+}
+
+/// A problem with a template or its parameter values that doesn't stop it being processed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// What the warning is about.
+    pub kind: WarningKind,
+    /// A description of the problem, e.g. "Parameter DB_HOST was renamed to DATABASE_HOST; give
+    /// its value as DATABASE_HOST instead."
+    pub message: String,
+}
+
+/// The warnings found loading and processing a template, in the order they were found, as
+/// returned by `Template::process_with_warnings`, so applications embedding ktmpl can show them
+/// rather than relying on its log.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Warnings {
+    warnings: Vec<Warning>,
+}
+
+impl Warnings {
+    /// Creates an empty collection of warnings.
+    pub fn new() -> Self {
+        Warnings::default()
+    }
+
+    /// Returns whether there are no warnings.
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Returns an iterator over the warnings, in the order they were found.
+    pub fn iter(&self) -> Iter<'_, Warning> {
+        self.warnings.iter()
+    }
+
+    /// Returns how many warnings there are.
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    /// Adds a warning after the existing ones.
+    pub fn push(&mut self, kind: WarningKind, message: String) {
+        self.warnings.push(Warning { kind, message });
+    }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+    type Item = &'a Warning;
+    type IntoIter = Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.message)
+    }
+}
@@ -0,0 +1,327 @@
+use std::env;
+use std::fs::{Permissions, create_dir_all, set_permissions, write};
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Command, Output, Stdio};
+
+fn ktmpl(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ktmpl"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("Failed to run ktmpl.")
+}
+
+fn ktmpl_with_stdin(args: &[&str], stdin: &str) -> Output {
+    ktmpl_with_path(args, stdin, &env::var("PATH").unwrap_or_default())
+}
+
+/// Runs ktmpl with fake versions of the given commands, such as `kubectl`, first on the `PATH`.
+fn ktmpl_with_fakes(test: &str, fakes: &[(&str, &str)], args: &[&str], stdin: &str) -> Output {
+    let bin = env::temp_dir().join(format!("ktmpl-cli-{}-{}", test, std::process::id()));
+
+    create_dir_all(&bin).unwrap();
+
+    for &(name, script) in fakes {
+        let path = bin.join(name);
+
+        write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        set_permissions(&path, Permissions::from_mode(0o755)).unwrap();
+    }
+
+    ktmpl_with_path(args, stdin, &format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default()))
+}
+
+fn ktmpl_with_path(args: &[&str], stdin: &str, path: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ktmpl"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("PATH", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run ktmpl.");
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().expect("Failed to run ktmpl.")
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "ktmpl failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn secret_before_template() {
+    let encoded = "config.yml: \"dXNlcm5hbWU6ICJjYXJsIgpwYXNzd29yZDogIngiCg==\"";
+    let output = ktmpl(&["--secret", "webapp", "default", "secret_example.yml", "-p", "PASSWORD", "x"]);
+
+    assert!(stdout(&output).contains(encoded));
+
+    let output = ktmpl(&["--secret-name", "webapp", "secret_example.yml", "-p", "PASSWORD", "x"]);
+
+    assert!(stdout(&output).contains(encoded));
+
+    let output = ktmpl(&["--secret", "webapp", "production", "secret_example.yml", "-p", "PASSWORD", "x"]);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Not all secrets specified were found"));
+}
+
+#[test]
+fn exit_codes_follow_error_kinds() {
+    let template = "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+                    data: {option: $(OPT)}\nparameters:\n  - name: OPT\n";
+
+    let output = ktmpl_with_stdin(&["-"], template);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Parameter OPT has no value"));
+    assert_eq!(ktmpl_with_stdin(&["-", "-p", "OPT", "x"], "objects: [").status.code(), Some(2));
+
+    let output = ktmpl_with_stdin(&["-", "-p", "OPT", "x", "--secret", "db", "default"], template);
+
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn lookup_uses_kube_context() {
+    let kubectl = "case \"$*\" in\n\
+                   'config view'*) echo 'contexts: [{name: prod, context: {cluster: prod, namespace: web}}]' ;;\n\
+                   *'--context prod get'*) echo 'data: {host: prod-db}' ;;\n\
+                   *) echo 'data: {host: current-db}' ;;\n\
+                   esac\n";
+    let template = "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+                    data: {host: $(DB_HOST)}\nparameters:\n  - name: DB_HOST\n";
+    let lookup = ["-", "--lookup", "-p", "DB_HOST", "lookup:configmap/web/db#host"];
+    let mut with_context = lookup.to_vec();
+
+    with_context.extend(&["--kube-context", "prod"]);
+
+    let output = ktmpl_with_fakes("lookup", &[("kubectl", kubectl)], &with_context, template);
+
+    assert!(stdout(&output).contains("host: \"prod-db\""));
+
+    let output = ktmpl_with_fakes("lookup", &[("kubectl", kubectl)], &lookup, template);
+
+    assert!(stdout(&output).contains("host: \"current-db\""));
+}
+
+#[test]
+fn signatures_cover_the_rendered_bytes() {
+    // The fake cosign accepts a signature that is a copy of the blob it reads from stdin.
+    let cosign = "while [ \"$1\" != --signature ]; do shift; done\n\
+                  [ \"$3\" = - ] && cat | cmp -s - \"$2\"\n";
+    // The fake curl serves the template at any URL, and a copy of it as the signature.
+    let dir = env::temp_dir().join(format!("ktmpl-cli-signatures-{}", std::process::id()));
+    let template = dir.join("example.yml");
+    let template = template.to_str().unwrap();
+    let curl = format!("cat > /dev/null\ncat {}\n", template);
+    let contents = "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+                    data: {a: b}\nparameters: []\n";
+
+    create_dir_all(&dir).unwrap();
+    write(template, contents).unwrap();
+    write(format!("{}.sig", template), contents).unwrap();
+    write(dir.join("other.sig"), "kind: Template\n").unwrap();
+
+    let fakes = [("cosign", cosign), ("curl", curl.as_str())];
+    let output = ktmpl_with_fakes("signatures", &fakes, &[template, "--verify-key", "key.pub"], "");
+
+    assert!(stdout(&output).contains("a: b"));
+
+    let other = dir.join("other.sig");
+    let args = [template, "--verify-key", "key.pub", "--signature", other.to_str().unwrap()];
+    let output = ktmpl_with_fakes("signatures", &fakes, &args, "");
+
+    assert_eq!(output.status.code(), Some(3));
+
+    let args = ["https://example.com/example.yml", "--verify-key", "key.pub"];
+    let output = ktmpl_with_fakes("signatures", &fakes, &args, "");
+
+    assert!(stdout(&output).contains("a: b"));
+}
+
+#[test]
+fn rollback_bundle_restores_pruned_objects() {
+    let kubectl = "case \"$*\" in\n\
+                   api-resources*) echo configmaps ;;\n\
+                   'get configmaps --all-namespaces'*) printf 'items:\\n\
+                     - {kind: ConfigMap, apiVersion: v1, metadata: {name: old, namespace: web, uid: abc}}\\n\
+                     - {kind: ConfigMap, apiVersion: v1, metadata: {name: app, namespace: web}}\\n' ;;\n\
+                   apply*) cat > /dev/null; echo applied ;;\n\
+                   delete*) echo \"deleted $3\" ;;\n\
+                   esac\n";
+    let template = "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+                    metadata: {name: app, namespace: web}\nparameters: []\n";
+    let dir = env::temp_dir().join(format!("ktmpl-cli-rollback-bundles-{}", std::process::id()));
+    let args = ["-", "--apply", "--prune", "--selector", "app=web", "--rollback-bundle", dir.to_str().unwrap()];
+    let output = ktmpl_with_fakes("rollback", &[("kubectl", kubectl)], &args, template);
+
+    assert_eq!(stdout(&output), "applied\ndeleted old\n");
+
+    let bundle = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    let bundle = std::fs::read_to_string(bundle).unwrap();
+    let restore = &bundle[bundle.find("restore:").unwrap()..];
+
+    assert!(restore.contains("name: old"));
+    assert!(!restore.contains("uid"));
+    assert!(bundle[..bundle.find("restore:").unwrap()].contains("name: app"));
+}
+
+#[test]
+fn apply_progress() {
+    let kubectl = "case \"$(cat)\" in\n\
+                   *broken*) echo 'Forbidden' >&2; exit 1 ;;\n\
+                   *unchanged*) echo 'configmap/unchanged unchanged' ;;\n\
+                   *) echo 'configmap/new created' ;;\n\
+                   esac\n";
+    let template = "kind: Template\napiVersion: v1\nobjects:\n\
+                    - {kind: ConfigMap, apiVersion: v1, metadata: {name: new}}\n\
+                    - {kind: ConfigMap, apiVersion: v1, metadata: {name: unchanged}}\n\
+                    - {kind: ConfigMap, apiVersion: v1, metadata: {name: broken}}\n\
+                    parameters: []\n";
+    let args = ["-", "--apply", "--progress", "plain"];
+    let output = ktmpl_with_fakes("apply-progress", &[("kubectl", kubectl)], &args, template);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.contains("[1/3] configmap/new applied\n"));
+    assert!(stderr.contains("[2/3] configmap/unchanged skipped (unchanged)\n"));
+    assert!(stderr.contains("[3/3] configmap/broken failed: "));
+    assert!(stderr.contains("1 applied, 1 skipped, 1 failed\n"));
+}
+
+#[test]
+fn release_progress() {
+    let dir = env::temp_dir().join(format!("ktmpl-cli-release-{}", std::process::id()));
+    let template = |parameter: &str| {
+        format!(
+            "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+             data: {{a: $({0})}}\nparameters:\n  - name: {0}\n",
+            parameter,
+        )
+    };
+
+    create_dir_all(&dir).unwrap();
+    write(dir.join("database.yml"), template("PASSWORD")).unwrap();
+    write(dir.join("web.yml"), template("HOST")).unwrap();
+    write(dir.join("worker.yml"), template("QUEUE")).unwrap();
+    write(
+        dir.join("release.yml"),
+        "templates:\n  - template: database.yml\n  - template: web.yml\n    dependsOn: [database]\n    \
+         parameters: {HOST: db}\n  - template: worker.yml\n    parameters: {QUEUE: jobs}\n    output: worker.out\n",
+    )
+    .unwrap();
+
+    let manifest = dir.join("release.yml");
+    let output = ktmpl(&["release", manifest.to_str().unwrap(), "--progress", "plain"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr.contains("[1/3] database failed: "));
+    assert!(stderr.contains("[2/3] web skipped (database was not rendered)\n"));
+    assert!(stderr.contains("[3/3] worker rendered\n"));
+    assert!(stderr.contains("1 rendered, 1 skipped, 1 failed\n"));
+    assert!(!dir.join("worker.out").exists());
+
+    let output = ktmpl(&["release", manifest.to_str().unwrap()]);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Parameter PASSWORD has no value"));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("skipped"));
+}
+
+#[test]
+fn renamed_parameter_warning() {
+    let template = "kind: Template\napiVersion: v1\nobjects:\n  - kind: ConfigMap\n    apiVersion: v1\n    \
+                    data: {host: $(DATABASE_HOST)}\nparameters:\n  - name: DATABASE_HOST\n    aliases: [DB_HOST]\n";
+    let output = ktmpl_with_stdin(&["-", "-p", "DB_HOST", "db"], template);
+    let warning = "Parameter DB_HOST was renamed to DATABASE_HOST; give its value as DATABASE_HOST instead.";
+
+    assert!(stdout(&output).contains("host: db"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).matches(warning).count(), 1);
+}
+
+#[test]
+fn quiet_suppresses_output() {
+    let template = "kind: Template\napiVersion: v1\nobjects:\n  - kind: Pod\n    apiVersion: v1\n    \
+                    spec: {serviceAccountName: $(ACCOUNT)}\nparameters:\n  - name: ACCOUNT\n";
+
+    assert_eq!(stdout(&ktmpl_with_stdin(&["-", "--quiet", "-p", "ACCOUNT", "web"], template)), "");
+
+    let output = ktmpl_with_stdin(&["-", "-q", "--check-references", "-p", "ACCOUNT", "default"], template);
+
+    assert_eq!(stdout(&output), "");
+
+    let output = ktmpl_with_stdin(&["-", "--quiet", "--check-references", "-p", "ACCOUNT", "web"], template);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert_eq!(ktmpl_with_stdin(&["-", "--quiet"], template).status.code(), Some(2));
+}
+
+#[test]
+fn focus_conflicts_with_prune_and_rollback_bundle() {
+    // Objects outside the focus would be pruned, or missing from the bundle, as if they'd been removed.
+    let focused = ["example.yml", "--apply", "--focus", "kind=Deployment"];
+
+    for extra in [&["--prune", "--selector", "app=web"][..], &["--rollback-bundle", "bundles"][..]] {
+        let mut args = focused.to_vec();
+
+        args.extend(extra);
+
+        let output = ktmpl(&args);
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn overflowing_durations_are_invalid() {
+    for interval in ["999999999999999999h", "5x"] {
+        let output = ktmpl(&["operator", "--interval", interval]);
+
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid duration"));
+    }
+}
+
+#[test]
+fn parameter_file_pins() {
+    let pinned = "params.yml=ebf84bf442b86bccac0676a4d7d6f14222137adb252817f7a1a7b8fb41d508bb";
+    let pin = |pin: &str| ktmpl(&["example.yml", "-f", "params.yml", "--parameter-file-sha256", pin]);
+
+    assert!(stdout(&pin(pinned)).contains("carl"));
+    assert_eq!(pin("params.yml=00").status.code(), Some(3));
+    assert_eq!(pin(&pinned.replace("params", "other")).status.code(), Some(2));
+    assert_eq!(pin(&pinned["params.yml=".len()..]).status.code(), Some(2));
+
+    let dir = env::temp_dir().join(format!("ktmpl-cli-release-pins-{}", std::process::id()));
+    let manifest = dir.join("release.yml");
+    let release = |template_sha256: &str| {
+        write(
+            &manifest,
+            format!(
+                "templates:\n  - template: {0}/example.yml\n    templateSha256: \"{1}\"\n    parameterFiles:\n      \
+                 - {{file: {0}/params.yml, sha256: {2}}}\n",
+                env!("CARGO_MANIFEST_DIR"),
+                template_sha256,
+                &pinned["params.yml=".len()..],
+            ),
+        )
+        .unwrap();
+
+        ktmpl(&["release", manifest.to_str().unwrap()])
+    };
+
+    create_dir_all(&dir).unwrap();
+
+    assert!(stdout(&release("4e3ec479f0e05cb591a91f2a11b70472d20bf0f5c3ec47a6f8ae2eb26fec238f")).contains("carl"));
+
+    let output = release("00");
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("example: SHA-256 digest"));
+}